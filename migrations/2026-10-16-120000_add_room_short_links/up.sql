@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE room_short_links (
+    slug VARCHAR PRIMARY KEY,
+    room_id UUID NOT NULL UNIQUE REFERENCES rooms(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
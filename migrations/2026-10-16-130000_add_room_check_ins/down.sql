@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE yamls DROP COLUMN checked_in_at, DROP COLUMN excluded;
+DROP TABLE room_check_ins
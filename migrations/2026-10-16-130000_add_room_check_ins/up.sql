@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE room_check_ins (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    opened_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    closed_at TIMESTAMP
+);
+
+ALTER TABLE yamls ADD COLUMN checked_in_at TIMESTAMP, ADD COLUMN excluded BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE yaml_annotations (
+    yaml_id UUID PRIMARY KEY REFERENCES yamls(id) ON DELETE CASCADE,
+    flag VARCHAR,
+    notes TEXT NOT NULL DEFAULT '',
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE yamls ADD COLUMN validation_warnings JSONB NOT NULL DEFAULT '[]';
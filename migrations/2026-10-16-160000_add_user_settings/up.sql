@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE user_settings (
+    user_id BIGINT PRIMARY KEY REFERENCES discord_users(id) ON DELETE CASCADE,
+    theme VARCHAR NOT NULL DEFAULT 'dark',
+    compact_layout BOOLEAN NOT NULL DEFAULT false
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE room_members;
+DROP TABLE room_join_codes;
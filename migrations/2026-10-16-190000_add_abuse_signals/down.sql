@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE abuse_signals;
+DROP INDEX yamls_room_id_content_fingerprint;
+ALTER TABLE yamls DROP COLUMN content_fingerprint;
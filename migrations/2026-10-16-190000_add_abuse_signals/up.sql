@@ -0,0 +1,13 @@
+-- Your SQL goes here
+ALTER TABLE yamls ADD COLUMN content_fingerprint VARCHAR;
+CREATE INDEX yamls_room_id_content_fingerprint ON yamls(room_id, content_fingerprint);
+
+CREATE TABLE abuse_signals (
+    id UUID PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES discord_users(id) ON DELETE CASCADE,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE,
+    kind VARCHAR NOT NULL,
+    details TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    reviewed_at TIMESTAMP
+);
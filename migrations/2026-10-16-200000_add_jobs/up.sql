@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE jobs (
+    id VARCHAR PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    room_id UUID REFERENCES rooms(id) ON DELETE CASCADE,
+    yaml_id UUID REFERENCES yamls(id) ON DELETE SET NULL,
+    user_id BIGINT REFERENCES discord_users(id) ON DELETE SET NULL,
+    enqueued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    resolved_at TIMESTAMP,
+    resolution VARCHAR,
+    worker_id VARCHAR
+);
+CREATE INDEX jobs_room_id_enqueued_at ON jobs(room_id, enqueued_at);
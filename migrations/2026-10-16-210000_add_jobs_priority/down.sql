@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX jobs_pending_enqueued_at;
+ALTER TABLE jobs DROP COLUMN priority;
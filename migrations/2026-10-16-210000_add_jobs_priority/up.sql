@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE jobs ADD COLUMN priority VARCHAR NOT NULL DEFAULT 'normal';
+CREATE INDEX jobs_pending_enqueued_at ON jobs(kind, priority, enqueued_at) WHERE resolved_at IS NULL;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE room_url_visibilities (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    visibility VARCHAR NOT NULL,
+    revealed_at TIMESTAMP
+);
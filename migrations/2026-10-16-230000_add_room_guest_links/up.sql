@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE room_guest_links (
+    code VARCHAR PRIMARY KEY,
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    guest_id BIGINT REFERENCES discord_users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at TIMESTAMP
+);
+
+CREATE INDEX room_guest_links_room_id ON room_guest_links(room_id);
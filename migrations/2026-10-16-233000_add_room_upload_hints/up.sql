@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE room_upload_hints (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    hints TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
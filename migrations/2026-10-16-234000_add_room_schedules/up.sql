@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE room_schedules (
+    id UUID PRIMARY KEY,
+    template_id UUID NOT NULL REFERENCES room_templates(id) ON DELETE CASCADE,
+    author_id BIGINT NOT NULL REFERENCES discord_users(id) ON DELETE CASCADE,
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+    start_time TIME NOT NULL,
+    open_for_hours INTEGER NOT NULL CHECK (open_for_hours > 0),
+    webhook_url TEXT NOT NULL DEFAULT '',
+    next_run_at TIMESTAMP NOT NULL,
+    last_room_id UUID REFERENCES rooms(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX room_schedules_next_run_at ON room_schedules(next_run_at);
+CREATE INDEX room_schedules_author_id ON room_schedules(author_id);
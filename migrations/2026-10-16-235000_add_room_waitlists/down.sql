@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE room_slot_caps;
+
+ALTER TABLE yamls DROP COLUMN waitlisted;
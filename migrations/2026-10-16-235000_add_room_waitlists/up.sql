@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE yamls ADD COLUMN waitlisted BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE room_slot_caps (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    max_slots INTEGER NOT NULL CHECK (max_slots > 0)
+);
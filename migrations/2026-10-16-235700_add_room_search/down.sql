@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX yamls_game_room_id;
+DROP INDEX discord_users_username_trgm;
+DROP INDEX rooms_author_id;
+DROP INDEX rooms_name_trgm;
+
+ALTER TABLE rooms DROP COLUMN archived;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE rooms ADD COLUMN archived BOOLEAN NOT NULL DEFAULT false;
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX rooms_name_trgm ON rooms USING gin (name gin_trgm_ops);
+CREATE INDEX rooms_author_id ON rooms(author_id);
+CREATE INDEX discord_users_username_trgm ON discord_users USING gin (username gin_trgm_ops);
+CREATE INDEX yamls_game_room_id ON yamls(game, room_id);
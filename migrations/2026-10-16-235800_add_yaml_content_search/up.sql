@@ -0,0 +1,2 @@
+-- Your SQL goes here
+CREATE INDEX yamls_content_trgm ON yamls USING gin (content gin_trgm_ops);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE room_idempotency_keys (
+    author_id BIGINT NOT NULL REFERENCES discord_users(id) ON DELETE CASCADE,
+    idempotency_key VARCHAR NOT NULL,
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (author_id, idempotency_key)
+);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE room_feature_policies (
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    feature VARCHAR NOT NULL,
+    policy VARCHAR NOT NULL,
+    PRIMARY KEY (room_id, feature)
+);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE room_settings_revisions (
+    id UUID PRIMARY KEY,
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    author_id BIGINT NOT NULL REFERENCES discord_users(id),
+    settings JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX room_settings_revisions_room_id_idx ON room_settings_revisions(room_id, created_at);
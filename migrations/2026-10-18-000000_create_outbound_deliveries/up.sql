@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE outbound_deliveries (
+    id UUID PRIMARY KEY,
+    channel VARCHAR NOT NULL,
+    target TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    dead_at TIMESTAMP
+);
+CREATE INDEX outbound_deliveries_next_attempt_at ON outbound_deliveries(next_attempt_at) WHERE dead_at IS NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE room_exact_yaml_storage;
+ALTER TABLE yamls DROP COLUMN original_content;
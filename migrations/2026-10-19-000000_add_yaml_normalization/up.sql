@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE yamls ADD COLUMN original_content TEXT;
+
+CREATE TABLE room_exact_yaml_storage (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM room_settings_revisions WHERE author_id IS NULL;
+ALTER TABLE room_settings_revisions ALTER COLUMN author_id SET NOT NULL;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE room_settings_revisions ALTER COLUMN author_id DROP NOT NULL;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE room_exact_yaml_storage (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO room_exact_yaml_storage (room_id) SELECT id FROM rooms WHERE exact_yaml_storage;
+ALTER TABLE rooms DROP COLUMN exact_yaml_storage;
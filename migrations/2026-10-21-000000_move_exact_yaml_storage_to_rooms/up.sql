@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE rooms ADD COLUMN exact_yaml_storage BOOLEAN NOT NULL DEFAULT false;
+UPDATE rooms SET exact_yaml_storage = true WHERE id IN (SELECT room_id FROM room_exact_yaml_storage);
+DROP TABLE room_exact_yaml_storage;
@@ -0,0 +1,41 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE room_upload_hints (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    hints TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO room_upload_hints (room_id, hints)
+SELECT id, upload_hints FROM rooms WHERE upload_hints <> '';
+
+CREATE TABLE room_slot_caps (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    max_slots INTEGER NOT NULL CHECK (max_slots > 0)
+);
+INSERT INTO room_slot_caps (room_id, max_slots)
+SELECT id, max_slots FROM rooms WHERE max_slots IS NOT NULL;
+
+CREATE TABLE room_feature_policies (
+    room_id UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    feature VARCHAR NOT NULL,
+    policy VARCHAR NOT NULL,
+    PRIMARY KEY (room_id, feature)
+);
+INSERT INTO room_feature_policies (room_id, feature, policy)
+SELECT rooms.id, policies.key, policies.value
+FROM rooms, jsonb_each_text(rooms.feature_policies) AS policies;
+
+CREATE TABLE room_url_visibilities (
+    room_id UUID PRIMARY KEY REFERENCES rooms(id) ON DELETE CASCADE,
+    visibility VARCHAR NOT NULL,
+    revealed_at TIMESTAMP
+);
+INSERT INTO room_url_visibilities (room_id, visibility, revealed_at)
+SELECT id, room_url_visibility, room_url_revealed_at FROM rooms
+WHERE room_url_visibility <> 'participants' OR room_url_revealed_at IS NOT NULL;
+
+ALTER TABLE rooms
+    DROP COLUMN upload_hints,
+    DROP COLUMN max_slots,
+    DROP COLUMN feature_policies,
+    DROP COLUMN room_url_visibility,
+    DROP COLUMN room_url_revealed_at;
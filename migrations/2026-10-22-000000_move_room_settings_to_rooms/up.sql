@@ -0,0 +1,31 @@
+-- Your SQL goes here
+ALTER TABLE rooms
+    ADD COLUMN upload_hints TEXT NOT NULL DEFAULT '',
+    ADD COLUMN max_slots INTEGER CHECK (max_slots > 0),
+    ADD COLUMN feature_policies JSONB NOT NULL DEFAULT '{}',
+    ADD COLUMN room_url_visibility VARCHAR NOT NULL DEFAULT 'participants',
+    ADD COLUMN room_url_revealed_at TIMESTAMP;
+
+UPDATE rooms SET upload_hints = room_upload_hints.hints
+FROM room_upload_hints WHERE room_upload_hints.room_id = rooms.id;
+
+UPDATE rooms SET max_slots = room_slot_caps.max_slots
+FROM room_slot_caps WHERE room_slot_caps.room_id = rooms.id;
+
+UPDATE rooms SET feature_policies = policies.policies
+FROM (
+    SELECT room_id, jsonb_object_agg(feature, policy) AS policies
+    FROM room_feature_policies
+    GROUP BY room_id
+) AS policies
+WHERE policies.room_id = rooms.id;
+
+UPDATE rooms SET
+    room_url_visibility = room_url_visibilities.visibility,
+    room_url_revealed_at = room_url_visibilities.revealed_at
+FROM room_url_visibilities WHERE room_url_visibilities.room_id = rooms.id;
+
+DROP TABLE room_upload_hints;
+DROP TABLE room_slot_caps;
+DROP TABLE room_feature_policies;
+DROP TABLE room_url_visibilities;
@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use diesel_async::AsyncPgConnection;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use crate::db::{self, AbuseSignalKind, RoomId};
+use crate::error::Result;
+
+const KEY_PREFIX: &str = "lobby:abuse:";
+// Discord snowflakes count milliseconds since the first second of 2015.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+const NEW_ACCOUNT_DAYS: i64 = 7;
+// How many new accounts need to upload the same YAML to a room before it looks like griefing.
+const DUPLICATE_OWNERS_THRESHOLD: usize = 3;
+const DELETE_WINDOW: Duration = Duration::from_secs(10 * 60);
+const DELETE_THRESHOLD: u64 = 10;
+
+/// When the discord account behind `user_id` was created.
+pub fn discord_account_created_at(user_id: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_millis((user_id >> 22) + DISCORD_EPOCH_MS)
+}
+
+fn is_new_account(user_id: i64, now: DateTime<Utc>) -> bool {
+    discord_account_created_at(user_id)
+        .is_some_and(|created_at| now - created_at < TimeDelta::days(NEW_ACCOUNT_DAYS))
+}
+
+/// Looks for patterns of griefing in public rooms and records them as signals for admins to
+/// review. When `ABUSE_AUTO_LIMIT_SECS` is set, users who trigger a signal also get their uploads
+/// paused for that long.
+///
+/// Counters and limits live in valkey. They're best effort, valkey errors are logged and never
+/// block anyone.
+pub struct AbuseDetector {
+    conn: ConnectionManager,
+    auto_limit: Option<Duration>,
+}
+
+impl AbuseDetector {
+    pub async fn new(valkey_url: &str) -> Result<Self> {
+        let client = redis::Client::open(valkey_url)?;
+        let conn = ConnectionManager::new(client).await?;
+        let auto_limit = std::env::var("ABUSE_AUTO_LIMIT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs);
+
+        Ok(Self { conn, auto_limit })
+    }
+
+    fn limit_key(user_id: i64) -> String {
+        format!("{}limited:{}", KEY_PREFIX, user_id)
+    }
+
+    pub async fn is_limited(&self, user_id: i64) -> bool {
+        let limited: redis::RedisResult<bool> =
+            self.conn.clone().exists(Self::limit_key(user_id)).await;
+        limited.unwrap_or_else(|e| {
+            tracing::warn!("Failed to check the abuse limit: {:?}", e);
+            false
+        })
+    }
+
+    pub async fn lift_limit(&self, user_id: i64) {
+        let res: redis::RedisResult<()> = self.conn.clone().del(Self::limit_key(user_id)).await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to lift the abuse limit: {:?}", e);
+        }
+    }
+
+    async fn signal(
+        &self,
+        user_id: i64,
+        room_id: RoomId,
+        kind: AbuseSignalKind,
+        details: &str,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<()> {
+        let recorded = db::record_abuse_signal(user_id, Some(room_id), kind, details, conn).await?;
+        if !recorded {
+            return Ok(());
+        }
+        tracing::warn!(user_id, %room_id, kind = kind.as_str(), "Abuse signal recorded");
+
+        let Some(auto_limit) = self.auto_limit else {
+            return Ok(());
+        };
+        let res: redis::RedisResult<()> = self
+            .conn
+            .clone()
+            .set_ex(
+                Self::limit_key(user_id),
+                kind.as_str(),
+                auto_limit.as_secs(),
+            )
+            .await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to set the abuse limit: {:?}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Call after `user_id` uploaded to a room.
+    #[tracing::instrument(skip(self, conn))]
+    pub async fn check_upload(
+        &self,
+        room_id: RoomId,
+        user_id: i64,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<()> {
+        let now = Utc::now();
+        if !is_new_account(user_id, now) {
+            return Ok(());
+        }
+
+        let owners = db::get_owners_of_matching_yamls(room_id, user_id, conn).await?;
+        let new_owners = owners
+            .into_iter()
+            .filter(|owner| is_new_account(*owner, now))
+            .count();
+        if new_owners < DUPLICATE_OWNERS_THRESHOLD {
+            return Ok(());
+        }
+
+        let details = format!(
+            "{} accounts created less than {} days ago uploaded the same YAML",
+            new_owners, NEW_ACCOUNT_DAYS
+        );
+        self.signal(
+            user_id,
+            room_id,
+            AbuseSignalKind::DuplicateYamls,
+            &details,
+            conn,
+        )
+        .await
+    }
+
+    /// Call after `user_id` deleted a YAML of theirs.
+    #[tracing::instrument(skip(self, conn))]
+    pub async fn check_delete(
+        &self,
+        room_id: RoomId,
+        user_id: i64,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<()> {
+        let key = format!("{}deletes:{}", KEY_PREFIX, user_id);
+        let mut redis = self.conn.clone();
+        let deletes: u64 = match redis.incr(&key, 1).await {
+            Ok(deletes) => deletes,
+            Err(e) => {
+                tracing::warn!("Failed to count deletions: {:?}", e);
+                return Ok(());
+            }
+        };
+        if deletes == 1 {
+            let res: redis::RedisResult<()> =
+                redis.expire(&key, DELETE_WINDOW.as_secs() as i64).await;
+            if let Err(e) = res {
+                tracing::warn!("Failed to expire the deletion counter: {:?}", e);
+            }
+        }
+
+        if deletes < DELETE_THRESHOLD {
+            return Ok(());
+        }
+
+        let details = format!(
+            "{} YAMLs deleted in less than {} minutes",
+            deletes,
+            DELETE_WINDOW.as_secs() / 60
+        );
+        self.signal(
+            user_id,
+            room_id,
+            AbuseSignalKind::DeleteLoop,
+            &details,
+            conn,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta};
+
+    use super::{discord_account_created_at, is_new_account};
+
+    #[test]
+    fn test_discord_account_created_at() {
+        // Example from discord's documentation.
+        let created_at = discord_account_created_at(175928847299117063).unwrap();
+        assert_eq!(
+            created_at,
+            DateTime::from_timestamp_millis(1462015105796).unwrap()
+        );
+
+        assert!(!is_new_account(
+            175928847299117063,
+            created_at + TimeDelta::days(8)
+        ));
+        assert!(is_new_account(
+            175928847299117063,
+            created_at + TimeDelta::days(1)
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 use ap_lobby::db::{Json, YamlId};
 use ap_lobby::error::{Error, Result};
-use ap_lobby::extractor::extract_features;
+use ap_lobby::extractor::{extract_features, load_feature_definitions};
 use ap_lobby::{db::YamlFile, schema::yamls};
 use diesel::prelude::*;
 use diesel_async::scoped_futures::ScopedFutureExt;
@@ -26,6 +26,11 @@ async fn main() -> Result<()> {
 
     let mut conn = db_pool.get().await?;
 
+    let index_path = std::path::PathBuf::from(
+        std::env::var("APWORLDS_INDEX_DIR").unwrap_or_else(|_| "./index".into()),
+    );
+    let feature_definitions = load_feature_definitions(&index_path)?;
+
     let all_yamls: Vec<(YamlId, String)> = yamls::table
         .select((yamls::id, yamls::content))
         .load(&mut conn)
@@ -38,7 +43,7 @@ async fn main() -> Result<()> {
                 else {
                     continue;
                 };
-                let Ok(features) = extract_features(&parsed, raw_yaml) else {
+                let Ok(features) = extract_features(&parsed, raw_yaml, &feature_definitions) else {
                     continue;
                 };
 
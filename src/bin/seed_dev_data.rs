@@ -0,0 +1,81 @@
+use ap_lobby::db::factories::{RoomFactory, RoomTemplateFactory, UserFactory, YamlFactory};
+use ap_lobby::error::Result;
+use diesel_async::pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager};
+use diesel_async::AsyncPgConnection;
+use dotenvy::dotenv;
+
+const GAMES: &[(&str, &[(&str, &str)])] = &[
+    ("A Link to the Past", &[("death_link", "true")]),
+    (
+        "Pokemon Emerald",
+        &[
+            ("trainersanity", "true"),
+            ("dexsanity", "\n    'false': 50\n    'true': 50"),
+        ],
+    ),
+    ("Pokemon Red and Blue", &[("trainersanity", "false")]),
+    ("Super Mario 64", &[("death_link", "false")]),
+];
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+
+    let db_url = std::env::var("DATABASE_URL").expect("Provide a DATABASE_URL env variable");
+    let mgr = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    let db_pool = Pool::builder(mgr)
+        .build()
+        .expect("Failed to create database pool, aborting");
+    let mut conn = db_pool.get().await?;
+
+    let mut users = Vec::new();
+    for id in 1..=6 {
+        users.push(UserFactory::new(id).create(&mut conn).await?);
+    }
+    let organizer = &users[0];
+
+    let template = RoomTemplateFactory::new(organizer.id)
+        .tpl_name("Weekly async")
+        .create(&mut conn)
+        .await?;
+
+    let rooms = vec![
+        RoomFactory::new(organizer.id)
+            .name("Weekly async")
+            .description("A room created from a template")
+            .from_template(template.id)
+            .create(&mut conn)
+            .await?,
+        RoomFactory::new(organizer.id)
+            .name("Limited room")
+            .yaml_limit_per_user(1)
+            .create(&mut conn)
+            .await?,
+        RoomFactory::new(users[1].id)
+            .name("Closed room")
+            .closed()
+            .create(&mut conn)
+            .await?,
+    ];
+
+    for room in &rooms {
+        for (i, user) in users.iter().enumerate() {
+            let (game, options) = GAMES[i % GAMES.len()];
+            let mut yaml = YamlFactory::new(room.id, user.id)
+                .player_name(&format!("{}{}", user.username, i))
+                .game(game);
+            for (name, value) in options.iter() {
+                yaml = yaml.option(name, value);
+            }
+            yaml.create(&mut conn).await?;
+        }
+    }
+
+    println!(
+        "Created {} users, 1 template and {} rooms",
+        users.len(),
+        rooms.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use rocket::data::{ByteUnit, Limits};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use crate::error::{Error, Result};
+
+/// A request body limit for a group of routes.
+///
+/// Rocket's own limits are per data type ("form", "data-form", "string"...) and shared by every
+/// route. Route limits live in the same `limits` table so they can be configured the same way,
+/// e.g. `ROCKET_LIMITS={yaml-upload="4MiB"}`, and routes check them with [`ContentLength`] so
+/// people get a proper message instead of a bare 413.
+pub struct BodyLimit {
+    pub name: &'static str,
+    pub default: ByteUnit,
+    /// What's being sent, for error messages.
+    pub what: &'static str,
+    /// Rocket limits the body goes through before the route gets to check it.
+    pub data_limits: &'static [&'static str],
+}
+
+pub const YAML_UPLOAD: BodyLimit = BodyLimit {
+    name: "yaml-upload",
+    default: ByteUnit::Mebibyte(2),
+    what: "YAML upload",
+    data_limits: &["data-form", "string"],
+};
+
+pub const ROOM_SETTINGS: BodyLimit = BodyLimit {
+    name: "room-settings",
+    default: ByteUnit::Kibibyte(256),
+    what: "room settings form",
+    data_limits: &["form"],
+};
+
+pub const ROOM_IMPORT: BodyLimit = BodyLimit {
+    name: "room-import",
+    default: ByteUnit::Mebibyte(16),
+    what: "room import",
+    data_limits: &["json"],
+};
+
+pub const ROUTE_LIMITS: [&BodyLimit; 3] = [&YAML_UPLOAD, &ROOM_SETTINGS, &ROOM_IMPORT];
+
+impl BodyLimit {
+    pub fn get(&self, limits: &Limits) -> ByteUnit {
+        limits.get(self.name).unwrap_or(self.default)
+    }
+}
+
+/// Adds the route limits that aren't configured to `limits`, and raises the data limits they go
+/// through so that the route limit is the one that applies.
+pub fn with_route_limits(mut limits: Limits) -> Limits {
+    for route_limit in ROUTE_LIMITS {
+        let limit = route_limit.get(&limits);
+        limits = limits.limit(route_limit.name, limit);
+        for data_limit in route_limit.data_limits {
+            if limits.get(data_limit).is_none_or(|current| current < limit) {
+                limits = limits.limit(*data_limit, limit);
+            }
+        }
+    }
+
+    limits
+}
+
+/// The request's announced body size, to check against a route limit.
+pub struct ContentLength {
+    length: Option<u64>,
+    limits: Limits,
+}
+
+impl ContentLength {
+    pub fn check(&self, limit: &BodyLimit) -> Result<()> {
+        let max = limit.get(&self.limits);
+        if self.length.is_some_and(|length| length > max.as_u64()) {
+            return Err(Error::too_large(format!(
+                "This {} is too big, the limit is {}",
+                limit.what, max
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ContentLength {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let length = request
+            .headers()
+            .get_one("Content-Length")
+            .and_then(|length| length.parse().ok());
+
+        Outcome::Success(ContentLength {
+            length,
+            limits: request.limits().clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::data::{ByteUnit, Limits};
+
+    use super::{with_route_limits, ContentLength, ROOM_SETTINGS, YAML_UPLOAD};
+
+    #[test]
+    fn test_with_route_limits() {
+        let configured = Limits::default()
+            .limit("string", ByteUnit::Mebibyte(1))
+            .limit("yaml-upload", ByteUnit::Mebibyte(4));
+        let limits = with_route_limits(configured);
+
+        assert_eq!(limits.get("yaml-upload"), Some(ByteUnit::Mebibyte(4)));
+        assert_eq!(limits.get("string"), Some(ByteUnit::Mebibyte(4)));
+        assert_eq!(limits.get("room-settings"), Some(ROOM_SETTINGS.default));
+        assert_eq!(limits.get("form"), Some(ROOM_SETTINGS.default));
+    }
+
+    #[test]
+    fn test_content_length_check() {
+        let limits = with_route_limits(Limits::default());
+        let content_length = |length| ContentLength {
+            length,
+            limits: limits.clone(),
+        };
+
+        assert!(content_length(None).check(&YAML_UPLOAD).is_ok());
+        assert!(content_length(Some(1024)).check(&YAML_UPLOAD).is_ok());
+        let err = content_length(Some(3 * 1024 * 1024))
+            .check(&YAML_UPLOAD)
+            .unwrap_err();
+        assert_eq!(err.status(), rocket::http::Status::PayloadTooLarge);
+    }
+}
@@ -0,0 +1,74 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{ContentType, Header},
+    Request, Response,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+enum RouteClass {
+    /// Static assets linked with their hash in the query string, a new build changes the URL.
+    VersionedAsset,
+    StaticAsset,
+    /// Pages and API responses, they depend on the session and change all the time.
+    Dynamic,
+}
+
+impl RouteClass {
+    fn from_request(req: &Request) -> Self {
+        let path = req.uri().path();
+        if path.starts_with("/static/") || path == "/favicon.ico" {
+            if req.uri().query().is_some() {
+                RouteClass::VersionedAsset
+            } else {
+                RouteClass::StaticAsset
+            }
+        } else {
+            RouteClass::Dynamic
+        }
+    }
+
+    fn cache_control(&self) -> &'static str {
+        match self {
+            RouteClass::VersionedAsset => "public, max-age=31536000, immutable",
+            RouteClass::StaticAsset => "public, max-age=3600",
+            RouteClass::Dynamic => "private, no-cache",
+        }
+    }
+}
+
+/// Sets a `Cache-Control` header on responses that don't already come with one.
+pub struct CacheControlFairing;
+
+#[rocket::async_trait]
+impl Fairing for CacheControlFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cache control fairing",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if res.headers().contains("Cache-Control") {
+            return;
+        }
+
+        let class = RouteClass::from_request(req);
+        // Only successful asset responses are worth keeping around, a 404 might not be one after
+        // the next deploy.
+        if class != RouteClass::Dynamic && res.status().class().is_success() {
+            res.set_header(Header::new("Cache-Control", class.cache_control()));
+            return;
+        }
+
+        let is_page_or_api = res
+            .content_type()
+            .is_some_and(|ct| ct.is_html() || ct == ContentType::JSON);
+        if is_page_or_api {
+            res.set_header(Header::new(
+                "Cache-Control",
+                RouteClass::Dynamic.cache_control(),
+            ));
+        }
+    }
+}
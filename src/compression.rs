@@ -0,0 +1,176 @@
+use std::io::{Cursor, Write};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{ContentType, Header, Status},
+    Request, Response,
+};
+
+// Below that, the headers cost more than what compression would save.
+const MIN_COMPRESS_SIZE: usize = 1024;
+// Quality 11 is way too slow to run on every response, 5 is close to gzip's speed while still
+// compressing noticeably better.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    fn compress(&self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut writer =
+                    brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+                writer.write_all(body)?;
+                writer.flush()?;
+                Ok(writer.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Picks the encoding to use from an `Accept-Encoding` header, brotli is preferred when the
+/// client supports both.
+fn negotiate_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let accepted = |name: &str| {
+        accept_encoding.split(',').any(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let coding = parts.next().unwrap_or_default();
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.);
+
+            coding.eq_ignore_ascii_case(name) && quality > 0.
+        })
+    };
+
+    if accepted(Encoding::Brotli.as_str()) {
+        Some(Encoding::Brotli)
+    } else if accepted(Encoding::Gzip.as_str()) {
+        Some(Encoding::Gzip)
+    } else {
+        None
+    }
+}
+
+/// Only text formats are worth compressing, images and archives are already compressed.
+fn is_compressible(content_type: &ContentType) -> bool {
+    content_type.top() == "text"
+        || [
+            ContentType::JSON,
+            ContentType::JavaScript,
+            ContentType::SVG,
+            ContentType::XML,
+        ]
+        .iter()
+        .any(|compressible| compressible.media_type() == content_type.media_type())
+        || content_type.sub() == "yaml"
+        || content_type.sub() == "x-yaml"
+}
+
+/// Compresses text responses with brotli or gzip depending on what the client accepts.
+pub struct CompressionFairing;
+
+#[rocket::async_trait]
+impl Fairing for CompressionFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Compression fairing",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if res.status() == Status::NoContent || res.status() == Status::NotModified {
+            return;
+        }
+        if res.headers().contains("Content-Encoding") {
+            return;
+        }
+        if !res.content_type().is_some_and(|ct| is_compressible(&ct)) {
+            return;
+        }
+
+        // Whether we end up compressing or not, the response depends on the header.
+        res.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+
+        let Some(encoding) = req
+            .headers()
+            .get("Accept-Encoding")
+            .find_map(negotiate_encoding)
+        else {
+            return;
+        };
+
+        let body = match res.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("Failed to read response body for compression: {}", e);
+                return;
+            }
+        };
+
+        if body.len() < MIN_COMPRESS_SIZE {
+            res.set_sized_body(body.len(), Cursor::new(body));
+            return;
+        }
+
+        match encoding.compress(&body) {
+            Ok(compressed) => {
+                res.set_header(Header::new("Content-Encoding", encoding.as_str()));
+                res.set_sized_body(compressed.len(), Cursor::new(compressed));
+            }
+            Err(e) => {
+                tracing::error!("Failed to compress response: {}", e);
+                res.set_sized_body(body.len(), Cursor::new(body));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::ContentType;
+
+    use super::{is_compressible, negotiate_encoding, Encoding};
+
+    #[test]
+    fn test_negotiate_encoding() {
+        assert_eq!(
+            negotiate_encoding("gzip, deflate, br"),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(negotiate_encoding("gzip;q=0.8"), Some(Encoding::Gzip));
+        assert_eq!(negotiate_encoding("br;q=0, gzip"), Some(Encoding::Gzip));
+        assert_eq!(negotiate_encoding("deflate"), None);
+        assert_eq!(negotiate_encoding(""), None);
+    }
+
+    #[test]
+    fn test_is_compressible() {
+        assert!(is_compressible(&ContentType::HTML));
+        assert!(is_compressible(&ContentType::JSON));
+        assert!(is_compressible(&ContentType::CSS));
+        assert!(is_compressible(&ContentType::new("application", "x-yaml")));
+        assert!(!is_compressible(&ContentType::PNG));
+        assert!(!is_compressible(&ContentType::ZIP));
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::{AbuseSignalId, RoomId};
+use crate::error::Result;
+use crate::schema::{abuse_signals, discord_users, yamls};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+pub enum AbuseSignalKind {
+    /// Several new accounts uploaded the same YAML to a room.
+    DuplicateYamls,
+    /// Someone keeps deleting and uploading YAMLs.
+    DeleteLoop,
+}
+
+impl AbuseSignalKind {
+    pub const ALL: [AbuseSignalKind; 2] =
+        [AbuseSignalKind::DuplicateYamls, AbuseSignalKind::DeleteLoop];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbuseSignalKind::DuplicateYamls => "duplicate_yamls",
+            AbuseSignalKind::DeleteLoop => "delete_loop",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AbuseSignalKind::DuplicateYamls => "Duplicate YAMLs from new accounts",
+            AbuseSignalKind::DeleteLoop => "Delete/upload loop",
+        }
+    }
+}
+
+impl Display for AbuseSignalKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl ToSql<Text, Pg> for AbuseSignalKind {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for AbuseSignalKind {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        AbuseSignalKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| format!("Unknown abuse signal kind: {}", value).into())
+    }
+}
+
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = abuse_signals)]
+pub struct AbuseSignal {
+    pub id: AbuseSignalId,
+    pub user_id: i64,
+    pub room_id: Option<RoomId>,
+    pub kind: AbuseSignalKind,
+    pub details: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=abuse_signals)]
+struct NewAbuseSignal<'a> {
+    id: AbuseSignalId,
+    user_id: i64,
+    room_id: Option<RoomId>,
+    kind: AbuseSignalKind,
+    details: &'a str,
+}
+
+/// Records a signal for review. Returns false if the same signal is already waiting for review,
+/// in which case nothing is recorded.
+#[tracing::instrument(skip(conn))]
+pub async fn record_abuse_signal(
+    user_id: i64,
+    room_id: Option<RoomId>,
+    kind: AbuseSignalKind,
+    details: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<bool> {
+    let pending = abuse_signals::table
+        .filter(abuse_signals::user_id.eq(user_id))
+        .filter(abuse_signals::room_id.is_not_distinct_from(room_id))
+        .filter(abuse_signals::kind.eq(kind))
+        .filter(abuse_signals::reviewed_at.is_null());
+    let already_pending = diesel::select(diesel::dsl::exists(pending))
+        .get_result::<bool>(conn)
+        .await?;
+    if already_pending {
+        return Ok(false);
+    }
+
+    diesel::insert_into(abuse_signals::table)
+        .values(NewAbuseSignal {
+            id: AbuseSignalId::new_v4(),
+            user_id,
+            room_id,
+            kind,
+            details,
+        })
+        .execute(conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Signals waiting for review along with the name of the user they're about, oldest first.
+#[tracing::instrument(skip(conn))]
+pub async fn list_pending_abuse_signals(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(AbuseSignal, String)>> {
+    Ok(abuse_signals::table
+        .inner_join(discord_users::table)
+        .filter(abuse_signals::reviewed_at.is_null())
+        .order(abuse_signals::created_at.asc())
+        .select((AbuseSignal::as_select(), discord_users::username))
+        .load(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_abuse_signal(
+    signal_id: AbuseSignalId,
+    conn: &mut AsyncPgConnection,
+) -> Result<AbuseSignal> {
+    Ok(abuse_signals::table
+        .find(signal_id)
+        .select(AbuseSignal::as_select())
+        .first(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn mark_abuse_signal_reviewed(
+    signal_id: AbuseSignalId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(abuse_signals::table.find(signal_id))
+        .set(abuse_signals::reviewed_at.eq(diesel::dsl::now))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Owners of YAMLs in the room that have the same content as one of `owner_id`'s, `owner_id`
+/// included.
+#[tracing::instrument(skip(conn))]
+pub async fn get_owners_of_matching_yamls(
+    room_id: RoomId,
+    owner_id: i64,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<i64>> {
+    let owner_fingerprints = yamls::table
+        .filter(yamls::room_id.eq(room_id))
+        .filter(yamls::owner_id.eq(owner_id))
+        .filter(yamls::content_fingerprint.is_not_null())
+        .select(yamls::content_fingerprint);
+
+    Ok(yamls::table
+        .filter(yamls::room_id.eq(room_id))
+        .filter(yamls::content_fingerprint.eq_any(owner_fingerprints))
+        .select(yamls::owner_id)
+        .distinct()
+        .load(conn)
+        .await?)
+}
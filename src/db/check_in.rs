@@ -0,0 +1,198 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+use crate::db::RoomId;
+use crate::error::{Error, Result};
+use crate::schema::{room_check_ins, yamls};
+
+#[derive(Insertable)]
+#[diesel(table_name=room_check_ins)]
+struct NewCheckIn {
+    room_id: RoomId,
+}
+
+/// A room's check-in window. Players confirm they're still in while it's open, which lets the
+/// organizer drop the YAMLs of people who vanished before generating.
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = room_check_ins)]
+pub struct CheckIn {
+    pub opened_at: NaiveDateTime,
+    pub closed_at: Option<NaiveDateTime>,
+}
+
+impl CheckIn {
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+    }
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_room_check_in(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<CheckIn>> {
+    Ok(room_check_ins::table
+        .find(room_id)
+        .select(CheckIn::as_select())
+        .first(conn)
+        .await
+        .optional()?)
+}
+
+/// Opens a new check-in round. Confirmations and exclusions from a previous round are reset so
+/// that everyone has to check in again.
+#[tracing::instrument(skip(conn))]
+pub async fn open_room_check_in(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<()> {
+    diesel::insert_into(room_check_ins::table)
+        .values(NewCheckIn { room_id })
+        .on_conflict(room_check_ins::room_id)
+        .do_update()
+        .set((
+            room_check_ins::opened_at.eq(diesel::dsl::now),
+            room_check_ins::closed_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await?;
+
+    diesel::update(yamls::table.filter(yamls::room_id.eq(room_id)))
+        .set((
+            yamls::checked_in_at.eq(None::<NaiveDateTime>),
+            yamls::excluded.eq(false),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn close_room_check_in(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<()> {
+    diesel::update(room_check_ins::table.find(room_id))
+        .set(room_check_ins::closed_at.eq(diesel::dsl::now))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Confirms every YAML `owner_id` has in the room. Returns how many got confirmed.
+#[tracing::instrument(skip(conn))]
+pub async fn check_in_yamls(
+    room_id: RoomId,
+    owner_id: i64,
+    conn: &mut AsyncPgConnection,
+) -> Result<usize> {
+    Ok(diesel::update(
+        yamls::table
+            .filter(yamls::room_id.eq(room_id))
+            .filter(yamls::owner_id.eq(owner_id))
+            .filter(yamls::checked_in_at.is_null()),
+    )
+    .set(yamls::checked_in_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?)
+}
+
+/// Excludes every YAML that wasn't confirmed during the check-in and gives the slots that freed
+/// up to the waitlisted YAMLs that were. Returns how many got excluded.
+#[tracing::instrument(skip(conn))]
+pub async fn exclude_unconfirmed_yamls(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<usize> {
+    conn.transaction::<_, Error, _>(|conn| {
+        async move {
+            let excluded = diesel::update(
+                yamls::table
+                    .filter(yamls::room_id.eq(room_id))
+                    .filter(yamls::checked_in_at.is_null()),
+            )
+            .set(yamls::excluded.eq(true))
+            .execute(conn)
+            .await?;
+
+            let promoted = super::promote_waitlisted_yamls(room_id, conn).await?;
+            if !promoted.is_empty() {
+                tracing::info!(%room_id, ?promoted, "Promoted YAMLs off the waitlist");
+            }
+
+            Ok(excluded)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[cfg(all(test, feature = "factories"))]
+mod tests {
+    use diesel_async::{AsyncConnection, AsyncPgConnection};
+
+    use crate::db::factories::{RoomFactory, UserFactory, YamlFactory};
+    use crate::db::{self, Yaml};
+
+    // Needs a migrated database, set DATABASE_URL and run
+    // `cargo test --features factories -- --ignored`. Nothing gets committed.
+    async fn test_conn() -> AsyncPgConnection {
+        let url =
+            std::env::var("DATABASE_URL").expect("Set DATABASE_URL to run the database tests");
+        let mut conn = AsyncPgConnection::establish(&url).await.unwrap();
+        conn.begin_test_transaction().await.unwrap();
+        conn
+    }
+
+    fn find<'a>(yamls: &'a [Yaml], player_name: &str) -> &'a Yaml {
+        yamls
+            .iter()
+            .find(|yaml| yaml.player_name == player_name)
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a database"]
+    async fn test_exclude_unconfirmed_promotes_waitlisted_yamls() {
+        let mut conn = test_conn().await;
+        let organizer = UserFactory::new(1001).create(&mut conn).await.unwrap();
+        let player = UserFactory::new(1002).create(&mut conn).await.unwrap();
+        let room = RoomFactory::new(organizer.id)
+            .create(&mut conn)
+            .await
+            .unwrap();
+        db::set_room_slot_cap(&room, Some(1), Some(organizer.id), &mut conn)
+            .await
+            .unwrap();
+        for (owner_id, player_name) in [
+            (organizer.id, "Vanished"),
+            (player.id, "Waiting"),
+            (organizer.id, "AlsoVanished"),
+        ] {
+            YamlFactory::new(room.id, owner_id)
+                .player_name(player_name)
+                .create(&mut conn)
+                .await
+                .unwrap();
+        }
+        let yamls = db::get_yamls_for_room(room.id, &mut conn).await.unwrap();
+        assert!(find(&yamls, "Waiting").waitlisted);
+
+        db::open_room_check_in(room.id, &mut conn).await.unwrap();
+        db::check_in_yamls(room.id, player.id, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            db::exclude_unconfirmed_yamls(room.id, &mut conn)
+                .await
+                .unwrap(),
+            2
+        );
+
+        let yamls = db::get_yamls_for_room(room.id, &mut conn).await.unwrap();
+        let waiting = find(&yamls, "Waiting");
+        assert!(!waiting.excluded && !waiting.waitlisted);
+        // Excluded YAMLs don't get the freed slots.
+        let also_vanished = find(&yamls, "AlsoVanished");
+        assert!(also_vanished.excluded && also_vanished.waitlisted);
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt::Display;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::{DeliveryId, Json};
+use crate::error::{Error, Result};
+use crate::schema::outbound_deliveries;
+
+/// Deliveries are dead-lettered after that many failed attempts.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+const FIRST_RETRY_DELAY: TimeDelta = TimeDelta::seconds(30);
+const MAX_RETRY_DELAY: TimeDelta = TimeDelta::hours(2);
+
+/// Where a delivery goes. Each channel has its own adapter in the deliverer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+pub enum DeliveryChannel {
+    /// The target is a discord webhook URL, the payload the message to execute it with.
+    DiscordWebhook,
+}
+
+impl DeliveryChannel {
+    pub const ALL: [DeliveryChannel; 1] = [DeliveryChannel::DiscordWebhook];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryChannel::DiscordWebhook => "discord_webhook",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeliveryChannel::DiscordWebhook => "Discord webhook",
+        }
+    }
+}
+
+impl Display for DeliveryChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl ToSql<Text, Pg> for DeliveryChannel {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for DeliveryChannel {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        DeliveryChannel::ALL
+            .into_iter()
+            .find(|channel| channel.as_str() == value)
+            .ok_or_else(|| format!("Unknown delivery channel: {}", value).into())
+    }
+}
+
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = outbound_deliveries)]
+pub struct Delivery {
+    pub id: DeliveryId,
+    pub channel: DeliveryChannel,
+    pub target: String,
+    pub payload: Json<serde_json::Value>,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// Set once the delivery gave up, it stays around for admins to look at.
+    pub dead_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=outbound_deliveries)]
+struct NewDelivery<'a> {
+    id: DeliveryId,
+    channel: DeliveryChannel,
+    target: &'a str,
+    payload: Json<serde_json::Value>,
+}
+
+/// When to try again after `attempts` failed attempts, or `None` once the delivery should be
+/// dead-lettered. The delay doubles with every attempt.
+pub fn next_delivery_attempt(attempts: i32, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    if attempts >= MAX_DELIVERY_ATTEMPTS {
+        return None;
+    }
+
+    let delay = FIRST_RETRY_DELAY * 2i32.pow(attempts.saturating_sub(1) as u32);
+    Some(now + delay.min(MAX_RETRY_DELAY))
+}
+
+/// Queues something to send. It goes out on the deliverer's next tick.
+#[tracing::instrument(skip(payload, conn))]
+pub async fn enqueue_delivery(
+    channel: DeliveryChannel,
+    target: &str,
+    payload: serde_json::Value,
+    conn: &mut AsyncPgConnection,
+) -> Result<DeliveryId> {
+    let id = DeliveryId::new_v4();
+    diesel::insert_into(outbound_deliveries::table)
+        .values(NewDelivery {
+            id,
+            channel,
+            target,
+            payload: Json(payload),
+        })
+        .execute(conn)
+        .await?;
+
+    Ok(id)
+}
+
+/// Deliveries that should be attempted by `now`, oldest first.
+#[tracing::instrument(skip(conn))]
+pub async fn get_due_deliveries(
+    now: NaiveDateTime,
+    limit: i64,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<Delivery>> {
+    Ok(outbound_deliveries::table
+        .filter(outbound_deliveries::dead_at.is_null())
+        .filter(outbound_deliveries::next_attempt_at.le(now))
+        .order(outbound_deliveries::next_attempt_at.asc())
+        .limit(limit)
+        .select(Delivery::as_select())
+        .load(conn)
+        .await?)
+}
+
+/// Delivered messages aren't kept.
+#[tracing::instrument(skip(conn))]
+pub async fn record_delivery_success(
+    delivery_id: DeliveryId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::delete(outbound_deliveries::table.find(delivery_id))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Schedules the next attempt, or dead-letters the delivery once it ran out of attempts.
+#[tracing::instrument(skip(conn))]
+pub async fn record_delivery_failure(
+    delivery: &Delivery,
+    error: &str,
+    now: NaiveDateTime,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let attempts = delivery.attempts + 1;
+    let next_attempt_at = next_delivery_attempt(attempts, now);
+    diesel::update(outbound_deliveries::table.find(delivery.id))
+        .set((
+            outbound_deliveries::attempts.eq(attempts),
+            outbound_deliveries::last_error.eq(error),
+            outbound_deliveries::next_attempt_at.eq(next_attempt_at.unwrap_or(now)),
+            outbound_deliveries::dead_at.eq(next_attempt_at.is_none().then_some(now)),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Dead-lettered deliveries, most recent first.
+#[tracing::instrument(skip(conn))]
+pub async fn list_dead_deliveries(conn: &mut AsyncPgConnection) -> Result<Vec<Delivery>> {
+    Ok(outbound_deliveries::table
+        .filter(outbound_deliveries::dead_at.is_not_null())
+        .order(outbound_deliveries::dead_at.desc())
+        .select(Delivery::as_select())
+        .load(conn)
+        .await?)
+}
+
+/// Gives a dead-lettered delivery a new set of attempts, starting right away.
+#[tracing::instrument(skip(conn))]
+pub async fn retry_dead_delivery(
+    delivery_id: DeliveryId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let updated = diesel::update(
+        outbound_deliveries::table
+            .find(delivery_id)
+            .filter(outbound_deliveries::dead_at.is_not_null()),
+    )
+    .set((
+        outbound_deliveries::attempts.eq(0),
+        outbound_deliveries::next_attempt_at.eq(diesel::dsl::now),
+        outbound_deliveries::dead_at.eq(None::<NaiveDateTime>),
+    ))
+    .execute(conn)
+    .await?;
+    if updated == 0 {
+        return Err(Error::not_found("Unknown failed delivery"));
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn delete_dead_delivery(
+    delivery_id: DeliveryId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::delete(
+        outbound_deliveries::table
+            .find(delivery_id)
+            .filter(outbound_deliveries::dead_at.is_not_null()),
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeDelta};
+
+    use super::{next_delivery_attempt, MAX_DELIVERY_ATTEMPTS};
+
+    #[test]
+    fn test_next_delivery_attempt() {
+        let now = NaiveDateTime::default();
+        assert_eq!(
+            next_delivery_attempt(1, now),
+            Some(now + TimeDelta::seconds(30))
+        );
+        assert_eq!(
+            next_delivery_attempt(3, now),
+            Some(now + TimeDelta::minutes(2))
+        );
+        assert_eq!(
+            next_delivery_attempt(MAX_DELIVERY_ATTEMPTS - 1, now),
+            Some(now + TimeDelta::minutes(32))
+        );
+        assert_eq!(next_delivery_attempt(MAX_DELIVERY_ATTEMPTS, now), None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Builders creating realistic rows in the database, used by tests and to seed a local
+//! development database.
+use apwm::Manifest;
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel_async::AsyncPgConnection;
+
+use crate::db::{
+    self, DiscordUser, Json, NewRoom, NewRoomTemplate, Room, RoomId, RoomTemplate, RoomTemplateId,
+    YamlFile,
+};
+use crate::error::Result;
+use crate::extractor::FeatureDefinitions;
+
+pub struct UserFactory {
+    id: i64,
+    username: String,
+}
+
+impl UserFactory {
+    pub fn new(id: i64) -> Self {
+        Self {
+            id,
+            username: format!("user{}", id),
+        }
+    }
+
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = username.to_string();
+        self
+    }
+
+    pub async fn create(self, conn: &mut AsyncPgConnection) -> Result<DiscordUser> {
+        db::upsert_discord_user(self.id, &self.username, conn).await?;
+
+        Ok(DiscordUser {
+            id: self.id,
+            username: self.username,
+        })
+    }
+}
+
+pub struct RoomFactory {
+    name: String,
+    description: String,
+    author_id: i64,
+    close_date: NaiveDateTime,
+    yaml_validation: bool,
+    allow_unsupported: bool,
+    yaml_limit_per_user: Option<i32>,
+    manifest: Manifest,
+    show_apworlds: bool,
+    from_template_id: Option<RoomTemplateId>,
+}
+
+impl RoomFactory {
+    pub fn new(author_id: i64) -> Self {
+        Self {
+            name: "Test room".to_string(),
+            description: "".to_string(),
+            author_id,
+            close_date: chrono::Utc::now().naive_utc() + TimeDelta::days(7),
+            // Validation needs workers, most setups using factories won't have any.
+            yaml_validation: false,
+            allow_unsupported: true,
+            yaml_limit_per_user: None,
+            manifest: Manifest::new(),
+            show_apworlds: true,
+            from_template_id: None,
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn close_date(mut self, close_date: NaiveDateTime) -> Self {
+        self.close_date = close_date;
+        self
+    }
+
+    pub fn closed(mut self) -> Self {
+        self.close_date = chrono::Utc::now().naive_utc() - TimeDelta::days(1);
+        self
+    }
+
+    pub fn yaml_validation(mut self, yaml_validation: bool) -> Self {
+        self.yaml_validation = yaml_validation;
+        self
+    }
+
+    pub fn yaml_limit_per_user(mut self, limit: i32) -> Self {
+        self.yaml_limit_per_user = Some(limit);
+        self
+    }
+
+    pub fn manifest(mut self, manifest: Manifest) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    pub fn from_template(mut self, template_id: RoomTemplateId) -> Self {
+        self.from_template_id = Some(template_id);
+        self
+    }
+
+    pub async fn create(self, conn: &mut AsyncPgConnection) -> Result<Room> {
+        let new_room = NewRoom {
+            id: RoomId::new_v4(),
+            name: &self.name,
+            close_date: self.close_date,
+            description: &self.description,
+            room_url: "",
+            author_id: Some(self.author_id),
+            yaml_limit_per_user: self.yaml_limit_per_user,
+            yaml_validation: self.yaml_validation,
+            allow_unsupported: self.allow_unsupported,
+            yaml_limit_bypass_list: vec![],
+            manifest: Json(self.manifest),
+            show_apworlds: self.show_apworlds,
+            from_template_id: Some(self.from_template_id),
+            exact_yaml_storage: None,
+            upload_hints: None,
+            max_slots: None,
+            feature_policies: None,
+            room_url_visibility: None,
+        };
+
+        db::create_room(&new_room, conn).await
+    }
+}
+
+pub struct RoomTemplateFactory {
+    tpl_name: String,
+    author_id: i64,
+    global: bool,
+    manifest: Manifest,
+}
+
+impl RoomTemplateFactory {
+    pub fn new(author_id: i64) -> Self {
+        Self {
+            tpl_name: "Test template".to_string(),
+            author_id,
+            global: false,
+            manifest: Manifest::new(),
+        }
+    }
+
+    pub fn tpl_name(mut self, tpl_name: &str) -> Self {
+        self.tpl_name = tpl_name.to_string();
+        self
+    }
+
+    pub fn global(mut self, global: bool) -> Self {
+        self.global = global;
+        self
+    }
+
+    pub fn manifest(mut self, manifest: Manifest) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    pub async fn create(self, conn: &mut AsyncPgConnection) -> Result<RoomTemplate> {
+        let new_tpl = NewRoomTemplate {
+            id: RoomTemplateId::new_v4(),
+            tpl_name: &self.tpl_name,
+            name: &self.tpl_name,
+            close_date: chrono::Utc::now().naive_utc(),
+            description: "",
+            room_url: "",
+            author_id: Some(self.author_id),
+            yaml_limit_per_user: None,
+            yaml_validation: false,
+            allow_unsupported: true,
+            yaml_limit_bypass_list: vec![],
+            manifest: Json(self.manifest),
+            show_apworlds: true,
+            global: self.global,
+        };
+
+        db::create_room_template(&new_tpl, conn).await
+    }
+}
+
+pub struct YamlFactory {
+    room_id: RoomId,
+    owner_id: i64,
+    player_name: String,
+    game: String,
+    options: Vec<(String, String)>,
+}
+
+impl YamlFactory {
+    pub fn new(room_id: RoomId, owner_id: i64) -> Self {
+        Self {
+            room_id,
+            owner_id,
+            player_name: "Player".to_string(),
+            game: "A Link to the Past".to_string(),
+            options: vec![],
+        }
+    }
+
+    pub fn player_name(mut self, player_name: &str) -> Self {
+        self.player_name = player_name.to_string();
+        self
+    }
+
+    pub fn game(mut self, game: &str) -> Self {
+        self.game = game.to_string();
+        self
+    }
+
+    /// Adds an option to the game section of the YAML, the value is inserted verbatim.
+    pub fn option(mut self, name: &str, value: &str) -> Self {
+        self.options.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn content(&self) -> String {
+        let mut content = format!(
+            "name: {}\ngame: {}\n{}:\n",
+            self.player_name, self.game, self.game
+        );
+        for (name, value) in &self.options {
+            content += &format!("  {}: {}\n", name, value);
+        }
+
+        content
+    }
+
+    pub async fn create(self, conn: &mut AsyncPgConnection) -> Result<()> {
+        let content = self.content();
+        let parsed: YamlFile = serde_yaml::from_str(&content)?;
+        let features =
+            crate::extractor::extract_features(&parsed, &content, &FeatureDefinitions::new())?;
+
+        db::add_yaml_to_room(
+            self.room_id,
+            self.owner_id,
+            &self.game,
+            &content,
+            &parsed,
+            features,
+            vec![],
+            None,
+            conn,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
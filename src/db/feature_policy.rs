@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rocket::form::FromFormField;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Json, RoomId};
+use crate::error::{Error, Result};
+use crate::extractor::{YamlFeature, YamlFeatures};
+use crate::schema::rooms;
+
+/// What happens when a YAML uploaded to a room might have a feature on.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    FromFormField,
+    FromSqlRow,
+    AsExpression,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum FeaturePolicy {
+    #[default]
+    #[field(value = "allow")]
+    Allow,
+    /// The upload goes through, the player gets told about it.
+    #[field(value = "warn")]
+    Warn,
+    #[field(value = "block")]
+    Block,
+}
+
+/// Policies set by the organizer. Features that aren't in there are allowed.
+pub type FeaturePolicies = BTreeMap<YamlFeature, FeaturePolicy>;
+
+impl FeaturePolicy {
+    pub const ALL: [FeaturePolicy; 3] = [
+        FeaturePolicy::Allow,
+        FeaturePolicy::Warn,
+        FeaturePolicy::Block,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeaturePolicy::Allow => "allow",
+            FeaturePolicy::Warn => "warn",
+            FeaturePolicy::Block => "block",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FeaturePolicy::Allow => "Allowed",
+            FeaturePolicy::Warn => "Allowed with a warning",
+            FeaturePolicy::Block => "Not allowed",
+        }
+    }
+}
+
+impl Display for FeaturePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl ToSql<Text, Pg> for FeaturePolicy {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for FeaturePolicy {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        FeaturePolicy::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == value)
+            .ok_or_else(|| format!("Unknown feature policy: {}", value).into())
+    }
+}
+
+/// Policies are stored by feature name. Features that don't exist anymore are skipped rather
+/// than failing to load the room.
+pub(crate) fn feature_policies_from_names(
+    policies: BTreeMap<String, FeaturePolicy>,
+) -> FeaturePolicies {
+    policies
+        .into_iter()
+        .filter_map(|(feature, policy)| Some((YamlFeature::from_name(&feature)?, policy)))
+        .collect()
+}
+
+/// Allowed features aren't stored.
+pub(crate) fn feature_policies_by_name(
+    policies: &FeaturePolicies,
+) -> BTreeMap<String, FeaturePolicy> {
+    policies
+        .iter()
+        .filter(|(_, policy)| **policy != FeaturePolicy::Allow)
+        .map(|(feature, policy)| (feature.as_str().to_string(), *policy))
+        .collect()
+}
+
+/// Replaces all of the room's policies.
+#[tracing::instrument(skip(conn))]
+pub async fn set_room_feature_policies(
+    room_id: RoomId,
+    policies: &FeaturePolicies,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(rooms::table.find(room_id))
+        .set(rooms::feature_policies.eq(Json(feature_policies_by_name(policies))))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Checks the features extracted from a YAML against the room's policies. Returns the warnings
+/// to show to the player, or an error if a blocked feature might be on.
+pub fn check_feature_policies(
+    policies: &FeaturePolicies,
+    player_name: &str,
+    features: &YamlFeatures,
+) -> Result<Vec<String>> {
+    let mut warnings = vec![];
+    for (feature, policy) in policies {
+        let probability = features.get(feature).copied().unwrap_or(0);
+        if probability == 0 {
+            continue;
+        }
+
+        let has_feature = if probability >= 10000 {
+            format!("{}'s YAML has {} on", player_name, feature.as_str())
+        } else {
+            format!(
+                "{}'s YAML might have {} on ({:.2}%)",
+                player_name,
+                feature.as_str(),
+                probability as f64 / 100.
+            )
+        };
+        match policy {
+            FeaturePolicy::Allow => {}
+            FeaturePolicy::Warn => warnings.push(format!(
+                "{}, check the room's rules before playing with it",
+                has_feature
+            )),
+            FeaturePolicy::Block => {
+                return Err(Error::validation(format!(
+                    "{}, this room doesn't allow it. Turn it off and upload again.",
+                    has_feature
+                )))
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        check_feature_policies, feature_policies_by_name, feature_policies_from_names,
+        FeaturePolicies, FeaturePolicy,
+    };
+    use crate::extractor::YamlFeature;
+
+    #[test]
+    fn test_check_feature_policies() {
+        let policies = FeaturePolicies::from([
+            (YamlFeature::DeathLink, FeaturePolicy::Block),
+            (YamlFeature::DexSanity, FeaturePolicy::Warn),
+        ]);
+
+        let features = HashMap::from([(YamlFeature::DexSanity, 5000)]);
+        assert_eq!(
+            check_feature_policies(&policies, "Ash", &features).unwrap(),
+            vec!["Ash's YAML might have DexSanity on (50.00%), check the room's rules before playing with it"]
+        );
+
+        let features = HashMap::from([(YamlFeature::DeathLink, 0), (YamlFeature::TrapLink, 10000)]);
+        assert!(check_feature_policies(&policies, "Ash", &features)
+            .unwrap()
+            .is_empty());
+
+        let features = HashMap::from([(YamlFeature::DeathLink, 10000)]);
+        assert!(check_feature_policies(&policies, "Ash", &features).is_err());
+    }
+
+    #[test]
+    fn test_stored_feature_policies() {
+        let policies = FeaturePolicies::from([
+            (YamlFeature::DeathLink, FeaturePolicy::Block),
+            (YamlFeature::TrapLink, FeaturePolicy::Allow),
+        ]);
+        let mut stored = feature_policies_by_name(&policies);
+        assert_eq!(
+            serde_json::to_string(&stored).unwrap(),
+            r#"{"DeathLink":"block"}"#
+        );
+
+        stored.insert("RemovedLink".to_string(), FeaturePolicy::Warn);
+        assert_eq!(
+            feature_policies_from_names(stored),
+            FeaturePolicies::from([(YamlFeature::DeathLink, FeaturePolicy::Block)])
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::short_link::generate_readable_code;
+use crate::db::{upsert_discord_user, RoomId};
+use crate::error::{Error, Result};
+use crate::schema::{discord_users, room_guest_links, yamls};
+
+// Guest links let someone upload without logging in, they have to be hard to guess.
+const GUEST_LINK_CODE_LEN: usize = 16;
+const MAX_GUEST_NAME_LEN: usize = 32;
+
+/// A single-use link an organizer hands to someone without a discord account so they can upload
+/// to the room.
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = room_guest_links)]
+pub struct GuestLink {
+    pub code: String,
+    pub room_id: RoomId,
+    /// The pseudonymous user uploading through the link, set on the first upload attempt.
+    pub guest_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+impl GuestLink {
+    pub fn is_used(&self) -> bool {
+        self.used_at.is_some()
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=room_guest_links)]
+struct NewGuestLink<'a> {
+    code: &'a str,
+    room_id: RoomId,
+}
+
+/// Discord IDs are positive, guests get negative ones so they can never collide.
+pub fn new_guest_id() -> i64 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let random = u64::from_le_bytes(bytes[..8].try_into().unwrap()) >> 1;
+    -(random as i64) - 1
+}
+
+pub fn is_guest(user_id: i64) -> bool {
+    user_id < 0
+}
+
+/// The name guests are shown as. It's marked so that nobody mistakes them for a discord user.
+pub fn guest_display_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::validation("Pick a name to upload as"));
+    }
+    if name.chars().count() > MAX_GUEST_NAME_LEN {
+        return Err(Error::validation(format!(
+            "Names can't be longer than {} characters",
+            MAX_GUEST_NAME_LEN
+        )));
+    }
+
+    Ok(format!("{} (guest)", name))
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn create_guest_link(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<String> {
+    let code = generate_readable_code(GUEST_LINK_CODE_LEN);
+    diesel::insert_into(room_guest_links::table)
+        .values(NewGuestLink {
+            code: &code,
+            room_id,
+        })
+        .execute(conn)
+        .await?;
+
+    Ok(code)
+}
+
+/// The room's guest links along with the name of the guest who used them, oldest first.
+#[tracing::instrument(skip(conn))]
+pub async fn list_guest_links(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(GuestLink, Option<String>)>> {
+    Ok(room_guest_links::table
+        .left_join(discord_users::table)
+        .filter(room_guest_links::room_id.eq(room_id))
+        .order(room_guest_links::created_at.asc())
+        .select((GuestLink::as_select(), discord_users::username.nullable()))
+        .load(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(code, conn))]
+pub async fn get_unused_guest_link(
+    room_id: RoomId,
+    code: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<GuestLink> {
+    room_guest_links::table
+        .find(code)
+        .filter(room_guest_links::room_id.eq(room_id))
+        .filter(room_guest_links::used_at.is_null())
+        .select(GuestLink::as_select())
+        .first(conn)
+        .await
+        .optional()?
+        .ok_or_else(|| Error::not_found("This guest link doesn't exist or has already been used"))
+}
+
+/// Creates or renames the guest user behind the link. It's created on the first upload attempt,
+/// before validation, so that jobs can be attributed to it and retries reuse it.
+#[tracing::instrument(skip(link, conn))]
+pub async fn upsert_guest_for_link(
+    link: &GuestLink,
+    guest_name: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<i64> {
+    let guest_id = link.guest_id.unwrap_or_else(new_guest_id);
+    upsert_discord_user(guest_id, guest_name, conn).await?;
+    diesel::update(room_guest_links::table.find(&link.code))
+        .set(room_guest_links::guest_id.eq(guest_id))
+        .execute(conn)
+        .await?;
+
+    Ok(guest_id)
+}
+
+/// Marks the link as used. Meant to run in the same transaction as the upload so that a link
+/// can't be used twice.
+#[tracing::instrument(skip(code, conn))]
+pub async fn claim_guest_link(
+    room_id: RoomId,
+    code: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let claimed = diesel::update(
+        room_guest_links::table
+            .find(code)
+            .filter(room_guest_links::room_id.eq(room_id))
+            .filter(room_guest_links::used_at.is_null()),
+    )
+    .set(room_guest_links::used_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+    if claimed == 0 {
+        return Err(Error::conflict("This guest link has already been used"));
+    }
+
+    Ok(())
+}
+
+/// Removes the link, along with everything the guest uploaded to the room.
+#[tracing::instrument(skip(code, conn))]
+pub async fn revoke_guest_link(
+    room_id: RoomId,
+    code: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let link = room_guest_links::table
+        .find(code)
+        .filter(room_guest_links::room_id.eq(room_id))
+        .select(GuestLink::as_select())
+        .first(conn)
+        .await?;
+
+    if let Some(guest_id) = link.guest_id {
+        diesel::delete(
+            yamls::table
+                .filter(yamls::room_id.eq(room_id))
+                .filter(yamls::owner_id.eq(guest_id)),
+        )
+        .execute(conn)
+        .await?;
+    }
+    diesel::delete(room_guest_links::table.find(code))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{guest_display_name, is_guest, new_guest_id};
+
+    #[test]
+    fn test_new_guest_id() {
+        for _ in 0..100 {
+            assert!(is_guest(new_guest_id()));
+        }
+        assert!(!is_guest(175928847299117063));
+    }
+
+    #[test]
+    fn test_guest_display_name() {
+        assert_eq!(guest_display_name(" Zelda ").unwrap(), "Zelda (guest)");
+        assert!(guest_display_name("  ").is_err());
+        assert!(guest_display_name(&"a".repeat(33)).is_err());
+    }
+}
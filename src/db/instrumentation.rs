@@ -1,24 +1,121 @@
-use std::time::Instant;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use chrono::NaiveDateTime;
 use diesel::connection::Instrumentation;
 use once_cell::sync::Lazy;
 use prometheus::{HistogramOpts, HistogramVec};
 
+const MAX_SLOW_QUERIES: usize = 20;
+
 #[derive(Default)]
 pub struct DbInstrumentation {
     query_start: Option<Instant>,
 }
 
+#[derive(Clone, Debug)]
+pub struct SlowQuery {
+    pub query: String,
+    pub duration: Duration,
+    pub recorded_at: NaiveDateTime,
+}
+
 pub static QUERY_HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
     HistogramVec::new(
         HistogramOpts::new("diesel_query_seconds", "SQL query duration").buckets(vec![
             0.000005, 0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.1, 1.0,
         ]),
-        &["query"],
+        &["statement", "table"],
     )
     .expect("Failed to create query histogram")
 });
 
+pub static POOL_WAIT_HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "pool_wait_seconds",
+            "Time spent waiting for a connection from the pool",
+        )
+        .buckets(vec![
+            0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0,
+        ]),
+        &["pool"],
+    )
+    .expect("Failed to create pool wait histogram")
+});
+
+static SLOW_QUERY_THRESHOLD: Lazy<Duration> = Lazy::new(|| {
+    let threshold_ms = std::env::var("DB_SLOW_QUERY_THRESHOLD_MS")
+        .ok()
+        .and_then(|threshold| threshold.parse().ok())
+        .unwrap_or(200);
+
+    Duration::from_millis(threshold_ms)
+});
+
+static SLOW_QUERIES: Lazy<Mutex<Vec<SlowQuery>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Returns the slowest queries seen since startup, slowest first.
+pub fn slowest_queries() -> Vec<SlowQuery> {
+    SLOW_QUERIES.lock().unwrap().clone()
+}
+
+fn record_slow_query(query: &str, duration: Duration) {
+    let mut slow_queries = SLOW_QUERIES.lock().unwrap();
+    if slow_queries.len() >= MAX_SLOW_QUERIES
+        && slow_queries.last().map(|q| q.duration) >= Some(duration)
+    {
+        return;
+    }
+
+    slow_queries.push(SlowQuery {
+        query: query.to_string(),
+        duration,
+        recorded_at: chrono::Utc::now().naive_utc(),
+    });
+    slow_queries.sort_by(|a, b| b.duration.cmp(&a.duration));
+    slow_queries.truncate(MAX_SLOW_QUERIES);
+}
+
+/// Returns the statement kind and the main table a query operates on.
+///
+/// This only looks at the first keyword and the first identifier following the relevant clause,
+/// which is good enough for the queries diesel generates.
+fn query_labels(query: &str) -> (&'static str, &str) {
+    let statement = match query
+        .split_whitespace()
+        .next()
+        .map(|word| word.to_ascii_uppercase())
+        .as_deref()
+    {
+        Some("SELECT") => "select",
+        Some("INSERT") => "insert",
+        Some("UPDATE") => "update",
+        Some("DELETE") => "delete",
+        Some("BEGIN") | Some("COMMIT") | Some("ROLLBACK") => "transaction",
+        _ => "other",
+    };
+
+    let clause = match statement {
+        "insert" => " INTO ",
+        "update" => "UPDATE ",
+        _ => " FROM ",
+    };
+
+    // Subqueries (like the ones used for pagination) start with a parenthesis, skip them until we
+    // find an actual table name.
+    let table = query
+        .match_indices(clause)
+        .filter_map(|(idx, _)| query[idx + clause.len()..].split_whitespace().next())
+        .find(|table| !table.starts_with('('))
+        .and_then(|table| table.rsplit('.').next())
+        .map(|table| table.trim_matches(|c| c == '"' || c == ')'))
+        .filter(|table| !table.is_empty())
+        .unwrap_or("unknown");
+
+    (statement, table)
+}
+
 impl Instrumentation for DbInstrumentation {
     fn on_connection_event(&mut self, event: diesel::connection::InstrumentationEvent<'_>) {
         match event {
@@ -32,11 +129,19 @@ impl Instrumentation for DbInstrumentation {
                 };
                 let elapsed = query_start.elapsed();
                 let query = query.to_string().replace('\n', " ");
+                // Everything after `--` are the bind params, drop them so that we never log or
+                // export user data.
                 let query = query.split("--").next().unwrap().trim();
+                let (statement, table) = query_labels(query);
                 QUERY_HISTOGRAM
-                    .with_label_values(&[query])
+                    .with_label_values(&[statement, table])
                     .observe(elapsed.as_secs_f64());
                 tracing::event!(tracing::Level::INFO, %query, "Query finished");
+
+                if elapsed >= *SLOW_QUERY_THRESHOLD {
+                    tracing::warn!(%query, duration_ms = elapsed.as_millis() as u64, "Slow query");
+                    record_slow_query(query, elapsed);
+                }
             }
             diesel::connection::InstrumentationEvent::StartEstablishConnection { .. } => {
                 tracing::event!(tracing::Level::INFO, "StartEstablishConnection");
@@ -60,3 +165,35 @@ impl Instrumentation for DbInstrumentation {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::query_labels;
+
+    #[test]
+    fn test_query_labels() {
+        assert_eq!(
+            query_labels(r#"SELECT "rooms"."id" FROM "rooms" WHERE "rooms"."id" = $1"#),
+            ("select", "rooms")
+        );
+        assert_eq!(
+            query_labels(r#"INSERT INTO "yamls" ("id", "room_id") VALUES ($1, $2)"#),
+            ("insert", "yamls")
+        );
+        assert_eq!(
+            query_labels(r#"UPDATE "rooms" SET "name" = $1 WHERE "rooms"."id" = $2"#),
+            ("update", "rooms")
+        );
+        assert_eq!(
+            query_labels(r#"DELETE FROM "yamls" WHERE "yamls"."id" = $1"#),
+            ("delete", "yamls")
+        );
+        assert_eq!(
+            query_labels(
+                r#"SELECT *, COUNT(*) OVER () FROM (SELECT "rooms"."id" FROM "rooms") t LIMIT $1"#
+            ),
+            ("select", "rooms")
+        );
+        assert_eq!(query_labels("BEGIN"), ("transaction", "unknown"));
+    }
+}
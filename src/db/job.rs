@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::{RoomId, YamlId};
+use crate::error::Result;
+use crate::schema::{discord_users, jobs};
+
+const JOBS_PAGE_SIZE: i64 = 200;
+// Jobs the lobby lost track of (e.g. it restarted and the job expired before any worker picked it
+// up) never get resolved. Don't let them hold the oldest pending age up forever.
+const PENDING_JOBS_WINDOW: TimeDelta = TimeDelta::days(1);
+
+/// What the lobby knows about a queue job. Valkey forgets about jobs once their result expires,
+/// this stays around to tie them back to rooms and YAMLs.
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = jobs)]
+pub struct JobRecord {
+    pub id: String,
+    pub kind: String,
+    pub room_id: Option<RoomId>,
+    pub yaml_id: Option<YamlId>,
+    pub user_id: Option<i64>,
+    pub enqueued_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+    pub resolution: Option<String>,
+    pub worker_id: Option<String>,
+    pub priority: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=jobs)]
+struct NewJobRecord<'a> {
+    id: &'a str,
+    kind: &'a str,
+    priority: &'a str,
+    room_id: Option<RoomId>,
+    user_id: Option<i64>,
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn record_enqueued_job(
+    job_id: &str,
+    kind: &str,
+    priority: &str,
+    room_id: Option<RoomId>,
+    user_id: Option<i64>,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::insert_into(jobs::table)
+        .values(NewJobRecord {
+            id: job_id,
+            kind,
+            priority,
+            room_id,
+            user_id,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn link_job_to_yaml(
+    job_id: &str,
+    yaml_id: YamlId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(jobs::table.find(job_id))
+        .set(jobs::yaml_id.eq(yaml_id))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Records how a job ended. Only the first resolution sticks, a worker resolving a job the lobby
+/// already gave up on doesn't overwrite it.
+#[tracing::instrument(skip(conn))]
+pub async fn record_job_resolution(
+    job_id: &str,
+    resolution: &str,
+    worker_id: Option<&str>,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(jobs::table.find(job_id).filter(jobs::resolved_at.is_null()))
+        .set((
+            jobs::resolved_at.eq(diesel::dsl::now),
+            jobs::resolution.eq(resolution),
+            jobs::worker_id.eq(worker_id),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Latest jobs along with the name of the user who triggered them, for a room or for all rooms.
+#[tracing::instrument(skip(conn))]
+pub async fn list_jobs(
+    room_id: Option<RoomId>,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(JobRecord, Option<String>)>> {
+    let mut query = jobs::table
+        .left_join(discord_users::table)
+        .select((JobRecord::as_select(), discord_users::username.nullable()))
+        .order(jobs::enqueued_at.desc())
+        .limit(JOBS_PAGE_SIZE)
+        .into_boxed();
+    if let Some(room_id) = room_id {
+        query = query.filter(jobs::room_id.eq(room_id));
+    }
+
+    Ok(query.load(conn).await?)
+}
+
+/// The last validation job of each YAML in the room.
+#[tracing::instrument(skip(conn))]
+pub async fn get_latest_validation_jobs_for_room(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<HashMap<YamlId, JobRecord>> {
+    let jobs = jobs::table
+        .filter(jobs::room_id.eq(room_id))
+        .filter(jobs::kind.eq("yaml_validation"))
+        .filter(jobs::yaml_id.is_not_null())
+        .distinct_on(jobs::yaml_id)
+        .order((jobs::yaml_id, jobs::enqueued_at.desc()))
+        .select(JobRecord::as_select())
+        .load::<JobRecord>(conn)
+        .await?;
+
+    Ok(jobs
+        .into_iter()
+        .filter_map(|job| Some((job.yaml_id?, job)))
+        .collect())
+}
+
+/// When the oldest job that's still waiting to be resolved was enqueued, per kind and priority.
+/// Only jobs enqueued in the last day are considered.
+#[tracing::instrument(skip(conn))]
+pub async fn get_oldest_pending_jobs(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(String, String, NaiveDateTime)>> {
+    let since = chrono::Utc::now().naive_utc() - PENDING_JOBS_WINDOW;
+    let oldest = jobs::table
+        .filter(jobs::resolved_at.is_null())
+        .filter(jobs::enqueued_at.gt(since))
+        .group_by((jobs::kind, jobs::priority))
+        .select((
+            jobs::kind,
+            jobs::priority,
+            diesel::dsl::min(jobs::enqueued_at),
+        ))
+        .load::<(String, String, Option<NaiveDateTime>)>(conn)
+        .await?;
+
+    Ok(oldest
+        .into_iter()
+        .filter_map(|(kind, priority, enqueued_at)| Some((kind, priority, enqueued_at?)))
+        .collect())
+}
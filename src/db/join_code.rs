@@ -0,0 +1,146 @@
+use diesel::prelude::*;
+use diesel::Insertable;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::short_link::generate_readable_code;
+use crate::db::{Room, RoomId};
+use crate::error::Result;
+use crate::schema::{room_join_codes, room_members, yamls};
+
+const JOIN_CODE_LEN: usize = 8;
+
+#[derive(Insertable)]
+#[diesel(table_name=room_join_codes)]
+struct NewJoinCode<'a> {
+    room_id: RoomId,
+    code: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=room_members)]
+struct NewRoomMember {
+    room_id: RoomId,
+    user_id: i64,
+}
+
+/// Codes are shown in lowercase but people will type them however they like.
+fn normalize_join_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_room_join_code(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<String>> {
+    Ok(room_join_codes::table
+        .find(room_id)
+        .select(room_join_codes::code)
+        .first(conn)
+        .await
+        .optional()?)
+}
+
+/// Gives the room a new join code, enabling the protection if it wasn't already. People who
+/// joined with a previous code, or already uploaded to the room, stay in.
+#[tracing::instrument(skip(conn))]
+pub async fn regenerate_room_join_code(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<String> {
+    let code = generate_readable_code(JOIN_CODE_LEN);
+    diesel::insert_into(room_join_codes::table)
+        .values(NewJoinCode {
+            room_id,
+            code: &code,
+        })
+        .on_conflict(room_join_codes::room_id)
+        .do_update()
+        .set((
+            room_join_codes::code.eq(&code),
+            room_join_codes::created_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+        .await?;
+
+    diesel::insert_into(room_members::table)
+        .values(
+            yamls::table
+                .filter(yamls::room_id.eq(room_id))
+                .select((yamls::room_id, yamls::owner_id))
+                .distinct(),
+        )
+        .into_columns((room_members::room_id, room_members::user_id))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+
+    Ok(code)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn remove_room_join_code(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<()> {
+    diesel::delete(room_join_codes::table.find(room_id))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether `user_id` has to enter the room's join code before uploading to it.
+#[tracing::instrument(skip(room, conn))]
+pub async fn needs_join_code(
+    room: &Room,
+    user_id: i64,
+    conn: &mut AsyncPgConnection,
+) -> Result<bool> {
+    if room.settings.author_id == user_id {
+        return Ok(false);
+    }
+    if get_room_join_code(room.id, conn).await?.is_none() {
+        return Ok(false);
+    }
+
+    let has_joined = diesel::select(diesel::dsl::exists(
+        room_members::table.find((room.id, user_id)),
+    ))
+    .get_result::<bool>(conn)
+    .await?;
+
+    Ok(!has_joined)
+}
+
+/// Records that `user_id` joined the room if `code` is the room's current join code. Returns
+/// whether it was.
+#[tracing::instrument(skip(code, conn))]
+pub async fn join_room(
+    room_id: RoomId,
+    user_id: i64,
+    code: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<bool> {
+    let Some(expected_code) = get_room_join_code(room_id, conn).await? else {
+        return Ok(true);
+    };
+    if normalize_join_code(code) != expected_code {
+        return Ok(false);
+    }
+
+    diesel::insert_into(room_members::table)
+        .values(NewRoomMember { room_id, user_id })
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_join_code;
+
+    #[test]
+    fn test_normalize_join_code() {
+        assert_eq!(normalize_join_code(" AbCd2345\n"), "abcd2345");
+    }
+}
@@ -1,35 +1,91 @@
 use crate::error::Result;
-use crate::schema::{rooms, yamls};
+use crate::extractor::YamlFeature;
+use crate::schema::{discord_users, rooms, yamls};
 
-use diesel::dsl::{exists, AsSelect, SqlTypeOf};
+use chrono::NaiveDateTime;
+use diesel::dsl::{exists, now, sql, AsSelect, SqlTypeOf};
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel_async::AsyncPgConnection;
 
+mod abuse_signal;
+mod check_in;
+mod delivery;
+#[cfg(feature = "factories")]
+pub mod factories;
+mod feature_policy;
+mod guest_link;
 pub mod instrumentation;
+mod job;
+mod join_code;
 mod json;
 mod pagination;
 mod room;
+mod room_creation;
+mod room_revision;
+mod room_schedule;
 mod room_template;
+mod room_url;
+mod short_link;
+mod timeline;
 pub mod types;
+mod upload_hint;
 mod user;
+mod user_token;
+mod waitlist;
+mod worker_credential;
 mod yaml;
+mod yaml_annotation;
+mod yaml_search;
 
+pub use abuse_signal::*;
+pub use check_in::*;
+pub use delivery::*;
+pub use feature_policy::*;
+pub use guest_link::*;
+pub use job::*;
+pub use join_code::*;
 pub use json::Json;
 pub use pagination::{Paginate, Paginated};
 pub use room::*;
+pub use room_creation::*;
+pub use room_revision::*;
+pub use room_schedule::*;
 pub use room_template::*;
+pub use room_url::*;
+pub use short_link::*;
+pub use timeline::*;
 pub use types::*;
+pub use upload_hint::*;
 pub use user::*;
+pub use user_token::*;
+pub use waitlist::*;
+pub use worker_credential::*;
 pub use yaml::*;
+pub use yaml_annotation::*;
+pub use yaml_search::*;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, rocket::FromFormField)]
 pub enum RoomStatus {
+    #[field(value = "open")]
     Open,
+    #[field(value = "closed")]
     Closed,
+    #[default]
+    #[field(value = "any")]
     Any,
 }
 
+impl RoomStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomStatus::Open => "open",
+            RoomStatus::Closed => "closed",
+            RoomStatus::Any => "any",
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Author {
     Any,
@@ -53,10 +109,31 @@ pub async fn list_rooms(
     Ok(query.load_and_count_pages::<Room>(conn).await?)
 }
 
+/// Turns user input into an `ILIKE` pattern matching it anywhere.
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
 #[derive(Debug)]
 pub struct RoomFilter {
     pub with_yaml_from: WithYaml,
     pub author: Author,
+    pub status: RoomStatus,
+    /// Part of the room's name.
+    pub name: Option<String>,
+    /// Only rooms with a YAML for this game.
+    pub game: Option<String>,
+    /// Part of the organizer's username.
+    pub author_name: Option<String>,
+    pub closes_after: Option<NaiveDateTime>,
+    pub closes_before: Option<NaiveDateTime>,
+    pub include_archived: bool,
+    /// Only rooms where this user has a YAML that might have this feature on.
+    pub own_yaml_feature: Option<(i64, YamlFeature)>,
 }
 
 impl Default for RoomFilter {
@@ -64,6 +141,14 @@ impl Default for RoomFilter {
         Self {
             with_yaml_from: WithYaml::Any,
             author: Author::Any,
+            status: RoomStatus::Any,
+            name: None,
+            game: None,
+            author_name: None,
+            closes_after: None,
+            closes_before: None,
+            include_archived: false,
+            own_yaml_feature: None,
         }
     }
 }
@@ -87,9 +172,108 @@ impl RoomFilter {
             WithYaml::Any => query,
         };
 
+        // The filters above are OR'ed together, everything below narrows them down.
+        let query = match self.status {
+            RoomStatus::Open => query.filter(rooms::close_date.gt(now)),
+            RoomStatus::Closed => query.filter(rooms::close_date.le(now)),
+            RoomStatus::Any => query,
+        };
+        let query = match &self.name {
+            Some(name) => query.filter(rooms::name.ilike(contains_pattern(name))),
+            None => query,
+        };
+        let query = match &self.game {
+            Some(game) => query.filter(exists(
+                yamls::table.filter(
+                    yamls::room_id
+                        .eq(rooms::id)
+                        .and(yamls::game.eq(game.clone())),
+                ),
+            )),
+            None => query,
+        };
+        let query = match &self.author_name {
+            Some(author_name) => query.filter(
+                rooms::author_id.eq_any(
+                    discord_users::table
+                        .filter(discord_users::username.ilike(contains_pattern(author_name)))
+                        .select(discord_users::id),
+                ),
+            ),
+            None => query,
+        };
+        let query = match self.closes_after {
+            Some(closes_after) => query.filter(rooms::close_date.ge(closes_after)),
+            None => query,
+        };
+        let query = match self.closes_before {
+            Some(closes_before) => query.filter(rooms::close_date.lt(closes_before)),
+            None => query,
+        };
+        let query = match self.own_yaml_feature {
+            Some((user_id, feature)) => query.filter(exists(
+                yamls::table.filter(
+                    yamls::room_id
+                        .eq(rooms::id)
+                        .and(yamls::owner_id.eq(user_id))
+                        .and(
+                            sql::<diesel::sql_types::Bool>("(yamls.features ->> ")
+                                .bind::<diesel::sql_types::Text, _>(feature.as_str())
+                                .sql(")::int > 0"),
+                        ),
+                ),
+            )),
+            None => query,
+        };
+        let query = if self.include_archived {
+            query
+        } else {
+            query.filter(rooms::archived.eq(false))
+        };
+
         query.order_by(rooms::close_date.desc())
     }
 
+    pub fn with_status(mut self, status: RoomStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn with_game(mut self, game: Option<String>) -> Self {
+        self.game = game;
+        self
+    }
+
+    pub fn with_author_name(mut self, author_name: Option<String>) -> Self {
+        self.author_name = author_name;
+        self
+    }
+
+    pub fn closing_between(
+        mut self,
+        closes_after: Option<NaiveDateTime>,
+        closes_before: Option<NaiveDateTime>,
+    ) -> Self {
+        self.closes_after = closes_after;
+        self.closes_before = closes_before;
+        self
+    }
+
+    pub fn with_archived(mut self, include_archived: bool) -> Self {
+        self.include_archived = include_archived;
+        self
+    }
+
+    pub fn with_own_yaml_feature(mut self, user_id: i64, feature: Option<YamlFeature>) -> Self {
+        self.own_yaml_feature = feature.map(|feature| (user_id, feature));
+        self
+    }
+
     pub fn with_yamls_from(mut self, with_yaml_from: WithYaml) -> Self {
         self.with_yaml_from = with_yaml_from;
         self
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::db::RoomId;
 use anyhow::Context;
 use apwm::{Index, Manifest};
@@ -8,12 +10,16 @@ use diesel::prelude::*;
 use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use diesel_async::{AsyncPgConnection, RunQueryDsl};
 
-use crate::db::Json;
-use crate::error::Result;
-use crate::schema::{discord_users, room_templates, rooms};
+use crate::db::feature_policy::{feature_policies_by_name, feature_policies_from_names};
+use crate::db::{FeaturePolicies, FeaturePolicy, Json, RoomUrlVisibility};
+use crate::error::{Error, Result};
+use crate::schema::{discord_users, room_templates, rooms, yamls};
 
 use super::RoomTemplateId;
 
+// Only offer to copy from the last few rooms, older YAMLs are unlikely to still be valid.
+const PREVIOUS_ROOMS_LIMIT: i64 = 10;
+
 #[derive(Insertable, AsChangeset, Debug)]
 #[diesel(table_name=rooms)]
 pub struct NewRoom<'a> {
@@ -31,6 +37,12 @@ pub struct NewRoom<'a> {
     pub manifest: Json<Manifest>,
     pub show_apworlds: bool,
     pub from_template_id: Option<Option<RoomTemplateId>>,
+    // `None` keeps the current value of these, or the default for new rooms.
+    pub exact_yaml_storage: Option<bool>,
+    pub upload_hints: Option<&'a str>,
+    pub max_slots: Option<Option<i32>>,
+    pub feature_policies: Option<Json<BTreeMap<String, FeaturePolicy>>>,
+    pub room_url_visibility: Option<RoomUrlVisibility>,
 }
 
 #[derive(Debug, Clone)]
@@ -48,6 +60,15 @@ pub struct RoomSettings {
     pub show_apworlds: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    // Templates don't have the settings below, they're always left to their default.
+    /// Store YAMLs byte for byte instead of tidying them on upload.
+    pub exact_yaml_storage: bool,
+    /// Markdown the organizer wants players to read before uploading, empty if there's none.
+    pub upload_hints: String,
+    /// How many YAMLs can play in the room, `None` if there's no cap.
+    pub max_slots: Option<i32>,
+    pub feature_policies: FeaturePolicies,
+    pub room_url_visibility: RoomUrlVisibility,
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +76,10 @@ pub struct Room {
     pub id: RoomId,
     pub settings: RoomSettings,
     pub from_template_id: Option<RoomTemplateId>,
+    /// Archived rooms are left out of room lists unless asked for.
+    pub archived: bool,
+    /// When the organizer revealed the URL of a room using the manual visibility.
+    pub room_url_revealed_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Clone)]
@@ -98,6 +123,13 @@ impl<
         ST12,
         ST13,
         ST14,
+        ST15,
+        ST16,
+        ST17,
+        ST18,
+        ST19,
+        ST20,
+        ST21,
     >
     Queryable<
         (
@@ -116,6 +148,13 @@ impl<
             ST12,
             ST13,
             ST14,
+            ST15,
+            ST16,
+            ST17,
+            ST18,
+            ST19,
+            ST20,
+            ST21,
         ),
         DB,
     > for Room
@@ -136,6 +175,13 @@ where
         NaiveDateTime,
         NaiveDateTime,
         Option<RoomTemplateId>,
+        bool,
+        bool,
+        String,
+        Option<i32>,
+        Json<BTreeMap<String, FeaturePolicy>>,
+        RoomUrlVisibility,
+        Option<NaiveDateTime>,
     ): FromStaticSqlRow<
         (
             ST0,
@@ -153,6 +199,13 @@ where
             ST12,
             ST13,
             ST14,
+            ST15,
+            ST16,
+            ST17,
+            ST18,
+            ST19,
+            ST20,
+            ST21,
         ),
         DB,
     >,
@@ -173,6 +226,13 @@ where
         NaiveDateTime,
         NaiveDateTime,
         Option<RoomTemplateId>,
+        bool,
+        bool,
+        String,
+        Option<i32>,
+        Json<BTreeMap<String, FeaturePolicy>>,
+        RoomUrlVisibility,
+        Option<NaiveDateTime>,
     );
 
     fn build(row: Self::Row) -> diesel::deserialize::Result<Self> {
@@ -192,8 +252,15 @@ where
                 show_apworlds: row.11,
                 created_at: row.12,
                 updated_at: row.13,
+                exact_yaml_storage: row.16,
+                upload_hints: row.17,
+                max_slots: row.18,
+                feature_policies: feature_policies_from_names(row.19 .0),
+                room_url_visibility: row.20,
             },
             from_template_id: row.14,
+            archived: row.15,
+            room_url_revealed_at: row.21,
         })
     }
 }
@@ -314,6 +381,11 @@ where
                 show_apworlds: row.11,
                 created_at: row.12,
                 updated_at: row.13,
+                exact_yaml_storage: false,
+                upload_hints: String::new(),
+                max_slots: None,
+                feature_policies: FeaturePolicies::new(),
+                room_url_visibility: RoomUrlVisibility::default(),
             },
             global: row.14,
             tpl_name: row.15,
@@ -337,6 +409,11 @@ impl RoomSettings {
             show_apworlds: true,
             created_at: Self::default_close_date()?,
             updated_at: Self::default_close_date()?,
+            exact_yaml_storage: false,
+            upload_hints: String::new(),
+            max_slots: None,
+            feature_policies: FeaturePolicies::new(),
+            room_url_visibility: RoomUrlVisibility::default(),
         })
     }
 
@@ -346,6 +423,31 @@ impl RoomSettings {
             .with_second(0)
             .context("Failed to create default datetime")?)
     }
+
+    /// The row for a room with these settings. Updating a room with `author_id: None` leaves
+    /// its author alone.
+    pub fn as_new_room(&self, id: RoomId, author_id: Option<i64>) -> NewRoom<'_> {
+        NewRoom {
+            id,
+            name: &self.name,
+            close_date: self.close_date,
+            description: &self.description,
+            room_url: &self.room_url,
+            author_id,
+            yaml_validation: self.yaml_validation,
+            allow_unsupported: self.allow_unsupported,
+            yaml_limit_per_user: self.yaml_limit_per_user,
+            yaml_limit_bypass_list: self.yaml_limit_bypass_list.clone(),
+            manifest: self.manifest.clone(),
+            show_apworlds: self.show_apworlds,
+            from_template_id: None,
+            exact_yaml_storage: Some(self.exact_yaml_storage),
+            upload_hints: Some(&self.upload_hints),
+            max_slots: Some(self.max_slots),
+            feature_policies: Some(Json(feature_policies_by_name(&self.feature_policies))),
+            room_url_visibility: Some(self.room_url_visibility),
+        }
+    }
 }
 
 impl Room {
@@ -390,6 +492,60 @@ pub async fn delete_room(room_id: RoomId, conn: &mut AsyncPgConnection) -> Resul
     Ok(())
 }
 
+/// Only closed rooms can be archived, they'd be hard to find again while people upload to them.
+#[tracing::instrument(skip(conn))]
+pub async fn set_room_archived(
+    room_id: RoomId,
+    archived: bool,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let updated = diesel::update(
+        rooms::table.find(room_id).filter(
+            rooms::close_date
+                .le(diesel::dsl::now)
+                .or(rooms::archived.eq(true)),
+        ),
+    )
+    .set(rooms::archived.eq(archived))
+    .execute(conn)
+    .await?;
+    if updated == 0 {
+        return Err(Error::conflict("Only closed rooms can be archived"));
+    }
+
+    Ok(())
+}
+
+/// Only applies to YAMLs uploaded from now on.
+#[tracing::instrument(skip(room, conn), fields(room_id = %room.id))]
+pub async fn set_room_exact_yaml_storage(
+    room: &Room,
+    exact: bool,
+    author_id: Option<i64>,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let new_room = NewRoom {
+        exact_yaml_storage: Some(exact),
+        ..room.settings.as_new_room(room.id, None)
+    };
+    super::update_room_with_revision(room, &new_room, author_id, conn).await
+}
+
+/// Moves the close date of a room that's still open to now.
+#[tracing::instrument(skip(conn))]
+pub async fn close_room_now(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<()> {
+    diesel::update(
+        rooms::table
+            .find(room_id)
+            .filter(rooms::close_date.gt(diesel::dsl::now)),
+    )
+    .set(rooms::close_date.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(skip(conn))]
 pub async fn get_room(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<Room> {
     Ok(rooms::table
@@ -411,3 +567,80 @@ pub async fn get_room_and_author(
         .first(conn)
         .await?)
 }
+
+/// Other rooms `user_id` uploaded YAMLs to, most recent first.
+#[tracing::instrument(skip(conn))]
+pub async fn get_previous_rooms_of_user(
+    user_id: i64,
+    current_room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(RoomId, String)>> {
+    let rooms = rooms::table
+        .inner_join(yamls::table)
+        .filter(yamls::owner_id.eq(user_id))
+        .filter(rooms::id.ne(current_room_id))
+        .select((rooms::id, rooms::name, rooms::close_date))
+        .distinct()
+        .order(rooms::close_date.desc())
+        .limit(PREVIOUS_ROOMS_LIMIT)
+        .load::<(RoomId, String, NaiveDateTime)>(conn)
+        .await?;
+
+    Ok(rooms
+        .into_iter()
+        .map(|(room_id, name, _)| (room_id, name))
+        .collect())
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_open_rooms(conn: &mut AsyncPgConnection) -> Result<Vec<Room>> {
+    Ok(rooms::table
+        .filter(rooms::close_date.gt(diesel::dsl::now))
+        .order(rooms::close_date.asc())
+        .select(Room::as_select())
+        .load(conn)
+        .await?)
+}
+
+/// Returns the game of every yaml in open rooms, along with the room it's in.
+#[tracing::instrument(skip(conn))]
+pub async fn get_games_in_open_rooms(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(RoomId, String)>> {
+    Ok(yamls::table
+        .inner_join(rooms::table)
+        .filter(rooms::close_date.gt(diesel::dsl::now))
+        .select((yamls::room_id, yamls::game))
+        .load(conn)
+        .await?)
+}
+
+/// Returns the number of yamls for each open room.
+#[tracing::instrument(skip(conn))]
+pub async fn count_yamls_per_open_room(conn: &mut AsyncPgConnection) -> Result<Vec<(RoomId, i64)>> {
+    Ok(rooms::table
+        .left_join(yamls::table)
+        .filter(rooms::close_date.gt(diesel::dsl::now))
+        .group_by(rooms::id)
+        .select((rooms::id, diesel::dsl::count(yamls::id.nullable())))
+        .load(conn)
+        .await?)
+}
+
+/// Returns the number of closed rooms along with the total number of yamls in them.
+#[tracing::instrument(skip(conn))]
+pub async fn count_closed_rooms_and_yamls(conn: &mut AsyncPgConnection) -> Result<(i64, i64)> {
+    let rooms_count = rooms::table
+        .filter(rooms::close_date.le(diesel::dsl::now))
+        .count()
+        .get_result(conn)
+        .await?;
+    let yamls_count = yamls::table
+        .inner_join(rooms::table)
+        .filter(rooms::close_date.le(diesel::dsl::now))
+        .count()
+        .get_result(conn)
+        .await?;
+
+    Ok((rooms_count, yamls_count))
+}
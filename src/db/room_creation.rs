@@ -0,0 +1,105 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel::prelude::*;
+use diesel::Insertable;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::{Room, RoomId};
+use crate::error::{Error, Result};
+use crate::schema::{room_idempotency_keys, rooms};
+
+// Double submits end up a few seconds apart, but the close date can be picked again by hand.
+const DUPLICATE_CLOSE_DATE_WINDOW: TimeDelta = TimeDelta::minutes(10);
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+#[derive(Insertable)]
+#[diesel(table_name=room_idempotency_keys)]
+struct NewIdempotencyKey<'a> {
+    author_id: i64,
+    idempotency_key: &'a str,
+    room_id: RoomId,
+}
+
+/// An open room of `author_id` with the same name and about the same close date, which is most
+/// likely the same room submitted twice.
+#[tracing::instrument(skip(conn))]
+pub async fn find_duplicate_room(
+    author_id: i64,
+    name: &str,
+    close_date: NaiveDateTime,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<Room>> {
+    Ok(rooms::table
+        .filter(rooms::author_id.eq(author_id))
+        .filter(rooms::name.eq(name.trim()))
+        .filter(rooms::close_date.gt(diesel::dsl::now))
+        .filter(rooms::close_date.ge(close_date - DUPLICATE_CLOSE_DATE_WINDOW))
+        .filter(rooms::close_date.le(close_date + DUPLICATE_CLOSE_DATE_WINDOW))
+        .filter(rooms::archived.eq(false))
+        .order(rooms::created_at.desc())
+        .select(Room::as_select())
+        .first(conn)
+        .await
+        .optional()?)
+}
+
+pub fn check_idempotency_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(Error::validation(format!(
+            "The idempotency key should be between 1 and {} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        )));
+    }
+
+    Ok(())
+}
+
+/// The room that was created the first time `author_id` sent this idempotency key.
+#[tracing::instrument(skip(key, conn))]
+pub async fn get_room_for_idempotency_key(
+    author_id: i64,
+    key: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<Room>> {
+    Ok(room_idempotency_keys::table
+        .inner_join(rooms::table)
+        .filter(room_idempotency_keys::author_id.eq(author_id))
+        .filter(room_idempotency_keys::idempotency_key.eq(key))
+        .select(Room::as_select())
+        .first(conn)
+        .await
+        .optional()?)
+}
+
+/// Meant to run in the same transaction as the room creation. Two requests racing with the same
+/// key make the second one fail with a conflict once the first one commits, the room to return
+/// is then the first one's, from [`get_room_for_idempotency_key`].
+#[tracing::instrument(skip(key, conn))]
+pub async fn record_idempotency_key(
+    author_id: i64,
+    key: &str,
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::insert_into(room_idempotency_keys::table)
+        .values(NewIdempotencyKey {
+            author_id,
+            idempotency_key: key,
+            room_id,
+        })
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_idempotency_key;
+
+    #[test]
+    fn test_check_idempotency_key() {
+        assert!(check_idempotency_key("a5b1c9e2-retry").is_ok());
+        assert!(check_idempotency_key("").is_err());
+        assert!(check_idempotency_key(&"k".repeat(256)).is_err());
+    }
+}
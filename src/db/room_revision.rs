@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use apwm::Manifest;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use crate::db::feature_policy::{feature_policies_by_name, feature_policies_from_names};
+use crate::db::{
+    FeaturePolicy, Json, NewRoom, Room, RoomId, RoomRevisionId, RoomSettings, RoomUrlVisibility,
+};
+use crate::error::{Error, Result};
+use crate::schema::{discord_users, room_settings_revisions};
+
+/// The settings an organizer can edit, as they were before an edit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomSettingsSnapshot {
+    pub name: String,
+    pub close_date: NaiveDateTime,
+    pub description: String,
+    pub room_url: String,
+    pub yaml_validation: bool,
+    pub allow_unsupported: bool,
+    pub yaml_limit_per_user: Option<i32>,
+    pub yaml_limit_bypass_list: Vec<i64>,
+    pub manifest: Manifest,
+    pub show_apworlds: bool,
+    // Older snapshots don't have the settings below.
+    #[serde(default)]
+    pub exact_yaml_storage: bool,
+    #[serde(default)]
+    pub upload_hints: String,
+    #[serde(default)]
+    pub max_slots: Option<i32>,
+    /// By feature name, like on the room.
+    #[serde(default)]
+    pub feature_policies: BTreeMap<String, FeaturePolicy>,
+    #[serde(default)]
+    pub room_url_visibility: RoomUrlVisibility,
+}
+
+impl From<&RoomSettings> for RoomSettingsSnapshot {
+    fn from(settings: &RoomSettings) -> Self {
+        Self {
+            name: settings.name.clone(),
+            close_date: settings.close_date,
+            description: settings.description.clone(),
+            room_url: settings.room_url.clone(),
+            yaml_validation: settings.yaml_validation,
+            allow_unsupported: settings.allow_unsupported,
+            yaml_limit_per_user: settings.yaml_limit_per_user,
+            yaml_limit_bypass_list: settings.yaml_limit_bypass_list.clone(),
+            manifest: settings.manifest.0.clone(),
+            show_apworlds: settings.show_apworlds,
+            exact_yaml_storage: settings.exact_yaml_storage,
+            upload_hints: settings.upload_hints.clone(),
+            max_slots: settings.max_slots,
+            feature_policies: feature_policies_by_name(&settings.feature_policies),
+            room_url_visibility: settings.room_url_visibility,
+        }
+    }
+}
+
+impl RoomSettingsSnapshot {
+    /// The full settings of a room by `author_id` with these settings.
+    pub fn into_settings(self, author_id: i64) -> RoomSettings {
+        let now = chrono::Utc::now().naive_utc();
+        RoomSettings {
+            name: self.name,
+            close_date: self.close_date,
+            description: self.description,
+            room_url: self.room_url,
+            author_id,
+            yaml_validation: self.yaml_validation,
+            allow_unsupported: self.allow_unsupported,
+            yaml_limit_per_user: self.yaml_limit_per_user,
+            yaml_limit_bypass_list: self.yaml_limit_bypass_list,
+            manifest: Json(self.manifest),
+            show_apworlds: self.show_apworlds,
+            created_at: now,
+            updated_at: now,
+            exact_yaml_storage: self.exact_yaml_storage,
+            upload_hints: self.upload_hints,
+            max_slots: self.max_slots,
+            feature_policies: feature_policies_from_names(self.feature_policies),
+            room_url_visibility: self.room_url_visibility,
+        }
+    }
+
+    pub fn manifest_differs(&self, other: &RoomSettingsSnapshot) -> bool {
+        serde_json::to_value(&self.manifest).ok() != serde_json::to_value(&other.manifest).ok()
+    }
+
+    /// Names of the settings that aren't the same in both snapshots.
+    pub fn changed_settings(&self, other: &RoomSettingsSnapshot) -> Vec<&'static str> {
+        [
+            ("name", self.name != other.name),
+            ("close date", self.close_date != other.close_date),
+            ("description", self.description != other.description),
+            ("room URL", self.room_url != other.room_url),
+            (
+                "YAML validation",
+                self.yaml_validation != other.yaml_validation,
+            ),
+            (
+                "unsupported games",
+                self.allow_unsupported != other.allow_unsupported,
+            ),
+            (
+                "YAML limit",
+                self.yaml_limit_per_user != other.yaml_limit_per_user
+                    || self.yaml_limit_bypass_list != other.yaml_limit_bypass_list,
+            ),
+            ("apworlds", self.manifest_differs(other)),
+            ("apworlds list", self.show_apworlds != other.show_apworlds),
+            (
+                "YAML storage",
+                self.exact_yaml_storage != other.exact_yaml_storage,
+            ),
+            ("upload hints", self.upload_hints != other.upload_hints),
+            ("slot cap", self.max_slots != other.max_slots),
+            (
+                "feature rules",
+                self.feature_policies != other.feature_policies,
+            ),
+            (
+                "room URL visibility",
+                self.room_url_visibility != other.room_url_visibility,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = room_settings_revisions)]
+pub struct RoomRevision {
+    pub id: RoomRevisionId,
+    pub room_id: RoomId,
+    /// Who made the edit that replaced these settings, `None` for the admin API key.
+    pub author_id: Option<i64>,
+    pub settings: Json<RoomSettingsSnapshot>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=room_settings_revisions)]
+struct NewRoomRevision {
+    id: RoomRevisionId,
+    room_id: RoomId,
+    author_id: Option<i64>,
+    settings: Json<RoomSettingsSnapshot>,
+}
+
+/// Updates the room's settings and keeps the ones it had before as a revision, so that the edit
+/// can be rolled back. Edits that don't change anything don't leave a revision.
+#[tracing::instrument(skip(previous, new_room, conn))]
+pub async fn update_room_with_revision(
+    previous: &Room,
+    new_room: &NewRoom<'_>,
+    author_id: Option<i64>,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let snapshot = RoomSettingsSnapshot::from(&previous.settings);
+    let room_id = previous.id;
+
+    conn.transaction::<_, Error, _>(|conn| {
+        async move {
+            super::update_room(new_room, conn).await?;
+            let updated = super::get_room(room_id, conn).await?;
+            if snapshot
+                .changed_settings(&RoomSettingsSnapshot::from(&updated.settings))
+                .is_empty()
+            {
+                return Ok(());
+            }
+
+            diesel::insert_into(room_settings_revisions::table)
+                .values(NewRoomRevision {
+                    id: RoomRevisionId::new_v4(),
+                    room_id,
+                    author_id,
+                    settings: Json(snapshot),
+                })
+                .execute(conn)
+                .await?;
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Revisions of the room along with the username of whoever replaced them, newest first. Edits
+/// made with the admin API key don't have a username.
+#[tracing::instrument(skip(conn))]
+pub async fn list_room_revisions(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(RoomRevision, Option<String>)>> {
+    Ok(room_settings_revisions::table
+        .left_join(discord_users::table)
+        .filter(room_settings_revisions::room_id.eq(room_id))
+        .order(room_settings_revisions::created_at.desc())
+        .select((
+            RoomRevision::as_select(),
+            discord_users::username.nullable(),
+        ))
+        .load(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_room_revision(
+    room_id: RoomId,
+    revision_id: RoomRevisionId,
+    conn: &mut AsyncPgConnection,
+) -> Result<RoomRevision> {
+    room_settings_revisions::table
+        .find(revision_id)
+        .filter(room_settings_revisions::room_id.eq(room_id))
+        .select(RoomRevision::as_select())
+        .first(conn)
+        .await
+        .optional()?
+        .ok_or_else(|| Error::not_found("Unknown revision"))
+}
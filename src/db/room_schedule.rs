@@ -0,0 +1,195 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime, TimeDelta};
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::{RoomId, RoomScheduleId, RoomTemplateId};
+use crate::error::Result;
+use crate::schema::{room_schedules, room_templates};
+
+pub const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Creates a room from a template every week and closes the previous one.
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = room_schedules)]
+pub struct RoomSchedule {
+    pub id: RoomScheduleId,
+    pub template_id: RoomTemplateId,
+    pub author_id: i64,
+    /// Index in [`WEEKDAYS`].
+    pub weekday: i16,
+    /// UTC.
+    pub start_time: NaiveTime,
+    pub open_for_hours: i32,
+    /// Discord webhook the new rooms get posted to, empty if there's none.
+    pub webhook_url: String,
+    pub next_run_at: NaiveDateTime,
+    pub last_room_id: Option<RoomId>,
+    pub created_at: NaiveDateTime,
+}
+
+impl RoomSchedule {
+    pub fn weekday_name(&self) -> &'static str {
+        WEEKDAYS.get(self.weekday as usize).copied().unwrap_or("")
+    }
+
+    pub fn open_for(&self) -> TimeDelta {
+        TimeDelta::hours(self.open_for_hours.into())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=room_schedules)]
+pub struct NewRoomSchedule<'a> {
+    pub id: RoomScheduleId,
+    pub template_id: RoomTemplateId,
+    pub author_id: i64,
+    pub weekday: i16,
+    pub start_time: NaiveTime,
+    pub open_for_hours: i32,
+    pub webhook_url: &'a str,
+    pub next_run_at: NaiveDateTime,
+}
+
+/// The first time strictly after `after` that falls on `weekday` (an index in [`WEEKDAYS`]) at
+/// `start_time`.
+pub fn next_schedule_run(
+    weekday: i16,
+    start_time: NaiveTime,
+    after: NaiveDateTime,
+) -> NaiveDateTime {
+    let days_ahead =
+        (i64::from(weekday) - i64::from(after.weekday().num_days_from_monday())).rem_euclid(7);
+    let run = (after.date() + TimeDelta::days(days_ahead)).and_time(start_time);
+    if run > after {
+        run
+    } else {
+        run + TimeDelta::weeks(1)
+    }
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn create_room_schedule(
+    new_schedule: &NewRoomSchedule<'_>,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::insert_into(room_schedules::table)
+        .values(new_schedule)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The author's schedules along with the name of their template, next run first.
+#[tracing::instrument(skip(conn))]
+pub async fn list_room_schedules_for_author(
+    author_id: i64,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<(RoomSchedule, String)>> {
+    Ok(room_schedules::table
+        .inner_join(room_templates::table)
+        .filter(room_schedules::author_id.eq(author_id))
+        .order(room_schedules::next_run_at.asc())
+        .select((RoomSchedule::as_select(), room_templates::tpl_name))
+        .load(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_room_schedule(
+    schedule_id: RoomScheduleId,
+    conn: &mut AsyncPgConnection,
+) -> Result<RoomSchedule> {
+    Ok(room_schedules::table
+        .find(schedule_id)
+        .select(RoomSchedule::as_select())
+        .first(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn delete_room_schedule(
+    schedule_id: RoomScheduleId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::delete(room_schedules::table.find(schedule_id))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_due_room_schedules(
+    now: NaiveDateTime,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<RoomSchedule>> {
+    Ok(room_schedules::table
+        .filter(room_schedules::next_run_at.le(now))
+        .order(room_schedules::next_run_at.asc())
+        .select(RoomSchedule::as_select())
+        .load(conn)
+        .await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn record_room_schedule_run(
+    schedule_id: RoomScheduleId,
+    room_id: RoomId,
+    next_run_at: NaiveDateTime,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(room_schedules::table.find(schedule_id))
+        .set((
+            room_schedules::last_room_id.eq(room_id),
+            room_schedules::next_run_at.eq(next_run_at),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, NaiveTime};
+
+    use super::next_schedule_run;
+
+    fn date(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_next_schedule_run() {
+        let friday = 4;
+        let eight_pm = NaiveTime::from_hms_opt(20, 0, 0).unwrap();
+
+        // 2024-03-06 is a wednesday.
+        assert_eq!(
+            next_schedule_run(friday, eight_pm, date("2024-03-06 12:00:00")),
+            date("2024-03-08 20:00:00")
+        );
+        assert_eq!(
+            next_schedule_run(friday, eight_pm, date("2024-03-08 19:59:59")),
+            date("2024-03-08 20:00:00")
+        );
+        assert_eq!(
+            next_schedule_run(friday, eight_pm, date("2024-03-08 20:00:00")),
+            date("2024-03-15 20:00:00")
+        );
+        assert_eq!(
+            next_schedule_run(0, eight_pm, date("2024-03-10 21:00:00")),
+            date("2024-03-11 20:00:00")
+        );
+    }
+}
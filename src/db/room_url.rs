@@ -0,0 +1,253 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rocket::form::FromFormField;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Room, RoomId};
+use crate::error::Result;
+use crate::schema::rooms;
+
+/// Who gets to see a room's URL. The organizer always does.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    FromFormField,
+    FromSqlRow,
+    AsExpression,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomUrlVisibility {
+    #[field(value = "everyone")]
+    Everyone,
+    /// Players with a YAML in the room.
+    #[default]
+    #[field(value = "participants")]
+    Participants,
+    /// Players with a YAML in the room, once it's closed.
+    #[field(value = "after_close")]
+    AfterClose,
+    /// Players with a YAML in the room, once the organizer reveals it.
+    #[field(value = "manual")]
+    Manual,
+}
+
+impl RoomUrlVisibility {
+    pub const ALL: [RoomUrlVisibility; 4] = [
+        RoomUrlVisibility::Everyone,
+        RoomUrlVisibility::Participants,
+        RoomUrlVisibility::AfterClose,
+        RoomUrlVisibility::Manual,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomUrlVisibility::Everyone => "everyone",
+            RoomUrlVisibility::Participants => "participants",
+            RoomUrlVisibility::AfterClose => "after_close",
+            RoomUrlVisibility::Manual => "manual",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RoomUrlVisibility::Everyone => "Everyone",
+            RoomUrlVisibility::Participants => "Players with a YAML",
+            RoomUrlVisibility::AfterClose => "Players with a YAML, after closing",
+            RoomUrlVisibility::Manual => "Players with a YAML, once revealed",
+        }
+    }
+}
+
+impl Display for RoomUrlVisibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl ToSql<Text, Pg> for RoomUrlVisibility {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for RoomUrlVisibility {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        RoomUrlVisibility::ALL
+            .into_iter()
+            .find(|visibility| visibility.as_str() == value)
+            .ok_or_else(|| format!("Unknown room URL visibility: {}", value).into())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RoomUrlPolicy {
+    pub visibility: RoomUrlVisibility,
+    pub revealed_at: Option<NaiveDateTime>,
+}
+
+impl RoomUrlPolicy {
+    pub fn has_visibility(&self, visibility: &RoomUrlVisibility) -> bool {
+        &self.visibility == visibility
+    }
+
+    pub fn is_manual(&self) -> bool {
+        self.visibility == RoomUrlVisibility::Manual
+    }
+
+    /// Whether someone can see the room's URL. Both the room page and anything else exposing the
+    /// URL go through this.
+    pub fn shows_room_url(&self, room: &Room, is_participant: bool, is_organizer: bool) -> bool {
+        if room.settings.room_url.is_empty() {
+            return false;
+        }
+        if is_organizer {
+            return true;
+        }
+
+        match self.visibility {
+            RoomUrlVisibility::Everyone => true,
+            RoomUrlVisibility::Participants => is_participant,
+            RoomUrlVisibility::AfterClose => is_participant && room.is_closed(),
+            RoomUrlVisibility::Manual => is_participant && self.revealed_at.is_some(),
+        }
+    }
+}
+
+impl Room {
+    /// Rooms that never changed it only show their URL to participants.
+    pub fn url_policy(&self) -> RoomUrlPolicy {
+        RoomUrlPolicy {
+            visibility: self.settings.room_url_visibility,
+            revealed_at: self.room_url_revealed_at,
+        }
+    }
+}
+
+/// Changing the visibility hides the URL again if it had been revealed.
+#[tracing::instrument(skip(conn))]
+pub async fn set_room_url_visibility(
+    room_id: RoomId,
+    visibility: RoomUrlVisibility,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(rooms::table.find(room_id))
+        .set((
+            rooms::room_url_visibility.eq(visibility),
+            rooms::room_url_revealed_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Reveals the URL of a room using the manual visibility. Returns false if the room doesn't use
+/// it.
+#[tracing::instrument(skip(conn))]
+pub async fn reveal_room_url(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<bool> {
+    let updated = diesel::update(
+        rooms::table
+            .find(room_id)
+            .filter(rooms::room_url_visibility.eq(RoomUrlVisibility::Manual)),
+    )
+    .set(rooms::room_url_revealed_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+
+    Ok(updated == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::{RoomUrlPolicy, RoomUrlVisibility};
+    use crate::db::{FeaturePolicies, Json, Room, RoomId, RoomSettings};
+
+    fn room(closed: bool) -> Room {
+        let now = chrono::Utc::now().naive_utc();
+        Room {
+            id: RoomId::new_v4(),
+            settings: RoomSettings {
+                name: "Room".to_string(),
+                close_date: if closed {
+                    now - TimeDelta::days(1)
+                } else {
+                    now + TimeDelta::days(1)
+                },
+                description: "".to_string(),
+                room_url: "https://archipelago.gg/room/abc".to_string(),
+                author_id: 1,
+                yaml_validation: true,
+                allow_unsupported: false,
+                yaml_limit_per_user: None,
+                yaml_limit_bypass_list: vec![],
+                manifest: Json(apwm::Manifest::new()),
+                show_apworlds: true,
+                created_at: now,
+                updated_at: now,
+                exact_yaml_storage: false,
+                upload_hints: String::new(),
+                max_slots: None,
+                feature_policies: FeaturePolicies::new(),
+                room_url_visibility: RoomUrlVisibility::default(),
+            },
+            from_template_id: None,
+            archived: false,
+            room_url_revealed_at: None,
+        }
+    }
+
+    fn policy(visibility: RoomUrlVisibility, revealed: bool) -> RoomUrlPolicy {
+        RoomUrlPolicy {
+            visibility,
+            revealed_at: revealed.then(|| chrono::Utc::now().naive_utc()),
+        }
+    }
+
+    #[test]
+    fn test_shows_room_url() {
+        let open = room(false);
+        let closed = room(true);
+
+        for visibility in RoomUrlVisibility::ALL {
+            assert!(policy(visibility, false).shows_room_url(&open, false, true));
+        }
+
+        assert!(policy(RoomUrlVisibility::Everyone, false).shows_room_url(&open, false, false));
+        assert!(!policy(RoomUrlVisibility::Participants, false).shows_room_url(&open, false, false));
+        assert!(policy(RoomUrlVisibility::Participants, false).shows_room_url(&open, true, false));
+        assert!(!policy(RoomUrlVisibility::AfterClose, false).shows_room_url(&open, true, false));
+        assert!(policy(RoomUrlVisibility::AfterClose, false).shows_room_url(&closed, true, false));
+        assert!(!policy(RoomUrlVisibility::AfterClose, false).shows_room_url(&closed, false, false));
+        assert!(!policy(RoomUrlVisibility::Manual, false).shows_room_url(&closed, true, false));
+        assert!(policy(RoomUrlVisibility::Manual, true).shows_room_url(&open, true, false));
+
+        let mut without_url = open.clone();
+        without_url.settings.room_url = "".to_string();
+        assert!(!policy(RoomUrlVisibility::Everyone, false).shows_room_url(
+            &without_url,
+            true,
+            true
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::Insertable;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::RoomId;
+use crate::error::{Error, Result};
+use crate::schema::room_short_links;
+
+// No 0/O, 1/l/I so that slugs survive being read out loud or copied from a projector.
+const SLUG_ALPHABET: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+const SLUG_LEN: usize = 6;
+const MAX_SLUG_ATTEMPTS: usize = 5;
+
+#[derive(Insertable)]
+#[diesel(table_name=room_short_links)]
+struct NewShortLink<'a> {
+    slug: &'a str,
+    room_id: RoomId,
+}
+
+/// Random code made of easy to read characters, `len` can't be more than 16.
+pub(crate) fn generate_readable_code(len: usize) -> String {
+    uuid::Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(len)
+        .map(|byte| SLUG_ALPHABET[*byte as usize % SLUG_ALPHABET.len()] as char)
+        .collect()
+}
+
+fn generate_slug() -> String {
+    generate_readable_code(SLUG_LEN)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_room_short_link(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<String>> {
+    Ok(room_short_links::table
+        .filter(room_short_links::room_id.eq(room_id))
+        .select(room_short_links::slug)
+        .first(conn)
+        .await
+        .optional()?)
+}
+
+/// Gives the room a new slug, or its first one. The previous one, if any, stops resolving.
+#[tracing::instrument(skip(conn))]
+pub async fn regenerate_room_short_link(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<String> {
+    for _ in 0..MAX_SLUG_ATTEMPTS {
+        let slug = generate_slug();
+        let inserted = diesel::insert_into(room_short_links::table)
+            .values(&NewShortLink {
+                slug: &slug,
+                room_id,
+            })
+            .on_conflict(room_short_links::room_id)
+            .do_update()
+            .set((
+                room_short_links::slug.eq(&slug),
+                room_short_links::created_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+            .await;
+
+        match inserted {
+            Ok(_) => return Ok(slug),
+            // Slug already taken by another room, roll again.
+            Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(Error::conflict(
+        "Couldn't find a free short link, try again",
+    ))
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn resolve_room_short_link(slug: &str, conn: &mut AsyncPgConnection) -> Result<RoomId> {
+    Ok(room_short_links::table
+        .find(slug)
+        .select(room_short_links::room_id)
+        .first(conn)
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_slug, SLUG_ALPHABET, SLUG_LEN};
+
+    #[test]
+    fn test_generate_slug() {
+        let slug = generate_slug();
+        assert_eq!(slug.len(), SLUG_LEN);
+        assert!(slug.bytes().all(|c| SLUG_ALPHABET.contains(&c)));
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+
+use crate::db::{Json, RoomId};
+use crate::error::Result;
+use crate::jobs::UnknownReference;
+use crate::schema::yamls;
+
+/// Activity of a room on a given (UTC) day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimelineDay {
+    pub day: NaiveDate,
+    pub uploads: usize,
+    /// Uploads that passed validation with warnings.
+    pub uploads_with_warnings: usize,
+    pub check_ins: usize,
+}
+
+impl TimelineDay {
+    fn empty(day: NaiveDate) -> Self {
+        Self {
+            day,
+            uploads: 0,
+            uploads_with_warnings: 0,
+            check_ins: 0,
+        }
+    }
+}
+
+/// Day by day uploads and check-ins for the YAMLs currently in the room. Deleted YAMLs aren't
+/// accounted for.
+#[tracing::instrument(skip(conn))]
+pub async fn get_room_timeline(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<TimelineDay>> {
+    let yamls = yamls::table
+        .filter(yamls::room_id.eq(room_id))
+        .select((
+            yamls::created_at,
+            yamls::checked_in_at,
+            yamls::validation_warnings,
+        ))
+        .load::<(
+            NaiveDateTime,
+            Option<NaiveDateTime>,
+            Json<Vec<UnknownReference>>,
+        )>(conn)
+        .await?;
+
+    Ok(build_timeline(yamls.into_iter().map(
+        |(created_at, checked_in_at, warnings)| (created_at, checked_in_at, !warnings.is_empty()),
+    )))
+}
+
+/// Buckets `(created_at, checked_in_at, has_warnings)` per day. Days without any activity
+/// between the first and the last one are included so the timeline has no holes.
+fn build_timeline(
+    yamls: impl Iterator<Item = (NaiveDateTime, Option<NaiveDateTime>, bool)>,
+) -> Vec<TimelineDay> {
+    let mut days = BTreeMap::new();
+    for (created_at, checked_in_at, has_warnings) in yamls {
+        let day = created_at.date();
+        let entry = days.entry(day).or_insert_with(|| TimelineDay::empty(day));
+        entry.uploads += 1;
+        if has_warnings {
+            entry.uploads_with_warnings += 1;
+        }
+
+        if let Some(checked_in_at) = checked_in_at {
+            let day = checked_in_at.date();
+            days.entry(day)
+                .or_insert_with(|| TimelineDay::empty(day))
+                .check_ins += 1;
+        }
+    }
+
+    let (Some(first), Some(last)) = (days.keys().next(), days.keys().next_back()) else {
+        return vec![];
+    };
+    first
+        .iter_days()
+        .take_while(|day| day <= last)
+        .map(|day| {
+            days.get(&day)
+                .cloned()
+                .unwrap_or_else(|| TimelineDay::empty(day))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{build_timeline, TimelineDay};
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_build_timeline() {
+        let timeline = build_timeline(
+            [
+                (at(1, 10), None, false),
+                (at(1, 23), Some(at(4, 1)), true),
+                (at(3, 0), None, false),
+            ]
+            .into_iter(),
+        );
+
+        let day = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        assert_eq!(
+            timeline,
+            vec![
+                TimelineDay {
+                    day: day(1),
+                    uploads: 2,
+                    uploads_with_warnings: 1,
+                    check_ins: 0,
+                },
+                TimelineDay::empty(day(2)),
+                TimelineDay {
+                    day: day(3),
+                    uploads: 1,
+                    uploads_with_warnings: 0,
+                    check_ins: 0,
+                },
+                TimelineDay {
+                    day: day(4),
+                    uploads: 0,
+                    uploads_with_warnings: 0,
+                    check_ins: 1,
+                },
+            ]
+        );
+        assert!(build_timeline(std::iter::empty()).is_empty());
+    }
+}
@@ -22,7 +22,7 @@ macro_rules! new_id_type {
 
         use sql::*;
         $(
-            #[derive(Clone, Copy, Debug, UriDisplayPath, FromForm)]
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, UriDisplayPath, FromForm)]
             #[derive(FromSqlRow, AsExpression)]
             #[diesel(sql_type=$sql_type)]
             pub struct $rust_type(Uuid);
@@ -70,4 +70,10 @@ new_id_type!(
     SqlRoomId => RoomId,
     SqlYamlId => YamlId,
     SqlRoomTemplateId => RoomTemplateId,
+    SqlWorkerCredentialId => WorkerCredentialId,
+    SqlAbuseSignalId => AbuseSignalId,
+    SqlRoomScheduleId => RoomScheduleId,
+    SqlUserTokenId => UserTokenId,
+    SqlRoomRevisionId => RoomRevisionId,
+    SqlDeliveryId => DeliveryId,
 );
@@ -0,0 +1,31 @@
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use crate::db::RoomId;
+use crate::error::{Error, Result};
+use crate::schema::rooms;
+
+const MAX_UPLOAD_HINTS_LEN: usize = 10_000;
+
+/// Empty hints remove them.
+#[tracing::instrument(skip(hints, conn))]
+pub async fn set_room_upload_hints(
+    room_id: RoomId,
+    hints: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let hints = hints.trim();
+    if hints.chars().count() > MAX_UPLOAD_HINTS_LEN {
+        return Err(Error::validation(format!(
+            "Upload hints can't be longer than {} characters",
+            MAX_UPLOAD_HINTS_LEN
+        )));
+    }
+
+    diesel::update(rooms::table.find(room_id))
+        .set(rooms::upload_hints.eq(hints))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
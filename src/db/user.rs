@@ -1,9 +1,18 @@
+use std::fmt::Display;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
 use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
 use diesel::{Insertable, Queryable};
 use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rocket::form::FromFormField;
+use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
-use crate::schema::discord_users;
+use crate::schema::{discord_users, user_settings};
 
 #[derive(Insertable, Queryable)]
 #[diesel(table_name=discord_users)]
@@ -33,3 +42,124 @@ pub async fn upsert_discord_user(
 
     Ok(())
 }
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    FromFormField,
+    FromSqlRow,
+    AsExpression,
+)]
+#[serde(rename_all = "snake_case")]
+#[diesel(sql_type = Text)]
+pub enum Theme {
+    #[default]
+    #[field(value = "dark")]
+    Dark,
+    #[field(value = "light")]
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+
+    /// Stylesheet loaded on top of `base.css`, the dark theme is the base one.
+    pub fn stylesheet(&self) -> Option<&'static str> {
+        match self {
+            Theme::Dark => None,
+            Theme::Light => Some("light.css"),
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Dark => f.write_str("Dark"),
+            Theme::Light => f.write_str("Light"),
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for Theme {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for Theme {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Theme::ALL
+            .into_iter()
+            .find(|theme| theme.as_str() == value)
+            .ok_or_else(|| format!("Unknown theme: {}", value).into())
+    }
+}
+
+/// Display preferences. They're stored in the database so that they follow the user across
+/// devices, and copied in the session on login so that rendering a page doesn't need a query.
+#[derive(
+    Clone, Debug, Default, PartialEq, Serialize, Deserialize, Selectable, Queryable, AsChangeset,
+)]
+#[diesel(table_name = user_settings)]
+pub struct UserSettings {
+    pub theme: Theme,
+    pub compact_layout: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_settings)]
+struct NewUserSettings {
+    user_id: i64,
+    theme: Theme,
+    compact_layout: bool,
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_user_settings(user_id: i64, conn: &mut AsyncPgConnection) -> Result<UserSettings> {
+    Ok(user_settings::table
+        .find(user_id)
+        .select(UserSettings::as_select())
+        .first(conn)
+        .await
+        .optional()?
+        .unwrap_or_default())
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn set_user_settings(
+    user_id: i64,
+    settings: &UserSettings,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::insert_into(user_settings::table)
+        .values(NewUserSettings {
+            user_id,
+            theme: settings.theme,
+            compact_layout: settings.compact_layout,
+        })
+        .on_conflict(user_settings::user_id)
+        .do_update()
+        .set(settings)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
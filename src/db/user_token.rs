@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::IntervalDsl;
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rocket::form::FromFormField;
+
+use crate::db::worker_credential::{generate_token, hash_token};
+use crate::db::UserTokenId;
+use crate::error::{Error, Result};
+use crate::schema::user_tokens;
+
+// Scripts can hit the API in a loop, there's no point in writing the timestamp on every request.
+const LAST_USED_RESOLUTION_MINUTES: i32 = 1;
+const TOKEN_PREFIX: &str = "apu_";
+const MAX_TOKENS_PER_USER: i64 = 20;
+
+/// What a personal token is allowed to do on behalf of its user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromFormField)]
+pub enum UserTokenScope {
+    #[field(value = "read-own-rooms")]
+    ReadOwnRooms,
+    #[field(value = "upload-yaml")]
+    UploadYaml,
+    #[field(value = "download-yamls")]
+    DownloadYamls,
+}
+
+impl UserTokenScope {
+    pub const ALL: [UserTokenScope; 3] = [
+        UserTokenScope::ReadOwnRooms,
+        UserTokenScope::UploadYaml,
+        UserTokenScope::DownloadYamls,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserTokenScope::ReadOwnRooms => "read-own-rooms",
+            UserTokenScope::UploadYaml => "upload-yaml",
+            UserTokenScope::DownloadYamls => "download-yamls",
+        }
+    }
+}
+
+impl Display for UserTokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserTokenScope::ReadOwnRooms => f.write_str("List my rooms"),
+            UserTokenScope::UploadYaml => f.write_str("Upload YAMLs"),
+            UserTokenScope::DownloadYamls => f.write_str("Download room YAMLs"),
+        }
+    }
+}
+
+/// A token a user hands to their scripts so they can use the API as them. Only the token's hash
+/// is stored, the token itself is shown once when it's created.
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = user_tokens)]
+pub struct UserToken {
+    pub id: UserTokenId,
+    pub user_id: i64,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl UserToken {
+    pub fn allows(&self, scope: UserTokenScope) -> bool {
+        self.scopes.iter().any(|allowed| allowed == scope.as_str())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=user_tokens)]
+struct NewUserToken<'a> {
+    id: UserTokenId,
+    user_id: i64,
+    name: &'a str,
+    token_hash: &'a str,
+    scopes: Vec<String>,
+}
+
+/// The user's tokens that haven't been revoked, newest first.
+#[tracing::instrument(skip(conn))]
+pub async fn list_user_tokens(
+    user_id: i64,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<UserToken>> {
+    Ok(user_tokens::table
+        .filter(user_tokens::user_id.eq(user_id))
+        .filter(user_tokens::revoked_at.is_null())
+        .select(UserToken::as_select())
+        .order(user_tokens::created_at.desc())
+        .load(conn)
+        .await?)
+}
+
+/// Creates a token allowed to do `scopes` as `user_id`. Returns it along with the token.
+#[tracing::instrument(skip(conn))]
+pub async fn create_user_token(
+    user_id: i64,
+    name: &str,
+    scopes: &[UserTokenScope],
+    conn: &mut AsyncPgConnection,
+) -> Result<(UserToken, String)> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::validation("Give the token a name to recognize it"));
+    }
+    if scopes.is_empty() {
+        return Err(Error::validation("A token needs at least one permission"));
+    }
+    let active_tokens: i64 = user_tokens::table
+        .filter(user_tokens::user_id.eq(user_id))
+        .filter(user_tokens::revoked_at.is_null())
+        .count()
+        .get_result(conn)
+        .await?;
+    if active_tokens >= MAX_TOKENS_PER_USER {
+        return Err(Error::conflict(format!(
+            "You can't have more than {} tokens, revoke one first",
+            MAX_TOKENS_PER_USER
+        )));
+    }
+
+    let token = generate_token(TOKEN_PREFIX);
+    let scopes = UserTokenScope::ALL
+        .into_iter()
+        .filter(|scope| scopes.contains(scope))
+        .map(|scope| scope.as_str().to_string())
+        .collect();
+    let user_token = diesel::insert_into(user_tokens::table)
+        .values(NewUserToken {
+            id: UserTokenId::new_v4(),
+            user_id,
+            name,
+            token_hash: &hash_token(&token),
+            scopes,
+        })
+        .returning(UserToken::as_returning())
+        .get_result(conn)
+        .await?;
+
+    Ok((user_token, token))
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn revoke_user_token(
+    user_id: i64,
+    id: UserTokenId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let updated = diesel::update(
+        user_tokens::table
+            .find(id)
+            .filter(user_tokens::user_id.eq(user_id))
+            .filter(user_tokens::revoked_at.is_null()),
+    )
+    .set(user_tokens::revoked_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+
+    if updated == 0 {
+        return Err(Error::not_found("No active token with that ID"));
+    }
+
+    Ok(())
+}
+
+/// Returns the token's details if it exists and hasn't been revoked.
+#[tracing::instrument(skip(token, conn))]
+pub async fn authenticate_user_token(
+    token: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<UserToken>> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+
+    let user_token = user_tokens::table
+        .filter(user_tokens::token_hash.eq(hash_token(token)))
+        .filter(user_tokens::revoked_at.is_null())
+        .select(UserToken::as_select())
+        .first(conn)
+        .await
+        .optional()?;
+
+    let Some(user_token) = user_token else {
+        return Ok(None);
+    };
+
+    diesel::update(user_tokens::table.find(user_token.id).filter(
+        user_tokens::last_used_at.is_null().or(
+            user_tokens::last_used_at.lt(diesel::dsl::now - LAST_USED_RESOLUTION_MINUTES.minutes()),
+        ),
+    ))
+    .set(user_tokens::last_used_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+
+    Ok(Some(user_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::{UserToken, UserTokenScope};
+    use crate::db::UserTokenId;
+
+    #[test]
+    fn test_user_token_allows() {
+        let token = UserToken {
+            id: UserTokenId::new_v4(),
+            user_id: 1,
+            name: "script".to_string(),
+            scopes: vec![UserTokenScope::UploadYaml.as_str().to_string()],
+            created_at: NaiveDateTime::default(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+
+        assert!(token.allows(UserTokenScope::UploadYaml));
+        assert!(!token.allows(UserTokenScope::DownloadYamls));
+        assert!(!token.allows(UserTokenScope::ReadOwnRooms));
+    }
+}
@@ -0,0 +1,95 @@
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+use crate::db::{NewRoom, Room, RoomId, YamlId};
+use crate::error::{Error, Result};
+use crate::schema::{rooms, yamls};
+
+#[tracing::instrument(skip(room, conn), fields(room_id = %room.id))]
+pub async fn set_room_slot_cap(
+    room: &Room,
+    max_slots: Option<i32>,
+    author_id: Option<i64>,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    if max_slots.is_some_and(|max_slots| max_slots <= 0) {
+        return Err(Error::validation("The room should have at least one slot"));
+    }
+
+    let new_room = NewRoom {
+        max_slots: Some(max_slots),
+        ..room.settings.as_new_room(room.id, None)
+    };
+    super::update_room_with_revision(room, &new_room, author_id, conn).await
+}
+
+/// Locks the room's cap until the end of the transaction and returns how many slots are still
+/// free, `None` if the room isn't capped. Excluded and waitlisted YAMLs don't take a slot.
+async fn lock_free_slots(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<Option<i64>> {
+    let max_slots = rooms::table
+        .find(room_id)
+        .select(rooms::max_slots)
+        .for_update()
+        .first::<Option<i32>>(conn)
+        .await?;
+    let Some(max_slots) = max_slots else {
+        return Ok(None);
+    };
+
+    let taken_slots: i64 = yamls::table
+        .filter(yamls::room_id.eq(room_id))
+        .filter(yamls::waitlisted.eq(false))
+        .filter(yamls::excluded.eq(false))
+        .count()
+        .get_result(conn)
+        .await?;
+
+    Ok(Some((i64::from(max_slots) - taken_slots).max(0)))
+}
+
+/// Whether a YAML added to the room now should go on the waitlist. Call it in the transaction
+/// that adds the YAML so that concurrent uploads can't both take the last slot.
+pub(crate) async fn should_waitlist(room_id: RoomId, conn: &mut AsyncPgConnection) -> Result<bool> {
+    Ok(lock_free_slots(room_id, conn).await? == Some(0))
+}
+
+/// Gives the free slots to the oldest waitlisted YAMLs, every one of them if the room isn't
+/// capped anymore. Returns the player names of the promoted YAMLs.
+#[tracing::instrument(skip(conn))]
+pub async fn promote_waitlisted_yamls(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<String>> {
+    conn.transaction::<_, Error, _>(|conn| {
+        async move {
+            let mut query = yamls::table
+                .filter(yamls::room_id.eq(room_id))
+                .filter(yamls::waitlisted.eq(true))
+                .filter(yamls::excluded.eq(false))
+                .order(yamls::created_at.asc())
+                .select((yamls::id, yamls::player_name))
+                .into_boxed();
+            if let Some(free_slots) = lock_free_slots(room_id, conn).await? {
+                query = query.limit(free_slots);
+            }
+            let promoted: Vec<(YamlId, String)> = query.load(conn).await?;
+            if promoted.is_empty() {
+                return Ok(vec![]);
+            }
+
+            diesel::update(yamls::table)
+                .filter(yamls::id.eq_any(promoted.iter().map(|(yaml_id, _)| *yaml_id)))
+                .set(yamls::waitlisted.eq(false))
+                .execute(conn)
+                .await?;
+
+            Ok(promoted
+                .into_iter()
+                .map(|(_, player_name)| player_name)
+                .collect())
+        }
+        .scope_boxed()
+    })
+    .await
+}
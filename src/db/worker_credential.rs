@@ -0,0 +1,192 @@
+use base64::Engine;
+use chrono::NaiveDateTime;
+use diesel::dsl::IntervalDsl;
+use diesel::prelude::*;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use sha2::{Digest, Sha256};
+
+use crate::db::WorkerCredentialId;
+use crate::error::{Error, Result};
+use crate::schema::worker_credentials;
+
+// Workers poll constantly, there's no point in writing the timestamp on every claim.
+const LAST_USED_RESOLUTION_MINUTES: i32 = 1;
+const TOKEN_PREFIX: &str = "apw_";
+
+/// A token a worker authenticates with against the queues it's allowed to work on. Only the
+/// token's hash is stored, the token itself is shown once when it's created or rotated.
+#[derive(Debug, Clone, Selectable, Queryable)]
+#[diesel(table_name = worker_credentials)]
+pub struct WorkerCredential {
+    pub id: WorkerCredentialId,
+    pub name: String,
+    pub queues: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub rotated_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=worker_credentials)]
+struct NewWorkerCredential<'a> {
+    id: WorkerCredentialId,
+    name: &'a str,
+    token_hash: &'a str,
+    queues: &'a [String],
+}
+
+pub(crate) fn generate_token(prefix: &str) -> String {
+    let bytes = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()]
+        .iter()
+        .flat_map(|uuid| *uuid.as_bytes())
+        .collect::<Vec<_>>();
+
+    format!(
+        "{}{}",
+        prefix,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    )
+}
+
+// Tokens are random so a plain hash is enough, there's nothing to brute force.
+pub(crate) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn list_worker_credentials(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<WorkerCredential>> {
+    Ok(worker_credentials::table
+        .select(WorkerCredential::as_select())
+        .order(worker_credentials::created_at.desc())
+        .load(conn)
+        .await?)
+}
+
+/// Creates a credential allowed on `queues`. Returns it along with its token.
+#[tracing::instrument(skip(conn))]
+pub async fn create_worker_credential(
+    name: &str,
+    queues: &[String],
+    conn: &mut AsyncPgConnection,
+) -> Result<(WorkerCredential, String)> {
+    let token = generate_token(TOKEN_PREFIX);
+    let credential = diesel::insert_into(worker_credentials::table)
+        .values(NewWorkerCredential {
+            id: WorkerCredentialId::new_v4(),
+            name,
+            token_hash: &hash_token(&token),
+            queues,
+        })
+        .returning(WorkerCredential::as_returning())
+        .get_result(conn)
+        .await?;
+
+    Ok((credential, token))
+}
+
+/// Gives the credential a new token, the previous one stops working right away.
+#[tracing::instrument(skip(conn))]
+pub async fn rotate_worker_credential(
+    id: WorkerCredentialId,
+    conn: &mut AsyncPgConnection,
+) -> Result<(WorkerCredential, String)> {
+    let token = generate_token(TOKEN_PREFIX);
+    let credential = diesel::update(
+        worker_credentials::table
+            .find(id)
+            .filter(worker_credentials::revoked_at.is_null()),
+    )
+    .set((
+        worker_credentials::token_hash.eq(hash_token(&token)),
+        worker_credentials::rotated_at.eq(diesel::dsl::now),
+    ))
+    .returning(WorkerCredential::as_returning())
+    .get_result(conn)
+    .await
+    .optional()?;
+
+    let Some(credential) = credential else {
+        return Err(Error::not_found("No active worker credential with that ID"));
+    };
+
+    Ok((credential, token))
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn revoke_worker_credential(
+    id: WorkerCredentialId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let updated = diesel::update(
+        worker_credentials::table
+            .find(id)
+            .filter(worker_credentials::revoked_at.is_null()),
+    )
+    .set(worker_credentials::revoked_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+
+    if updated == 0 {
+        return Err(Error::not_found("No active worker credential with that ID"));
+    }
+
+    Ok(())
+}
+
+/// Returns the credential `token` belongs to if it's allowed on `queue` and hasn't been revoked.
+#[tracing::instrument(skip(token, conn))]
+pub async fn authenticate_worker(
+    token: &str,
+    queue: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<Option<WorkerCredential>> {
+    let credential = worker_credentials::table
+        .filter(worker_credentials::token_hash.eq(hash_token(token)))
+        .filter(worker_credentials::revoked_at.is_null())
+        .select(WorkerCredential::as_select())
+        .first(conn)
+        .await
+        .optional()?;
+
+    let Some(credential) = credential else {
+        return Ok(None);
+    };
+    if !credential.queues.iter().any(|allowed| allowed == queue) {
+        return Ok(None);
+    }
+
+    diesel::update(
+        worker_credentials::table.find(credential.id).filter(
+            worker_credentials::last_used_at
+                .is_null()
+                .or(worker_credentials::last_used_at
+                    .lt(diesel::dsl::now - LAST_USED_RESOLUTION_MINUTES.minutes())),
+        ),
+    )
+    .set(worker_credentials::last_used_at.eq(diesel::dsl::now))
+    .execute(conn)
+    .await?;
+
+    Ok(Some(credential))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, hash_token, TOKEN_PREFIX};
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token(TOKEN_PREFIX);
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_ne!(token, generate_token(TOKEN_PREFIX));
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_eq!(hash_token(&token).len(), 64);
+    }
+}
@@ -1,14 +1,20 @@
 use std::collections::HashMap;
 
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::{Insertable, Queryable, Selectable};
-use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 
 use crate::db::{Json, Room, RoomId, YamlId};
-use crate::error::Result;
+use crate::deadline::Deadline;
+use crate::error::{Error, Result};
 use crate::extractor::YamlFeatures;
+use crate::jobs::UnknownReference;
 use crate::schema::{discord_users, rooms, yamls};
+use crate::yaml::ValidatedYaml;
 
 #[derive(Insertable)]
 #[diesel(table_name=yamls)]
@@ -20,13 +26,46 @@ pub struct NewYaml<'a> {
     player_name: &'a str,
     game: &'a str,
     features: Json<YamlFeatures>,
+    validation_warnings: Json<Vec<UnknownReference>>,
+    content_fingerprint: String,
+    waitlisted: bool,
+    original_content: Option<&'a str>,
+}
+
+/// Hash of a YAML's content ignoring the player name, comments, blank lines and separators. Used
+/// to spot the same file being uploaded under different names.
+pub fn content_fingerprint(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty()
+            || line.trim_start().starts_with('#')
+            || line.starts_with("name:")
+            || line == "---"
+        {
+            continue;
+        }
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
 }
 
 #[derive(Debug, Selectable, Queryable)]
 pub struct Yaml {
+    pub room_id: RoomId,
     pub content: String,
     pub player_name: String,
     pub owner_id: i64,
+    pub excluded: bool,
+    pub waitlisted: bool,
+    /// The document as it was uploaded, when tidying it changed it.
+    pub original_content: Option<String>,
 }
 
 #[derive(Debug, Selectable, Queryable)]
@@ -37,6 +76,11 @@ pub struct YamlWithoutContent {
     pub game: String,
     pub owner_id: i64,
     pub features: Json<YamlFeatures>,
+    pub checked_in_at: Option<NaiveDateTime>,
+    pub excluded: bool,
+    pub validation_warnings: Json<Vec<UnknownReference>>,
+    /// Uploaded once the room was full, it gets a slot when one frees up.
+    pub waitlisted: bool,
 }
 
 #[derive(Deserialize, Debug)]
@@ -46,6 +90,20 @@ pub enum YamlGame {
     Map(HashMap<String, f64>),
 }
 
+impl YamlGame {
+    /// Every game this YAML can roll.
+    pub fn possible_games(&self) -> Vec<&str> {
+        match self {
+            YamlGame::Name(name) => vec![name],
+            YamlGame::Map(map) => map
+                .iter()
+                .filter(|(_, probability)| **probability != 0.)
+                .map(|(game, _)| game.as_str())
+                .collect(),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct YamlFile {
     pub game: YamlGame,
@@ -63,7 +121,7 @@ pub async fn get_yamls_for_room_with_author_names(
         .first::<Room>(conn)
         .await;
     let Ok(_room) = room else {
-        Err(anyhow::anyhow!("Couldn't get room"))?
+        Err(Error::not_found("Couldn't get room"))?
     };
 
     Ok(yamls::table
@@ -85,7 +143,7 @@ pub async fn get_yamls_for_room(
         .first::<Room>(conn)
         .await;
     let Ok(_room) = room else {
-        Err(anyhow::anyhow!("Couldn't get room"))?
+        Err(Error::not_found("Couldn't get room"))?
     };
 
     Ok(yamls::table
@@ -95,6 +153,48 @@ pub async fn get_yamls_for_room(
         .await?)
 }
 
+/// The database's clock. `updated_at` is set from it, so cursors compared against that column have
+/// to come from here rather than from the app server's clock.
+#[tracing::instrument(skip(conn))]
+pub async fn current_timestamp(conn: &mut AsyncPgConnection) -> Result<NaiveDateTime> {
+    Ok(diesel::select(diesel::dsl::now).get_result(conn).await?)
+}
+
+/// The room's YAMLs updated after `since`, all of them without it. Oldest update first.
+#[tracing::instrument(skip(conn))]
+pub async fn get_yamls_for_room_updated_since(
+    room_id: RoomId,
+    since: Option<NaiveDateTime>,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<YamlWithoutContent>> {
+    let mut query = yamls::table
+        .filter(yamls::room_id.eq(room_id))
+        .select(YamlWithoutContent::as_select())
+        .order(yamls::updated_at.asc())
+        .into_boxed();
+    if let Some(since) = since {
+        query = query.filter(yamls::updated_at.gt(since));
+    }
+
+    Ok(query.load(conn).await?)
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_yaml_ids_for_room(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<YamlId>> {
+    Ok(yamls::table
+        .filter(yamls::room_id.eq(room_id))
+        .select(yamls::id)
+        .load(conn)
+        .await?)
+}
+
+/// Returns whether the YAML went on the room's waitlist. The document is tidied before being
+/// stored unless the room asked for exact storage, the upload is kept next to it when that
+/// changed anything.
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip(conn, content))]
 pub async fn add_yaml_to_room(
     room_id: RoomId,
@@ -103,22 +203,103 @@ pub async fn add_yaml_to_room(
     content: &str,
     parsed: &YamlFile,
     features: YamlFeatures,
+    validation_warnings: Vec<UnknownReference>,
+    validation_job_id: Option<&str>,
     conn: &mut AsyncPgConnection,
-) -> Result<()> {
+) -> Result<bool> {
+    let yaml_id = YamlId::new_v4();
+    let waitlisted = super::should_waitlist(room_id, conn).await?;
+    let exact_storage = rooms::table
+        .find(room_id)
+        .select(rooms::exact_yaml_storage)
+        .first::<bool>(conn)
+        .await?;
+    let tidied = if exact_storage {
+        content.to_string()
+    } else {
+        crate::yaml::tidy_document(content)
+    };
     let new_yaml = NewYaml {
-        id: YamlId::new_v4(),
+        id: yaml_id,
         owner_id,
         room_id,
-        content,
+        content: &tidied,
         player_name: &parsed.name,
         game: game_name,
         features: Json(features),
+        validation_warnings: Json(validation_warnings),
+        content_fingerprint: content_fingerprint(&tidied),
+        waitlisted,
+        original_content: (tidied != content).then_some(content),
     };
 
     diesel::insert_into(yamls::table)
         .values(new_yaml)
         .execute(conn)
         .await?;
+    if let Some(job_id) = validation_job_id {
+        super::link_job_to_yaml(job_id, yaml_id, conn).await?;
+    }
+
+    Ok(waitlisted)
+}
+
+/// Adds YAMLs that went through the room's checks, all or none of them. Statements are aborted
+/// once `deadline` runs out. A guest upload passes its link so that claiming it happens in the
+/// same transaction, a failed insert doesn't burn the link.
+///
+/// Returns how many of the YAMLs went on the waitlist.
+#[tracing::instrument(skip(yamls, guest_link, deadline, conn))]
+pub async fn add_validated_yamls_to_room(
+    room_id: RoomId,
+    owner_id: i64,
+    yamls: Vec<ValidatedYaml<'_>>,
+    guest_link: Option<&str>,
+    deadline: &Deadline,
+    conn: &mut AsyncPgConnection,
+) -> Result<usize> {
+    let transaction = conn.transaction::<usize, Error, _>(|conn| {
+        async move {
+            deadline.set_statement_timeout(conn).await?;
+            if let Some(code) = guest_link {
+                super::claim_guest_link(room_id, code, conn).await?;
+            }
+
+            let mut waitlisted_nb = 0;
+            for yaml in yamls {
+                let waitlisted = add_yaml_to_room(
+                    room_id,
+                    owner_id,
+                    &yaml.game_name,
+                    yaml.document,
+                    yaml.parsed,
+                    yaml.features,
+                    yaml.unknown_references,
+                    yaml.validation_job_id.as_deref(),
+                    conn,
+                )
+                .await?;
+                waitlisted_nb += usize::from(waitlisted);
+            }
+
+            Ok(waitlisted_nb)
+        }
+        .scope_boxed()
+    });
+
+    deadline.run(transaction).await?
+}
+
+#[tracing::instrument(skip(validation_warnings, conn))]
+pub async fn set_yaml_validation_warnings(
+    yaml_id: YamlId,
+    validation_warnings: &[UnknownReference],
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(yamls::table.find(yaml_id))
+        .set(yamls::validation_warnings.eq(Json(validation_warnings.to_vec())))
+        .execute(conn)
+        .await?;
 
     Ok(())
 }
@@ -132,6 +313,27 @@ pub async fn remove_yaml(yaml_id: YamlId, conn: &mut AsyncPgConnection) -> Resul
     Ok(())
 }
 
+/// Replaces the document and player name together so they can't get out of sync.
+#[tracing::instrument(skip(conn, content))]
+pub async fn rename_yaml(
+    yaml_id: YamlId,
+    content: &str,
+    original_content: Option<&str>,
+    player_name: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::update(yamls::table.find(yaml_id))
+        .set((
+            yamls::content.eq(content),
+            yamls::original_content.eq(original_content),
+            yamls::player_name.eq(player_name),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(skip(conn))]
 pub async fn get_yaml_by_id(yaml_id: YamlId, conn: &mut AsyncPgConnection) -> Result<Yaml> {
     Ok(yamls::table
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
+use diesel::{Insertable, Queryable, Selectable};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rocket::form::FromFormField;
+
+use crate::db::{RoomId, YamlId};
+use crate::error::Result;
+use crate::schema::{yaml_annotations, yamls};
+
+/// Organizer triage state for a YAML. Only ever shown to the room's organizer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromFormField, FromSqlRow, AsExpression)]
+#[diesel(sql_type = Text)]
+pub enum YamlFlag {
+    #[field(value = "needs_review")]
+    NeedsReview,
+    #[field(value = "approved")]
+    Approved,
+    #[field(value = "problematic")]
+    Problematic,
+}
+
+impl YamlFlag {
+    pub const ALL: [YamlFlag; 3] = [
+        YamlFlag::NeedsReview,
+        YamlFlag::Approved,
+        YamlFlag::Problematic,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            YamlFlag::NeedsReview => "needs_review",
+            YamlFlag::Approved => "approved",
+            YamlFlag::Problematic => "problematic",
+        }
+    }
+
+    /// Background of the flag's badge, keep in sync with `.yaml-flag` in base.sass.
+    pub fn badge_color(&self) -> &'static str {
+        match self {
+            YamlFlag::NeedsReview => "#866013",
+            YamlFlag::Approved => "#267326",
+            YamlFlag::Problematic => "#b31919",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            YamlFlag::NeedsReview => "Needs review",
+            YamlFlag::Approved => "Approved",
+            YamlFlag::Problematic => "Problematic",
+        }
+    }
+}
+
+impl Display for YamlFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl ToSql<Text, Pg> for YamlFlag {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for YamlFlag {
+    fn from_sql(bytes: PgValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        YamlFlag::ALL
+            .into_iter()
+            .find(|flag| flag.as_str() == value)
+            .ok_or_else(|| format!("Unknown yaml flag: {}", value).into())
+    }
+}
+
+#[derive(Debug, Clone, Default, Selectable, Queryable)]
+#[diesel(table_name = yaml_annotations)]
+pub struct YamlAnnotation {
+    pub flag: Option<YamlFlag>,
+    pub notes: String,
+}
+
+impl YamlAnnotation {
+    pub fn has_flag(&self, flag: &YamlFlag) -> bool {
+        self.flag.as_ref() == Some(flag)
+    }
+
+    /// Value of the row's `data-flag` attribute, used by the room page's filter.
+    pub fn filter_key(&self) -> &'static str {
+        self.flag.map(|flag| flag.as_str()).unwrap_or("none")
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=yaml_annotations)]
+struct NewYamlAnnotation<'a> {
+    yaml_id: YamlId,
+    flag: Option<YamlFlag>,
+    notes: &'a str,
+}
+
+#[tracing::instrument(skip(conn))]
+pub async fn get_yaml_annotations_for_room(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<HashMap<YamlId, YamlAnnotation>> {
+    Ok(yaml_annotations::table
+        .inner_join(yamls::table)
+        .filter(yamls::room_id.eq(room_id))
+        .select((yaml_annotations::yaml_id, YamlAnnotation::as_select()))
+        .load::<(YamlId, YamlAnnotation)>(conn)
+        .await?
+        .into_iter()
+        .collect())
+}
+
+#[tracing::instrument(skip(conn, notes))]
+pub async fn set_yaml_annotation(
+    yaml_id: YamlId,
+    flag: Option<YamlFlag>,
+    notes: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    diesel::insert_into(yaml_annotations::table)
+        .values(NewYamlAnnotation {
+            yaml_id,
+            flag,
+            notes,
+        })
+        .on_conflict(yaml_annotations::yaml_id)
+        .do_update()
+        .set((
+            yaml_annotations::flag.eq(flag),
+            yaml_annotations::notes.eq(notes),
+            yaml_annotations::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
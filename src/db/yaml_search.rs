@@ -0,0 +1,164 @@
+use diesel::prelude::*;
+use diesel_async::AsyncPgConnection;
+
+use crate::db::{contains_pattern, Paginate, RoomId, YamlId};
+use crate::error::{Error, Result};
+use crate::schema::{rooms, yamls};
+
+// Shorter queries can't use the trigram index and end up scanning every YAML.
+pub const MIN_YAML_SEARCH_LEN: usize = 3;
+const MAX_SNIPPETS: usize = 3;
+// How many characters of the line are kept on each side of the match.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// A line of a YAML that matched the search, split around the match so it can be highlighted.
+#[derive(Debug, PartialEq)]
+pub struct YamlSnippet {
+    pub line: usize,
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+}
+
+#[derive(Debug)]
+pub struct YamlSearchResult {
+    pub yaml_id: YamlId,
+    pub room_id: RoomId,
+    pub room_name: String,
+    pub player_name: String,
+    pub game: String,
+    pub snippets: Vec<YamlSnippet>,
+}
+
+/// Byte range of the first case insensitive occurrence of `needle` in `haystack`.
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    haystack.char_indices().find_map(|(start, _)| {
+        let mut rest = haystack[start..].char_indices();
+        let mut end = start;
+        for expected in needle.chars() {
+            let (offset, c) = rest.next()?;
+            if !c.to_lowercase().eq(expected.to_lowercase()) {
+                return None;
+            }
+            end = start + offset + c.len_utf8();
+        }
+        Some((start, end))
+    })
+}
+
+/// The first lines of `content` containing `query`, trimmed down to some context around it.
+pub fn content_snippets(content: &str, query: &str) -> Vec<YamlSnippet> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let (start, end) = find_ignore_case(line, query)?;
+            let before = &line[..start];
+            let before_len = before.chars().count();
+            let before = if before_len > SNIPPET_CONTEXT {
+                let cut = before
+                    .chars()
+                    .skip(before_len - SNIPPET_CONTEXT)
+                    .collect::<String>();
+                format!("…{}", cut)
+            } else {
+                before.trim_start().to_string()
+            };
+            let after = &line[end..];
+            let after = if after.chars().count() > SNIPPET_CONTEXT {
+                let cut = after.chars().take(SNIPPET_CONTEXT).collect::<String>();
+                format!("{}…", cut)
+            } else {
+                after.to_string()
+            };
+
+            Some(YamlSnippet {
+                line: idx + 1,
+                before,
+                matched: line[start..end].to_string(),
+                after,
+            })
+        })
+        .take(MAX_SNIPPETS)
+        .collect()
+}
+
+/// Every YAML whose content contains `query`, newest first, across all rooms.
+#[tracing::instrument(skip(conn))]
+pub async fn search_yaml_contents(
+    query: &str,
+    page: u64,
+    conn: &mut AsyncPgConnection,
+) -> Result<(Vec<YamlSearchResult>, u64)> {
+    let query = query.trim();
+    if query.chars().count() < MIN_YAML_SEARCH_LEN {
+        return Err(Error::validation(format!(
+            "Searches need at least {} characters",
+            MIN_YAML_SEARCH_LEN
+        )));
+    }
+
+    let (rows, max_pages) = yamls::table
+        .inner_join(rooms::table)
+        .filter(yamls::content.ilike(contains_pattern(query)))
+        .order(yamls::created_at.desc())
+        .select((
+            yamls::id,
+            yamls::room_id,
+            rooms::name,
+            yamls::player_name,
+            yamls::game,
+            yamls::content,
+        ))
+        .paginate(page)
+        .load_and_count_pages::<(YamlId, RoomId, String, String, String, String)>(conn)
+        .await?;
+
+    let results = rows
+        .into_iter()
+        .map(
+            |(yaml_id, room_id, room_name, player_name, game, content)| YamlSearchResult {
+                yaml_id,
+                room_id,
+                room_name,
+                player_name,
+                game,
+                snippets: content_snippets(&content, query),
+            },
+        )
+        .collect();
+
+    Ok((results, max_pages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_snippets, YamlSnippet};
+
+    #[test]
+    fn test_content_snippets() {
+        let content = "name: Link\ngame: A Link to the Past\nA Link to the Past:\n  Goal: ganon\n";
+        let snippets = content_snippets(content, "GOAL");
+        assert_eq!(
+            snippets,
+            vec![YamlSnippet {
+                line: 4,
+                before: "".into(),
+                matched: "Goal".into(),
+                after: ": ganon".into(),
+            }]
+        );
+
+        let snippets = content_snippets(content, "link");
+        assert_eq!(snippets.len(), 3);
+        assert_eq!(snippets[0].matched, "Link");
+        assert_eq!(snippets[1].line, 2);
+
+        let long_line = format!("{}needle{}", "a".repeat(50), "é".repeat(50));
+        let snippets = content_snippets(&long_line, "needle");
+        assert_eq!(snippets[0].before, format!("…{}", "a".repeat(40)));
+        assert_eq!(snippets[0].after, format!("{}…", "é".repeat(40)));
+
+        assert!(content_snippets(content, "kokiri").is_empty());
+    }
+}
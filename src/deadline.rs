@@ -0,0 +1,114 @@
+use std::future::Future;
+use std::time::Duration;
+
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, Shutdown};
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+
+const DEFAULT_REQUEST_DEADLINE: Duration = Duration::from_secs(30);
+
+/// Time budget for the current request.
+///
+/// Rocket keeps running handlers after the client went away, so this is what bounds how long an
+/// abandoned request can hold on to a validation job or a database connection. Long waits should
+/// go through [`Deadline::run`], which gives up once the budget is spent or the server is shutting
+/// down.
+#[derive(Clone)]
+pub struct Deadline {
+    at: Instant,
+    shutdown: Option<Shutdown>,
+}
+
+struct RequestDeadline(Instant);
+
+impl Deadline {
+    pub fn after(duration: Duration) -> Self {
+        Self {
+            at: Instant::now() + duration,
+            shutdown: None,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Drives `fut` to completion unless the deadline expires or the server shuts down first, in
+    /// which case `fut` is dropped.
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output> {
+        let shutdown = async {
+            match self.shutdown.clone() {
+                Some(shutdown) => shutdown.await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            output = fut => Ok(output),
+            _ = tokio::time::sleep_until(self.at) => {
+                tracing::warn!("Request deadline exceeded");
+                Err(Error::unavailable(
+                    "This took too long. The lobby might be overloaded, try again in a bit.",
+                ))
+            }
+            _ = shutdown => Err(Error::unavailable(
+                "The lobby is restarting, try again in a bit.",
+            )),
+        }
+    }
+
+    /// Makes postgres abort statements of the current transaction that would outlive the
+    /// deadline. Dropping a query future doesn't stop the query server side, this does.
+    pub async fn set_statement_timeout(&self, conn: &mut AsyncPgConnection) -> Result<()> {
+        // A timeout of 0 disables it, make sure we always keep at least some budget.
+        let timeout_ms = self.remaining().as_millis().max(1);
+        diesel::sql_query(format!("SET LOCAL statement_timeout = {}", timeout_ms))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Deadline {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let at = request
+            .local_cache(|| {
+                let budget = std::env::var("REQUEST_DEADLINE_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_REQUEST_DEADLINE);
+                RequestDeadline(Instant::now() + budget)
+            })
+            .0;
+        let shutdown = request.guard::<Shutdown>().await.succeeded();
+
+        Outcome::Success(Deadline { at, shutdown })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Deadline;
+    use rocket::http::Status;
+    use std::time::Duration;
+
+    #[rocket::async_test]
+    async fn test_run() {
+        let deadline = Deadline::after(Duration::from_millis(50));
+        assert_eq!(deadline.run(async { 42 }).await.unwrap(), 42);
+
+        let res = deadline
+            .run(tokio::time::sleep(Duration::from_secs(10)))
+            .await;
+        assert_eq!(res.unwrap_err().status(), Status::ServiceUnavailable);
+        assert_eq!(deadline.remaining(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel_async::pooled_connection::deadpool::Pool;
+use diesel_async::AsyncPgConnection;
+
+use crate::db::{self, Delivery, DeliveryChannel};
+use crate::error::Result;
+use crate::locks::LockManager;
+
+const LOCK_NAME: &str = "outbound_deliveries";
+const TICK: Duration = Duration::from_secs(15);
+const BATCH_SIZE: i64 = 50;
+
+/// Sends the queued [`Delivery`]s, so that features only have to enqueue what they want to send.
+/// Failed deliveries are retried with an exponential backoff and dead-lettered after
+/// [`db::MAX_DELIVERY_ATTEMPTS`], admins can look at those and retry them.
+///
+/// Every instance runs a deliverer, the lock makes sure only one of them does the work.
+pub struct Deliverer {
+    db_pool: Pool<AsyncPgConnection>,
+    locks: LockManager,
+    http: reqwest::Client,
+}
+
+impl Deliverer {
+    pub fn new(db_pool: Pool<AsyncPgConnection>, locks: LockManager) -> Self {
+        Self {
+            db_pool,
+            locks,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build the delivery HTTP client"),
+        }
+    }
+
+    pub fn start(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_due_deliveries().await {
+                    tracing::error!("Failed to run outbound deliveries: {:?}", e);
+                }
+            }
+        });
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn run_due_deliveries(&self) -> Result<()> {
+        let Some(lock) = self.locks.try_acquire(LOCK_NAME, TICK).await? else {
+            return Ok(());
+        };
+
+        let mut conn = self.db_pool.get().await?;
+        let deliveries =
+            db::get_due_deliveries(Utc::now().naive_utc(), BATCH_SIZE, &mut conn).await?;
+        for delivery in deliveries {
+            if !lock.is_held() {
+                return Ok(());
+            }
+
+            match self.send(&delivery).await {
+                Ok(()) => db::record_delivery_success(delivery.id, &mut conn).await?,
+                Err(e) => {
+                    tracing::warn!(
+                        delivery_id = %delivery.id,
+                        attempts = delivery.attempts + 1,
+                        "Failed to deliver: {:?}",
+                        e
+                    );
+                    db::record_delivery_failure(
+                        &delivery,
+                        &e.to_string(),
+                        Utc::now().naive_utc(),
+                        &mut conn,
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        lock.release().await
+    }
+
+    async fn send(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        match delivery.channel {
+            DeliveryChannel::DiscordWebhook => self.send_discord_webhook(delivery).await,
+        }
+    }
+
+    async fn send_discord_webhook(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        let res = self
+            .http
+            .post(&delivery.target)
+            .json(&delivery.payload.0)
+            .send()
+            .await?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(anyhow!("Discord answered with {}", status));
+        }
+
+        Ok(())
+    }
+}
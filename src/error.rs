@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::io::Cursor;
 use std::sync::OnceLock;
 
@@ -6,13 +7,29 @@ use rocket::request::{FromRequest, Outcome};
 use rocket::response::{self, Responder};
 use rocket::{Request, Response};
 
+use crate::request_id::RequestId;
 use crate::session::Session;
 
 pub type Result<T> = std::result::Result<T, Error>;
 pub type ApiResult<T> = std::result::Result<T, ApiError>;
 
+/// Errors returned by the lobby.
+///
+/// Every variant keeps the underlying `anyhow::Error` so that the full context chain is still
+/// available for logs, while the variant itself decides which status code the user gets.
 #[derive(Debug)]
-pub struct Error(pub anyhow::Error);
+pub enum Error {
+    NotFound(anyhow::Error),
+    Forbidden(anyhow::Error),
+    Conflict(anyhow::Error),
+    Validation(anyhow::Error),
+    TooLarge(anyhow::Error),
+    Upstream(anyhow::Error),
+    /// The lobby couldn't handle the request in time, or is going away. Retrying later should
+    /// work.
+    Unavailable(anyhow::Error),
+    Internal(anyhow::Error),
+}
 
 #[derive(Debug)]
 pub struct ApiError {
@@ -20,20 +37,132 @@ pub struct ApiError {
     pub status: Status,
 }
 
+impl Error {
+    pub fn not_found(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::NotFound(anyhow::anyhow!(msg.into()))
+    }
+
+    pub fn forbidden(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::Forbidden(anyhow::anyhow!(msg.into()))
+    }
+
+    pub fn conflict(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::Conflict(anyhow::anyhow!(msg.into()))
+    }
+
+    pub fn validation(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::Validation(anyhow::anyhow!(msg.into()))
+    }
+
+    pub fn too_large(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::TooLarge(anyhow::anyhow!(msg.into()))
+    }
+
+    pub fn unavailable(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::Unavailable(anyhow::anyhow!(msg.into()))
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            Self::NotFound(_) => Status::NotFound,
+            Self::Forbidden(_) => Status::Forbidden,
+            Self::Conflict(_) => Status::Conflict,
+            Self::Validation(_) => Status::BadRequest,
+            Self::TooLarge(_) => Status::PayloadTooLarge,
+            Self::Upstream(_) => Status::BadGateway,
+            Self::Unavailable(_) => Status::ServiceUnavailable,
+            Self::Internal(_) => Status::InternalServerError,
+        }
+    }
+
+    pub fn inner(&self) -> &anyhow::Error {
+        match self {
+            Self::NotFound(e)
+            | Self::Forbidden(e)
+            | Self::Conflict(e)
+            | Self::Validation(e)
+            | Self::TooLarge(e)
+            | Self::Upstream(e)
+            | Self::Unavailable(e)
+            | Self::Internal(e) => e,
+        }
+    }
+
+    pub fn into_inner(self) -> anyhow::Error {
+        match self {
+            Self::NotFound(e)
+            | Self::Forbidden(e)
+            | Self::Conflict(e)
+            | Self::Validation(e)
+            | Self::TooLarge(e)
+            | Self::Upstream(e)
+            | Self::Unavailable(e)
+            | Self::Internal(e) => e,
+        }
+    }
+
+    /// Replaces the wrapped error while keeping the variant, and thus the status code.
+    fn map(self, f: impl FnOnce(anyhow::Error) -> anyhow::Error) -> Self {
+        match self {
+            Self::NotFound(e) => Self::NotFound(f(e)),
+            Self::Forbidden(e) => Self::Forbidden(f(e)),
+            Self::Conflict(e) => Self::Conflict(f(e)),
+            Self::Validation(e) => Self::Validation(f(e)),
+            Self::TooLarge(e) => Self::TooLarge(f(e)),
+            Self::Upstream(e) => Self::Upstream(f(e)),
+            Self::Unavailable(e) => Self::Unavailable(f(e)),
+            Self::Internal(e) => Self::Internal(f(e)),
+        }
+    }
+
+    /// Picks a variant for errors coming from the libraries we talk to. Anything we don't
+    /// recognize is an internal error.
+    fn classify(error: anyhow::Error) -> Self {
+        use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+        match error.downcast_ref::<DieselError>() {
+            Some(DieselError::NotFound) => return Self::NotFound(error),
+            Some(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                return Self::Conflict(error)
+            }
+            Some(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+                return Self::Validation(error)
+            }
+            _ => {}
+        }
+
+        if error.downcast_ref::<reqwest::Error>().is_some() {
+            return Self::Upstream(error);
+        }
+
+        if error.downcast_ref::<serde_yaml::Error>().is_some() {
+            return Self::Validation(error);
+        }
+
+        Self::Internal(error)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner().fmt(f)
+    }
+}
+
 impl<E> From<E> for Error
 where
     E: Into<anyhow::Error>,
 {
     fn from(error: E) -> Self {
-        Error(error.into())
+        Error::classify(error.into())
     }
 }
 
 impl From<Error> for ApiError {
     fn from(error: Error) -> Self {
         Self {
-            error: error.0,
-            status: Status::InternalServerError,
+            status: error.status(),
+            error: error.into_inner(),
         }
     }
 }
@@ -43,10 +172,7 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(error: E) -> Self {
-        Self {
-            error: error.into(),
-            status: Status::InternalServerError,
-        }
+        Error::classify(error.into()).into()
     }
 }
 
@@ -57,7 +183,7 @@ pub trait WithStatus<T> {
 impl<T> WithStatus<T> for Result<T> {
     fn status(self, status: Status) -> ApiResult<T> {
         self.map_err(|error| ApiError {
-            error: error.0,
+            error: error.into_inner(),
             status,
         })
     }
@@ -69,7 +195,7 @@ pub trait WithContext<T> {
 
 impl<T> WithContext<T> for Result<T> {
     fn context(self, context: &'static str) -> Self {
-        Ok(anyhow::Context::context(self.map_err(|s| s.0), context)?)
+        self.map_err(|error| error.map(|e| e.context(context)))
     }
 }
 
@@ -109,7 +235,15 @@ impl Responder<'_, 'static> for Error {
             lock.set("/".to_string()).unwrap();
             RedirectTo(lock)
         });
-        let error_message = self.0.to_string();
+        let request_id = RequestId::from_request_sync(request);
+        log_error(&self, request_id);
+
+        // Only server side failures are worth reporting, give people something to quote.
+        let error_message = if self.status().class().is_server_error() {
+            format!("{} (request ID: {})", self, request_id)
+        } else {
+            self.to_string()
+        };
 
         let mut session = Session::from_request_sync(request);
         session.err_msg.push(error_message);
@@ -119,12 +253,63 @@ impl Responder<'_, 'static> for Error {
     }
 }
 
+fn log_error(error: &Error, request_id: &RequestId) {
+    match error {
+        Error::Upstream(e) | Error::Unavailable(e) | Error::Internal(e) => {
+            tracing::error!(%request_id, status = %error.status(), error = ?e, "Request failed")
+        }
+        _ => tracing::info!(
+            %request_id,
+            status = %error.status(),
+            error = ?error.inner(),
+            "Request rejected"
+        ),
+    }
+}
+
 impl Responder<'_, 'static> for ApiError {
-    fn respond_to(self, _: &Request<'_>) -> response::Result<'static> {
-        let error = self.error.to_string();
+    fn respond_to(self, request: &Request<'_>) -> response::Result<'static> {
+        let error = if self.status.class().is_server_error() {
+            let request_id = RequestId::from_request_sync(request);
+            tracing::error!(%request_id, status = %self.status, error = ?self.error, "Request failed");
+            format!("{} (request ID: {})", self.error, request_id)
+        } else {
+            self.error.to_string()
+        };
         Response::build()
             .status(self.status)
             .sized_body(error.len(), Cursor::new(error))
             .ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiError, Error, Result, WithContext};
+    use rocket::http::Status;
+
+    #[test]
+    fn test_classify() {
+        let err: Error = diesel::result::Error::NotFound.into();
+        assert_eq!(err.status(), Status::NotFound);
+
+        let err: Error = serde_yaml::from_str::<u32>("nope").unwrap_err().into();
+        assert_eq!(err.status(), Status::BadRequest);
+
+        let err: Error = anyhow::anyhow!("Something broke").into();
+        assert_eq!(err.status(), Status::InternalServerError);
+    }
+
+    #[test]
+    fn test_context_keeps_variant() {
+        let res: Result<()> = Err(Error::forbidden("Not yours"));
+        let err = res.context("Couldn't delete the room").unwrap_err();
+
+        assert_eq!(err.status(), Status::Forbidden);
+        assert_eq!(err.to_string(), "Couldn't delete the room");
+        assert_eq!(err.inner().root_cause().to_string(), "Not yours");
+
+        let api_err: ApiError = err.into();
+        assert_eq!(api_err.status, Status::Forbidden);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use super::{Extractor, YamlFeature};
+use crate::error::Result;
+
+const DEFINITIONS_FILE: &str = "features.yaml";
+
+/// How to tell that a YAML has a feature, as written in the index's `features.yaml`:
+///
+/// ```yaml
+/// Some Game:
+///   - feature: DeathLink
+///     option: death_link
+///   - feature: TrainerSanity
+///     option: trainer_mode
+///     values: [all, gym_leaders]
+///   - feature: OrbSanity
+///     option: orb_bundle_size
+///     outside: [10, 200]
+/// ```
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureRule {
+    pub feature: YamlFeature,
+    pub option: String,
+    /// The values of the option that enable the feature. Any trueish value does when it's empty.
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// For numeric options, the feature is enabled when the value is outside of this range,
+    /// bounds included in the range.
+    pub outside: Option<(u64, u64)>,
+}
+
+/// Rules per game name. Games listed here don't go through their built-in extractor.
+pub type FeatureDefinitions = HashMap<String, Vec<FeatureRule>>;
+
+pub fn parse_feature_definitions(content: &str) -> anyhow::Result<FeatureDefinitions> {
+    let definitions: FeatureDefinitions = serde_yaml::from_str(content)?;
+    for (game, rules) in &definitions {
+        for rule in rules {
+            if !rule.values.is_empty() && rule.outside.is_some() {
+                anyhow::bail!(
+                    "{}: the rule for {} can't have both `values` and `outside`",
+                    game,
+                    rule.option
+                );
+            }
+        }
+    }
+
+    Ok(definitions)
+}
+
+/// Reads the feature definitions of the index checked out at `index_path`. An index without any
+/// definitions only uses the built-in extractors.
+pub fn load_feature_definitions(index_path: &Path) -> anyhow::Result<FeatureDefinitions> {
+    let path = index_path.join(DEFINITIONS_FILE);
+    if !path.exists() {
+        return Ok(FeatureDefinitions::new());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    parse_feature_definitions(&content)
+        .with_context(|| format!("Invalid feature definitions in {:?}", path))
+}
+
+fn option_value_to_u64(value: &Value) -> Result<u64> {
+    if let Some(value) = value.as_u64() {
+        return Ok(value);
+    }
+
+    Ok(value
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Not a number"))?
+        .parse()?)
+}
+
+impl FeatureRule {
+    pub fn apply(&self, extractor: &mut Extractor) -> Result<()> {
+        if let Some((min, max)) = self.outside {
+            return extractor.register_ranged_feature(
+                self.feature,
+                &self.option,
+                min,
+                max,
+                option_value_to_u64,
+            );
+        }
+        if self.values.is_empty() {
+            return extractor.register_feature(self.feature, &self.option);
+        }
+
+        extractor.register_feature_with_values(self.feature, &self.option, &self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_yaml::Value;
+
+    use super::{parse_feature_definitions, FeatureRule};
+    use crate::error::Result;
+    use crate::extractor::{Extractor, YamlFeature};
+
+    #[test]
+    fn test_parse_feature_definitions() {
+        let definitions = parse_feature_definitions(
+            "Test:\n  - feature: DeathLink\n    option: death_link\n  - feature: OrbSanity\n    option: orbs\n    outside: [2, 5]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            definitions["Test"][1],
+            FeatureRule {
+                feature: YamlFeature::OrbSanity,
+                option: "orbs".into(),
+                values: vec![],
+                outside: Some((2, 5)),
+            }
+        );
+
+        assert!(parse_feature_definitions("Test:\n  - feature: Nope\n    option: nope\n").is_err());
+        assert!(parse_feature_definitions(
+            "Test:\n  - feature: OrbSanity\n    option: orbs\n    values: [a]\n    outside: [2, 5]\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_apply_feature_rules() -> Result<()> {
+        let definitions = parse_feature_definitions(
+            r#"
+Test:
+  - feature: TrainerSanity
+    option: trainer_mode
+    values: [all, gym_leaders]
+  - feature: DexSanity
+    option: dex
+  - feature: OrbSanity
+    option: orbs
+    outside: [2, 5]
+"#,
+        )
+        .unwrap();
+        let raw_yaml = r#"
+Test:
+  trainer_mode:
+    all: 10
+    gym_leaders: 10
+    none: 20
+  dex: 'true'
+  orbs: '3'
+        "#;
+        let yaml: Value = serde_yaml::from_str(raw_yaml)?;
+        let mut extractor = Extractor::new(&yaml)?;
+        extractor.set_game("Test", 10000)?;
+        for rule in &definitions["Test"] {
+            rule.apply(&mut extractor)?;
+        }
+
+        let expected = HashMap::from([
+            (YamlFeature::TrainerSanity, 5000),
+            (YamlFeature::DexSanity, 10000),
+        ]);
+        assert_eq!(extractor.finalize(), expected);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,7 @@
-use anyhow::anyhow;
 use serde_yaml::Value;
 
 use super::{Extractor, FeatureExtractor, YamlFeature};
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 pub struct JakAndDaxter;
 
@@ -52,11 +51,13 @@ impl FeatureExtractor for JakAndDaxter {
 
 fn orb_value_to_u64(value: &Value) -> Result<u64> {
     let Some(value) = value.as_str() else {
-        Err(anyhow!("Invalid orb option value"))?
+        Err(Error::validation("Invalid orb option value"))?
     };
 
     let Some(value) = value.split('_').next() else {
-        Err(anyhow!("Invalid orb option value. Should be x_orb(s)"))?
+        Err(Error::validation(
+            "Invalid orb option value. Should be x_orb(s)",
+        ))?
     };
 
     Ok(value.parse::<u64>()?)
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use anyhow::anyhow;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
@@ -8,15 +8,54 @@ use serde_yaml::Value;
 
 use crate::db::YamlFile;
 
+mod definitions;
 mod jd;
 mod pokemon;
 
-#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub use definitions::{
+    load_feature_definitions, parse_feature_definitions, FeatureDefinitions, FeatureRule,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum YamlFeature {
     DeathLink,
     TrainerSanity,
     DexSanity,
     OrbSanity,
+    TrapLink,
+    EnergyLink,
+    RingLink,
+}
+
+impl YamlFeature {
+    pub const ALL: [YamlFeature; 7] = [
+        YamlFeature::DeathLink,
+        YamlFeature::TrainerSanity,
+        YamlFeature::DexSanity,
+        YamlFeature::OrbSanity,
+        YamlFeature::TrapLink,
+        YamlFeature::EnergyLink,
+        YamlFeature::RingLink,
+    ];
+
+    /// Same as the serialized name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            YamlFeature::DeathLink => "DeathLink",
+            YamlFeature::TrainerSanity => "TrainerSanity",
+            YamlFeature::DexSanity => "DexSanity",
+            YamlFeature::OrbSanity => "OrbSanity",
+            YamlFeature::TrapLink => "TrapLink",
+            YamlFeature::EnergyLink => "EnergyLink",
+            YamlFeature::RingLink => "RingLink",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<YamlFeature> {
+        YamlFeature::ALL
+            .into_iter()
+            .find(|feature| feature.as_str() == name)
+    }
 }
 
 pub type YamlFeatures = HashMap<YamlFeature, u32>;
@@ -53,7 +92,9 @@ fn is_trueish(option: &Value) -> bool {
 impl<'a> Extractor<'a> {
     pub fn new(yaml: &'a Value) -> Result<Extractor<'a>> {
         let Some(_) = yaml.as_mapping() else {
-            Err(anyhow!("The main body of the YAML should be a map"))?
+            Err(Error::validation(
+                "The main body of the YAML should be a map",
+            ))?
         };
 
         Ok(Self {
@@ -76,6 +117,39 @@ impl<'a> Extractor<'a> {
         Ok(())
     }
 
+    /// Like [`Extractor::register_feature`], for options that enable the feature with some of
+    /// their values only.
+    pub fn register_feature_with_values(
+        &mut self,
+        feature: YamlFeature,
+        path: &str,
+        values: &[String],
+    ) -> Result<()> {
+        let Some((game_yaml, _)) = self.current_game else {
+            panic!("You should call set_game before")
+        };
+
+        let Some(option) = game_yaml.get(path) else {
+            return Ok(());
+        };
+
+        let is_enabled = |value: &Value| match value {
+            Value::String(value) => values.contains(value),
+            Value::Bool(value) => values.contains(&value.to_string()),
+            Value::Number(value) => values.contains(&value.to_string()),
+            _ => false,
+        };
+        let option_probability = get_option_probability(option, &is_enabled)?;
+        let new_value = self.get_weighted_probality(option_probability);
+
+        if new_value != 0 {
+            let current_value = self.features.entry(feature).or_default();
+            *current_value += new_value;
+        }
+
+        Ok(())
+    }
+
     pub fn get_option_probability(
         &mut self,
         path: &str,
@@ -89,7 +163,7 @@ impl<'a> Extractor<'a> {
             return Ok(0);
         };
 
-        get_option_probability(option, is_true_callback)
+        get_option_probability(option, &is_true_callback)
     }
 
     pub fn register_ranged_feature(
@@ -142,11 +216,13 @@ impl<'a> Extractor<'a> {
 
     pub fn set_game(&mut self, game_name: &'a str, probability: u32) -> Result<()> {
         let Some(map) = self.yaml.as_mapping() else {
-            Err(anyhow!("The main body of the YAML should be a map"))?
+            Err(Error::validation(
+                "The main body of the YAML should be a map",
+            ))?
         };
 
         let Some(game_yaml) = map.get(game_name) else {
-            Err(anyhow!(format!(
+            Err(Error::validation(format!(
                 "The requested game isn't present in the YAML: {}",
                 game_name
             )))?
@@ -180,8 +256,8 @@ fn get_option_map<K: for<'a> Deserialize<'a> + std::hash::Hash + Eq>(
     }
 
     let Some(map) = option.as_mapping() else {
-        Err(anyhow!(
-            "Option should either be value or a mapping of the same type"
+        Err(Error::validation(
+            "Option should either be value or a mapping of the same type",
         ))?
     };
 
@@ -198,7 +274,7 @@ fn get_option_map<K: for<'a> Deserialize<'a> + std::hash::Hash + Eq>(
 
 fn get_option_probability(
     option: &serde_yaml::Value,
-    is_true_callback: fn(&Value) -> bool,
+    is_true_callback: &dyn Fn(&Value) -> bool,
 ) -> Result<u32> {
     if is_true_callback(option) {
         return Ok(10000);
@@ -234,6 +310,15 @@ impl FeatureExtractor for DefaultExtractor {
         extractor.register_feature(YamlFeature::DeathLink, "death_link")?;
         extractor.register_feature(YamlFeature::DeathLink, "deathlink")?;
         extractor.register_feature(YamlFeature::DeathLink, "DeathLink")?;
+        extractor.register_feature(YamlFeature::TrapLink, "trap_link")?;
+        extractor.register_feature(YamlFeature::TrapLink, "traplink")?;
+        extractor.register_feature(YamlFeature::TrapLink, "TrapLink")?;
+        extractor.register_feature(YamlFeature::EnergyLink, "energy_link")?;
+        extractor.register_feature(YamlFeature::EnergyLink, "energylink")?;
+        extractor.register_feature(YamlFeature::EnergyLink, "EnergyLink")?;
+        extractor.register_feature(YamlFeature::RingLink, "ring_link")?;
+        extractor.register_feature(YamlFeature::RingLink, "ringlink")?;
+        extractor.register_feature(YamlFeature::RingLink, "RingLink")?;
         Ok(())
     }
 }
@@ -258,13 +343,20 @@ pub static EXTRACTORS: Lazy<HashMap<&'static str, Box<dyn FeatureExtractor + Sen
         extractors
     });
 
-pub fn extract_features(parsed: &YamlFile, raw_yaml: &str) -> Result<YamlFeatures> {
+/// `definitions` are the index's feature definitions, see [`IndexManager::feature_definitions`].
+///
+/// [`IndexManager::feature_definitions`]: crate::index_manager::IndexManager::feature_definitions
+pub fn extract_features(
+    parsed: &YamlFile,
+    raw_yaml: &str,
+    definitions: &FeatureDefinitions,
+) -> Result<YamlFeatures> {
     let yaml: Value = serde_yaml::from_str(raw_yaml)?;
     let mut extractor = Extractor::new(&yaml)?;
 
     match &parsed.game {
         crate::db::YamlGame::Name(name) => {
-            extract_features_from_yaml(&mut extractor, name.as_str(), 10000)?;
+            extract_features_from_yaml(&mut extractor, name.as_str(), 10000, definitions)?;
         }
         crate::db::YamlGame::Map(map) => {
             let total: f64 = map.values().sum();
@@ -273,7 +365,12 @@ pub fn extract_features(parsed: &YamlFile, raw_yaml: &str) -> Result<YamlFeature
                     continue;
                 }
                 let probability = (weight / total) * 10000.;
-                extract_features_from_yaml(&mut extractor, game.as_str(), probability as u32)?;
+                extract_features_from_yaml(
+                    &mut extractor,
+                    game.as_str(),
+                    probability as u32,
+                    definitions,
+                )?;
             }
         }
     }
@@ -285,12 +382,20 @@ fn extract_features_from_yaml<'a>(
     extractor: &mut Extractor<'a>,
     game_name: &'a str,
     probability: u32,
+    definitions: &FeatureDefinitions,
 ) -> Result<()> {
     extractor.set_game(game_name, probability)?;
 
     let default_extractor = DefaultExtractor {};
     default_extractor.extract_features(extractor)?;
 
+    if let Some(rules) = definitions.get(game_name) {
+        for rule in rules {
+            rule.apply(extractor)?;
+        }
+        return Ok(());
+    }
+
     let Some(game_extractor) = EXTRACTORS.get(game_name) else {
         return Ok(());
     };
@@ -635,6 +740,30 @@ Test:
         Ok(())
     }
 
+    #[test]
+    fn test_extract_default_links() -> Result<()> {
+        let raw_yaml = r#"
+Test:
+  trap_link: true
+  EnergyLink:
+    true: 25
+    false: 75
+  ring_link: false
+        "#;
+        let yaml: Value = serde_yaml::from_str(raw_yaml)?;
+        let mut extractor = Extractor::new(&yaml)?;
+        extractor.set_game("Test", 10000)?;
+        DefaultExtractor {}.extract_features(&mut extractor)?;
+
+        let expected = HashMap::from([
+            (YamlFeature::TrapLink, 10000),
+            (YamlFeature::EnergyLink, 2500),
+        ]);
+        assert_eq!(extractor.finalize(), expected);
+
+        Ok(())
+    }
+
     #[test]
     fn test_extract_weighted() -> Result<()> {
         let game_extractor = TestExtractor {};
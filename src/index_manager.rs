@@ -2,7 +2,9 @@ use anyhow::{bail, Context, Result};
 use http::header::CONTENT_DISPOSITION;
 use rocket::http::Header;
 use semver::Version;
+use std::sync::Arc;
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{Read, Write},
     path::{Path, PathBuf},
@@ -12,14 +14,19 @@ use tokio::sync::RwLock;
 use apwm::{Index, Manifest};
 use git2::{Repository, ResetType};
 
+use crate::extractor::FeatureDefinitions;
 use crate::utils::ZipFile;
 
 pub struct IndexManager {
     pub index: RwLock<Index>,
+    feature_definitions: RwLock<Arc<FeatureDefinitions>>,
     index_path: PathBuf,
     index_repo_url: String,
     index_repo_branch: String,
     pub apworlds_path: PathBuf,
+    /// Local apworld files that replace a world from the index, for world developers testing
+    /// their changes. Keyed by world name.
+    pub dev_overrides: BTreeMap<String, PathBuf>,
 }
 
 impl IndexManager {
@@ -38,14 +45,33 @@ impl IndexManager {
 
         let index_file = index_path.join("index.toml");
         let index = apwm::Index::new(&index_file)?;
+        let feature_definitions = crate::extractor::load_feature_definitions(&index_path)?;
 
         let apworlds_path = std::path::PathBuf::from(
             std::env::var("APWORLDS_PATH").expect("Provide a `APWORLDS_PATH` env variable"),
         );
 
+        let dev_overrides =
+            parse_dev_overrides(&std::env::var("APWORLDS_DEV_OVERRIDES").unwrap_or_default())?;
+        for (world_name, path) in &dev_overrides {
+            if !index.worlds.contains_key(world_name) {
+                tracing::warn!("Dev override for {} which isn't in the index", world_name);
+            }
+            if !path.exists() {
+                bail!(
+                    "The dev override for {} points to {:?} which doesn't exist",
+                    world_name,
+                    path
+                );
+            }
+            tracing::warn!("Using {:?} instead of the index for {}", path, world_name);
+        }
+
         let manager = Self {
             index: RwLock::new(index),
+            feature_definitions: RwLock::new(Arc::new(feature_definitions)),
             apworlds_path,
+            dev_overrides,
             index_path,
             index_repo_url,
             index_repo_branch,
@@ -61,14 +87,21 @@ impl IndexManager {
             &self.index_path,
         )?;
         let new_index = self.parse_index()?;
+        let feature_definitions = crate::extractor::load_feature_definitions(&self.index_path)?;
         new_index
             .refresh_into(&self.apworlds_path, false, None)
             .await?;
         *self.index.write().await = new_index;
+        *self.feature_definitions.write().await = Arc::new(feature_definitions);
 
         Ok(())
     }
 
+    /// The rules from the index's `features.yaml`, to pass to the extractor.
+    pub async fn feature_definitions(&self) -> Arc<FeatureDefinitions> {
+        self.feature_definitions.read().await.clone()
+    }
+
     fn parse_index(&self) -> Result<Index> {
         let index_file = self.index_path.join("index.toml");
         let index = apwm::Index::new(&index_file)?;
@@ -76,6 +109,10 @@ impl IndexManager {
         Ok(index)
     }
 
+    pub fn dev_override(&self, world_name: &str) -> Option<&Path> {
+        self.dev_overrides.get(world_name).map(PathBuf::as_path)
+    }
+
     pub async fn get_apworld_from_game_name(
         &self,
         manifest: &Manifest,
@@ -104,13 +141,17 @@ impl IndexManager {
         }
 
         for (world_name, (world, version)) in &worlds {
-            let origin = world.get_version(version).unwrap();
-
-            if origin.is_supported() {
-                continue;
-            }
+            let file_path = match self.dev_override(world_name) {
+                Some(path) => path.to_path_buf(),
+                None => {
+                    let origin = world.get_version(version).unwrap();
+                    if origin.is_supported() {
+                        continue;
+                    }
+                    index.get_world_local_path(apworlds_path, world_name, version)
+                }
+            };
 
-            let file_path = index.get_world_local_path(apworlds_path, world_name, version);
             writer.start_file(format!("{}/{}.apworld", prefix, world_name), options)?;
             File::open(&file_path)
                 .with_context(|| format!("Can't open {:?}", file_path))?
@@ -129,6 +170,21 @@ impl IndexManager {
     }
 }
 
+/// Parses `world=path` pairs separated by commas.
+fn parse_dev_overrides(overrides: &str) -> Result<BTreeMap<String, PathBuf>> {
+    overrides
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let Some((world_name, path)) = entry.split_once('=') else {
+                bail!("Invalid dev override `{}`, expected `world=path`", entry);
+            };
+            Ok((world_name.trim().to_string(), PathBuf::from(path.trim())))
+        })
+        .collect()
+}
+
 fn clone_or_update(repo_url: &str, repo_branch: &str, path: &Path) -> Result<()> {
     let repo = Repository::init(path)?;
 
@@ -146,3 +202,22 @@ fn clone_or_update(repo_url: &str, repo_branch: &str, path: &Path) -> Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::parse_dev_overrides;
+
+    #[test]
+    fn test_parse_dev_overrides() {
+        let overrides =
+            parse_dev_overrides(" clique=/tmp/clique.apworld, zelda = ./zelda.apworld,").unwrap();
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides["clique"], PathBuf::from("/tmp/clique.apworld"));
+        assert_eq!(overrides["zelda"], PathBuf::from("./zelda.apworld"));
+
+        assert!(parse_dev_overrides("").unwrap().is_empty());
+        assert!(parse_dev_overrides("clique").is_err());
+    }
+}
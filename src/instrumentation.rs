@@ -1,11 +1,27 @@
-use prometheus::{IntCounterVec, IntGaugeVec, Opts, Registry};
+use ap_lobby::db;
+use diesel_async::AsyncPgConnection;
+use prometheus::{IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry};
 use wq::QueueStats;
 
+/// Maximum number of open rooms that get their own `room_id` label. Every other room is
+/// aggregated under `other` so that a busy instance can't blow up the number of series.
+const MAX_LABELED_ROOMS: usize = 20;
+
+const YAML_COUNT_BUCKETS: &[(i64, &str)] = &[
+    (0, "0"),
+    (5, "1-5"),
+    (20, "6-20"),
+    (50, "21-50"),
+    (100, "51-100"),
+    (i64::MAX, "100+"),
+];
+
 #[derive(Clone)]
 pub struct QueueCounters {
     pub jobs_counter: IntCounterVec,
     pub jobs_scheduled: IntGaugeVec,
     pub jobs_claimed: IntGaugeVec,
+    pub oldest_pending_job_age: IntGaugeVec,
 }
 
 impl QueueCounters {
@@ -26,11 +42,19 @@ impl QueueCounters {
                 Opts::new("queue_jobs_claimed", "The current number of jobs claimed"),
                 &["queue"],
             )?,
+            oldest_pending_job_age: IntGaugeVec::new(
+                Opts::new(
+                    "queue_oldest_pending_job_age_seconds",
+                    "How long the oldest unresolved job of the last day has been waiting",
+                ),
+                &["queue", "priority"],
+            )?,
         };
 
         registry.register(Box::new(ret.jobs_counter.clone()))?;
         registry.register(Box::new(ret.jobs_scheduled.clone()))?;
         registry.register(Box::new(ret.jobs_claimed.clone()))?;
+        registry.register(Box::new(ret.oldest_pending_job_age.clone()))?;
 
         Ok(ret)
     }
@@ -54,4 +78,107 @@ impl QueueCounters {
             .with_label_values(&[queue_name])
             .set(stats.jobs_claimed as i64);
     }
+
+    /// Unlike the scheduled/claimed gauges, the age of the oldest pending job tells a busy queue
+    /// (jobs come and go) from a stuck one (the same job keeps waiting).
+    pub async fn refresh_pending_jobs(
+        &self,
+        conn: &mut AsyncPgConnection,
+    ) -> ap_lobby::error::Result<()> {
+        let oldest_pending_jobs = db::get_oldest_pending_jobs(conn).await?;
+        let now = chrono::Utc::now().naive_utc();
+
+        // Stop exporting queues that caught up instead of keeping their last age forever.
+        self.oldest_pending_job_age.reset();
+        for (queue, priority, enqueued_at) in oldest_pending_jobs {
+            self.oldest_pending_job_age
+                .with_label_values(&[&queue, &priority])
+                .set((now - enqueued_at).num_seconds().max(0));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RoomMetrics {
+    pub open_rooms: IntGauge,
+    pub closed_rooms: IntGauge,
+    pub closed_rooms_yamls: IntGauge,
+    pub open_rooms_by_yaml_count: IntGaugeVec,
+    pub yamls_per_open_room: IntGaugeVec,
+}
+
+impl RoomMetrics {
+    pub fn new(registry: &Registry) -> ap_lobby::error::Result<Self> {
+        let ret = Self {
+            open_rooms: IntGauge::new("rooms_open", "The number of open rooms")?,
+            closed_rooms: IntGauge::new("rooms_closed", "The number of closed rooms")?,
+            closed_rooms_yamls: IntGauge::new(
+                "rooms_closed_yamls",
+                "The total number of yamls in closed rooms",
+            )?,
+            open_rooms_by_yaml_count: IntGaugeVec::new(
+                Opts::new(
+                    "rooms_open_by_yaml_count",
+                    "The number of open rooms, bucketed by their number of yamls",
+                ),
+                &["yamls"],
+            )?,
+            yamls_per_open_room: IntGaugeVec::new(
+                Opts::new(
+                    "rooms_open_yamls",
+                    "The number of yamls in the biggest open rooms",
+                ),
+                &["room_id"],
+            )?,
+        };
+
+        registry.register(Box::new(ret.open_rooms.clone()))?;
+        registry.register(Box::new(ret.closed_rooms.clone()))?;
+        registry.register(Box::new(ret.closed_rooms_yamls.clone()))?;
+        registry.register(Box::new(ret.open_rooms_by_yaml_count.clone()))?;
+        registry.register(Box::new(ret.yamls_per_open_room.clone()))?;
+
+        Ok(ret)
+    }
+
+    pub async fn refresh(&self, conn: &mut AsyncPgConnection) -> ap_lobby::error::Result<()> {
+        let mut open_rooms = db::count_yamls_per_open_room(conn).await?;
+        let (closed_rooms, closed_rooms_yamls) = db::count_closed_rooms_and_yamls(conn).await?;
+
+        self.open_rooms.set(open_rooms.len() as i64);
+        self.closed_rooms.set(closed_rooms);
+        self.closed_rooms_yamls.set(closed_rooms_yamls);
+
+        self.open_rooms_by_yaml_count.reset();
+        for (_, label) in YAML_COUNT_BUCKETS {
+            self.open_rooms_by_yaml_count.with_label_values(&[*label]);
+        }
+        for (_, yaml_count) in &open_rooms {
+            let (_, label) = YAML_COUNT_BUCKETS
+                .iter()
+                .find(|(max, _)| yaml_count <= max)
+                .expect("The last bucket should catch everything");
+            self.open_rooms_by_yaml_count
+                .with_label_values(&[*label])
+                .inc();
+        }
+
+        // Rooms come and go, reset so that we don't keep exporting rooms that closed.
+        self.yamls_per_open_room.reset();
+        open_rooms.sort_by(|a, b| b.1.cmp(&a.1));
+        let split_at = open_rooms.len().min(MAX_LABELED_ROOMS);
+        let (labeled, others) = open_rooms.split_at(split_at);
+        for (room_id, yaml_count) in labeled {
+            self.yamls_per_open_room
+                .with_label_values(&[&room_id.to_string()])
+                .set(*yaml_count);
+        }
+        self.yamls_per_open_room
+            .with_label_values(&["other"])
+            .set(others.iter().map(|(_, yaml_count)| yaml_count).sum());
+
+        Ok(())
+    }
 }
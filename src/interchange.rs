@@ -0,0 +1,248 @@
+//! Moves rooms between lobby instances. A room is exported as a JSON payload signed with a key
+//! shared by the instances that trust each other, so an import can't be tampered with on the
+//! way. Users don't exist from one instance to the other: YAML owners are exported as
+//! pseudonyms and whoever imports the room ends up owning every YAML.
+
+use std::collections::BTreeMap;
+
+use apwm::{Index, Manifest, VersionReq};
+use base64::Engine;
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::db::RoomSettingsSnapshot;
+use crate::error::{Error, Result};
+
+pub const INTERCHANGE_VERSION: u32 = 1;
+
+/// The key rooms are signed with, from `ROOM_INTERCHANGE_KEY`. Without it, rooms can't be
+/// exported nor imported.
+#[derive(Debug, Clone, Default)]
+pub struct InterchangeKey(Option<String>);
+
+impl InterchangeKey {
+    pub fn new(key: Option<String>) -> Self {
+        Self(key.filter(|key| !key.is_empty()))
+    }
+
+    pub fn get(&self) -> Result<&[u8]> {
+        self.0
+            .as_deref()
+            .map(str::as_bytes)
+            .ok_or_else(|| Error::not_found("Moving rooms isn't set up on this lobby"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterchangeYaml {
+    pub player_name: String,
+    /// Same for every YAML of one owner, without saying who they are.
+    pub owner: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomInterchange {
+    pub version: u32,
+    /// Where the room comes from, for people looking at the payload.
+    pub origin: String,
+    pub exported_at: NaiveDateTime,
+    pub settings: RoomSettingsSnapshot,
+    /// The version each enabled world resolved to on the origin.
+    pub worlds: BTreeMap<String, String>,
+    pub yamls: Vec<InterchangeYaml>,
+}
+
+/// What actually gets sent from one instance to the other.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRoomInterchange {
+    /// The [`RoomInterchange`] as base64 encoded JSON, so that it's signed byte for byte.
+    pub payload: String,
+    /// HMAC-SHA256 of the payload, hex encoded.
+    pub signature: String,
+}
+
+impl RoomInterchange {
+    pub fn sign(&self, key: &[u8]) -> Result<SignedRoomInterchange> {
+        let payload = base64::engine::general_purpose::STANDARD.encode(serde_json::to_vec(self)?);
+        let signature = hex(&hmac_sha256(key, payload.as_bytes()).finalize().into_bytes());
+
+        Ok(SignedRoomInterchange { payload, signature })
+    }
+
+    /// Builds the manifest of the imported room against this instance's index. Worlds are
+    /// pinned to the version they had on the origin. Worlds with a version that isn't available
+    /// here use the latest one instead and worlds that don't exist here are left out, both are
+    /// reported so that the organizer can check on them.
+    pub fn manifest_for_index(&self, index: &Index) -> (Manifest, Vec<String>) {
+        let mut manifest = Manifest::new();
+        manifest.new_apworld_policy = self.settings.manifest.new_apworld_policy.clone();
+        let mut notes = vec![];
+
+        for (world_name, world) in &index.worlds {
+            let Some(version) = self.worlds.get(world_name.as_str()) else {
+                manifest.add_version_req(world_name, VersionReq::Disabled);
+                continue;
+            };
+
+            match semver::Version::parse(version) {
+                Ok(version) if world.versions.contains_key(&version) => {
+                    manifest.add_version_req(world_name, VersionReq::Specific(version));
+                }
+                _ => {
+                    notes.push(format!(
+                        "{} {} isn't available here, the room uses the latest version instead",
+                        world.display_name, version
+                    ));
+                    manifest.add_version_req(world_name, VersionReq::Latest);
+                }
+            }
+        }
+        for world_name in self.worlds.keys() {
+            if !index.worlds.contains_key(world_name.as_str()) {
+                notes.push(format!(
+                    "{} doesn't exist on this lobby, its YAMLs can't be imported",
+                    world_name
+                ));
+            }
+        }
+
+        (manifest, notes)
+    }
+}
+
+impl SignedRoomInterchange {
+    pub fn verify(&self, key: &[u8]) -> Result<RoomInterchange> {
+        let signature = unhex(&self.signature)
+            .ok_or_else(|| Error::validation("The room signature isn't valid hex"))?;
+        if hmac_sha256(key, self.payload.as_bytes())
+            .verify_slice(&signature)
+            .is_err()
+        {
+            return Err(Error::forbidden(
+                "This room wasn't signed with this lobby's interchange key",
+            ));
+        }
+
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(&self.payload)
+            .map_err(|_| Error::validation("The room payload isn't valid base64"))?;
+        let interchange: RoomInterchange = serde_json::from_slice(&payload)
+            .map_err(|e| Error::validation(format!("The room payload isn't valid: {}", e)))?;
+        if interchange.version != INTERCHANGE_VERSION {
+            return Err(Error::validation(format!(
+                "This room was exported with version {} of the format, this lobby only knows version {}",
+                interchange.version, INTERCHANGE_VERSION
+            )));
+        }
+
+        Ok(interchange)
+    }
+}
+
+/// Keyed so that pseudonyms can't be matched back to discord IDs by hashing them all.
+pub fn owner_pseudonym(key: &[u8], owner_id: i64) -> String {
+    let mac = hmac_sha256(key, format!("owner:{}", owner_id).as_bytes());
+    format!("owner-{}", &hex(&mac.finalize().into_bytes())[..12])
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(message);
+    mac
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use apwm::Manifest;
+    use chrono::NaiveDateTime;
+
+    use hmac::Mac;
+
+    use super::{hex, hmac_sha256, owner_pseudonym, unhex, RoomInterchange, INTERCHANGE_VERSION};
+    use crate::db::{RoomSettingsSnapshot, RoomUrlVisibility};
+
+    fn interchange() -> RoomInterchange {
+        RoomInterchange {
+            version: INTERCHANGE_VERSION,
+            origin: "https://lobby.example.com".to_string(),
+            exported_at: NaiveDateTime::default(),
+            settings: RoomSettingsSnapshot {
+                name: "Weekly async".to_string(),
+                close_date: NaiveDateTime::default(),
+                description: String::new(),
+                room_url: String::new(),
+                yaml_validation: true,
+                allow_unsupported: false,
+                yaml_limit_per_user: None,
+                yaml_limit_bypass_list: vec![],
+                manifest: Manifest::new(),
+                show_apworlds: true,
+                exact_yaml_storage: false,
+                upload_hints: String::new(),
+                max_slots: None,
+                feature_policies: BTreeMap::new(),
+                room_url_visibility: RoomUrlVisibility::default(),
+            },
+            worlds: BTreeMap::from([("clique".to_string(), "1.0.0".to_string())]),
+            yamls: vec![],
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")
+                .finalize()
+                .into_bytes()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_unhex() {
+        assert_eq!(unhex("00ff1a"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(unhex(&hex(b"roundtrip")), Some(b"roundtrip".to_vec()));
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("zz"), None);
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signed = interchange().sign(b"shared key").unwrap();
+        let verified = signed.verify(b"shared key").unwrap();
+        assert_eq!(verified.settings.name, "Weekly async");
+
+        assert!(signed.verify(b"other key").is_err());
+        let mut tampered = signed.clone();
+        tampered.payload.insert(0, 'A');
+        assert!(tampered.verify(b"shared key").is_err());
+    }
+
+    #[test]
+    fn test_owner_pseudonym() {
+        assert_eq!(owner_pseudonym(b"key", 1), owner_pseudonym(b"key", 1));
+        assert_ne!(owner_pseudonym(b"key", 1), owner_pseudonym(b"key", 2));
+        assert_ne!(owner_pseudonym(b"key", 1), owner_pseudonym(b"other", 1));
+    }
+}
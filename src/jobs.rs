@@ -1,8 +1,9 @@
 use std::collections::HashMap;
+use std::fmt::Display;
 
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use wq::WorkQueue;
+use wq::{JobId, WorkQueue};
 
 #[derive(Serialize, Deserialize)]
 pub struct YamlValidationParams {
@@ -10,9 +11,48 @@ pub struct YamlValidationParams {
     pub yaml: String,
     pub otlp_context: HashMap<String, String>,
 }
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct YamlValidationResponse {
     pub error: Option<String>,
+    // Older workers don't report those.
+    #[serde(default)]
+    pub unknown_references: Vec<UnknownReference>,
+}
+
+/// An item, location or group name used in a YAML option that the game doesn't know about.
+/// Generation doesn't fail on those, the option just ends up doing nothing.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UnknownReference {
+    pub game: String,
+    pub option: String,
+    pub name: String,
+}
+
+impl Display for UnknownReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: `{}` refers to \"{}\" which doesn't exist",
+            self.game, self.option, self.name
+        )
+    }
 }
 
 pub type YamlValidationQueue = WorkQueue<YamlValidationParams, YamlValidationResponse>;
+
+pub fn job_id_to_string(job_id: &JobId) -> String {
+    match serde_json::to_value(job_id) {
+        Ok(serde_json::Value::String(job_id)) => job_id,
+        Ok(value) => value.to_string(),
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+/// Tags the current sentry scope with a queue job ID so that errors can be tied to the job. Meant
+/// to be called from a request handler, which runs in its own hub, the tag doesn't outlive the
+/// request.
+pub fn tag_sentry_job_id(job_id: &JobId) {
+    let job_id = job_id_to_string(job_id);
+    sentry::configure_scope(|scope| scope.set_tag("job_id", &job_id));
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use diesel_async::AsyncPgConnection;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::error::Result;
+
+const CACHE_KEY: &str = "lobby:landing_stats";
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// What anonymous visitors get to see on the landing page. Rooms are only shared by link, so
+/// this never contains anything about a specific room.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LandingStats {
+    pub open_rooms: i64,
+    pub open_rooms_yamls: i64,
+    pub closed_rooms: i64,
+}
+
+impl LandingStats {
+    pub async fn load(conn: &mut AsyncPgConnection) -> Result<Self> {
+        let open_rooms = db::count_yamls_per_open_room(conn).await?;
+        let (closed_rooms, _) = db::count_closed_rooms_and_yamls(conn).await?;
+
+        Ok(Self {
+            open_rooms: open_rooms.len() as i64,
+            open_rooms_yamls: open_rooms.iter().map(|(_, yamls)| yamls).sum(),
+            closed_rooms,
+        })
+    }
+}
+
+/// Short lived valkey cache for [`LandingStats`], so that link previews and bots hitting the
+/// landing page don't each cost a round of queries.
+///
+/// The cache is best effort, valkey errors are logged and treated as a miss.
+pub struct LandingCache {
+    conn: ConnectionManager,
+    ttl: Duration,
+}
+
+impl LandingCache {
+    pub async fn new(valkey_url: &str) -> Result<Self> {
+        let client = redis::Client::open(valkey_url)?;
+        let conn = ConnectionManager::new(client).await?;
+        let ttl = std::env::var("LANDING_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+
+        Ok(Self { conn, ttl })
+    }
+
+    /// Loads fresh stats from the database and caches them.
+    #[tracing::instrument(skip_all)]
+    pub async fn refresh(&self, conn: &mut AsyncPgConnection) -> Result<LandingStats> {
+        let stats = LandingStats::load(conn).await?;
+        self.set(&stats).await;
+
+        Ok(stats)
+    }
+
+    /// Drops the cached stats. Call this after anything that changes rooms or their yamls.
+    pub async fn invalidate(&self) {
+        let res: redis::RedisResult<()> = self.conn.clone().del(CACHE_KEY).await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to invalidate the landing cache: {:?}", e);
+        }
+    }
+
+    pub async fn get(&self) -> Option<LandingStats> {
+        let cached: redis::RedisResult<Option<String>> = self.conn.clone().get(CACHE_KEY).await;
+        match cached {
+            Ok(cached) => serde_json::from_str(&cached?).ok(),
+            Err(e) => {
+                tracing::warn!("Failed to read the landing cache: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn set(&self, stats: &LandingStats) {
+        let serialized = serde_json::to_string(stats).unwrap();
+        let res: redis::RedisResult<()> = self
+            .conn
+            .clone()
+            .set_ex(CACHE_KEY, serialized, self.ttl.as_secs())
+            .await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to fill the landing cache: {:?}", e);
+        }
+    }
+}
@@ -1,8 +1,19 @@
+pub mod abuse;
+pub mod body_limit;
 pub mod db;
+pub mod deadline;
+pub mod delivery;
 pub mod error;
 pub mod extractor;
 pub mod index_manager;
+pub mod interchange;
 pub mod jobs;
+pub mod landing_cache;
+pub mod locks;
+pub mod pending_uploads;
+pub mod redaction;
+pub mod request_id;
+pub mod room_scheduler;
 pub mod schema;
 pub mod session;
 pub mod utils;
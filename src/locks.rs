@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Script};
+use tokio::task::JoinHandle;
+
+use crate::error::{Error, Result};
+
+const KEY_PREFIX: &str = "lobby:lock:";
+// Leases are renewed every third of their TTL, in whole milliseconds.
+const MIN_TTL: Duration = Duration::from_millis(3);
+
+// Only touch the lock if we're still the one holding it, otherwise another instance already took
+// over after our lease expired.
+const RENEW_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+/// Hands out locks shared by every lobby instance talking to the same valkey.
+///
+/// Used for work that should only happen once across the deployment (index refreshes, recurring
+/// jobs...).
+#[derive(Clone)]
+pub struct LockManager {
+    conn: ConnectionManager,
+}
+
+/// A held lock. The lease is renewed in the background until the guard is dropped or released.
+///
+/// Every acquisition gets a strictly increasing fencing token. Anything written while holding the
+/// lock should carry it so that a holder that stalled past its lease can be told apart from the
+/// current one.
+pub struct LockGuard {
+    key: String,
+    token: u64,
+    conn: ConnectionManager,
+    lost: Arc<AtomicBool>,
+    renewal: JoinHandle<()>,
+    released: bool,
+}
+
+impl LockManager {
+    pub async fn new(valkey_url: &str) -> Result<Self> {
+        let client = redis::Client::open(valkey_url)?;
+        let conn = ConnectionManager::new(client).await?;
+
+        Ok(Self { conn })
+    }
+
+    /// Tries to take the lock `name` for `ttl`. Returns `None` if someone else holds it.
+    pub async fn try_acquire(&self, name: &str, ttl: Duration) -> Result<Option<LockGuard>> {
+        if ttl < MIN_TTL {
+            return Err(Error::Internal(anyhow::anyhow!(
+                "Lock {} needs a TTL of at least {:?}, got {:?}",
+                name,
+                MIN_TTL,
+                ttl
+            )));
+        }
+
+        let key = format!("{}{}", KEY_PREFIX, name);
+        let mut conn = self.conn.clone();
+
+        let token: u64 = conn.incr(format!("{}:fence", key), 1).await?;
+        let acquired: bool = redis::cmd("SET")
+            .arg(&key)
+            .arg(token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async::<Option<String>>(&mut conn)
+            .await?
+            .is_some();
+
+        if !acquired {
+            return Ok(None);
+        }
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let renewal = tokio::spawn(renew(conn.clone(), key.clone(), token, ttl, lost.clone()));
+
+        Ok(Some(LockGuard {
+            key,
+            token,
+            conn,
+            lost,
+            renewal,
+            released: false,
+        }))
+    }
+}
+
+impl LockGuard {
+    pub fn fencing_token(&self) -> u64 {
+        self.token
+    }
+
+    /// Whether we still hold the lock. This turns false if a renewal found someone else owning
+    /// it, at which point the work done under the lock should be abandoned.
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::Relaxed)
+    }
+
+    pub async fn release(mut self) -> Result<()> {
+        self.released = true;
+        self.renewal.abort();
+        release(&mut self.conn, &self.key, self.token).await
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+
+        self.renewal.abort();
+        // Outside of a runtime (during shutdown, on a plain thread...) there's nothing to run the
+        // release on and the lock stays taken until its lease expires. Use `release` to avoid that.
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!(
+                "Lock {} dropped outside of a runtime, waiting for its lease to expire",
+                self.key
+            );
+            return;
+        };
+
+        let mut conn = self.conn.clone();
+        let key = std::mem::take(&mut self.key);
+        let token = self.token;
+        runtime.spawn(async move {
+            if let Err(e) = release(&mut conn, &key, token).await {
+                tracing::warn!("Failed to release lock {}: {:?}", key, e);
+            }
+        });
+    }
+}
+
+async fn release(conn: &mut ConnectionManager, key: &str, token: u64) -> Result<()> {
+    Script::new(RELEASE_SCRIPT)
+        .key(key)
+        .arg(token)
+        .invoke_async::<i64>(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn renew(
+    mut conn: ConnectionManager,
+    key: String,
+    token: u64,
+    ttl: Duration,
+    lost: Arc<AtomicBool>,
+) {
+    let script = Script::new(RENEW_SCRIPT);
+    let mut interval = tokio::time::interval(ttl / 3);
+    // The first tick completes immediately and we just set the lease.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let renewed = script
+            .key(&key)
+            .arg(token)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async::<i64>(&mut conn)
+            .await;
+
+        match renewed {
+            Ok(1) => {}
+            Ok(_) => {
+                tracing::warn!("Lost lock {} (fencing token {})", key, token);
+                lost.store(true, Ordering::Relaxed);
+                return;
+            }
+            // Keep trying, the lease might still be valid until the next tick.
+            Err(e) => tracing::warn!("Failed to renew lock {}: {:?}", key, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LockManager;
+    use std::time::Duration;
+
+    // Needs a running valkey, `docker compose up valkey`, set VALKEY_URL and run with `--ignored`.
+    async fn lock_manager() -> LockManager {
+        let url = std::env::var("VALKEY_URL").expect("Set VALKEY_URL to run the lock tests");
+        LockManager::new(&url).await.unwrap()
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs valkey"]
+    async fn test_lock_is_exclusive() {
+        let locks = lock_manager().await;
+        let name = format!("test-{}", uuid::Uuid::new_v4());
+
+        let guard = locks
+            .try_acquire(&name, Duration::from_secs(5))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(guard.is_held());
+        assert!(locks
+            .try_acquire(&name, Duration::from_secs(5))
+            .await
+            .unwrap()
+            .is_none());
+
+        let first_token = guard.fencing_token();
+        guard.release().await.unwrap();
+
+        let guard = locks
+            .try_acquire(&name, Duration::from_secs(5))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(guard.fencing_token() > first_token);
+        guard.release().await.unwrap();
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs valkey"]
+    async fn test_lock_rejects_tiny_ttl() {
+        let locks = lock_manager().await;
+        let name = format!("test-{}", uuid::Uuid::new_v4());
+
+        assert!(locks
+            .try_acquire(&name, Duration::from_millis(2))
+            .await
+            .is_err());
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs valkey"]
+    async fn test_lock_is_renewed() {
+        let locks = lock_manager().await;
+        let name = format!("test-{}", uuid::Uuid::new_v4());
+
+        let guard = locks
+            .try_acquire(&name, Duration::from_millis(300))
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(900)).await;
+
+        assert!(guard.is_held());
+        assert!(locks
+            .try_acquire(&name, Duration::from_millis(300))
+            .await
+            .unwrap()
+            .is_none());
+        guard.release().await.unwrap();
+    }
+}
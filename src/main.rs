@@ -1,11 +1,15 @@
 #![allow(clippy::too_many_arguments)]
 
-use std::collections::HashMap;
 use std::sync::Arc;
 
-use anyhow::Context as _;
-use ap_lobby::db::instrumentation::{DbInstrumentation, QUERY_HISTOGRAM};
+use ap_lobby::abuse::AbuseDetector;
+use ap_lobby::db::instrumentation::{DbInstrumentation, POOL_WAIT_HISTOGRAM, QUERY_HISTOGRAM};
+use ap_lobby::db::UserSettings;
+use ap_lobby::delivery::Deliverer;
 use ap_lobby::session::{AdminSession, AdminToken, Session};
+use ap_lobby::utils::constant_time_eq;
+use cache_control::CacheControlFairing;
+use compression::CompressionFairing;
 use diesel::{ConnectionError, ConnectionResult};
 use diesel_async::async_connection_wrapper::AsyncConnectionWrapper;
 use diesel_async::pooled_connection::deadpool::Pool;
@@ -15,8 +19,10 @@ use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenvy::dotenv;
 use futures_util::future::BoxFuture;
 use futures_util::FutureExt;
-use instrumentation::QueueCounters;
+use instrumentation::{QueueCounters, RoomMetrics};
 use otlp::TracingFairing;
+use pool::{PoolGauges, ShedThreshold};
+use request_tracking::RequestIdFairing;
 use rocket::data::{Limits, ToByteUnit};
 use rocket::http::{CookieJar, Method, Status};
 use rocket::response::Redirect;
@@ -30,21 +36,37 @@ use rustls::crypto::ring;
 use rustls::pki_types::{ServerName, UnixTime};
 use rustls::Error as TLSError;
 use rustls::{DigitallySignedStruct, SignatureScheme};
+use sentry_context::with_request_hub;
 
 use ap_lobby::index_manager::IndexManager;
+use ap_lobby::interchange::InterchangeKey;
 use ap_lobby::jobs::YamlValidationQueue;
-use views::queues::QueueTokens;
+use ap_lobby::landing_cache::LandingCache;
+use ap_lobby::locks::LockManager;
+use ap_lobby::pending_uploads::PendingUploads;
+use ap_lobby::redaction::RedactedOptions;
+use ap_lobby::room_scheduler::RoomScheduler;
+use views::og::PublicOrigin;
+use views::queues::{QueueTokens, WorkerMtls};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations/");
 
+mod cache_control;
+mod compression;
 mod instrumentation;
 mod otlp;
+mod pool;
+mod request_tracking;
+mod sentry_context;
 mod views;
 
 pub struct Discord;
 
 pub struct Context {
     db_pool: Pool<AsyncPgConnection>,
+    landing_cache: LandingCache,
+    abuse: AbuseDetector,
+    pending_uploads: PendingUploads,
 }
 
 const CSS_VERSION: &str = std::env!("CSS_VERSION");
@@ -60,6 +82,8 @@ struct TplContext<'a> {
     warning_msg: Vec<String>,
     css_version: &'a str,
     js_version: &'a str,
+    confirmation_token: String,
+    settings: UserSettings,
 }
 
 impl<'a> TplContext<'a> {
@@ -73,6 +97,8 @@ impl<'a> TplContext<'a> {
             warning_msg: session.warning_msg.drain(..).collect(),
             css_version: CSS_VERSION,
             js_version: JS_VERSION,
+            confirmation_token: session.confirmation_token(),
+            settings: session.settings.clone(),
         };
 
         session
@@ -101,21 +127,65 @@ fn unauthorized(req: &Request) -> ap_lobby::error::Result<Redirect> {
     )))
 }
 
+/// Lets Prometheus scrape `/metrics` with `Authorization: Bearer <token>` instead of an admin
+/// session, set through `METRICS_SCRAPE_TOKEN`.
 #[derive(Clone)]
-struct MetricsRoute(PrometheusMetrics, QueueCounters);
+struct MetricsScrapeToken(Option<String>);
+
+impl MetricsScrapeToken {
+    fn allows(&self, req: &Request<'_>) -> bool {
+        let Some(expected) = self.0.as_deref() else {
+            return false;
+        };
+
+        req.headers()
+            .get_one("Authorization")
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), expected.as_bytes()))
+    }
+}
+
+#[derive(Clone)]
+struct MetricsRoute(
+    PrometheusMetrics,
+    QueueCounters,
+    PoolGauges,
+    RoomMetrics,
+    MetricsScrapeToken,
+);
 
 #[rocket::async_trait]
 impl Handler for MetricsRoute {
     async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
-        let rocket::outcome::Outcome::Success(_admin_session) = req.guard::<AdminSession>().await
-        else {
-            return Outcome::Error(Status::Forbidden);
-        };
+        if !self.4.allows(req) {
+            let rocket::outcome::Outcome::Success(_admin_session) =
+                req.guard::<AdminSession>().await
+            else {
+                return Outcome::Error(Status::Forbidden);
+            };
+        }
 
         let yaml_validation_queue = req.rocket().state::<YamlValidationQueue>().unwrap();
         let stats = yaml_validation_queue.get_stats().await.unwrap();
         self.1.update_queue("yaml_validation", stats);
 
+        let ctx = req.rocket().state::<Context>().unwrap();
+        self.2.update_pool("postgres", &ctx.db_pool);
+        match ctx.db_conn().await {
+            Ok(mut conn) => {
+                if let Err(e) = self.3.refresh(&mut conn).await {
+                    tracing::error!("Failed to refresh room metrics: {:?}", e);
+                }
+                if let Err(e) = self.1.refresh_pending_jobs(&mut conn).await {
+                    tracing::error!("Failed to refresh pending job metrics: {:?}", e);
+                }
+            }
+            Err(e) => tracing::error!(
+                "Failed to get a connection to refresh room metrics: {:?}",
+                e
+            ),
+        }
+
         self.0.handle(req, data).await
     }
 }
@@ -133,7 +203,9 @@ async fn main() -> ap_lobby::error::Result<()> {
         std::env::set_var("RUST_LOG", "debug");
     }
 
+    let sampling = otlp::SamplingConfig::from_env();
     let _sentry_guard = if let Ok(sentry_dsn) = std::env::var("SENTRY_DSN") {
+        let sentry_sampling = sampling.clone();
         Some(sentry::init((
             sentry_dsn,
             sentry::ClientOptions {
@@ -143,7 +215,9 @@ async fn main() -> ap_lobby::error::Result<()> {
                         .unwrap_or_else(|_| "dev".to_string())
                         .into(),
                 ),
-                traces_sample_rate: 1.0,
+                traces_sampler: Some(Arc::new(move |ctx: &sentry::TransactionContext| {
+                    sentry_sampling.sentry_rate_for(ctx.name())
+                })),
                 ..Default::default()
             },
         )))
@@ -155,12 +229,25 @@ async fn main() -> ap_lobby::error::Result<()> {
         .install_default()
         .expect("Failed to set ring as crypto provider");
     let otlp_endpoint = std::env::var("OTLP_ENDPOINT").ok();
-    let _guard = otlp::init_tracing_subscriber(otlp_endpoint);
+    let json_logs = std::env::var("LOG_FORMAT").is_ok_and(|format| format == "json");
+    let _guard = otlp::init_tracing_subscriber(otlp_endpoint, sampling, json_logs);
 
     let db_url = std::env::var("DATABASE_URL").expect("Provide a DATABASE_URL env variable");
     let valkey_url = std::env::var("VALKEY_URL").expect("Provide a VALKEY_URL env variable");
     let admin_token =
         AdminToken(std::env::var("ADMIN_TOKEN").expect("Provide a ADMIN_TOKEN env variable"));
+    let metrics_scrape_token = MetricsScrapeToken(
+        std::env::var("METRICS_SCRAPE_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
+    );
+
+    let redacted_options =
+        RedactedOptions::parse(&std::env::var("YAML_REDACTED_OPTIONS").unwrap_or_default())
+            .expect("Invalid YAML_REDACTED_OPTIONS");
+    let interchange_key = InterchangeKey::new(std::env::var("ROOM_INTERCHANGE_KEY").ok());
+    let public_origin =
+        PublicOrigin::new(&std::env::var("PUBLIC_URL").expect("Provide a PUBLIC_URL env variable"));
 
     diesel::connection::set_default_instrumentation(|| {
         Some(Box::new(DbInstrumentation::default()))
@@ -174,6 +261,8 @@ async fn main() -> ap_lobby::error::Result<()> {
     let db_pool = Pool::builder(mgr)
         .build()
         .expect("Failed to create database pool, aborting");
+    let shed_threshold =
+        ShedThreshold::from_env(db_pool.status().max_size).expect("Invalid DB_POOL_SHED_WAITING");
     {
         let connection = db_pool
             .get()
@@ -189,11 +278,45 @@ async fn main() -> ap_lobby::error::Result<()> {
         .await?;
     }
 
-    let ctx = Context { db_pool };
+    let landing_cache = LandingCache::new(&valkey_url)
+        .await
+        .expect("Failed to create landing page cache");
+    let abuse = AbuseDetector::new(&valkey_url)
+        .await
+        .expect("Failed to create abuse detector");
+    let pending_uploads = PendingUploads::new(&valkey_url)
+        .await
+        .expect("Failed to create pending uploads store");
+    let scheduler_landing_cache = LandingCache::new(&valkey_url)
+        .await
+        .expect("Failed to create landing page cache");
+    let locks = LockManager::new(&valkey_url)
+        .await
+        .expect("Failed to create lock manager");
+    RoomScheduler::new(
+        db_pool.clone(),
+        locks.clone(),
+        scheduler_landing_cache,
+        public_origin.0.clone(),
+    )
+    .start();
+    Deliverer::new(db_pool.clone(), locks).start();
+
+    let ctx = Context {
+        db_pool,
+        landing_cache,
+        abuse,
+        pending_uploads,
+    };
 
     let limits = Limits::default().limit("string", 2.megabytes());
 
     let figment = rocket::Config::figment().merge(("limits", limits));
+    let configured_limits = figment.extract_inner::<Limits>("limits")?;
+    let figment = figment.merge((
+        "limits",
+        ap_lobby::body_limit::with_route_limits(configured_limits),
+    ));
     let prometheus = PrometheusMetrics::new().with_request_filter(|request| {
         request.uri().path() != "/metrics"
             && request.uri().path().segments().last() != Some("claim_job")
@@ -202,6 +325,10 @@ async fn main() -> ap_lobby::error::Result<()> {
         .registry()
         .register(Box::new(QUERY_HISTOGRAM.clone()))
         .expect("Failed to register query histogram");
+    prometheus
+        .registry()
+        .register(Box::new(POOL_WAIT_HISTOGRAM.clone()))
+        .expect("Failed to register pool wait histogram");
 
     let index_manager = IndexManager::new()?;
     if std::env::var("SKIP_APWORLDS_UPDATE").is_err() {
@@ -214,30 +341,85 @@ async fn main() -> ap_lobby::error::Result<()> {
         .expect("Failed to create job queue for yaml validation");
     yaml_validation_queue.start_reclaim_checker();
 
-    let queue_tokens = QueueTokens(HashMap::from([(
-        "yaml_validation",
-        std::env::var("YAML_VALIDATION_QUEUE_TOKEN").context("YAML_VALIDATION_QUEUE_TOKEN")?,
-    )]));
+    let queue_tokens = QueueTokens(
+        [(
+            "yaml_validation",
+            std::env::var("YAML_VALIDATION_QUEUE_TOKEN").ok(),
+        )]
+        .into_iter()
+        .filter_map(|(queue, token)| Some((queue, token?)))
+        .collect(),
+    );
+    let worker_mtls = WorkerMtls::from_env().expect("Invalid QUEUE_MTLS_REQUIRED");
+    if worker_mtls.required && figment.find_value("tls.mutual").is_err() {
+        tracing::warn!(
+            "QUEUE_MTLS_REQUIRED is set without tls.mutual, every worker request will be rejected"
+        );
+    }
     let queue_counters = QueueCounters::new(prometheus.registry())?;
+    let pool_gauges = PoolGauges::new(prometheus.registry())?;
+    let room_metrics = RoomMetrics::new(prometheus.registry())?;
 
     rocket::custom(figment.clone())
+        .attach(RequestIdFairing)
         .attach(TracingFairing)
         .attach(prometheus.clone())
-        .mount("/", views::routes())
-        .mount("/", views::room_manager::routes())
-        .mount("/", views::room_templates::routes())
-        .mount("/", views::apworlds::routes())
-        .mount("/auth/", views::auth::routes())
-        .mount("/api/", views::api::routes())
-        .mount("/metrics", MetricsRoute(prometheus, queue_counters))
-        .mount("/queues", views::queues::routes())
+        .attach(CacheControlFairing)
+        .attach(CompressionFairing)
+        .mount("/", with_request_hub(views::routes()))
+        .mount("/", with_request_hub(views::room_manager::routes()))
+        .mount("/", with_request_hub(views::room_templates::routes()))
+        .mount("/", with_request_hub(views::room_schedules::routes()))
+        .mount("/", with_request_hub(views::room_search::routes()))
+        .mount("/", with_request_hub(views::room_features::routes()))
+        .mount("/", with_request_hub(views::room_timeline::routes()))
+        .mount("/", with_request_hub(views::room_validation::routes()))
+        .mount("/", with_request_hub(views::room_revisions::routes()))
+        .mount("/", with_request_hub(views::room_url::routes()))
+        .mount("/", with_request_hub(views::upload_hints::routes()))
+        .mount("/", with_request_hub(views::slot_caps::routes()))
+        .mount("/", with_request_hub(views::feature_policies::routes()))
+        .mount("/", with_request_hub(views::yaml_storage::routes()))
+        .mount("/", with_request_hub(views::apworlds::routes()))
+        .mount("/", with_request_hub(views::admin::routes()))
+        .mount("/", with_request_hub(views::og::routes()))
+        .mount("/", with_request_hub(views::embed::routes()))
+        .mount("/", with_request_hub(views::short_links::routes()))
+        .mount("/", with_request_hub(views::check_in::routes()))
+        .mount("/", with_request_hub(views::copy_yamls::routes()))
+        .mount("/", with_request_hub(views::join_codes::routes()))
+        .mount("/", with_request_hub(views::guest_links::routes()))
+        .mount("/", with_request_hub(views::yaml_annotations::routes()))
+        .mount("/", with_request_hub(views::yaml_renames::routes()))
+        .mount("/", with_request_hub(views::settings::routes()))
+        .mount("/", with_request_hub(views::user_tokens::routes()))
+        .mount("/", with_request_hub(views::a11y::routes()))
+        .mount("/", with_request_hub(views::worker_credentials::routes()))
+        .mount("/auth/", with_request_hub(views::auth::routes()))
+        .mount("/api/", with_request_hub(views::api::routes()))
+        .mount(
+            "/metrics",
+            with_request_hub(MetricsRoute(
+                prometheus,
+                queue_counters,
+                pool_gauges,
+                room_metrics,
+                metrics_scrape_token,
+            )),
+        )
+        .mount("/queues", with_request_hub(views::queues::routes()))
         .register("/", catchers![unauthorized])
         .manage(ctx)
         .manage(figment)
         .manage(admin_token)
+        .manage(redacted_options)
+        .manage(interchange_key)
         .manage(index_manager)
         .manage(yaml_validation_queue)
         .manage(queue_tokens)
+        .manage(worker_mtls)
+        .manage(shed_threshold)
+        .manage(public_origin)
         .attach(OAuth2::<Discord>::fairing("discord"))
         .launch()
         .await
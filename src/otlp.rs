@@ -1,9 +1,13 @@
-use opentelemetry::{global, trace::TracerProvider, KeyValue};
+use opentelemetry::{
+    global,
+    trace::{Link, SamplingDecision, SamplingResult, SpanKind, TraceId, TracerProvider},
+    KeyValue,
+};
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::{
     propagation::TraceContextPropagator,
     runtime,
-    trace::{BatchConfig, RandomIdGenerator, Tracer},
+    trace::{BatchConfig, RandomIdGenerator, Sampler, ShouldSample, Tracer},
     Resource,
 };
 use opentelemetry_semantic_conventions::{
@@ -31,12 +35,158 @@ fn resource() -> Resource {
     )
 }
 
-fn init_tracer(endpoint: &str) -> Tracer {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RouteGroup {
+    Static,
+    Upload,
+    Queue,
+    Default,
+}
+
+impl RouteGroup {
+    /// Classifies a route, span or transaction name into a sampling group.
+    pub fn from_name(name: &str) -> Self {
+        if name.starts_with("/static") || name.contains("favicon") || name == "dist" {
+            return RouteGroup::Static;
+        }
+
+        if name.starts_with("/queues") || name.contains("claim_job") || name.contains("resolve_job")
+        {
+            return RouteGroup::Queue;
+        }
+
+        if name.contains("upload") {
+            return RouteGroup::Upload;
+        }
+
+        RouteGroup::Default
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Static => "static",
+            RouteGroup::Upload => "upload",
+            RouteGroup::Queue => "queue",
+            RouteGroup::Default => "default",
+        }
+    }
+}
+
+/// Per route group trace sampling rates.
+///
+/// Each rate can be configured with `TRACES_SAMPLE_RATE` (default group),
+/// `TRACES_SAMPLE_RATE_STATIC`, `TRACES_SAMPLE_RATE_UPLOAD` and `TRACES_SAMPLE_RATE_QUEUE`.
+///
+/// When `TRACES_TAIL_SAMPLING` is set, every trace is exported with `sampling.group` and
+/// `sampling.rate` attributes instead, so that the collector can keep all error traces and apply
+/// the rates to the rest.
+#[derive(Clone, Debug)]
+pub struct SamplingConfig {
+    pub default_rate: f64,
+    pub static_rate: f64,
+    pub upload_rate: f64,
+    pub queue_rate: f64,
+    pub tail_sampling: bool,
+}
+
+fn rate_from_env(name: &str, default: f64) -> f64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|rate| rate.parse::<f64>().ok())
+        .map(|rate| rate.clamp(0.0, 1.0))
+        .unwrap_or(default)
+}
+
+impl SamplingConfig {
+    pub fn from_env() -> Self {
+        let default_rate = rate_from_env("TRACES_SAMPLE_RATE", 1.0);
+
+        Self {
+            default_rate,
+            static_rate: rate_from_env("TRACES_SAMPLE_RATE_STATIC", default_rate),
+            upload_rate: rate_from_env("TRACES_SAMPLE_RATE_UPLOAD", default_rate),
+            queue_rate: rate_from_env("TRACES_SAMPLE_RATE_QUEUE", default_rate),
+            tail_sampling: std::env::var("TRACES_TAIL_SAMPLING").is_ok(),
+        }
+    }
+
+    pub fn rate_for(&self, group: RouteGroup) -> f64 {
+        match group {
+            RouteGroup::Static => self.static_rate,
+            RouteGroup::Upload => self.upload_rate,
+            RouteGroup::Queue => self.queue_rate,
+            RouteGroup::Default => self.default_rate,
+        }
+    }
+
+    /// Returns the sentry sample rate for a transaction. In tail sampling mode everything is
+    /// sent and the filtering happens later.
+    pub fn sentry_rate_for(&self, transaction_name: &str) -> f32 {
+        if self.tail_sampling {
+            return 1.0;
+        }
+
+        self.rate_for(RouteGroup::from_name(transaction_name)) as f32
+    }
+}
+
+/// Root span sampler applying the per route group rates from [`SamplingConfig`].
+#[derive(Clone, Debug)]
+struct RouteGroupSampler(SamplingConfig);
+
+impl ShouldSample for RouteGroupSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&opentelemetry::Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let route = attributes
+            .iter()
+            .find(|kv| {
+                matches!(
+                    kv.key.as_str(),
+                    "http.route" | "url.path" | "uri" | "http.target"
+                )
+            })
+            .map(|kv| kv.value.as_str().into_owned())
+            .unwrap_or_else(|| name.to_string());
+
+        let group = RouteGroup::from_name(&route);
+        let rate = self.0.rate_for(group);
+
+        if self.0.tail_sampling {
+            return SamplingResult {
+                decision: SamplingDecision::RecordAndSample,
+                attributes: vec![
+                    KeyValue::new("sampling.group", group.as_str()),
+                    KeyValue::new("sampling.rate", rate),
+                ],
+                trace_state: Default::default(),
+            };
+        }
+
+        Sampler::TraceIdRatioBased(rate).should_sample(
+            parent_context,
+            trace_id,
+            name,
+            span_kind,
+            attributes,
+            links,
+        )
+    }
+}
+
+fn init_tracer(endpoint: &str, sampling: SamplingConfig) -> Tracer {
     let provider = opentelemetry_otlp::new_pipeline()
         .tracing()
         .with_trace_config(
             opentelemetry_sdk::trace::Config::default()
                 .with_id_generator(RandomIdGenerator::default())
+                .with_sampler(Sampler::ParentBased(Box::new(RouteGroupSampler(sampling))))
                 .with_resource(resource()),
         )
         .with_batch_config(BatchConfig::default())
@@ -53,16 +203,33 @@ fn init_tracer(endpoint: &str) -> Tracer {
     provider.tracer("tracing-otel-subscriber")
 }
 
-pub fn init_tracing_subscriber(endpoint: Option<String>) -> OtelGuard {
+/// Sets up logging and tracing. With `json_logs`, logs are written as one JSON object per line
+/// including the fields of the spans they happened in, which is what log aggregators want.
+pub fn init_tracing_subscriber(
+    endpoint: Option<String>,
+    sampling: SamplingConfig,
+    json_logs: bool,
+) -> OtelGuard {
+    let (json_layer, text_layer) = if json_logs {
+        let layer = tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true);
+        (Some(layer), None)
+    } else {
+        (None, Some(tracing_subscriber::fmt::layer()))
+    };
+
     let subscriber = tracing_subscriber::registry()
         .with(tracing_subscriber::filter::LevelFilter::from_level(
             Level::INFO,
         ))
-        .with(tracing_subscriber::fmt::layer())
+        .with(json_layer)
+        .with(text_layer)
         .with(sentry_tracing::layer());
 
     if let Some(endpoint) = endpoint {
-        let tracer = init_tracer(&endpoint);
+        let tracer = init_tracer(&endpoint, sampling);
         subscriber.with(OpenTelemetryLayer::new(tracer)).init();
     } else {
         subscriber.init();
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::db::RoomId;
+use crate::error::{Error, Result};
+use crate::yaml::{set_document_name, NameCollision};
+
+const KEY_PREFIX: &str = "lobby:pending_upload:";
+const TTL: Duration = Duration::from_secs(60 * 60);
+
+/// An upload that got rejected because a player name was taken, kept so it can be retried with
+/// one of the suggested names without picking the files again.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingUpload {
+    pub documents: Vec<String>,
+    pub collision: NameCollision,
+}
+
+impl PendingUpload {
+    /// The documents of the upload, with the colliding one renamed to `new_name`.
+    pub fn renamed_documents(&self, new_name: &str) -> Result<Vec<String>> {
+        let mut documents = self.documents.clone();
+        let Some(document) = documents.get_mut(self.collision.document_index) else {
+            return Err(Error::not_found("This upload can't be retried anymore"));
+        };
+        *document = set_document_name(document, new_name)?;
+
+        Ok(documents)
+    }
+}
+
+/// Keeps at most one [`PendingUpload`] per user and room in valkey, for an hour.
+///
+/// This is a convenience, valkey errors are logged and behave as if nothing was stashed.
+pub struct PendingUploads {
+    conn: ConnectionManager,
+}
+
+impl PendingUploads {
+    pub async fn new(valkey_url: &str) -> Result<Self> {
+        let client = redis::Client::open(valkey_url)?;
+        let conn = ConnectionManager::new(client).await?;
+
+        Ok(Self { conn })
+    }
+
+    fn key(room_id: RoomId, user_id: i64) -> String {
+        format!("{}{}:{}", KEY_PREFIX, room_id, user_id)
+    }
+
+    pub async fn stash(&self, room_id: RoomId, user_id: i64, upload: &PendingUpload) {
+        let Ok(serialized) = serde_json::to_string(upload) else {
+            return;
+        };
+        let res: redis::RedisResult<()> = self
+            .conn
+            .clone()
+            .set_ex(Self::key(room_id, user_id), serialized, TTL.as_secs())
+            .await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to stash a pending upload: {:?}", e);
+        }
+    }
+
+    pub async fn get(&self, room_id: RoomId, user_id: i64) -> Option<PendingUpload> {
+        let stashed: redis::RedisResult<Option<String>> =
+            self.conn.clone().get(Self::key(room_id, user_id)).await;
+        match stashed {
+            Ok(stashed) => serde_json::from_str(&stashed?).ok(),
+            Err(e) => {
+                tracing::warn!("Failed to read a pending upload: {:?}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn discard(&self, room_id: RoomId, user_id: i64) {
+        let res: redis::RedisResult<()> = self.conn.clone().del(Self::key(room_id, user_id)).await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to discard a pending upload: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Instant;
+
+use anyhow::anyhow;
+use ap_lobby::db::instrumentation::POOL_WAIT_HISTOGRAM;
+use ap_lobby::error::{Error, Result};
+use diesel_async::pooled_connection::deadpool::{Object, Pool};
+use diesel_async::AsyncPgConnection;
+use prometheus::{IntGaugeVec, Opts, Registry};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use crate::Context;
+
+impl Context {
+    /// Gets a connection from the database pool, recording how long we had to wait for it.
+    pub async fn db_conn(&self) -> Result<Object<AsyncPgConnection>> {
+        let start = Instant::now();
+        let conn = self.db_pool.get().await;
+        POOL_WAIT_HISTOGRAM
+            .with_label_values(&["postgres"])
+            .observe(start.elapsed().as_secs_f64());
+
+        Ok(conn?)
+    }
+}
+
+#[derive(Clone)]
+pub struct PoolGauges {
+    pub max_size: IntGaugeVec,
+    pub size: IntGaugeVec,
+    pub in_use: IntGaugeVec,
+    pub waiting: IntGaugeVec,
+}
+
+impl PoolGauges {
+    pub fn new(registry: &Registry) -> Result<Self> {
+        let ret = Self {
+            max_size: IntGaugeVec::new(
+                Opts::new(
+                    "pool_max_size",
+                    "The maximum number of connections in the pool",
+                ),
+                &["pool"],
+            )?,
+            size: IntGaugeVec::new(
+                Opts::new("pool_size", "The current number of connections in the pool"),
+                &["pool"],
+            )?,
+            in_use: IntGaugeVec::new(
+                Opts::new("pool_in_use", "The number of connections currently in use"),
+                &["pool"],
+            )?,
+            waiting: IntGaugeVec::new(
+                Opts::new(
+                    "pool_waiting",
+                    "The number of tasks waiting for a connection to become available",
+                ),
+                &["pool"],
+            )?,
+        };
+
+        registry.register(Box::new(ret.max_size.clone()))?;
+        registry.register(Box::new(ret.size.clone()))?;
+        registry.register(Box::new(ret.in_use.clone()))?;
+        registry.register(Box::new(ret.waiting.clone()))?;
+
+        Ok(ret)
+    }
+
+    pub fn update_pool(&self, pool_name: &str, pool: &Pool<AsyncPgConnection>) {
+        let status = pool.status();
+
+        self.max_size
+            .with_label_values(&[pool_name])
+            .set(status.max_size as i64);
+        self.size
+            .with_label_values(&[pool_name])
+            .set(status.size as i64);
+        self.in_use
+            .with_label_values(&[pool_name])
+            .set(status.size.saturating_sub(status.available) as i64);
+        self.waiting
+            .with_label_values(&[pool_name])
+            .set(status.waiting as i64);
+    }
+}
+
+/// How many requests can wait for a database connection before [`NotSaturated`] routes start
+/// failing, from `DB_POOL_SHED_WAITING`. Defaults to the size of the pool.
+pub struct ShedThreshold(usize);
+
+impl ShedThreshold {
+    pub fn from_env(pool_max_size: usize) -> anyhow::Result<Self> {
+        let max_waiting = std::env::var("DB_POOL_SHED_WAITING")
+            .ok()
+            .map(|max_waiting| max_waiting.trim().parse())
+            .transpose()?
+            .unwrap_or(pool_max_size);
+
+        Ok(Self(max_waiting))
+    }
+}
+
+/// Request guard for routes that aren't critical to the lobby working (apworld listings, bulk
+/// downloads...).
+///
+/// When the database pool is exhausted and requests are already piling up waiting for a
+/// connection, those routes fail early with a 503 instead of adding to the queue.
+pub struct NotSaturated;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for NotSaturated {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(ctx) = request.rocket().state::<Context>() else {
+            return Outcome::Success(NotSaturated);
+        };
+        let Some(ShedThreshold(max_waiting)) = request.rocket().state::<ShedThreshold>() else {
+            return Outcome::Error((
+                Status::InternalServerError,
+                Error::Internal(anyhow!("Shed threshold isn't managed")),
+            ));
+        };
+
+        let status = ctx.db_pool.status();
+        if status.available == 0 && status.waiting >= *max_waiting {
+            tracing::warn!(
+                waiting = status.waiting,
+                "Database pool saturated, shedding non critical request"
+            );
+            return Outcome::Error((
+                Status::ServiceUnavailable,
+                anyhow!("The lobby is under heavy load right now, try again in a bit").into(),
+            ));
+        }
+
+        Outcome::Success(NotSaturated)
+    }
+}
@@ -0,0 +1,302 @@
+//! Hides option values that shouldn't leave the hands of a YAML's owner, like webhook URLs or
+//! tokens some clients read from the YAML. Only what gets shown or downloaded is redacted, the
+//! stored YAML keeps the original values and so does the room's YAML archive when the organizer
+//! downloads it to generate.
+
+use anyhow::bail;
+
+const REDACTED: &str = "<redacted>";
+
+/// Option paths to redact, written as `Game.option`. `*` matches every game.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RedactedOptions(Vec<(String, String)>);
+
+impl RedactedOptions {
+    /// Reads a comma separated list of paths, e.g. `*.webhook_url,Some Game.server_token`.
+    pub fn parse(paths: &str) -> anyhow::Result<Self> {
+        let mut options = vec![];
+        for path in paths
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        {
+            // Game names can contain dots, option names can't.
+            let Some((game, option)) = path.rsplit_once('.') else {
+                bail!(
+                    "Redacted options should look like `Game.option`, got `{}`",
+                    path
+                );
+            };
+            if game.is_empty() || option.is_empty() {
+                bail!(
+                    "Redacted options should look like `Game.option`, got `{}`",
+                    path
+                );
+            }
+            options.push((game.to_string(), option.to_string()));
+        }
+
+        Ok(Self(options))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn matches(&self, game: &str, option: &str) -> bool {
+        self.0
+            .iter()
+            .any(|(g, o)| (g == "*" || g == game) && o == option)
+    }
+
+    /// Masks the values of the redacted options in a raw YAML, leaving the rest of it as it was
+    /// uploaded. The result still parses, with the same options. Values written over several
+    /// lines, like block scalars, are masked as a whole, and flow mappings that could hide a
+    /// redacted option are masked entirely.
+    pub fn redact(&self, content: &str) -> String {
+        if self.is_empty() {
+            return content.to_string();
+        }
+
+        let mut section: Option<String> = None;
+        let mut option_indent = None;
+        // Indentation of the option whose values are on the next lines, and of those values.
+        let mut redacting_under: Option<(usize, Option<usize>)> = None;
+        // Indentation of a key whose value got masked, what's indented more continues the value.
+        let mut dropping_under = None;
+        // How deep a masked flow collection going over several lines still is.
+        let mut dropping_flow = 0;
+        let mut redacted_nb = 0;
+        let mut redacted = String::with_capacity(content.len());
+
+        for line in content.split_inclusive('\n') {
+            let body = line.trim_end_matches(['\r', '\n']);
+            let line_ending = &line[body.len()..];
+            let trimmed = body.trim_start();
+            let indent = body.len() - trimmed.len();
+
+            if dropping_flow > 0 {
+                dropping_flow += flow_depth(trimmed);
+                continue;
+            }
+            if trimmed.is_empty() {
+                redacted.push_str(line);
+                continue;
+            }
+            match dropping_under {
+                Some(key_indent) if indent > key_indent => continue,
+                _ => dropping_under = None,
+            }
+            if let Some((parent_indent, value_indent)) = &mut redacting_under {
+                if indent > *parent_indent {
+                    let value_indent = *value_indent.get_or_insert(indent);
+                    if indent > value_indent {
+                        // The rest of a value that's already masked.
+                        continue;
+                    }
+                    if trimmed.starts_with('#') {
+                        redacted.push_str(line);
+                        continue;
+                    }
+
+                    redacted_nb += 1;
+                    redacted.push_str(&body[..indent]);
+                    redacted.push_str(&redact_nested_line(trimmed, redacted_nb));
+                    redacted.push_str(line_ending);
+                    continue;
+                }
+                redacting_under = None;
+            }
+            if trimmed.starts_with('#') {
+                redacted.push_str(line);
+                continue;
+            }
+            if indent == 0 && body.starts_with("---") {
+                section = None;
+                redacted.push_str(line);
+                continue;
+            }
+
+            if indent == 0 {
+                if trimmed.starts_with(['{', '[']) && self.flow_may_hide(None, trimmed) {
+                    tracing::debug!("Redacting a document written as a flow mapping");
+                    redacted.push_str(&format!("'{}'", REDACTED));
+                    redacted.push_str(line_ending);
+                    dropping_flow = flow_depth(trimmed);
+                    continue;
+                }
+
+                section = line_key(trimmed).map(str::to_string);
+                option_indent = None;
+                let value = line_value(trimmed);
+                if let Some(game) = section.as_deref() {
+                    if value.starts_with(['{', '[']) && self.flow_may_hide(Some(game), value) {
+                        tracing::debug!("Redacting {}, written as a flow mapping", game);
+                        redacted.push_str(&body[..trimmed.find(':').unwrap_or(body.len())]);
+                        redacted.push_str(&format!(": '{}'", REDACTED));
+                        redacted.push_str(line_ending);
+                        dropping_flow = flow_depth(value);
+                        continue;
+                    }
+                }
+                redacted.push_str(line);
+                continue;
+            }
+
+            let Some(game) = &section else {
+                redacted.push_str(line);
+                continue;
+            };
+            // Options are the first level under the game, anything deeper belongs to an option.
+            let is_option = indent == *option_indent.get_or_insert(indent);
+            let option = line_key(trimmed);
+            let Some(option) = option.filter(|option| is_option && self.matches(game, option))
+            else {
+                redacted.push_str(line);
+                continue;
+            };
+
+            let key_len = trimmed.find(':').unwrap_or(trimmed.len());
+            let value = line_value(trimmed);
+            if value.is_empty() || value.starts_with('#') {
+                // The values are on the next lines.
+                redacting_under = Some((indent, None));
+                redacted.push_str(line);
+            } else {
+                // Block scalars and multi-line values continue on the next, more indented, lines.
+                tracing::debug!("Redacting {}.{}", game, option);
+                redacted.push_str(&body[..indent + key_len]);
+                redacted.push_str(&format!(": '{}'", REDACTED));
+                redacted.push_str(line_ending);
+                dropping_under = Some(indent);
+            }
+        }
+
+        redacted
+    }
+
+    /// Whether a flow collection could contain one of the redacted options of `game`, or of any
+    /// game when it isn't known. Collections going on over several lines can't be checked and are
+    /// assumed to.
+    fn flow_may_hide(&self, game: Option<&str>, value: &str) -> bool {
+        let value = value.split(" #").next().unwrap_or_default().trim_end();
+        let is_closed = value.ends_with(['}', ']']);
+
+        self.0.iter().any(|(g, option)| {
+            (g == "*" || game.is_none_or(|game| g == game))
+                && (!is_closed || value.contains(option.as_str()))
+        })
+    }
+}
+
+/// How many flow collections a line opens without closing them. Brackets in quotes are counted
+/// too, which only ever makes more of a masked collection go.
+fn flow_depth(line: &str) -> i32 {
+    line.chars()
+        .map(|c| match c {
+            '{' | '[' => 1,
+            '}' | ']' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The value of a `key: value` line, empty if it's on the next lines.
+fn line_value(line: &str) -> &str {
+    line.split_once(':').map_or("", |(_, value)| value).trim()
+}
+
+/// The key of a `key: value` line, without its quotes.
+fn line_key(line: &str) -> Option<&str> {
+    let (key, _) = line.split_once(':')?;
+    let key = key.trim();
+    let key = key
+        .strip_prefix('\'')
+        .and_then(|key| key.strip_suffix('\''))
+        .or_else(|| key.strip_prefix('"').and_then(|key| key.strip_suffix('"')))
+        .unwrap_or(key);
+
+    Some(key)
+}
+
+/// Lines under a redacted option are either weighted values or list items. Weights aren't secret
+/// and are kept, values are numbered so that the mapping doesn't end up with duplicate keys.
+fn redact_nested_line(line: &str, redacted_nb: usize) -> String {
+    if line.starts_with("- ") || line == "-" {
+        return format!("- '{} {}'", REDACTED, redacted_nb);
+    }
+    if let Some((_, weight)) = line.rsplit_once(':') {
+        let weight = weight.trim();
+        if weight.parse::<f64>().is_ok() {
+            return format!("'{} {}': {}", REDACTED, redacted_nb, weight);
+        }
+    }
+
+    format!("'{} {}'", REDACTED, redacted_nb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RedactedOptions;
+
+    #[test]
+    fn test_parse_redacted_options() {
+        let options = RedactedOptions::parse(" *.webhook_url, Some.Game.token ,").unwrap();
+        assert!(options.matches("Clique", "webhook_url"));
+        assert!(options.matches("Some.Game", "token"));
+        assert!(!options.matches("Clique", "token"));
+
+        assert!(RedactedOptions::parse("").unwrap().is_empty());
+        assert!(RedactedOptions::parse("webhook_url").is_err());
+        assert!(RedactedOptions::parse("Clique.").is_err());
+    }
+
+    #[test]
+    fn test_redact() {
+        let options = RedactedOptions::parse("*.webhook_url,Clique.token").unwrap();
+        let content = "name: Player\r\ngame: Clique\r\nClique:\r\n  color: red\r\n  token: \"abc:def\" # mine\r\n  webhook_url:\r\n    'https://example.com/hook': 50\r\n    'https://example.com/other': 50\r\n  hard_mode: true\r\n---\r\nname: Other\r\ngame: Other\r\nOther:\r\n  token: visible\r\n  webhook_url:\r\n    - https://example.com/hook\r\n";
+
+        let redacted = options.redact(content);
+        assert_eq!(
+            redacted,
+            "name: Player\r\ngame: Clique\r\nClique:\r\n  color: red\r\n  token: '<redacted>'\r\n  webhook_url:\r\n    '<redacted> 1': 50\r\n    '<redacted> 2': 50\r\n  hard_mode: true\r\n---\r\nname: Other\r\ngame: Other\r\nOther:\r\n  token: visible\r\n  webhook_url:\r\n    - '<redacted> 3'\r\n"
+        );
+        assert!(crate::yaml::parse_raw_yamls(&[&redacted]).is_ok());
+
+        assert_eq!(RedactedOptions::default().redact(content), content);
+    }
+
+    #[test]
+    fn test_redact_block_scalars() {
+        let options = RedactedOptions::parse("*.webhook_url,Clique.token").unwrap();
+        let content = "name: Player\ngame: Clique\nClique:\n  token: |\n    secret\n\n    # still secret\n  webhook_url:\n    - >-\n      https://example.com/\n      hook\n  hard_mode: true\n";
+
+        let redacted = options.redact(content);
+        assert_eq!(
+            redacted,
+            "name: Player\ngame: Clique\nClique:\n  token: '<redacted>'\n\n  webhook_url:\n    - '<redacted> 1'\n  hard_mode: true\n"
+        );
+        assert!(!redacted.contains("secret") && !redacted.contains("example.com"));
+        assert!(crate::yaml::parse_raw_yamls(&[&redacted]).is_ok());
+    }
+
+    #[test]
+    fn test_redact_flow_mappings() {
+        let options = RedactedOptions::parse("Clique.token").unwrap();
+        let content = "name: Player\ngame: {Clique: 50}\nClique: {color: red, token: secret}\nOther: {token: visible}\n";
+        assert_eq!(
+            options.redact(content),
+            "name: Player\ngame: {Clique: 50}\nClique: '<redacted>'\nOther: {token: visible}\n"
+        );
+
+        // Collections going over several lines can't be checked.
+        let content = "name: Player\ngame: Clique\nClique: {\n  token: secret\n}\nOther: {}\n";
+        assert_eq!(
+            options.redact(content),
+            "name: Player\ngame: Clique\nClique: '<redacted>'\nOther: {}\n"
+        );
+
+        let content = "{name: Player, game: Clique, Clique: {token: secret}}\n";
+        assert_eq!(options.redact(content), "'<redacted>'\n");
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::Display;
+
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Identifies a request in logs, traces, error messages and the `X-Request-Id` response header.
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Returns the request's ID. An ID set by the reverse proxy is kept so that its logs line up
+    /// with ours, otherwise a new one gets generated.
+    pub fn from_request_sync<'r>(request: &'r Request) -> &'r RequestId {
+        request.local_cache(|| {
+            let forwarded = request
+                .headers()
+                .get_one(REQUEST_ID_HEADER)
+                .filter(|id| is_valid_request_id(id));
+
+            match forwarded {
+                Some(id) => RequestId(id.to_string()),
+                None => RequestId(uuid::Uuid::new_v4().to_string()),
+            }
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// The ID ends up in logs and headers, don't let clients put anything weird in there.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r RequestId {
+    type Error = crate::error::Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::from_request_sync(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_request_id;
+
+    #[test]
+    fn test_is_valid_request_id() {
+        assert!(is_valid_request_id("1b4e28ba-2fa1-11d2-883f-0016d3cca427"));
+        assert!(is_valid_request_id("req_abc.123"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("abc\r\nX-Injected: 1"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
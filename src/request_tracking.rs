@@ -0,0 +1,40 @@
+use ap_lobby::request_id::{RequestId, REQUEST_ID_HEADER};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    Data, Request, Response,
+};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Gives every request an ID, attaches it to the request's trace and sends it back in the
+/// `X-Request-Id` header.
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID fairing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let request_id = RequestId::from_request_sync(req);
+        Span::current().set_attribute("request_id", request_id.to_string());
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let request_id = RequestId::from_request_sync(req);
+        res.set_header(Header::new(REQUEST_ID_HEADER, request_id.to_string()));
+
+        tracing::info!(
+            %request_id,
+            method = %req.method(),
+            path = %req.uri().path(),
+            status = res.status().code,
+            "Request handled"
+        );
+    }
+}
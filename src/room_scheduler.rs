@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel_async::pooled_connection::deadpool::Pool;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+
+use crate::db::{self, DeliveryChannel, NewRoom, Room, RoomId, RoomSchedule};
+use crate::error::{Error, Result};
+use crate::landing_cache::LandingCache;
+use crate::locks::LockManager;
+
+const LOCK_NAME: &str = "room_schedules";
+const TICK: Duration = Duration::from_secs(60);
+
+/// Opens the rooms of every due [`RoomSchedule`], closes the rooms they replace and queues a post
+/// of the new ones to the schedule's discord webhook.
+///
+/// Every instance runs a scheduler, the lock makes sure only one of them does the work.
+pub struct RoomScheduler {
+    db_pool: Pool<AsyncPgConnection>,
+    locks: LockManager,
+    landing_cache: LandingCache,
+    public_url: String,
+}
+
+impl RoomScheduler {
+    pub fn new(
+        db_pool: Pool<AsyncPgConnection>,
+        locks: LockManager,
+        landing_cache: LandingCache,
+        public_url: String,
+    ) -> Self {
+        Self {
+            db_pool,
+            locks,
+            landing_cache,
+            public_url,
+        }
+    }
+
+    pub fn start(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_due_schedules().await {
+                    tracing::error!("Failed to run room schedules: {:?}", e);
+                }
+            }
+        });
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn run_due_schedules(&self) -> Result<()> {
+        let Some(lock) = self.locks.try_acquire(LOCK_NAME, TICK).await? else {
+            return Ok(());
+        };
+
+        let mut conn = self.db_pool.get().await?;
+        let schedules = db::get_due_room_schedules(Utc::now().naive_utc(), &mut conn).await?;
+        for schedule in schedules {
+            if !lock.is_held() {
+                return Ok(());
+            }
+
+            // Failed schedules stay due and get retried on the next tick.
+            match self.run_schedule(&schedule, &mut conn).await {
+                Ok(room) => {
+                    if let Err(e) = self.announce(&schedule, &room, &mut conn).await {
+                        tracing::error!(
+                            schedule_id = %schedule.id,
+                            "Failed to queue the scheduled room's announcement: {:?}",
+                            e
+                        );
+                    }
+                }
+                Err(e) => tracing::error!(
+                    schedule_id = %schedule.id,
+                    "Failed to open a scheduled room: {:?}",
+                    e
+                ),
+            }
+        }
+
+        lock.release().await
+    }
+
+    #[tracing::instrument(skip(self, conn))]
+    async fn run_schedule(
+        &self,
+        schedule: &RoomSchedule,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<Room> {
+        let tpl = db::get_room_template_by_id(schedule.template_id, conn).await?;
+        let settings = &tpl.settings;
+        // Rooms opened late, after some downtime, still stay open for as long as they should.
+        let now = Utc::now().naive_utc();
+        let new_room = NewRoom {
+            close_date: now + schedule.open_for(),
+            room_url: "",
+            from_template_id: Some(Some(tpl.id)),
+            ..settings.as_new_room(RoomId::new_v4(), Some(schedule.author_id))
+        };
+        let next_run_at = db::next_schedule_run(schedule.weekday, schedule.start_time, now);
+
+        let room = conn
+            .transaction::<Room, Error, _>(|conn| {
+                async move {
+                    if let Some(last_room_id) = schedule.last_room_id {
+                        db::close_room_now(last_room_id, conn).await?;
+                    }
+                    let room = db::create_room(&new_room, conn).await?;
+                    db::record_room_schedule_run(schedule.id, room.id, next_run_at, conn).await?;
+                    Ok(room)
+                }
+                .scope_boxed()
+            })
+            .await?;
+        self.landing_cache.invalidate().await;
+
+        Ok(room)
+    }
+
+    async fn announce(
+        &self,
+        schedule: &RoomSchedule,
+        room: &Room,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<()> {
+        if schedule.webhook_url.is_empty() {
+            return Ok(());
+        }
+
+        let content = format!(
+            "**{}** is open until <t:{}:f>\n{}/room/{}",
+            room.settings.name,
+            room.settings.close_date.and_utc().timestamp(),
+            self.public_url,
+            room.id
+        );
+        let payload = serde_json::json!({
+            "content": content,
+            // Room names come from users, don't let them ping anyone.
+            "allowed_mentions": { "parse": [] },
+        });
+        db::enqueue_delivery(
+            DeliveryChannel::DiscordWebhook,
+            &schedule.webhook_url,
+            payload,
+            conn,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,5 +1,20 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    abuse_signals (id) {
+        id -> SqlAbuseSignalId,
+        user_id -> Int8,
+        room_id -> Nullable<SqlRoomId>,
+        kind -> Varchar,
+        details -> Text,
+        created_at -> Timestamp,
+        reviewed_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::db::types::sql::*;
@@ -10,6 +25,141 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    jobs (id) {
+        id -> Varchar,
+        kind -> Varchar,
+        room_id -> Nullable<SqlRoomId>,
+        yaml_id -> Nullable<SqlYamlId>,
+        user_id -> Nullable<Int8>,
+        enqueued_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        resolution -> Nullable<Varchar>,
+        worker_id -> Nullable<Varchar>,
+        priority -> Varchar,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    outbound_deliveries (id) {
+        id -> SqlDeliveryId,
+        channel -> Varchar,
+        target -> Text,
+        payload -> Jsonb,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        dead_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_check_ins (room_id) {
+        room_id -> SqlRoomId,
+        opened_at -> Timestamp,
+        closed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_guest_links (code) {
+        code -> Varchar,
+        room_id -> SqlRoomId,
+        guest_id -> Nullable<Int8>,
+        created_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_idempotency_keys (author_id, idempotency_key) {
+        author_id -> Int8,
+        idempotency_key -> Varchar,
+        room_id -> SqlRoomId,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_join_codes (room_id) {
+        room_id -> SqlRoomId,
+        code -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_members (room_id, user_id) {
+        room_id -> SqlRoomId,
+        user_id -> Int8,
+        joined_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_schedules (id) {
+        id -> SqlRoomScheduleId,
+        template_id -> SqlRoomTemplateId,
+        author_id -> Int8,
+        weekday -> Int2,
+        start_time -> Time,
+        open_for_hours -> Int4,
+        webhook_url -> Text,
+        next_run_at -> Timestamp,
+        last_room_id -> Nullable<SqlRoomId>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_settings_revisions (id) {
+        id -> SqlRoomRevisionId,
+        room_id -> SqlRoomId,
+        author_id -> Nullable<Int8>,
+        settings -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    room_short_links (slug) {
+        slug -> Varchar,
+        room_id -> SqlRoomId,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::db::types::sql::*;
@@ -54,6 +204,68 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         from_template_id -> Nullable<SqlRoomTemplateId>,
+        archived -> Bool,
+        exact_yaml_storage -> Bool,
+        upload_hints -> Text,
+        max_slots -> Nullable<Int4>,
+        feature_policies -> Jsonb,
+        room_url_visibility -> Varchar,
+        room_url_revealed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    user_settings (user_id) {
+        user_id -> Int8,
+        theme -> Varchar,
+        compact_layout -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    user_tokens (id) {
+        id -> SqlUserTokenId,
+        user_id -> Int8,
+        name -> Varchar,
+        token_hash -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    worker_credentials (id) {
+        id -> SqlWorkerCredentialId,
+        name -> Varchar,
+        token_hash -> Varchar,
+        queues -> Array<Text>,
+        created_at -> Timestamp,
+        rotated_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::types::sql::*;
+
+    yaml_annotations (yaml_id) {
+        yaml_id -> SqlYamlId,
+        flag -> Nullable<Varchar>,
+        notes -> Text,
+        updated_at -> Timestamp,
     }
 }
 
@@ -71,13 +283,61 @@ diesel::table! {
         features -> Json,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        checked_in_at -> Nullable<Timestamp>,
+        excluded -> Bool,
+        validation_warnings -> Jsonb,
+        content_fingerprint -> Nullable<Varchar>,
+        waitlisted -> Bool,
+        original_content -> Nullable<Text>,
     }
 }
 
+diesel::joinable!(abuse_signals -> discord_users (user_id));
+diesel::joinable!(abuse_signals -> rooms (room_id));
+diesel::joinable!(jobs -> discord_users (user_id));
+diesel::joinable!(jobs -> rooms (room_id));
+diesel::joinable!(jobs -> yamls (yaml_id));
+diesel::joinable!(room_check_ins -> rooms (room_id));
+diesel::joinable!(room_guest_links -> discord_users (guest_id));
+diesel::joinable!(room_guest_links -> rooms (room_id));
+diesel::joinable!(room_idempotency_keys -> discord_users (author_id));
+diesel::joinable!(room_idempotency_keys -> rooms (room_id));
+diesel::joinable!(room_join_codes -> rooms (room_id));
+diesel::joinable!(room_members -> discord_users (user_id));
+diesel::joinable!(room_members -> rooms (room_id));
+diesel::joinable!(room_schedules -> discord_users (author_id));
+diesel::joinable!(room_schedules -> room_templates (template_id));
+diesel::joinable!(room_schedules -> rooms (last_room_id));
+diesel::joinable!(room_settings_revisions -> discord_users (author_id));
+diesel::joinable!(room_settings_revisions -> rooms (room_id));
+diesel::joinable!(room_short_links -> rooms (room_id));
 diesel::joinable!(room_templates -> discord_users (author_id));
 diesel::joinable!(rooms -> discord_users (author_id));
 diesel::joinable!(rooms -> room_templates (from_template_id));
+diesel::joinable!(user_settings -> discord_users (user_id));
+diesel::joinable!(user_tokens -> discord_users (user_id));
+diesel::joinable!(yaml_annotations -> yamls (yaml_id));
 diesel::joinable!(yamls -> discord_users (owner_id));
 diesel::joinable!(yamls -> rooms (room_id));
 
-diesel::allow_tables_to_appear_in_same_query!(discord_users, room_templates, rooms, yamls,);
+diesel::allow_tables_to_appear_in_same_query!(
+    abuse_signals,
+    discord_users,
+    jobs,
+    outbound_deliveries,
+    room_check_ins,
+    room_guest_links,
+    room_idempotency_keys,
+    room_join_codes,
+    room_members,
+    room_schedules,
+    room_settings_revisions,
+    room_short_links,
+    room_templates,
+    rooms,
+    user_settings,
+    user_tokens,
+    worker_credentials,
+    yaml_annotations,
+    yamls,
+);
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use ap_lobby::request_id::RequestId;
+use ap_lobby::session::Session;
+use rocket::outcome::Outcome;
+use rocket::route::{self, Handler, Route};
+use rocket::{Data, Request};
+use sentry::{Hub, SentryFutureExt};
+use uuid::Uuid;
+
+/// Runs every handler of `routes` in its own sentry hub, tagged with the current user, route and
+/// room so that errors reported while handling a request can be tied back to what was being done.
+///
+/// The tags can't go on the thread's scope, requests share worker threads and would pick up each
+/// other's tags.
+pub fn with_request_hub(routes: impl Into<Vec<Route>>) -> Vec<Route> {
+    routes
+        .into()
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(RequestHubHandler(route.handler));
+            route
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct RequestHubHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for RequestHubHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let user_id = Session::from_request_sync(req).user_id;
+        let room_id = room_id_from_path(req);
+        let path = req.uri().path().to_string();
+        let request_id = RequestId::from_request_sync(req).to_string();
+        let route = req.route().map(|route| route.uri.to_string());
+
+        let hub = Arc::new(Hub::new_from_top(Hub::current()));
+        hub.configure_scope(|scope| {
+            scope.set_user(user_id.map(|user_id| sentry::User {
+                id: Some(user_id.to_string()),
+                ..Default::default()
+            }));
+            scope.set_tag("path", &path);
+            scope.set_tag("request_id", &request_id);
+            if let Some(route) = &route {
+                scope.set_tag("route", route);
+            }
+            if let Some(room_id) = room_id {
+                scope.set_tag("room_id", room_id);
+            }
+        });
+        hub.add_breadcrumb(sentry::Breadcrumb {
+            category: Some("request".into()),
+            message: Some(format!("{} {}", req.method(), path)),
+            ..Default::default()
+        });
+
+        let outcome = self.0.handle(req, data).bind_hub(hub.clone()).await;
+
+        let status = match &outcome {
+            Outcome::Success(response) => response.status(),
+            Outcome::Error(status) => *status,
+            Outcome::Forward((_, status)) => *status,
+        };
+        hub.add_breadcrumb(sentry::Breadcrumb {
+            category: Some("response".into()),
+            message: Some(format!(
+                "{} -> {}",
+                route.as_deref().unwrap_or(&path),
+                status
+            )),
+            ..Default::default()
+        });
+
+        outcome
+    }
+}
+
+fn room_id_from_path(req: &Request) -> Option<Uuid> {
+    let mut segments = req.uri().path().segments();
+    while let Some(segment) = segments.next() {
+        if segment == "room" || segment == "edit-room" {
+            return segments.next().and_then(|id| Uuid::parse_str(id).ok());
+        }
+    }
+
+    None
+}
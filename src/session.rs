@@ -1,5 +1,5 @@
-use crate::error::Result;
-use anyhow::anyhow;
+use crate::db::UserSettings;
+use crate::error::{Error, Result};
 use base64::Engine;
 use rocket::http::{Cookie, CookieJar, SameSite, Status};
 use rocket::request::{FromRequest, Outcome};
@@ -15,6 +15,9 @@ pub struct Session {
     pub warning_msg: Vec<String>,
     pub user_id: Option<i64>,
     pub redirect_on_login: Option<String>,
+    pub confirmation_token: Option<String>,
+    #[serde(default)]
+    pub settings: UserSettings,
 }
 
 #[derive(serde::Deserialize, Default, Debug)]
@@ -33,6 +36,8 @@ impl From<SessionRecovery> for Session {
             warning_msg: vec![],
             user_id: val.user_id,
             redirect_on_login: None,
+            confirmation_token: None,
+            settings: UserSettings::default(),
         }
     }
 }
@@ -123,6 +128,29 @@ impl Session {
         new_session
     }
 
+    /// Returns the token destructive forms need to send back, generating it if needed.
+    ///
+    /// Pages are compressed, so a secret that's the same on every response could be guessed
+    /// byte by byte from their size (BREACH). The token is masked with a new random pad each time
+    /// it's handed out instead.
+    pub fn confirmation_token(&mut self) -> String {
+        let token = self
+            .confirmation_token
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+
+        mask_token(token)
+    }
+
+    pub fn check_confirmation_token(&self, token: &str) -> Result<()> {
+        if self.confirmation_token.is_none() || unmask_token(token) != self.confirmation_token {
+            return Err(Error::forbidden(
+                "Invalid confirmation token. Reload the page and try again.",
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn save(&self, cookies: &CookieJar) -> Result<()> {
         let serialized = serde_json::to_string(&self).unwrap();
 
@@ -160,7 +188,7 @@ impl<'r> FromRequest<'r> for LoggedInSession {
 
         match new_session.user_id {
             Some(_) => Outcome::Success(LoggedInSession(new_session)),
-            None => Outcome::Error((Status::new(401), anyhow!("Not logged in").into())),
+            None => Outcome::Error((Status::new(401), Error::forbidden("Not logged in"))),
         }
     }
 }
@@ -174,7 +202,7 @@ impl<'r> FromRequest<'r> for AdminSession {
         let Outcome::Success(session) = session else {
             return Outcome::Error((
                 Status::Unauthorized,
-                crate::error::Error(anyhow!("You need to be admin")),
+                crate::error::Error::forbidden("You need to be admin"),
             ));
         };
 
@@ -184,7 +212,64 @@ impl<'r> FromRequest<'r> for AdminSession {
 
         Outcome::Error((
             Status::Unauthorized,
-            crate::error::Error(anyhow!("You need to be admin")),
+            crate::error::Error::forbidden("You need to be admin"),
         ))
     }
 }
+
+/// The pad followed by the token xored with it.
+fn mask_token(token: &str) -> String {
+    let pad = std::iter::repeat_with(|| uuid::Uuid::new_v4().into_bytes())
+        .flatten()
+        .take(token.len())
+        .collect::<Vec<_>>();
+    let masked = pad.iter().zip(token.bytes()).map(|(pad, byte)| pad ^ byte);
+
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(pad.iter().copied().chain(masked).collect::<Vec<_>>())
+}
+
+fn unmask_token(masked: &str) -> Option<String> {
+    let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(masked)
+        .ok()?;
+    if decoded.len() % 2 != 0 {
+        return None;
+    }
+
+    let (pad, masked) = decoded.split_at(decoded.len() / 2);
+    String::from_utf8(
+        pad.iter()
+            .zip(masked)
+            .map(|(pad, byte)| pad ^ byte)
+            .collect(),
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mask_token, unmask_token, Session};
+
+    #[test]
+    fn test_confirmation_token_is_masked() {
+        let mut session = Session::default();
+        let first = session.confirmation_token();
+        let second = session.confirmation_token();
+        assert_ne!(first, second);
+        assert_ne!(Some(&first), session.confirmation_token.as_ref());
+
+        assert!(session.check_confirmation_token(&first).is_ok());
+        assert!(session.check_confirmation_token(&second).is_ok());
+        assert!(session
+            .check_confirmation_token(session.confirmation_token.as_ref().unwrap())
+            .is_err());
+        assert!(session.check_confirmation_token("").is_err());
+        assert!(session
+            .check_confirmation_token(&mask_token("something else"))
+            .is_err());
+
+        assert_eq!(unmask_token(&mask_token("token")).as_deref(), Some("token"));
+        assert!(Session::default().check_confirmation_token(&first).is_err());
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ap_lobby::db::{
+    CheckIn, GuestLink, Json, RoomId, UserSettings, YamlAnnotation, YamlFlag, YamlId,
+    YamlWithoutContent,
+};
+use ap_lobby::error::{ApiResult, Result};
+use ap_lobby::jobs::UnknownReference;
+use ap_lobby::landing_cache::LandingStats;
+use ap_lobby::pending_uploads::PendingUpload;
+use ap_lobby::session::AdminSession;
+use ap_lobby::yaml::NameCollision;
+use askama::Template;
+use rocket::get;
+use rocket::serde::json::Json as JsonResponse;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+
+use super::fixtures::{room_fixture, room_tpl, yaml_fixture, AUTHOR_ID};
+use super::settings::SettingsTpl;
+use super::{IndexFilters, IndexTpl, RoomTpl};
+use crate::TplContext;
+
+// WCAG AA for normal text.
+const MIN_CONTRAST: f64 = 4.5;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct Violation {
+    pub(crate) rule: &'static str,
+    pub(crate) element: String,
+    pub(crate) message: String,
+}
+
+fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).expect("Audit selectors should be valid")
+}
+
+fn describe(element: &ElementRef) -> String {
+    let value = element.value();
+    let mut description = value.name().to_string();
+    if let Some(id) = value.id() {
+        description += &format!("#{}", id);
+    }
+    for class in value.classes() {
+        description += &format!(".{}", class);
+    }
+    if let Some(name) = value.attr("name") {
+        description += &format!("[name={}]", name);
+    }
+
+    description
+}
+
+fn has_text(element: &ElementRef) -> bool {
+    element.text().any(|text| !text.trim().is_empty())
+}
+
+fn has_accessible_name(element: &ElementRef) -> bool {
+    let value = element.value();
+    ["aria-label", "aria-labelledby", "title"]
+        .iter()
+        .any(|attr| value.attr(attr).is_some_and(|v| !v.trim().is_empty()))
+}
+
+/// Checks rendered HTML for the accessibility mistakes that are easy to make in a template:
+/// missing landmarks, form controls without a label, images without alt text, buttons and links
+/// without a name, and color pairs declared through `data-contrast-fg`/`data-contrast-bg` that
+/// aren't readable.
+pub(crate) fn audit(html: &str) -> Vec<Violation> {
+    let document = Html::parse_document(html);
+    let mut violations = vec![];
+    let mut violation = |rule: &'static str, element: &ElementRef, message: &str| {
+        violations.push(Violation {
+            rule,
+            element: describe(element),
+            message: message.to_string(),
+        })
+    };
+
+    for html in document.select(&selector("html")) {
+        if !html
+            .value()
+            .attr("lang")
+            .is_some_and(|lang| !lang.is_empty())
+        {
+            violation(
+                "html-lang",
+                &html,
+                "The document doesn't declare a language",
+            );
+        }
+    }
+
+    let root = document.root_element();
+    match document.select(&selector("main")).count() {
+        0 => violation("landmark-main", &root, "The page has no <main> landmark"),
+        1 => {}
+        _ => violation(
+            "landmark-main",
+            &root,
+            "The page has more than one <main> landmark",
+        ),
+    }
+    if document.select(&selector("nav")).next().is_none() {
+        violation("landmark-nav", &root, "The page has no <nav> landmark");
+    }
+
+    let labelled_ids = document
+        .select(&selector("label[for]"))
+        .filter_map(|label| label.value().attr("for"))
+        .collect::<HashSet<_>>();
+    for control in document.select(&selector("input, select, textarea")) {
+        let value = control.value();
+        if matches!(
+            value.attr("type"),
+            Some("hidden" | "submit" | "button" | "image" | "reset")
+        ) {
+            continue;
+        }
+
+        let labelled_by_for = value.id().is_some_and(|id| labelled_ids.contains(id));
+        let wrapped_in_label = control
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .any(|ancestor| ancestor.value().name() == "label");
+        if !labelled_by_for && !wrapped_in_label && !has_accessible_name(&control) {
+            violation("form-label", &control, "Form control without a label");
+        }
+    }
+
+    for img in document.select(&selector("img")) {
+        if img.value().attr("alt").is_none() {
+            violation("img-alt", &img, "Image without alt text");
+        }
+    }
+
+    for button in document.select(&selector("button")) {
+        if !has_text(&button) && !has_accessible_name(&button) {
+            violation("button-name", &button, "Button without a name");
+        }
+    }
+
+    for link in document.select(&selector("a[href]")) {
+        let has_img_alt = link
+            .select(&selector("img[alt]"))
+            .any(|img| !img.value().attr("alt").unwrap_or("").trim().is_empty());
+        if !has_text(&link) && !has_accessible_name(&link) && !has_img_alt {
+            violation("link-name", &link, "Link without a name");
+        }
+    }
+
+    for element in document.select(&selector("[data-contrast-fg][data-contrast-bg]")) {
+        let value = element.value();
+        let colors = (
+            parse_hex_color(value.attr("data-contrast-fg").unwrap()),
+            parse_hex_color(value.attr("data-contrast-bg").unwrap()),
+        );
+        let (Some(fg), Some(bg)) = colors else {
+            violation("contrast", &element, "Contrast colors should be #rrggbb");
+            continue;
+        };
+
+        let ratio = contrast_ratio(fg, bg);
+        if ratio < MIN_CONTRAST {
+            violation(
+                "contrast",
+                &element,
+                &format!(
+                    "Contrast ratio is {:.2}:1, should be at least {}:1",
+                    ratio, MIN_CONTRAST
+                ),
+            );
+        }
+    }
+
+    violations
+}
+
+fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn relative_luminance(color: [u8; 3]) -> f64 {
+    let [r, g, b] = color.map(|channel| {
+        let channel = channel as f64 / 255.;
+        if channel <= 0.03928 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    });
+
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn contrast_ratio(fg: [u8; 3], bg: [u8; 3]) -> f64 {
+    let (fg, bg) = (relative_luminance(fg), relative_luminance(bg));
+    let (lighter, darker) = if fg > bg { (fg, bg) } else { (bg, fg) };
+
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// Renders the main templates with sample data and audits each of them.
+pub(crate) fn audit_templates() -> Result<BTreeMap<&'static str, Vec<Violation>>> {
+    let base = |user_id: Option<i64>| TplContext {
+        is_admin: false,
+        is_logged_in: user_id.is_some(),
+        cur_module: "audit",
+        user_id,
+        err_msg: vec!["Sample error".to_string()],
+        warning_msg: vec!["Sample warning".to_string()],
+        css_version: "audit",
+        js_version: "audit",
+        confirmation_token: "audit".to_string(),
+        settings: UserSettings::default(),
+    };
+
+    let mut rendered = BTreeMap::new();
+    rendered.insert(
+        "landing",
+        IndexTpl {
+            base: base(None),
+            rooms: vec![],
+            current_page: 1,
+            max_pages: 1,
+            landing_stats: Some(LandingStats::default()),
+            index_filters: IndexFilters::default(),
+        }
+        .render()?,
+    );
+    rendered.insert(
+        "index",
+        IndexTpl {
+            base: base(Some(1)),
+            rooms: vec![room_fixture()],
+            current_page: 1,
+            max_pages: 2,
+            landing_stats: None,
+            index_filters: IndexFilters::default(),
+        }
+        .render()?,
+    );
+    rendered.insert("room", sample_room_tpl(base(Some(1))).render()?);
+    rendered.insert(
+        "room_player",
+        RoomTpl {
+            is_my_room: false,
+            short_link: None,
+            annotations: HashMap::new(),
+            join_code: None,
+            guest_links: vec![],
+            needs_join_code: true,
+            previous_rooms: vec![],
+            pending_upload: None,
+            ..sample_room_tpl(base(Some(2)))
+        }
+        .render()?,
+    );
+    rendered.insert(
+        "settings",
+        SettingsTpl {
+            base: base(Some(1)),
+            settings: UserSettings::default(),
+            tokens: vec![],
+            new_token: None,
+        }
+        .render()?,
+    );
+
+    Ok(rendered
+        .into_iter()
+        .map(|(name, html)| (name, audit(&html)))
+        .collect())
+}
+
+/// The organizer's view of a room, it has the most controls. There's one YAML per flag so that
+/// every badge gets its contrast checked.
+fn sample_room_tpl(base: TplContext<'static>) -> RoomTpl<'static> {
+    let room = room_fixture();
+    let mut yamls = vec![];
+    let mut annotations = HashMap::new();
+    for flag in YamlFlag::ALL {
+        let yaml = YamlWithoutContent {
+            validation_warnings: Json(vec![UnknownReference {
+                game: "A Link to the Past".to_string(),
+                option: "exclude_locations".to_string(),
+                name: "Unknown group".to_string(),
+            }]),
+            ..yaml_fixture(
+                &YamlId::new_v4().to_string(),
+                &format!("Player {}", flag),
+                "A Link to the Past",
+                AUTHOR_ID,
+            )
+        };
+        annotations.insert(
+            yaml.id,
+            YamlAnnotation {
+                flag: Some(flag),
+                notes: "Sample notes".to_string(),
+            },
+        );
+        yamls.push((yaml, "player".to_string()));
+    }
+
+    RoomTpl {
+        player_count: yamls.len(),
+        unique_player_count: 1,
+        unique_game_count: 1,
+        yamls,
+        has_room_url: true,
+        is_my_room: true,
+        short_link: Some("https://lobby.example/r/abc234".to_string()),
+        check_in: Some(CheckIn {
+            opened_at: room.settings.created_at,
+            closed_at: None,
+        }),
+        needs_check_in: true,
+        annotations,
+        join_code: Some("abcd2345".to_string()),
+        guest_links: vec![(
+            GuestLink {
+                code: "abcdefgh23456789".to_string(),
+                room_id: room.id,
+                guest_id: Some(-2),
+                created_at: room.settings.created_at,
+                used_at: Some(room.settings.created_at),
+            },
+            Some("Guest (guest)".to_string()),
+        )],
+        upload_hints: "Only **official** games please".to_string(),
+        previous_rooms: vec![(RoomId::new_v4(), "Last month".to_string())],
+        pending_upload: Some(PendingUpload {
+            documents: vec![],
+            collision: NameCollision {
+                document_index: 0,
+                player_name: "Player1".to_string(),
+                suggestions: vec!["Player1{number}".to_string(), "Player2".to_string()],
+            },
+        }),
+        ..room_tpl(base, room)
+    }
+}
+
+#[get("/admin/a11y")]
+#[tracing::instrument(skip_all)]
+fn audit_report(
+    _admin_session: AdminSession,
+) -> ApiResult<JsonResponse<BTreeMap<&'static str, Vec<Violation>>>> {
+    Ok(JsonResponse(audit_templates()?))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![audit_report]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{audit, contrast_ratio};
+
+    fn page(body: &str) -> String {
+        format!(
+            r#"<!DOCTYPE html><html lang="en"><body><nav></nav><main>{}</main></body></html>"#,
+            body
+        )
+    }
+
+    fn rules(html: &str) -> Vec<&'static str> {
+        audit(html).into_iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn test_clean_page() {
+        let html = page(
+            r##"<label for="name">Name</label><input id="name" type="text">
+            <label><input type="checkbox"> Wrapped</label>
+            <input type="hidden" name="token">
+            <select aria-label="Flag"></select>
+            <img src="a.png" alt="">
+            <button>Save</button>
+            <a href="/">Home</a>
+            <span data-contrast-fg="#ffffff" data-contrast-bg="#000000">ok</span>"##,
+        );
+        assert_eq!(audit(&html), vec![]);
+    }
+
+    #[test]
+    fn test_landmarks() {
+        assert_eq!(
+            rules("<html><body></body></html>"),
+            vec!["html-lang", "landmark-main", "landmark-nav"]
+        );
+    }
+
+    #[test]
+    fn test_violations() {
+        let html = page(
+            r##"<input id="orphan" type="text"><textarea name="notes"></textarea>
+            <img src="a.png"><button><i class="fa fa-x"></i></button>
+            <a href="#"><i class="fa fa-github"></i></a>
+            <span data-contrast-fg="#777777" data-contrast-bg="#888888">meh</span>"##,
+        );
+        assert_eq!(
+            rules(&html),
+            vec![
+                "form-label",
+                "form-label",
+                "img-alt",
+                "button-name",
+                "link-name",
+                "contrast"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        assert!((contrast_ratio([0, 0, 0], [255, 255, 255]) - 21.).abs() < 0.01);
+        assert!((contrast_ratio([255, 255, 255], [255, 255, 255]) - 1.).abs() < 0.01);
+    }
+}
@@ -0,0 +1,397 @@
+use ap_lobby::abuse::discord_account_created_at;
+use ap_lobby::db::instrumentation::{slowest_queries, SlowQuery};
+use ap_lobby::db::{
+    self, AbuseSignal, AbuseSignalId, Delivery, DeliveryId, JobRecord, RoomId, YamlSearchResult,
+};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::index_manager::IndexManager;
+use ap_lobby::session::{AdminSession, Session};
+use apwm::{Manifest, WorldOrigin};
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::{CookieJar, RawStr};
+use rocket::response::Redirect;
+use rocket::{get, post, State};
+use semver::Version;
+
+use super::ConfirmationForm;
+use crate::{Context, TplContext};
+
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+struct AdminDashboardTpl<'a> {
+    base: TplContext<'a>,
+    slow_queries: Vec<SlowQuery>,
+}
+
+#[get("/admin")]
+#[tracing::instrument(skip_all)]
+async fn dashboard<'a>(
+    _admin_session: AdminSession,
+    session: Session,
+    cookies: &CookieJar<'a>,
+) -> Result<AdminDashboardTpl<'a>> {
+    Ok(AdminDashboardTpl {
+        base: TplContext::from_session("admin", session, cookies),
+        slow_queries: slowest_queries(),
+    })
+}
+
+struct PendingSignal {
+    signal: AbuseSignal,
+    username: String,
+    account_created_at: String,
+    is_limited: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin/abuse.html")]
+struct AbuseSignalsTpl<'a> {
+    base: TplContext<'a>,
+    signals: Vec<PendingSignal>,
+}
+
+#[get("/admin/abuse")]
+#[tracing::instrument(skip_all)]
+async fn abuse_signals<'a>(
+    _admin_session: AdminSession,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<AbuseSignalsTpl<'a>> {
+    let mut conn = ctx.db_conn().await?;
+    let mut signals = vec![];
+    for (signal, username) in db::list_pending_abuse_signals(&mut conn).await? {
+        signals.push(PendingSignal {
+            account_created_at: discord_account_created_at(signal.user_id)
+                .map(|created_at| created_at.naive_utc().to_string())
+                .unwrap_or_default(),
+            is_limited: ctx.abuse.is_limited(signal.user_id).await,
+            signal,
+            username,
+        });
+    }
+
+    Ok(AbuseSignalsTpl {
+        base: TplContext::from_session("admin", session, cookies),
+        signals,
+    })
+}
+
+#[post("/admin/abuse/<signal_id>/dismiss", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, _admin_session, session, confirmation, ctx))]
+async fn dismiss_abuse_signal(
+    redirect_to: &RedirectTo,
+    signal_id: AbuseSignalId,
+    _admin_session: AdminSession,
+    session: Session,
+    confirmation: Form<ConfirmationForm<'_>>,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/admin/abuse");
+    confirmation.check(&session)?;
+
+    let mut conn = ctx.db_conn().await?;
+    db::mark_abuse_signal_reviewed(signal_id, &mut conn).await?;
+
+    Ok(Redirect::to("/admin/abuse"))
+}
+
+/// Lifts the automatic limit of the user the signal is about, which also marks it reviewed.
+#[post("/admin/abuse/<signal_id>/lift-limit", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, _admin_session, session, confirmation, ctx))]
+async fn lift_abuse_limit(
+    redirect_to: &RedirectTo,
+    signal_id: AbuseSignalId,
+    _admin_session: AdminSession,
+    session: Session,
+    confirmation: Form<ConfirmationForm<'_>>,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/admin/abuse");
+    confirmation.check(&session)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let signal = db::get_abuse_signal(signal_id, &mut conn).await?;
+    ctx.abuse.lift_limit(signal.user_id).await;
+    db::mark_abuse_signal_reviewed(signal_id, &mut conn).await?;
+
+    Ok(Redirect::to("/admin/abuse"))
+}
+
+#[derive(Template)]
+#[template(path = "admin/jobs.html")]
+struct JobsTpl<'a> {
+    base: TplContext<'a>,
+    room_id: Option<RoomId>,
+    jobs: Vec<(JobRecord, Option<String>)>,
+}
+
+#[get("/admin/jobs?<room>")]
+#[tracing::instrument(skip(_admin_session, session, cookies, ctx))]
+async fn jobs<'a>(
+    room: Option<RoomId>,
+    _admin_session: AdminSession,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<JobsTpl<'a>> {
+    let mut conn = ctx.db_conn().await?;
+    let jobs = db::list_jobs(room, &mut conn).await?;
+
+    Ok(JobsTpl {
+        base: TplContext::from_session("admin", session, cookies),
+        room_id: room,
+        jobs,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "admin/deliveries.html")]
+struct DeliveriesTpl<'a> {
+    base: TplContext<'a>,
+    deliveries: Vec<Delivery>,
+}
+
+impl DeliveriesTpl<'_> {
+    /// Webhook URLs carry their token, only show where they go.
+    fn target_host<'b>(&self, delivery: &'b Delivery) -> &'b str {
+        let target = delivery
+            .target
+            .split_once("://")
+            .map_or(delivery.target.as_str(), |(_, rest)| rest);
+        target.split('/').next().unwrap_or_default()
+    }
+}
+
+#[get("/admin/deliveries")]
+#[tracing::instrument(skip_all)]
+async fn deliveries<'a>(
+    _admin_session: AdminSession,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<DeliveriesTpl<'a>> {
+    let mut conn = ctx.db_conn().await?;
+    let deliveries = db::list_dead_deliveries(&mut conn).await?;
+
+    Ok(DeliveriesTpl {
+        base: TplContext::from_session("admin", session, cookies),
+        deliveries,
+    })
+}
+
+#[post("/admin/deliveries/<delivery_id>/retry", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, _admin_session, session, confirmation, ctx))]
+async fn retry_delivery(
+    redirect_to: &RedirectTo,
+    delivery_id: DeliveryId,
+    _admin_session: AdminSession,
+    session: Session,
+    confirmation: Form<ConfirmationForm<'_>>,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/admin/deliveries");
+    confirmation.check(&session)?;
+
+    let mut conn = ctx.db_conn().await?;
+    db::retry_dead_delivery(delivery_id, &mut conn).await?;
+
+    Ok(Redirect::to("/admin/deliveries"))
+}
+
+#[post("/admin/deliveries/<delivery_id>/delete", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, _admin_session, session, confirmation, ctx))]
+async fn delete_delivery(
+    redirect_to: &RedirectTo,
+    delivery_id: DeliveryId,
+    _admin_session: AdminSession,
+    session: Session,
+    confirmation: Form<ConfirmationForm<'_>>,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/admin/deliveries");
+    confirmation.check(&session)?;
+
+    let mut conn = ctx.db_conn().await?;
+    db::delete_dead_delivery(delivery_id, &mut conn).await?;
+
+    Ok(Redirect::to("/admin/deliveries"))
+}
+
+#[derive(Template)]
+#[template(path = "admin/yaml_search.html")]
+pub(crate) struct YamlSearchTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) query: String,
+    pub(crate) results: Vec<YamlSearchResult>,
+    pub(crate) current_page: u64,
+    pub(crate) max_pages: u64,
+}
+
+impl YamlSearchTpl<'_> {
+    fn page_url(&self, page: u64) -> String {
+        format!(
+            "?q={}&page={}",
+            RawStr::new(&self.query).percent_encode(),
+            page
+        )
+    }
+}
+
+/// Looks for some text in the content of every YAML on the instance, in any room.
+#[get("/admin/yamls/search?<q>&<page>")]
+#[tracing::instrument(skip(redirect_to, _admin_session, session, cookies, ctx))]
+async fn search_yamls<'a>(
+    redirect_to: &RedirectTo,
+    q: Option<String>,
+    page: Option<u64>,
+    _admin_session: AdminSession,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<YamlSearchTpl<'a>> {
+    redirect_to.set("/admin/yamls/search");
+
+    let query = q.unwrap_or_default();
+    let current_page = page.unwrap_or(1).max(1);
+    let (results, max_pages) = if query.trim().is_empty() {
+        (vec![], 1)
+    } else {
+        let mut conn = ctx.db_conn().await?;
+        db::search_yaml_contents(&query, current_page, &mut conn).await?
+    };
+
+    Ok(YamlSearchTpl {
+        base: TplContext::from_session("admin", session, cookies),
+        query,
+        results,
+        current_page,
+        max_pages: max_pages.max(1),
+    })
+}
+
+pub(crate) struct AffectedRoom {
+    pub(crate) room_id: RoomId,
+    pub(crate) name: String,
+    pub(crate) version: Version,
+    pub(crate) allow_unsupported: bool,
+    pub(crate) yamls: usize,
+}
+
+#[derive(Template)]
+#[template(path = "admin/world_impact.html")]
+pub(crate) struct WorldImpactTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) world_name: String,
+    pub(crate) display_name: String,
+    pub(crate) supported: bool,
+    pub(crate) rooms: Vec<AffectedRoom>,
+}
+
+/// Open rooms and YAMLs that would be affected by changing the support status of an apworld.
+/// The status itself comes from the apworld index, changing it happens there.
+#[get("/admin/worlds/impact?<world_name>")]
+#[tracing::instrument(skip(_admin_session, session, cookies, index_manager, ctx))]
+async fn world_impact<'a>(
+    world_name: &str,
+    _admin_session: AdminSession,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    index_manager: &State<IndexManager>,
+    ctx: &State<Context>,
+) -> Result<WorldImpactTpl<'a>> {
+    let index = index_manager.index.read().await.clone();
+    let latest = Manifest::from_index_with_latest_versions(&index)?;
+    let (latest_worlds, _) = latest.resolve_with(&index);
+    let Some((world, latest_version)) = latest_worlds
+        .into_iter()
+        .find_map(|(name, resolved)| (name == world_name).then_some(resolved))
+    else {
+        return Err(Error::not_found("This apworld doesn't exist in the index"));
+    };
+    let supported = world.supported
+        && matches!(
+            world.get_version(&latest_version).unwrap(),
+            WorldOrigin::Supported
+        );
+
+    let mut conn = ctx.db_conn().await?;
+    let open_rooms = db::get_open_rooms(&mut conn).await?;
+    let games = db::get_games_in_open_rooms(&mut conn).await?;
+
+    let mut rooms = vec![];
+    for room in open_rooms {
+        let manifest = &room.settings.manifest;
+        let (worlds, _) = manifest.resolve_with(&index);
+        let Some((room_world, version)) = worlds
+            .into_iter()
+            .find_map(|(name, resolved)| (name == world_name).then_some(resolved))
+        else {
+            continue;
+        };
+
+        let yamls = games
+            .iter()
+            .filter(|(room_id, game)| {
+                *room_id == room.id
+                    && manifest
+                        .resolve_from_game_name(game, &index)
+                        .is_ok_and(|(world, _)| world.path == room_world.path)
+            })
+            .count();
+
+        rooms.push(AffectedRoom {
+            room_id: room.id,
+            name: room.settings.name,
+            version,
+            allow_unsupported: room.settings.allow_unsupported,
+            yamls,
+        });
+    }
+
+    Ok(WorldImpactTpl {
+        base: TplContext::from_session("admin", session, cookies),
+        world_name: world_name.to_string(),
+        display_name: world.display_name,
+        supported,
+        rooms,
+    })
+}
+
+/// Pulls the index once the status change landed there, then goes back to the impact page so the
+/// new status can be checked against the affected rooms.
+#[post("/admin/worlds/<world_name>/refresh", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, _admin_session, session, confirmation, index_manager))]
+async fn refresh_world_index(
+    redirect_to: &RedirectTo,
+    world_name: &str,
+    _admin_session: AdminSession,
+    session: Session,
+    confirmation: Form<ConfirmationForm<'_>>,
+    index_manager: &State<IndexManager>,
+) -> Result<Redirect> {
+    let impact_url = format!("/admin/worlds/impact?world_name={}", world_name);
+    redirect_to.set(&impact_url);
+    confirmation.check(&session)?;
+
+    index_manager.update().await?;
+
+    Ok(Redirect::to(impact_url))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        dashboard,
+        abuse_signals,
+        dismiss_abuse_signal,
+        lift_abuse_limit,
+        jobs,
+        deliveries,
+        retry_delivery,
+        delete_delivery,
+        search_yamls,
+        world_impact,
+        refresh_world_index
+    ]
+}
@@ -1,27 +1,59 @@
+use std::convert::Infallible;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection};
 use http::header::CONTENT_DISPOSITION;
 use rocket::{
-    get,
-    http::{Header, Status},
-    routes, State,
+    delete, get,
+    http::{uri::Absolute, CookieJar, Header, Status},
+    patch, post,
+    request::{FromRequest, Outcome},
+    routes,
+    serde::json::Json,
+    Request, State,
 };
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::views::og::PublicOrigin;
+use crate::views::user_tokens::ApiSession;
 use crate::views::YamlContent;
+use crate::views::{get_my_room, sees_unredacted_yaml};
 use crate::Context;
 use ap_lobby::{
-    db::{self, RoomId, YamlId},
-    error::{ApiResult, WithContext, WithStatus},
+    body_limit::{ContentLength, ROOM_IMPORT, ROOM_SETTINGS, YAML_UPLOAD},
+    db::{
+        self, Author, NewRoom, Room, RoomFilter, RoomId, RoomSettings, RoomSettingsSnapshot,
+        RoomTemplateId, UserTokenScope, WithYaml, YamlId,
+    },
+    deadline::Deadline,
+    error::{ApiResult, Error, Result, WithContext, WithStatus},
+    index_manager::IndexManager,
+    interchange::{
+        owner_pseudonym, InterchangeKey, InterchangeYaml, RoomInterchange, SignedRoomInterchange,
+        INTERCHANGE_VERSION,
+    },
+    jobs::YamlValidationQueue,
+    redaction::RedactedOptions,
+    session::{LoggedInSession, Session},
+    yaml::GameOptions,
 };
 
-#[get("/room/<room_id>/download/<yaml_id>")]
-#[tracing::instrument(skip(ctx))]
+/// Redacted options are masked for everyone but the YAML's owner, the room's organizer and
+/// admins. With `original`, the YAML is downloaded as it was uploaded rather than tidied.
+#[get("/room/<room_id>/download/<yaml_id>?<original>")]
+#[tracing::instrument(skip(session, redacted_options, ctx))]
 pub(crate) async fn download_yaml<'a>(
     room_id: RoomId,
     yaml_id: YamlId,
+    original: Option<bool>,
+    session: Session,
+    redacted_options: &State<RedactedOptions>,
     ctx: &State<Context>,
 ) -> ApiResult<YamlContent<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
 
-    let _room = db::get_room(room_id, &mut conn)
+    let room = db::get_room(room_id, &mut conn)
         .await
         .context("Couldn't find the room")
         .status(Status::NotFound)?;
@@ -30,15 +62,845 @@ pub(crate) async fn download_yaml<'a>(
         .await
         .context("Couldn't find the YAML file")
         .status(Status::NotFound)?;
+    // The organizer of one room mustn't see the YAMLs of another one unredacted.
+    if yaml.room_id != room_id {
+        return Err(Error::not_found("Couldn't find the YAML file").into());
+    }
 
     let value = format!("attachment; filename=\"{}.yaml\"", yaml.sanitized_name());
+    let content = match yaml.original_content {
+        Some(original_content) if original.unwrap_or(false) => original_content,
+        _ => yaml.content,
+    };
+    let content = if sees_unredacted_yaml(&session, &room, yaml.owner_id) {
+        content
+    } else {
+        redacted_options.redact(&content)
+    };
 
     Ok(YamlContent {
-        content: yaml.content,
+        content,
         headers: Header::new(CONTENT_DISPOSITION.as_str(), value),
     })
 }
 
+/// What each option of the YAML is set to, for the YAML viewer.
+#[get("/room/<room_id>/yaml/<yaml_id>/options")]
+#[tracing::instrument(skip(session, redacted_options, ctx))]
+async fn yaml_options(
+    room_id: RoomId,
+    yaml_id: YamlId,
+    session: Session,
+    redacted_options: &State<RedactedOptions>,
+    ctx: &State<Context>,
+) -> ApiResult<Json<Vec<GameOptions>>> {
+    let mut conn = ctx.db_conn().await?;
+
+    let yaml = db::get_yaml_by_id(yaml_id, &mut conn)
+        .await
+        .context("Couldn't find the YAML file")
+        .status(Status::NotFound)?;
+    if yaml.room_id != room_id {
+        return Err(Error::not_found("Couldn't find the YAML file").into());
+    }
+
+    let room = db::get_room(room_id, &mut conn).await?;
+    if sees_unredacted_yaml(&session, &room, yaml.owner_id) {
+        return Ok(Json(ap_lobby::yaml::option_summary(&yaml.content)?));
+    }
+
+    Ok(Json(ap_lobby::yaml::option_summary(
+        &redacted_options.redact(&yaml.content),
+    )?))
+}
+
+#[derive(Serialize)]
+struct RoomStateSettings {
+    name: String,
+    description: String,
+    close_date: NaiveDateTime,
+    is_closed: bool,
+    updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct RoomStateYaml {
+    id: String,
+    player_name: String,
+    game: String,
+    checked_in_at: Option<NaiveDateTime>,
+    excluded: bool,
+    waitlisted: bool,
+}
+
+#[derive(Serialize)]
+struct RoomState {
+    /// Pass it back as `since` to only get what changed afterwards. It trails the database's
+    /// clock a bit so that writes committed late aren't missed, the same YAML can come back twice.
+    cursor: NaiveDateTime,
+    /// Left out when the settings didn't change since the cursor.
+    room: Option<RoomStateSettings>,
+    /// Markdown the organizer wants players to read before uploading. Always sent.
+    upload_hints: String,
+    /// YAMLs added or changed since the cursor.
+    yamls: Vec<RoomStateYaml>,
+    /// Every YAML currently in the room, the ones that aren't there anymore were deleted.
+    yaml_ids: Vec<String>,
+}
+
+const ROOM_STATE_CURSOR_OVERLAP: TimeDelta = TimeDelta::seconds(10);
+
+/// A room's settings and YAMLs. With `since`, only what changed after that cursor is returned so
+/// that trackers polling big rooms don't download everything every time.
+#[get("/room/<room_id>?<since>")]
+#[tracing::instrument(skip(ctx))]
+async fn room_state(
+    room_id: RoomId,
+    since: Option<&str>,
+    ctx: &State<Context>,
+) -> ApiResult<Json<RoomState>> {
+    let since = since
+        .map(|since| since.parse::<NaiveDateTime>())
+        .transpose()
+        .map_err(|_| Error::validation("`since` should be a cursor returned by this endpoint"))?;
+
+    let mut conn = ctx.db_conn().await?;
+    // One snapshot for everything, with the cursor taken from the same clock that sets
+    // `updated_at`.
+    let (cursor, room, yamls, yaml_ids) = conn
+        .build_transaction()
+        .repeatable_read()
+        .read_only()
+        .run::<_, Error, _>(|conn| {
+            async move {
+                let now = db::current_timestamp(conn).await?;
+                let room = db::get_room(room_id, conn)
+                    .await
+                    .context("Couldn't find the room")?;
+                let yamls = db::get_yamls_for_room_updated_since(room_id, since, conn).await?;
+                let yaml_ids = db::get_yaml_ids_for_room(room_id, conn).await?;
+
+                // A write that started before `now` but committed after our snapshot has an
+                // older `updated_at` than the cursor, overlap the window to still catch it.
+                Ok((now - ROOM_STATE_CURSOR_OVERLAP, room, yamls, yaml_ids))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    let upload_hints = room.settings.upload_hints.clone();
+
+    let room_changed = since.is_none_or(|since| room.settings.updated_at > since);
+    Ok(Json(RoomState {
+        cursor,
+        room: room_changed.then(|| RoomStateSettings {
+            is_closed: room.is_closed(),
+            name: room.settings.name,
+            description: room.settings.description,
+            close_date: room.settings.close_date,
+            updated_at: room.settings.updated_at,
+        }),
+        upload_hints,
+        yamls: yamls
+            .into_iter()
+            .map(|yaml| RoomStateYaml {
+                id: yaml.id.to_string(),
+                player_name: yaml.player_name,
+                game: yaml.game,
+                checked_in_at: yaml.checked_in_at,
+                excluded: yaml.excluded,
+                waitlisted: yaml.waitlisted,
+            })
+            .collect(),
+        yaml_ids: yaml_ids.iter().map(ToString::to_string).collect(),
+    }))
+}
+
+fn check_room_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::validation("The room name shouldn't be empty"));
+    }
+    if name.len() > 200 {
+        return Err(Error::validation(
+            "The room name shouldn't exceed 200 characters",
+        ));
+    }
+
+    Ok(name)
+}
+
+/// Settings to override when creating a room from a template or another room, everything else
+/// is copied over.
+#[derive(Deserialize)]
+struct NewRoomRequest {
+    name: Option<String>,
+    close_date: DateTime<Utc>,
+    yaml_limit_per_user: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct CreatedRoomResponse {
+    id: String,
+    url: String,
+}
+
+impl CreatedRoomResponse {
+    fn new(room: &Room, public_origin: &PublicOrigin) -> Self {
+        Self {
+            id: room.id.to_string(),
+            url: format!("{}/room/{}", public_origin.0, room.id),
+        }
+    }
+}
+
+/// Creates a room with `settings`, overridden by what's in `request`. The room URL is never
+/// copied, it belongs to the source room's generation.
+async fn create_room_from(
+    settings: &RoomSettings,
+    from_template_id: Option<RoomTemplateId>,
+    request: &NewRoomRequest,
+    session: &LoggedInSession,
+    ctx: &Context,
+    conn: &mut AsyncPgConnection,
+) -> Result<Room> {
+    let name = check_room_name(request.name.as_deref().unwrap_or(&settings.name))?;
+    if request.close_date <= Utc::now() {
+        return Err(Error::validation("The close date should be in the future"));
+    }
+    if request.yaml_limit_per_user.is_some_and(|limit| limit <= 0) {
+        return Err(Error::validation(
+            "The per player YAML limit should be greater or equal to 1",
+        ));
+    }
+
+    // The admin API key doesn't come with a user, the room stays with the source's author.
+    let author_id = session.0.user_id.unwrap_or(settings.author_id);
+    let new_room = NewRoom {
+        name,
+        close_date: request.close_date.naive_utc(),
+        room_url: "",
+        yaml_limit_per_user: request.yaml_limit_per_user.or(settings.yaml_limit_per_user),
+        from_template_id: Some(from_template_id),
+        ..settings.as_new_room(RoomId::new_v4(), Some(author_id))
+    };
+
+    let room = db::create_room(&new_room, conn).await?;
+    ctx.landing_cache.invalidate().await;
+
+    Ok(room)
+}
+
+#[post("/room-templates/<tpl_id>/rooms", data = "<request>")]
+#[tracing::instrument(skip(request, session, ctx, public_origin))]
+async fn create_room_from_template(
+    tpl_id: RoomTemplateId,
+    request: Json<NewRoomRequest>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<CreatedRoomResponse>> {
+    let mut conn = ctx.db_conn().await?;
+    let tpl = db::get_room_template_by_id(tpl_id, &mut conn)
+        .await
+        .context("Couldn't find the template")
+        .status(Status::NotFound)?;
+    let can_use =
+        session.0.is_admin || tpl.global || session.0.user_id == Some(tpl.settings.author_id);
+    if !can_use {
+        return Err(Error::not_found("Couldn't find the template").into());
+    }
+
+    let room = create_room_from(
+        &tpl.settings,
+        Some(tpl.id),
+        &request,
+        &session,
+        ctx,
+        &mut conn,
+    )
+    .await?;
+
+    Ok(Json(CreatedRoomResponse::new(&room, &public_origin)))
+}
+
+#[post("/rooms/<room_id>/clone", data = "<request>")]
+#[tracing::instrument(skip(request, session, ctx, public_origin))]
+async fn clone_room(
+    room_id: RoomId,
+    request: Json<NewRoomRequest>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<CreatedRoomResponse>> {
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Couldn't find the room")
+        .status(Status::NotFound)?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden("You can only clone your own rooms").into());
+    }
+    let has_join_code = db::get_room_join_code(room_id, &mut conn).await?.is_some();
+
+    let room = create_room_from(
+        &room.settings,
+        room.from_template_id,
+        &request,
+        &session,
+        ctx,
+        &mut conn,
+    )
+    .await?;
+    // The invite code is a secret of the source room, the clone gets its own one.
+    if has_join_code {
+        db::regenerate_room_join_code(room.id, &mut conn).await?;
+    }
+
+    Ok(Json(CreatedRoomResponse::new(&room, &public_origin)))
+}
+
+/// Tells a missing field apart from an explicit `null`, which clears the setting.
+fn nullable<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<T>>, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Room settings sent to the lifecycle endpoints. Fields that are left out keep their current
+/// value, or the same default as the room creation form.
+#[derive(Deserialize)]
+struct RoomSettingsRequest {
+    name: Option<String>,
+    description: Option<String>,
+    close_date: Option<DateTime<Utc>>,
+    room_url: Option<String>,
+    yaml_validation: Option<bool>,
+    allow_unsupported: Option<bool>,
+    #[serde(default, deserialize_with = "nullable")]
+    yaml_limit_per_user: Option<Option<i32>>,
+    show_apworlds: Option<bool>,
+}
+
+impl RoomSettingsRequest {
+    fn apply(&self, settings: &mut RoomSettings) -> Result<()> {
+        if let Some(name) = &self.name {
+            settings.name = check_room_name(name)?.to_string();
+        }
+        if let Some(description) = &self.description {
+            settings.description = description.trim().to_string();
+        }
+        if let Some(close_date) = self.close_date {
+            settings.close_date = close_date.naive_utc();
+        }
+        if let Some(room_url) = &self.room_url {
+            let room_url = room_url.trim();
+            if !room_url.is_empty() {
+                if let Err(e) = http::uri::Uri::parse::<Absolute>(room_url) {
+                    return Err(Error::Validation(anyhow::anyhow!(
+                        "Error while parsing room URL: {}",
+                        e
+                    )));
+                }
+            }
+            settings.room_url = room_url.to_string();
+        }
+        if let Some(yaml_validation) = self.yaml_validation {
+            settings.yaml_validation = yaml_validation;
+        }
+        if let Some(allow_unsupported) = self.allow_unsupported {
+            settings.allow_unsupported = allow_unsupported;
+        }
+        if let Some(yaml_limit_per_user) = self.yaml_limit_per_user {
+            if yaml_limit_per_user.is_some_and(|limit| limit <= 0) {
+                return Err(Error::validation(
+                    "The per player YAML limit should be greater or equal to 1",
+                ));
+            }
+            settings.yaml_limit_per_user = yaml_limit_per_user;
+        }
+        if let Some(show_apworlds) = self.show_apworlds {
+            settings.show_apworlds = show_apworlds;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct RoomResponse {
+    id: String,
+    url: String,
+    name: String,
+    description: String,
+    close_date: NaiveDateTime,
+    is_closed: bool,
+    room_url: String,
+    author_id: i64,
+    yaml_validation: bool,
+    allow_unsupported: bool,
+    yaml_limit_per_user: Option<i32>,
+    show_apworlds: bool,
+}
+
+impl RoomResponse {
+    fn new(room: Room, public_origin: &PublicOrigin) -> Self {
+        Self {
+            id: room.id.to_string(),
+            url: format!("{}/room/{}", public_origin.0, room.id),
+            is_closed: room.is_closed(),
+            name: room.settings.name,
+            description: room.settings.description,
+            close_date: room.settings.close_date,
+            room_url: room.settings.room_url,
+            author_id: room.settings.author_id,
+            yaml_validation: room.settings.yaml_validation,
+            allow_unsupported: room.settings.allow_unsupported,
+            yaml_limit_per_user: room.settings.yaml_limit_per_user,
+            show_apworlds: room.settings.show_apworlds,
+        }
+    }
+}
+
+/// The `Idempotency-Key` header, so that retrying a request doesn't do the same thing twice.
+struct IdempotencyKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = request.headers().get_one("Idempotency-Key");
+        Outcome::Success(IdempotencyKey(key.map(str::to_string)))
+    }
+}
+
+/// Creates a room with every game of the index on its latest version, like the creation form
+/// does. The admin API key doesn't come with a user, it has to say who the room belongs to
+/// with `author_id`.
+///
+/// Requests sent again with the same `Idempotency-Key` return the room the first one created.
+#[post("/rooms?<author_id>", data = "<request>")]
+#[tracing::instrument(skip(
+    content_length,
+    idempotency_key,
+    request,
+    session,
+    ctx,
+    index_manager,
+    public_origin
+))]
+async fn create_room(
+    author_id: Option<i64>,
+    idempotency_key: IdempotencyKey,
+    content_length: ContentLength,
+    request: Json<RoomSettingsRequest>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+    index_manager: &State<IndexManager>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<RoomResponse>> {
+    content_length.check(&ROOM_SETTINGS)?;
+    let author_id = match (session.0.user_id, author_id) {
+        (_, Some(author_id)) if session.0.is_admin => author_id,
+        (_, Some(_)) => {
+            return Err(Error::forbidden("Only admins can create rooms for someone else").into())
+        }
+        (Some(user_id), None) => user_id,
+        (None, None) => {
+            return Err(Error::validation("Pass the `author_id` the room belongs to").into())
+        }
+    };
+    if request.name.is_none() {
+        return Err(Error::validation("The room needs a name").into());
+    }
+    if request
+        .close_date
+        .is_none_or(|close_date| close_date <= Utc::now())
+    {
+        return Err(Error::validation("The close date should be in the future").into());
+    }
+
+    let mut settings = {
+        let index = index_manager.index.read().await;
+        RoomSettings::default(&index)?
+    };
+    request.apply(&mut settings)?;
+
+    let key = idempotency_key.0.as_deref();
+    if let Some(key) = key {
+        db::check_idempotency_key(key)?;
+    }
+
+    let mut conn = ctx.db_conn().await?;
+    let new_room = settings.as_new_room(RoomId::new_v4(), Some(author_id));
+    let created = conn
+        .transaction::<Room, Error, _>(|conn| {
+            async move {
+                if let Some(key) = key {
+                    if let Some(room) =
+                        db::get_room_for_idempotency_key(author_id, key, conn).await?
+                    {
+                        return Ok(room);
+                    }
+                }
+                let room = db::create_room(&new_room, conn).await?;
+                if let Some(key) = key {
+                    db::record_idempotency_key(author_id, key, room.id, conn).await?;
+                }
+                Ok(room)
+            }
+            .scope_boxed()
+        })
+        .await;
+    let room = match (created, key) {
+        // A retry with the same key raced us and committed first, our room was rolled back.
+        (Err(Error::Conflict(e)), Some(key)) => {
+            match db::get_room_for_idempotency_key(author_id, key, &mut conn).await? {
+                Some(room) => room,
+                None => return Err(Error::Conflict(e).into()),
+            }
+        }
+        (created, _) => created?,
+    };
+    ctx.landing_cache.invalidate().await;
+
+    Ok(Json(RoomResponse::new(room, &public_origin)))
+}
+
+/// Changes the room's settings, only the fields that are sent. `yaml_limit_per_user: null`
+/// removes the limit.
+#[patch("/rooms/<room_id>", data = "<request>")]
+#[tracing::instrument(skip(content_length, request, session, ctx, public_origin))]
+async fn update_room(
+    room_id: RoomId,
+    content_length: ContentLength,
+    request: Json<RoomSettingsRequest>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<RoomResponse>> {
+    content_length.check(&ROOM_SETTINGS)?;
+    let mut conn = ctx.db_conn().await?;
+    let previous = get_my_room(room_id, &session.0, &mut conn).await?;
+    let mut room = previous.clone();
+    request.apply(&mut room.settings)?;
+
+    // No author skips updating that field.
+    db::update_room_with_revision(
+        &previous,
+        &room.settings.as_new_room(room_id, None),
+        session.0.user_id,
+        &mut conn,
+    )
+    .await?;
+    ctx.landing_cache.invalidate().await;
+
+    let room = db::get_room(room_id, &mut conn).await?;
+    Ok(Json(RoomResponse::new(room, &public_origin)))
+}
+
+#[delete("/rooms/<room_id>")]
+#[tracing::instrument(skip(session, ctx))]
+async fn delete_room(
+    room_id: RoomId,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> ApiResult<()> {
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::delete_room(room_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct MyRoomsResponse {
+    rooms: Vec<RoomResponse>,
+    max_pages: u64,
+}
+
+/// The rooms the user organizes or has YAMLs in, latest close date first.
+#[get("/me/rooms?<page>")]
+#[tracing::instrument(skip(session, ctx, public_origin))]
+async fn my_rooms(
+    page: Option<u64>,
+    session: ApiSession,
+    ctx: &State<Context>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<MyRoomsResponse>> {
+    let session = session.require(UserTokenScope::ReadOwnRooms)?;
+    let Some(user_id) = session.0.user_id else {
+        return Err(Error::validation("The admin API key doesn't have rooms of its own").into());
+    };
+
+    let mut conn = ctx.db_conn().await?;
+    let filter = RoomFilter::default()
+        .with_author(Author::User(user_id))
+        .with_yamls_from(WithYaml::AndFor(user_id));
+    let (rooms, max_pages) = db::list_rooms(filter, page.unwrap_or(1), &mut conn).await?;
+
+    Ok(Json(MyRoomsResponse {
+        rooms: rooms
+            .into_iter()
+            .map(|room| RoomResponse::new(room, &public_origin))
+            .collect(),
+        max_pages,
+    }))
+}
+
+/// Exports the room and its YAMLs, signed so that another lobby sharing the interchange key can
+/// import it.
+#[get("/rooms/<room_id>/export")]
+#[tracing::instrument(skip(session, interchange_key, index_manager, ctx, public_origin))]
+async fn export_room(
+    room_id: RoomId,
+    session: LoggedInSession,
+    interchange_key: &State<InterchangeKey>,
+    index_manager: &State<IndexManager>,
+    ctx: &State<Context>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<SignedRoomInterchange>> {
+    let key = interchange_key.get()?;
+    let mut conn = ctx.db_conn().await?;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+
+    let worlds = {
+        let index = index_manager.index.read().await;
+        let (worlds, _) = room.settings.manifest.resolve_with(&index);
+        worlds
+            .into_iter()
+            .map(|(world_name, (_, version))| (world_name.to_string(), version.to_string()))
+            .collect()
+    };
+    let yamls = db::get_yamls_for_room(room_id, &mut conn)
+        .await?
+        .into_iter()
+        .filter(|yaml| !yaml.excluded)
+        .map(|yaml| InterchangeYaml {
+            owner: owner_pseudonym(key, yaml.owner_id),
+            player_name: yaml.player_name,
+            content: yaml.content,
+        })
+        .collect();
+
+    let interchange = RoomInterchange {
+        version: INTERCHANGE_VERSION,
+        origin: format!("{}/room/{}", public_origin.0, room_id),
+        exported_at: Utc::now().naive_utc(),
+        settings: RoomSettingsSnapshot {
+            // Discord IDs mean nothing on the other instance and shouldn't leave this one.
+            yaml_limit_bypass_list: vec![],
+            ..RoomSettingsSnapshot::from(&room.settings)
+        },
+        worlds,
+        yamls,
+    };
+
+    Ok(Json(interchange.sign(key)?))
+}
+
+#[derive(Serialize)]
+struct ImportedYaml {
+    player_name: String,
+    owner: String,
+    /// Why the YAML couldn't be imported.
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ImportResponse {
+    room: RoomResponse,
+    yamls: Vec<ImportedYaml>,
+    /// Worlds that changed on the way and warnings from the YAML checks.
+    warnings: Vec<String>,
+}
+
+/// Creates a room from another lobby's export. The room belongs to whoever imports it, and so do
+/// its YAMLs since their owners don't exist here. The importer bypasses the room's YAML limit to
+/// be able to hold them all. Every YAML goes through the same checks as an upload, the ones that
+/// don't pass are reported instead of failing the import.
+#[post("/rooms/import", data = "<request>")]
+#[tracing::instrument(skip(
+    content_length,
+    request,
+    session,
+    cookies,
+    interchange_key,
+    index_manager,
+    yaml_validation_queue,
+    deadline,
+    ctx,
+    public_origin
+))]
+async fn import_room(
+    content_length: ContentLength,
+    request: Json<SignedRoomInterchange>,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
+    interchange_key: &State<InterchangeKey>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+    public_origin: PublicOrigin,
+) -> ApiResult<Json<ImportResponse>> {
+    content_length.check(&ROOM_IMPORT)?;
+    let Some(author_id) = session.0.user_id else {
+        return Err(Error::validation("The admin API key can't own rooms").into());
+    };
+    // The YAMLs are added under the importer's account, same as an upload.
+    if ctx.abuse.is_limited(author_id).await {
+        return Err(Error::forbidden(
+            "Uploads from your account are paused for now, contact an admin if you think this is a mistake",
+        )
+        .into());
+    }
+    let interchange = request.verify(interchange_key.get()?)?;
+
+    let (manifest, mut warnings) = {
+        let index = index_manager.index.read().await;
+        interchange.manifest_for_index(&index)
+    };
+    let mut settings = interchange.settings.clone();
+    settings.manifest = manifest;
+    settings.yaml_limit_bypass_list = vec![author_id];
+    let settings = settings.into_settings(author_id);
+    let new_room = settings.as_new_room(RoomId::new_v4(), Some(author_id));
+
+    let mut conn = deadline.run(ctx.db_conn()).await??;
+    let room = db::create_room(&new_room, &mut conn).await?;
+    session.0.warning_msg.clear();
+
+    let mut yamls = vec![];
+    for yaml in interchange.yamls {
+        let copied = super::copy_yamls::copy_yaml(
+            &room,
+            &yaml.content,
+            &mut session,
+            cookies,
+            yaml_validation_queue,
+            index_manager,
+            &deadline,
+            &mut conn,
+        )
+        .await;
+
+        let error = match copied {
+            Ok(_) => None,
+            Err(e) if e.status().class().is_server_error() => return Err(e.into()),
+            Err(e) => Some(e.to_string()),
+        };
+        yamls.push(ImportedYaml {
+            player_name: yaml.player_name,
+            owner: yaml.owner,
+            error,
+        });
+    }
+    warnings.append(&mut session.0.warning_msg);
+    ctx.landing_cache.invalidate().await;
+
+    let room = db::get_room(room.id, &mut conn).await?;
+    Ok(Json(ImportResponse {
+        room: RoomResponse::new(room, &public_origin),
+        yamls,
+        warnings,
+    }))
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    /// Validation warnings and notices that would have been shown on the room page.
+    warnings: Vec<String>,
+}
+
+/// Uploads the YAML file sent as the body, with the same checks as the upload form.
+#[post("/room/<room_id>/yamls", data = "<yaml>")]
+#[tracing::instrument(skip(
+    content_length,
+    yaml,
+    session,
+    cookies,
+    index_manager,
+    yaml_validation_queue,
+    deadline,
+    ctx
+))]
+async fn upload_yaml(
+    room_id: RoomId,
+    content_length: ContentLength,
+    yaml: &str,
+    session: ApiSession,
+    cookies: &CookieJar<'_>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+) -> ApiResult<Json<UploadResponse>> {
+    content_length.check(&YAML_UPLOAD)?;
+    let is_token = session.is_token();
+    let mut session = session.require(UserTokenScope::UploadYaml)?;
+    if session.0.user_id.is_none() {
+        return Err(Error::validation("The admin API key can't own YAMLs").into());
+    }
+    session.0.warning_msg.clear();
+
+    let uploaded = super::add_yamls_to_room(
+        room_id,
+        &[yaml],
+        &mut session,
+        cookies,
+        index_manager,
+        yaml_validation_queue,
+        &deadline,
+        ctx,
+    )
+    .await;
+    // Uploading saves warnings in the session cookie. A token session must never end up in a
+    // cookie, it would give whoever holds it a full session.
+    if is_token {
+        cookies.remove_private("session");
+    }
+    uploaded?;
+
+    Ok(Json(UploadResponse {
+        warnings: std::mem::take(&mut session.0.warning_msg),
+    }))
+}
+
 pub fn routes() -> Vec<rocket::Route> {
-    routes![download_yaml,]
+    routes![
+        download_yaml,
+        yaml_options,
+        room_state,
+        create_room_from_template,
+        clone_room,
+        create_room,
+        update_room,
+        delete_room,
+        my_rooms,
+        export_room,
+        import_room,
+        upload_yaml
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoomSettingsRequest;
+
+    #[test]
+    fn test_room_settings_request_nullable_limit() {
+        let request: RoomSettingsRequest = serde_json::from_str("{}").unwrap();
+        assert_eq!(request.yaml_limit_per_user, None);
+
+        let request: RoomSettingsRequest =
+            serde_json::from_str(r#"{"yaml_limit_per_user": null}"#).unwrap();
+        assert_eq!(request.yaml_limit_per_user, Some(None));
+
+        let request: RoomSettingsRequest =
+            serde_json::from_str(r#"{"yaml_limit_per_user": 3}"#).unwrap();
+        assert_eq!(request.yaml_limit_per_user, Some(Some(3)));
+    }
 }
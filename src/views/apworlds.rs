@@ -14,8 +14,9 @@ use rocket::Responder;
 use rocket::State;
 use semver::Version;
 
+use crate::pool::NotSaturated;
 use crate::TplContext;
-use ap_lobby::error::Result;
+use ap_lobby::error::{Error, Result};
 use ap_lobby::index_manager::IndexManager;
 use ap_lobby::session::{AdminSession, LoggedInSession};
 use ap_lobby::utils::{RenamedFile, ZipFile};
@@ -27,6 +28,7 @@ struct WorldsListTpl<'a> {
     index: Index,
     supported_apworlds: Vec<(String, (World, Version))>,
     unsupported_apworlds: Vec<(String, (World, Version))>,
+    dev_overrides: Vec<String>,
 }
 
 #[derive(Responder)]
@@ -60,6 +62,7 @@ async fn list_worlds<'a>(
         index,
         supported_apworlds,
         unsupported_apworlds,
+        dev_overrides: index_manager.dev_overrides.keys().cloned().collect(),
     })
 }
 
@@ -68,6 +71,7 @@ async fn list_worlds<'a>(
 async fn download_all(
     index_manager: &State<IndexManager>,
     _session: LoggedInSession,
+    _not_saturated: NotSaturated,
 ) -> Result<ZipFile> {
     let index = index_manager.index.read().await.clone();
     let manifest = Manifest::from_index_with_latest_versions(&index)?;
@@ -82,6 +86,14 @@ async fn download_world<'a>(
     world_name: &str,
     _session: LoggedInSession,
 ) -> Result<APWorldResponse<'a>> {
+    if let Some(apworld_path) = index_manager.dev_override(world_name) {
+        let value = format!("attachment; filename=\"{}.apworld\"", world_name);
+        return Ok(APWorldResponse::NamedFile(RenamedFile {
+            inner: NamedFile::open(apworld_path).await?,
+            headers: Header::new(CONTENT_DISPOSITION.as_str(), value),
+        }));
+    }
+
     let index = index_manager.index.read().await;
 
     let world = index
@@ -98,10 +110,9 @@ async fn download_world<'a>(
     if origin.is_local() || origin.has_patches() {
         let apworld_path = world.get_path_for_origin(origin)?;
         if !apworld_path.exists() {
-            return Err(anyhow::anyhow!(
-                "This apworld seems to be in the host's index but not in their apworld folder."
-            )
-            .into());
+            return Err(Error::not_found(
+                "This apworld seems to be in the host's index but not in their apworld folder.",
+            ));
         }
 
         let value = format!("attachment; filename=\"{}.apworld\"", world_name);
@@ -143,10 +154,9 @@ async fn download_cached_world<'a>(
         .join(format!("{}-{}.apworld", world_name, version));
 
     if !apworld_path.exists() {
-        return Err(anyhow::anyhow!(
-            "This apworld seems to be in the host's index but not in their apworld folder."
-        )
-        .into());
+        return Err(Error::not_found(
+            "This apworld seems to be in the host's index but not in their apworld folder.",
+        ));
     }
 
     let value = format!("attachment; filename=\"{}.apworld\"", world_name);
@@ -82,7 +82,7 @@ async fn login_discord_callback(
 
     let discord_id = user.id.parse()?;
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     ap_lobby::db::upsert_discord_user(discord_id, &user.username, &mut conn).await?;
 
     let admins = discord_config
@@ -94,6 +94,7 @@ async fn login_discord_callback(
     session.is_admin = admins.contains(&discord_id.into());
     session.user_id = Some(user.id.parse()?);
     session.is_logged_in = true;
+    session.settings = ap_lobby::db::get_user_settings(discord_id, &mut conn).await?;
     session.save(cookies).unwrap();
 
     if let Some(redirect) = session.redirect_on_login {
@@ -0,0 +1,113 @@
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use super::{get_my_room, ConfirmationForm};
+use crate::Context;
+
+#[post("/room/<room_id>/check-in", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn check_in(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let check_in = db::get_room_check_in(room_id, &mut conn).await?;
+    if !check_in.is_some_and(|check_in| check_in.is_open()) {
+        return Err(Error::conflict("Check-in isn't open for this room"));
+    }
+
+    let checked_in = db::check_in_yamls(room_id, session.user_id(), &mut conn).await?;
+    if checked_in == 0 {
+        return Err(Error::validation(
+            "You don't have any YAML left to check in for in this room",
+        ));
+    }
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post("/room/<room_id>/check-in/open", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn open_check_in(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+
+    conn.transaction::<(), Error, _>(|conn| {
+        async move { db::open_room_check_in(room_id, conn).await }.scope_boxed()
+    })
+    .await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post("/room/<room_id>/check-in/close", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn close_check_in(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::close_room_check_in(room_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post(
+    "/room/<room_id>/check-in/exclude-unconfirmed",
+    data = "<confirmation>"
+)]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn exclude_unconfirmed(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    if db::get_room_check_in(room_id, &mut conn).await?.is_none() {
+        return Err(Error::conflict(
+            "Open the check-in before excluding unconfirmed YAMLs",
+        ));
+    }
+
+    db::exclude_unconfirmed_yamls(room_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![check_in, open_check_in, close_check_in, exclude_unconfirmed]
+}
@@ -0,0 +1,163 @@
+use ap_lobby::db::{self, Room, RoomId};
+use ap_lobby::deadline::Deadline;
+use ap_lobby::error::{Error, RedirectTo, Result, WithContext};
+use ap_lobby::index_manager::IndexManager;
+use ap_lobby::jobs::YamlValidationQueue;
+use ap_lobby::session::LoggedInSession;
+use diesel_async::AsyncPgConnection;
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct CopyYamlsForm<'a> {
+    confirmation_token: &'a str,
+    from_room: RoomId,
+}
+
+/// Copies the current user's YAMLs from another room. Every YAML goes through the same checks as
+/// an upload against this room's manifest, the ones that don't pass anymore are skipped and
+/// reported instead of failing the whole copy.
+#[post("/room/<room_id>/copy-yamls", data = "<form>")]
+#[tracing::instrument(skip(
+    redirect_to,
+    form,
+    session,
+    cookies,
+    ctx,
+    index_manager,
+    yaml_validation_queue,
+    deadline
+))]
+async fn copy_yamls(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    form: Form<CopyYamlsForm<'_>>,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = deadline.run(ctx.db_conn()).await??;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Unknown room")?;
+    if room.is_closed() {
+        return Err(Error::conflict("This room is closed, you're late"));
+    }
+    let user_id = session.user_id();
+    if ctx.abuse.is_limited(user_id).await {
+        return Err(Error::forbidden(
+            "Uploads from your account are paused for now, contact an admin if you think this is a mistake",
+        ));
+    }
+    if !session.0.is_admin && db::needs_join_code(&room, user_id, &mut conn).await? {
+        return Err(Error::forbidden(
+            "This room needs an invite code, enter it on the room page before uploading",
+        ));
+    }
+    if form.from_room == room_id {
+        return Err(Error::validation("Those YAMLs are already in this room"));
+    }
+
+    let yamls = db::get_yamls_for_room(form.from_room, &mut conn)
+        .await?
+        .into_iter()
+        .filter(|yaml| yaml.owner_id == user_id)
+        .collect::<Vec<_>>();
+    if yamls.is_empty() {
+        return Err(Error::validation("You don't have any YAML in that room"));
+    }
+
+    let mut failures = vec![];
+    let mut waitlisted_nb = 0;
+    for yaml in &yamls {
+        let copied = copy_yaml(
+            &room,
+            &yaml.content,
+            &mut session,
+            cookies,
+            yaml_validation_queue,
+            index_manager,
+            &deadline,
+            &mut conn,
+        )
+        .await;
+
+        match copied {
+            Ok(waitlisted) => waitlisted_nb += waitlisted,
+            Err(e) if e.status().class().is_server_error() => return Err(e),
+            Err(e) => failures.push(format!("{}: {}", yaml.player_name, e)),
+        }
+    }
+
+    let copied_nb = yamls.len() - failures.len();
+    if waitlisted_nb > 0 {
+        session.0.warning_msg.push(format!(
+            "This room is full, {} YAML(s) went on the waitlist. They'll get a slot if one frees up.",
+            waitlisted_nb
+        ));
+    }
+    if !failures.is_empty() {
+        session.0.warning_msg.push(format!(
+            "Copied {} out of {} YAML(s). These don't pass this room's checks anymore:",
+            copied_nb,
+            yamls.len()
+        ));
+        session.0.warning_msg.extend(failures);
+    }
+    if !session.0.warning_msg.is_empty() {
+        session.0.save(cookies)?;
+    }
+
+    if copied_nb > 0 {
+        ctx.landing_cache.invalidate().await;
+        if let Err(e) = ctx.abuse.check_upload(room_id, user_id, &mut conn).await {
+            tracing::warn!("Failed to run abuse checks on copy: {:?}", e);
+        }
+    }
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+/// Adds one YAML to the room for the current user, through the same checks as an upload. Returns
+/// how many of its games went on the waitlist.
+pub(crate) async fn copy_yaml(
+    room: &Room,
+    content: &str,
+    session: &mut LoggedInSession,
+    cookies: &CookieJar<'_>,
+    yaml_validation_queue: &YamlValidationQueue,
+    index_manager: &IndexManager,
+    deadline: &Deadline,
+    conn: &mut AsyncPgConnection,
+) -> Result<usize> {
+    let documents = ap_lobby::yaml::parse_raw_yamls(&[content])?;
+    let games = ap_lobby::yaml::parse_and_validate_yamls_for_room(
+        room,
+        &documents,
+        session,
+        cookies,
+        yaml_validation_queue,
+        index_manager,
+        deadline,
+        conn,
+    )
+    .await?;
+
+    db::add_validated_yamls_to_room(room.id, session.user_id(), games, None, deadline, conn).await
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![copy_yamls]
+}
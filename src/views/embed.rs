@@ -0,0 +1,95 @@
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{ApiResult, Result};
+use askama::Template;
+use chrono::NaiveDateTime;
+use rocket::http::Header;
+use rocket::serde::json::Json;
+use rocket::{get, routes, State};
+use serde::Serialize;
+
+use crate::views::og::{count_games, PublicOrigin};
+use crate::Context;
+
+// Embeds get loaded on every visit of the pages they're on, a slightly stale count is fine.
+const EMBED_CACHE_CONTROL: &str = "public, max-age=60";
+
+/// What a room's embed shows. Nothing in there isn't already on the room's Open Graph card.
+#[derive(Serialize)]
+pub(crate) struct RoomSummary {
+    pub(crate) name: String,
+    pub(crate) url: String,
+    pub(crate) close_date: NaiveDateTime,
+    pub(crate) is_closed: bool,
+    pub(crate) player_count: usize,
+    pub(crate) game_count: usize,
+}
+
+#[derive(Template)]
+#[template(path = "embed.html")]
+pub(crate) struct RoomEmbedTpl {
+    pub(crate) room: RoomSummary,
+}
+
+#[derive(rocket::Responder)]
+struct Cached<T> {
+    inner: T,
+    cache_control: Header<'static>,
+}
+
+impl<T> Cached<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            cache_control: Header::new("Cache-Control", EMBED_CACHE_CONTROL),
+        }
+    }
+}
+
+async fn get_room_summary(
+    room_id: RoomId,
+    public_origin: PublicOrigin,
+    ctx: &Context,
+) -> Result<RoomSummary> {
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn).await?;
+
+    Ok(RoomSummary {
+        is_closed: room.is_closed(),
+        url: format!("{}/room/{}", public_origin.0, room.id),
+        name: room.settings.name,
+        close_date: room.settings.close_date,
+        player_count: yamls.len(),
+        game_count: count_games(&yamls),
+    })
+}
+
+/// Compact summary of a room meant to be put in an iframe on other sites. It doesn't depend on
+/// who's looking at it so it can be cached publicly.
+#[get("/room/<room_id>/embed")]
+#[tracing::instrument(skip(ctx, public_origin))]
+async fn room_embed(
+    room_id: RoomId,
+    public_origin: PublicOrigin,
+    ctx: &State<Context>,
+) -> ApiResult<Cached<RoomEmbedTpl>> {
+    let room = get_room_summary(room_id, public_origin, ctx).await?;
+
+    Ok(Cached::new(RoomEmbedTpl { room }))
+}
+
+#[get("/room/<room_id>/embed.json")]
+#[tracing::instrument(skip(ctx, public_origin))]
+async fn room_embed_json(
+    room_id: RoomId,
+    public_origin: PublicOrigin,
+    ctx: &State<Context>,
+) -> ApiResult<Cached<Json<RoomSummary>>> {
+    let room = get_room_summary(room_id, public_origin, ctx).await?;
+
+    Ok(Cached::new(Json(room)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![room_embed, room_embed_json]
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use ap_lobby::body_limit::{ContentLength, ROOM_SETTINGS};
+use ap_lobby::db::{self, FeaturePolicies, FeaturePolicy, RoomId};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::extractor::YamlFeature;
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use super::get_my_room;
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct FeaturePoliciesForm<'a> {
+    confirmation_token: &'a str,
+    /// Keyed by feature, as in `policies[DeathLink]=block`.
+    policies: HashMap<&'a str, FeaturePolicy>,
+}
+
+/// Replaces the room's feature policies. They apply to uploads from now on, YAMLs already in the
+/// room are left alone.
+#[post("/room/<room_id>/feature-policies", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, content_length, form, session, ctx))]
+async fn set_feature_policies(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    content_length: ContentLength,
+    form: Form<FeaturePoliciesForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    content_length.check(&ROOM_SETTINGS)?;
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let policies = form
+        .policies
+        .iter()
+        .map(|(feature, policy)| {
+            let feature = YamlFeature::from_name(feature)
+                .ok_or_else(|| Error::validation(format!("Unknown feature: {}", feature)))?;
+            Ok((feature, *policy))
+        })
+        .collect::<Result<FeaturePolicies>>()?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::set_room_feature_policies(room_id, &policies, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![set_feature_policies]
+}
@@ -14,9 +14,10 @@ pub fn yaml_features(features: &Json<YamlFeatures>) -> askama::Result<String> {
     let mut features_content = String::new();
     for (feature, probability) in features.0.iter().sorted() {
         features_content += &format!(
-            "<img src=\"/static/images/icons/{}\" title=\"{}: {:.2}%\"/>",
+            "<img src=\"/static/images/icons/{}\" alt=\"{}\" title=\"{}: {:.2}%\"/>",
             feature_to_icon(feature),
             feature_to_name(feature),
+            feature_to_name(feature),
             *probability as f64 / 100.,
         );
     }
@@ -33,15 +34,21 @@ fn feature_to_icon(feature: &YamlFeature) -> &str {
         YamlFeature::TrainerSanity => "trainersanity.svg",
         YamlFeature::DexSanity => "dexsanity.svg",
         YamlFeature::OrbSanity => "jd-orb.svg",
+        YamlFeature::TrapLink => "trap-link.svg",
+        YamlFeature::EnergyLink => "energy-link.svg",
+        YamlFeature::RingLink => "ring-link.svg",
     }
 }
 
-fn feature_to_name(feature: &YamlFeature) -> &str {
+pub(crate) fn feature_to_name(feature: &YamlFeature) -> &str {
     match feature {
         YamlFeature::DeathLink => "Deathlink",
         YamlFeature::TrainerSanity => "Trainersanity",
         YamlFeature::DexSanity => "Dexsanity",
         YamlFeature::OrbSanity => "Extreme Orbsanity",
+        YamlFeature::TrapLink => "Traplink",
+        YamlFeature::EnergyLink => "Energylink",
+        YamlFeature::RingLink => "Ringlink",
     }
 }
 
@@ -0,0 +1,81 @@
+//! Sample data for rendering templates outside of a request, shared by the template tests and
+//! the accessibility audit.
+
+use std::collections::HashMap;
+
+use ap_lobby::db::{
+    FeaturePolicies, Json, Room, RoomId, RoomSettings, RoomUrlVisibility, YamlId,
+    YamlWithoutContent,
+};
+use ap_lobby::extractor::YamlFeature;
+use apwm::Manifest;
+use chrono::NaiveDateTime;
+use rocket::request::FromParam;
+
+use super::RoomTpl;
+use crate::TplContext;
+
+pub(crate) const ROOM_ID: &str = "b4c3a4e0-2b6a-4a3f-9f6e-2f8f4b3d1a00";
+pub(crate) const AUTHOR_ID: i64 = 1;
+pub(crate) const PLAYER_ID: i64 = 2;
+
+pub(crate) fn date(date: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+pub(crate) fn room_fixture() -> Room {
+    Room {
+        id: RoomId::from_param(ROOM_ID).unwrap(),
+        settings: RoomSettings {
+            name: "Weekly async".to_string(),
+            close_date: date("2024-12-01 20:00:00"),
+            description: "Some **markdown** description".to_string(),
+            room_url: "https://archipelago.gg/room/abc".to_string(),
+            author_id: AUTHOR_ID,
+            yaml_validation: true,
+            allow_unsupported: false,
+            yaml_limit_per_user: Some(2),
+            yaml_limit_bypass_list: vec![],
+            manifest: Json(Manifest::new()),
+            show_apworlds: true,
+            created_at: date("2024-11-01 20:00:00"),
+            updated_at: date("2024-11-01 20:00:00"),
+            exact_yaml_storage: false,
+            upload_hints: String::new(),
+            max_slots: None,
+            feature_policies: FeaturePolicies::new(),
+            room_url_visibility: RoomUrlVisibility::default(),
+        },
+        from_template_id: None,
+        archived: false,
+        room_url_revealed_at: None,
+    }
+}
+
+pub(crate) fn room_tpl(base: TplContext<'static>, room: Room) -> RoomTpl<'static> {
+    RoomTpl::new(
+        base,
+        room,
+        "organizer".to_string(),
+        "https://lobby.example".to_string(),
+    )
+}
+
+pub(crate) fn yaml_fixture(
+    id: &str,
+    player_name: &str,
+    game: &str,
+    owner_id: i64,
+) -> YamlWithoutContent {
+    YamlWithoutContent {
+        id: YamlId::from_param(id).unwrap(),
+        player_name: player_name.to_string(),
+        game: game.to_string(),
+        owner_id,
+        features: Json(HashMap::from([(YamlFeature::DeathLink, 10000)])),
+        checked_in_at: None,
+        excluded: false,
+        validation_warnings: Json(vec![]),
+        waitlisted: false,
+    }
+}
@@ -0,0 +1,192 @@
+use ap_lobby::body_limit::{ContentLength, YAML_UPLOAD};
+use ap_lobby::db::{self, Room, RoomId};
+use ap_lobby::deadline::Deadline;
+use ap_lobby::error::{Error, RedirectTo, Result, WithContext};
+use ap_lobby::index_manager::IndexManager;
+use ap_lobby::jobs::YamlValidationQueue;
+use ap_lobby::session::{LoggedInSession, Session};
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use super::{get_my_room, ConfirmationForm};
+use crate::{Context, TplContext};
+
+#[derive(Template)]
+#[template(path = "guest_upload.html")]
+pub(crate) struct GuestUploadTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) room: Room,
+    pub(crate) code: String,
+    pub(crate) upload_hints: String,
+}
+
+#[post("/room/<room_id>/guest-links", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn create_guest_link(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::create_guest_link(room_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+/// Invalidates the link. If a guest already used it, their YAMLs are removed from the room.
+#[post("/room/<room_id>/guest-links/<code>/revoke", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, code, confirmation, session, ctx))]
+async fn revoke_guest_link(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    code: &str,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::revoke_guest_link(room_id, code, &mut conn)
+        .await
+        .context("Unknown guest link")?;
+    super::promote_waitlisted_yamls(room_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[get("/room/<room_id>/guest/<code>")]
+#[tracing::instrument(skip(redirect_to, code, session, cookies, ctx))]
+async fn guest_upload_page<'a>(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    code: &str,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<GuestUploadTpl<'a>> {
+    redirect_to.set(&format!("/room/{}", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Unknown room")?;
+    if room.is_closed() {
+        return Err(Error::conflict("This room is closed, you're late"));
+    }
+    let link = db::get_unused_guest_link(room_id, code, &mut conn).await?;
+
+    Ok(GuestUploadTpl {
+        base: TplContext::from_session("room", session, cookies),
+        upload_hints: room.settings.upload_hints.clone(),
+        room,
+        code: link.code,
+    })
+}
+
+#[derive(rocket::form::FromForm)]
+struct GuestUploadForm<'a> {
+    confirmation_token: &'a str,
+    name: &'a str,
+    yaml: &'a str,
+}
+
+/// Uploads a YAML as the link's guest user. The visitor doesn't get logged in as that guest, the link
+/// is only good for this one upload.
+#[post("/room/<room_id>/guest/<code>", data = "<form>")]
+#[tracing::instrument(skip(
+    redirect_to,
+    code,
+    content_length,
+    form,
+    session,
+    cookies,
+    ctx,
+    index_manager,
+    yaml_validation_queue,
+    deadline
+))]
+async fn guest_upload(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    code: &str,
+    content_length: ContentLength,
+    form: Form<GuestUploadForm<'_>>,
+    mut session: Session,
+    cookies: &CookieJar<'_>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}/guest/{}", room_id, code));
+    content_length.check(&YAML_UPLOAD)?;
+    session.check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = deadline.run(ctx.db_conn()).await??;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Unknown room")?;
+    if room.is_closed() {
+        return Err(Error::conflict("This room is closed, you're late"));
+    }
+    let link = db::get_unused_guest_link(room_id, code, &mut conn).await?;
+    let guest_name = db::guest_display_name(form.name)?;
+    let guest_id = db::upsert_guest_for_link(&link, &guest_name, &mut conn).await?;
+
+    let mut guest_session = LoggedInSession(Session {
+        user_id: Some(guest_id),
+        ..Default::default()
+    });
+    let documents = ap_lobby::yaml::parse_raw_yamls(&[form.yaml])?;
+    let validated = ap_lobby::yaml::parse_and_validate_yamls_for_room(
+        &room,
+        &documents,
+        &mut guest_session,
+        cookies,
+        yaml_validation_queue,
+        index_manager,
+        &deadline,
+        &mut conn,
+    )
+    .await;
+    // Validation saves its warnings in the session cookie, put the visitor's own session back.
+    session.warning_msg.append(&mut guest_session.0.warning_msg);
+    session.save(cookies)?;
+    let games = validated?;
+
+    let waitlisted_nb =
+        db::add_validated_yamls_to_room(room_id, guest_id, games, Some(code), &deadline, &mut conn)
+            .await?;
+    if waitlisted_nb > 0 {
+        session.warning_msg.push(
+            "This room is full, your YAML went on the waitlist. It'll get a slot if one frees up."
+                .to_string(),
+        );
+        session.save(cookies)?;
+    }
+    ctx.landing_cache.invalidate().await;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        create_guest_link,
+        revoke_guest_link,
+        guest_upload_page,
+        guest_upload
+    ]
+}
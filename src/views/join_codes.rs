@@ -0,0 +1,86 @@
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use super::{get_my_room, ConfirmationForm};
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct JoinForm<'a> {
+    confirmation_token: &'a str,
+    code: &'a str,
+}
+
+#[post("/room/<room_id>/join", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, form, session, ctx))]
+async fn join(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    form: Form<JoinForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    if room.is_closed() {
+        return Err(Error::conflict("This room is closed, you're late"));
+    }
+
+    if !db::join_room(room_id, session.user_id(), form.code, &mut conn).await? {
+        return Err(Error::validation(
+            "That invite code is wrong, ask the organizer for the current one",
+        ));
+    }
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post("/room/<room_id>/join-code/regenerate", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn regenerate_join_code(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::regenerate_room_join_code(room_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post("/room/<room_id>/join-code/disable", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn disable_join_code(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::remove_room_join_code(room_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![join, regenerate_join_code, disable_join_code]
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use ap_lobby::error::Result;
+use ap_lobby::error::{Error, Result};
 use apwm::{Index, Manifest, NewApworldPolicy, VersionReq};
 use rocket::FromForm;
 
@@ -91,7 +91,7 @@ pub fn manifest_from_form(form: &ManifestForm, index: &Index) -> Result<Manifest
         }
 
         let Some(version_req) = form.version.get(world_name.as_str()) else {
-            Err(anyhow::anyhow!("Invalid form. You have a world that is enabled but doesn't have a version requirement"))?
+            Err(Error::validation("Invalid form. You have a world that is enabled but doesn't have a version requirement"))?
         };
 
         new_manifest.add_version_req(world_name, VersionReq::parse(version_req)?);
@@ -100,8 +100,8 @@ pub fn manifest_from_form(form: &ManifestForm, index: &Index) -> Result<Manifest
     let (_, errors) = new_manifest.resolve_with(index);
     if !errors.is_empty() {
         log::error!("{:?}", errors);
-        Err(anyhow::anyhow!(
-            "Error while resolving your room requirements with the current index"
+        Err(Error::validation(
+            "Error while resolving your room requirements with the current index",
         ))?
     }
 
@@ -1,39 +1,80 @@
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
 use std::io::{Cursor, Write};
 use std::path::PathBuf;
 
+use crate::pool::NotSaturated;
+use crate::views::og::PublicOrigin;
+use crate::views::user_tokens::ApiSession;
 use crate::{Context, TplContext};
-use ap_lobby::db::{self, Author, Room, RoomFilter, RoomId, YamlId, YamlWithoutContent};
-use ap_lobby::error::{Error, RedirectTo, Result, WithContext};
+use ap_lobby::body_limit::{ContentLength, YAML_UPLOAD};
+use ap_lobby::db::{
+    self, Author, CheckIn, FeaturePolicies, FeaturePolicy, GuestLink, Room, RoomFilter, RoomId,
+    RoomStatus, RoomUrlPolicy, RoomUrlVisibility, UserTokenScope, YamlAnnotation, YamlFlag, YamlId,
+    YamlWithoutContent,
+};
+use ap_lobby::deadline::Deadline;
+use ap_lobby::error::{ApiError, ApiResult, Error, RedirectTo, Result, WithContext};
+use ap_lobby::extractor::YamlFeature;
 use ap_lobby::index_manager::IndexManager;
 use ap_lobby::jobs::YamlValidationQueue;
+use ap_lobby::landing_cache::LandingStats;
+use ap_lobby::pending_uploads::PendingUpload;
+use ap_lobby::redaction::RedactedOptions;
 use ap_lobby::session::{LoggedInSession, Session};
 use ap_lobby::utils::ZipFile;
+use ap_lobby::yaml::NameCollision;
 use apwm::{World, WorldOrigin};
 use askama::Template;
-use diesel_async::scoped_futures::ScopedFutureExt;
-use diesel_async::AsyncConnection;
+use diesel_async::AsyncPgConnection;
 use http::header::CONTENT_DISPOSITION;
 use itertools::Itertools;
 use rocket::form::Form;
-use rocket::http::{ContentType, CookieJar, Header};
+use rocket::http::{ContentType, CookieJar, Header, Status};
 use rocket::response::Redirect;
 use rocket::routes;
 use rocket::{get, post, uri, State};
 use semver::Version;
-use tracing::Instrument;
 
+pub mod a11y;
+pub mod admin;
 pub mod api;
 pub mod apworlds;
 pub mod auth;
+pub mod check_in;
+pub mod copy_yamls;
+pub mod embed;
+pub mod feature_policies;
 pub mod filters;
+mod fixtures;
+pub mod guest_links;
+pub mod join_codes;
 pub mod manifest_editor;
+pub mod og;
 pub mod queues;
+pub mod room_features;
 pub mod room_manager;
+pub mod room_revisions;
+pub mod room_schedules;
+pub mod room_search;
 pub mod room_settings;
 pub mod room_templates;
+pub mod room_timeline;
+pub mod room_url;
+pub mod room_validation;
+pub mod settings;
+pub mod short_links;
+pub mod slot_caps;
+pub mod upload_hints;
+pub mod user_tokens;
+pub mod worker_credentials;
+pub mod yaml_annotations;
+pub mod yaml_renames;
+pub mod yaml_storage;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Template)]
 #[template(path = "room.html")]
@@ -47,7 +88,133 @@ struct RoomTpl<'a> {
     unique_game_count: usize,
     is_closed: bool,
     has_room_url: bool,
+    room_url_policy: RoomUrlPolicy,
     is_my_room: bool,
+    public_origin: String,
+    short_link: Option<String>,
+    check_in: Option<CheckIn>,
+    checked_in_count: usize,
+    needs_check_in: bool,
+    annotations: HashMap<YamlId, YamlAnnotation>,
+    join_code: Option<String>,
+    /// Guest links along with the name of the guest who used them.
+    guest_links: Vec<(GuestLink, Option<String>)>,
+    /// Markdown shown to players before they upload.
+    upload_hints: String,
+    /// How many YAMLs can play, the rest go on the waitlist.
+    slot_cap: Option<i32>,
+    feature_policies: FeaturePolicies,
+    needs_join_code: bool,
+    previous_rooms: Vec<(RoomId, String)>,
+    pending_upload: Option<PendingUpload>,
+}
+
+impl<'a> RoomTpl<'a> {
+    /// An open room without YAMLs nor any of the organizer's settings, seen by someone who isn't
+    /// its organizer. Fill in the rest with the struct update syntax.
+    fn new(base: TplContext<'a>, room: Room, author_name: String, public_origin: String) -> Self {
+        Self {
+            base,
+            room_url_policy: room.url_policy(),
+            upload_hints: room.settings.upload_hints.clone(),
+            slot_cap: room.settings.max_slots,
+            feature_policies: room.settings.feature_policies.clone(),
+            room,
+            author_name,
+            yamls: vec![],
+            player_count: 0,
+            unique_player_count: 0,
+            unique_game_count: 0,
+            is_closed: false,
+            has_room_url: false,
+            is_my_room: false,
+            public_origin,
+            short_link: None,
+            check_in: None,
+            checked_in_count: 0,
+            needs_check_in: false,
+            annotations: HashMap::new(),
+            join_code: None,
+            guest_links: vec![],
+            needs_join_code: false,
+            previous_rooms: vec![],
+            pending_upload: None,
+        }
+    }
+}
+
+impl RoomTpl<'_> {
+    fn annotation(&self, yaml_id: YamlId) -> YamlAnnotation {
+        self.annotations.get(&yaml_id).cloned().unwrap_or_default()
+    }
+
+    /// Number of columns in the yamls table before the action one.
+    fn leading_columns(&self) -> usize {
+        2 + self.check_in.is_some() as usize + self.is_my_room as usize
+    }
+
+    fn taken_slots(&self) -> usize {
+        self.yamls
+            .iter()
+            .filter(|yaml| !yaml.0.excluded && !yaml.0.waitlisted)
+            .count()
+    }
+
+    fn feature_name(&self, feature: &YamlFeature) -> String {
+        filters::feature_to_name(feature).to_string()
+    }
+
+    fn has_feature_policy(&self, feature: &YamlFeature, policy: &FeaturePolicy) -> bool {
+        self.feature_policies
+            .get(feature)
+            .copied()
+            .unwrap_or_default()
+            == *policy
+    }
+
+    /// The rules players should know about before uploading.
+    fn feature_rules(&self) -> Vec<String> {
+        self.feature_policies
+            .iter()
+            .filter(|(_, policy)| **policy != FeaturePolicy::Allow)
+            .map(|(feature, policy)| format!("{}: {}", filters::feature_to_name(feature), policy))
+            .collect()
+    }
+
+    /// How many playing YAMLs have each feature, for hosts to coordinate link groups. YAMLs that
+    /// only roll the feature some of the time are counted apart.
+    fn feature_summary(&self) -> Vec<String> {
+        let mut counts: BTreeMap<YamlFeature, (usize, usize)> = BTreeMap::new();
+        for (yaml, _) in &self.yamls {
+            if yaml.excluded || yaml.waitlisted {
+                continue;
+            }
+            for (feature, probability) in yaml.features.iter() {
+                let (always, sometimes) = counts.entry(*feature).or_default();
+                if *probability >= 10000 {
+                    *always += 1;
+                } else if *probability > 0 {
+                    *sometimes += 1;
+                }
+            }
+        }
+
+        counts
+            .into_iter()
+            .filter(|(_, (always, sometimes))| always + sometimes > 0)
+            .map(|(feature, (always, sometimes))| {
+                let name = filters::feature_to_name(&feature);
+                match (always, sometimes) {
+                    (0, sometimes) => format!("{} player(s) might have {} on", sometimes, name),
+                    (always, 0) => format!("{} player(s) have {} on", always, name),
+                    (always, sometimes) => format!(
+                        "{} player(s) have {} on, {} more might",
+                        always, name, sometimes
+                    ),
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(Template)]
@@ -57,9 +224,44 @@ struct RoomApworldsTpl<'a> {
     is_my_room: bool,
     supported_apworlds: Vec<(String, (World, Version))>,
     unsupported_apworlds: Vec<(String, (World, Version))>,
+    /// Display name of the apworlds needed by YAMLs in the room, along with who needs them.
+    required_by: Vec<(String, Vec<String>)>,
+    /// Display name of the room's apworlds that are replaced by a local build.
+    dev_overrides: Vec<String>,
     room: Room,
 }
 
+#[derive(rocket::form::FromForm, Default)]
+struct IndexFilters {
+    #[field(default = RoomStatus::Any)]
+    status: RoomStatus,
+    /// Only rooms where one of the user's YAMLs might have this feature on.
+    #[field(default = String::new())]
+    feature: String,
+}
+
+impl IndexFilters {
+    fn feature(&self) -> Option<YamlFeature> {
+        YamlFeature::from_name(&self.feature)
+    }
+
+    fn has_feature(&self, feature: &YamlFeature) -> bool {
+        self.feature() == Some(*feature)
+    }
+
+    /// Query string keeping the filters when going through pages, ready to have `page=` appended.
+    fn query_prefix(&self) -> String {
+        let mut prefix = String::new();
+        if self.status != RoomStatus::Any {
+            prefix += &format!("status={}&", self.status.as_str());
+        }
+        if let Some(feature) = self.feature() {
+            prefix += &format!("feature={}&", feature.as_str());
+        }
+        prefix
+    }
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTpl<'a> {
@@ -67,31 +269,57 @@ struct IndexTpl<'a> {
     rooms: Vec<Room>,
     current_page: u64,
     max_pages: u64,
+    landing_stats: Option<LandingStats>,
+    index_filters: IndexFilters,
 }
 
-#[get("/?<page>")]
+impl IndexTpl<'_> {
+    fn feature_name(&self, feature: &YamlFeature) -> String {
+        filters::feature_to_name(feature).to_string()
+    }
+}
+
+#[get("/?<page>&<index_filters..>")]
 #[tracing::instrument(skip_all)]
 async fn root<'a>(
     page: Option<u64>,
+    index_filters: IndexFilters,
     session: Session,
     cookies: &'a CookieJar<'_>,
     ctx: &'a State<Context>,
 ) -> Result<IndexTpl<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
-    let current_page = page.unwrap_or(1);
+    let Some(user_id) = session.user_id else {
+        // Anonymous traffic is mostly link previews and bots, keep it away from the database.
+        let landing_stats = match ctx.landing_cache.get().await {
+            Some(stats) => stats,
+            None => {
+                let mut conn = ctx.db_conn().await?;
+                ctx.landing_cache.refresh(&mut conn).await?
+            }
+        };
+
+        return Ok(IndexTpl {
+            base: TplContext::from_session("index", session, cookies),
+            rooms: vec![],
+            current_page: 1,
+            max_pages: 1,
+            landing_stats: Some(landing_stats),
+            index_filters,
+        });
+    };
 
-    let (rooms, max_pages) = if let Some(user_id) = session.user_id {
-        let your_rooms_filter = RoomFilter::default()
-            .with_author(Author::User(user_id))
-            .with_yamls_from(db::WithYaml::AndFor(user_id));
+    let mut conn = ctx.db_conn().await?;
+    let current_page = page.unwrap_or(1);
 
-        db::list_rooms(your_rooms_filter, current_page, &mut conn).await?
-    } else {
-        (vec![], 1)
-    };
+    let your_rooms_filter = RoomFilter::default()
+        .with_author(Author::User(user_id))
+        .with_yamls_from(db::WithYaml::AndFor(user_id))
+        .with_status(index_filters.status)
+        .with_own_yaml_feature(user_id, index_filters.feature());
+    let (rooms, max_pages) = db::list_rooms(your_rooms_filter, current_page, &mut conn).await?;
 
     if rooms.is_empty() && current_page != 1 {
-        return Box::pin(root(None, session, cookies, ctx)).await;
+        return Box::pin(root(None, index_filters, session, cookies, ctx)).await;
     }
 
     Ok(IndexTpl {
@@ -99,18 +327,21 @@ async fn root<'a>(
         rooms,
         current_page,
         max_pages,
+        landing_stats: None,
+        index_filters,
     })
 }
 
 #[get("/room/<room_id>")]
-#[tracing::instrument(skip(ctx, session, cookies))]
+#[tracing::instrument(skip(ctx, session, cookies, public_origin))]
 async fn room<'a>(
     room_id: RoomId,
     ctx: &State<Context>,
     session: Session,
     cookies: &CookieJar<'a>,
+    public_origin: PublicOrigin,
 ) -> Result<RoomTpl<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let (room, author_name) = db::get_room_and_author(room_id, &mut conn).await?;
     let mut yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn).await?;
     yamls.sort_by(|a, b| a.0.game.cmp(&b.0.game));
@@ -122,22 +353,72 @@ async fn room<'a>(
         .count();
 
     let is_my_room = session.is_admin || session.user_id == Some(room.settings.author_id);
+    let (short_link, annotations, join_code, guest_links) = if is_my_room {
+        // Created from the button on the page, not on a GET.
+        let slug = db::get_room_short_link(room_id, &mut conn).await?;
+        (
+            slug.map(|slug| short_links::short_link_url(&public_origin, &slug)),
+            db::get_yaml_annotations_for_room(room_id, &mut conn).await?,
+            db::get_room_join_code(room_id, &mut conn).await?,
+            db::list_guest_links(room_id, &mut conn).await?,
+        )
+    } else {
+        (None, HashMap::new(), None, vec![])
+    };
+    let needs_join_code = match session.user_id {
+        Some(user_id) if !is_my_room && !room.is_closed() => {
+            db::needs_join_code(&room, user_id, &mut conn).await?
+        }
+        _ => false,
+    };
+    let (previous_rooms, pending_upload) = match session.user_id {
+        Some(user_id) if !room.is_closed() && !needs_join_code => (
+            db::get_previous_rooms_of_user(user_id, room_id, &mut conn).await?,
+            ctx.pending_uploads.get(room_id, user_id).await,
+        ),
+        _ => (vec![], None),
+    };
+    let check_in = db::get_room_check_in(room_id, &mut conn).await?;
+    let checked_in_count = yamls
+        .iter()
+        .filter(|yaml| yaml.0.checked_in_at.is_some())
+        .count();
+    let needs_check_in = check_in.as_ref().is_some_and(|check_in| check_in.is_open())
+        && yamls
+            .iter()
+            .any(|yaml| Some(yaml.0.owner_id) == session.user_id && yaml.0.checked_in_at.is_none());
     let current_user_has_yaml_in_room = yamls
         .iter()
-        .any(|yaml| Some(yaml.0.owner_id) == session.user_id)
-        || is_my_room;
+        .any(|yaml| Some(yaml.0.owner_id) == session.user_id);
+    let has_room_url =
+        room.url_policy()
+            .shows_room_url(&room, current_user_has_yaml_in_room, is_my_room);
+    let is_closed = room.is_closed();
 
     Ok(RoomTpl {
-        base: TplContext::from_session("room", session, cookies),
         player_count: yamls.len(),
         unique_player_count,
         unique_game_count,
-        is_closed: room.is_closed(),
-        has_room_url: !room.settings.room_url.is_empty() && current_user_has_yaml_in_room,
-        author_name,
-        room,
+        is_closed,
+        has_room_url,
         yamls,
         is_my_room,
+        short_link,
+        check_in,
+        checked_in_count,
+        needs_check_in,
+        annotations,
+        join_code,
+        guest_links,
+        needs_join_code,
+        previous_rooms,
+        pending_upload,
+        ..RoomTpl::new(
+            TplContext::from_session("room", session, cookies),
+            room,
+            author_name,
+            public_origin.0,
+        )
     })
 }
 
@@ -149,111 +430,326 @@ struct Yamls<'a> {
 #[post("/room/<room_id>/upload", data = "<yaml_form>")]
 #[tracing::instrument(skip(
     redirect_to,
+    content_length,
     yaml_form,
     session,
     cookies,
     ctx,
     index_manager,
-    yaml_validation_queue
+    yaml_validation_queue,
+    deadline
 ))]
 async fn upload_yaml(
     redirect_to: &RedirectTo,
     room_id: RoomId,
+    content_length: ContentLength,
     yaml_form: Form<Yamls<'_>>,
     mut session: LoggedInSession,
     cookies: &CookieJar<'_>,
     index_manager: &State<IndexManager>,
     yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
     ctx: &State<Context>,
 ) -> Result<Redirect> {
     redirect_to.set(&format!("/room/{}", room_id));
+    content_length.check(&YAML_UPLOAD)?;
+
+    add_yamls_to_room(
+        room_id,
+        &yaml_form.yamls,
+        &mut session,
+        cookies,
+        index_manager,
+        yaml_validation_queue,
+        &deadline,
+        ctx,
+    )
+    .await?;
 
-    let mut conn = ctx.db_pool.get().await?;
+    Ok(Redirect::to(uri!(room(room_id))))
+}
+
+#[derive(rocket::form::FromForm)]
+struct RetryUploadForm<'a> {
+    confirmation_token: &'a str,
+    name: &'a str,
+}
+
+/// Uploads the last upload rejected because of a name collision again, with the colliding
+/// player renamed.
+#[post("/room/<room_id>/upload/retry", data = "<retry_form>")]
+#[tracing::instrument(skip(
+    redirect_to,
+    retry_form,
+    session,
+    cookies,
+    ctx,
+    index_manager,
+    yaml_validation_queue,
+    deadline
+))]
+async fn retry_upload(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    retry_form: Form<RetryUploadForm<'_>>,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(retry_form.confirmation_token)?;
+
+    let user_id = session.user_id();
+    let Some(pending_upload) = ctx.pending_uploads.get(room_id, user_id).await else {
+        return Err(Error::not_found(
+            "There's nothing to retry anymore, upload your YAML again",
+        ));
+    };
+    let documents = pending_upload.renamed_documents(retry_form.name)?;
+    ctx.pending_uploads.discard(room_id, user_id).await;
+
+    let documents = documents.iter().map(String::as_str).collect::<Vec<_>>();
+    add_yamls_to_room(
+        room_id,
+        &documents,
+        &mut session,
+        cookies,
+        index_manager,
+        yaml_validation_queue,
+        &deadline,
+        ctx,
+    )
+    .await?;
+
+    Ok(Redirect::to(uri!(room(room_id))))
+}
+
+#[post("/room/<room_id>/upload/discard", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn discard_pending_upload(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    ctx.pending_uploads
+        .discard(room_id, session.user_id())
+        .await;
+
+    Ok(Redirect::to(uri!(room(room_id))))
+}
+
+async fn add_yamls_to_room(
+    room_id: RoomId,
+    yamls: &[&str],
+    session: &mut LoggedInSession,
+    cookies: &CookieJar<'_>,
+    index_manager: &IndexManager,
+    yaml_validation_queue: &YamlValidationQueue,
+    deadline: &Deadline,
+    ctx: &Context,
+) -> Result<()> {
+    let mut conn = deadline.run(ctx.db_conn()).await??;
     let room = db::get_room(room_id, &mut conn)
         .await
         .context("Unknown room")?;
     if room.is_closed() {
-        return Err(anyhow::anyhow!("This room is closed, you're late").into());
+        return Err(Error::conflict("This room is closed, you're late"));
+    }
+    let user_id = session.user_id();
+    if ctx.abuse.is_limited(user_id).await {
+        return Err(Error::forbidden(
+            "Uploads from your account are paused for now, contact an admin if you think this is a mistake",
+        ));
+    }
+    if !session.0.is_admin && db::needs_join_code(&room, user_id, &mut conn).await? {
+        return Err(Error::forbidden(
+            "This room needs an invite code, enter it on the room page before uploading",
+        ));
     }
 
-    let documents = ap_lobby::yaml::parse_raw_yamls(&yaml_form.yamls)?;
-    let games = ap_lobby::yaml::parse_and_validate_yamls_for_room(
+    let documents = ap_lobby::yaml::parse_raw_yamls(yamls)?;
+    let validated = ap_lobby::yaml::parse_and_validate_yamls_for_room(
         &room,
         &documents,
-        &mut session,
+        session,
         cookies,
         yaml_validation_queue,
         index_manager,
+        deadline,
         &mut conn,
     )
-    .await?;
-
-    conn.transaction::<(), Error, _>(|conn| {
-        async move {
-            for (game_name, document, parsed, features) in games {
-                db::add_yaml_to_room(
-                    room_id,
-                    session.0.user_id.unwrap(),
-                    &game_name,
-                    document,
-                    parsed,
-                    features,
-                    conn,
-                )
-                .await?;
+    .await;
+    let games = match validated {
+        Ok(games) => games,
+        Err(e) => {
+            if let Some(collision) = e.inner().downcast_ref::<NameCollision>() {
+                if !collision.suggestions.is_empty() {
+                    let pending_upload = PendingUpload {
+                        documents: documents.iter().map(|(doc, _)| doc.clone()).collect(),
+                        collision: collision.clone(),
+                    };
+                    ctx.pending_uploads
+                        .stash(room_id, user_id, &pending_upload)
+                        .await;
+                }
             }
-            Ok(())
+            return Err(e);
         }
-        .scope_boxed()
-    })
-    .instrument(tracing::info_span!("add_yamls_to_room_transaction"))
-    .await?;
+    };
 
-    Ok(Redirect::to(uri!(room(room_id))))
+    let waitlisted_nb =
+        db::add_validated_yamls_to_room(room_id, user_id, games, None, deadline, &mut conn).await?;
+    if waitlisted_nb > 0 {
+        session.0.warning_msg.push(format!(
+            "This room is full, {} YAML(s) went on the waitlist. They'll get a slot if one frees up.",
+            waitlisted_nb
+        ));
+        session.0.save(cookies)?;
+    }
+    ctx.landing_cache.invalidate().await;
+    ctx.pending_uploads.discard(room_id, user_id).await;
+    if let Err(e) = ctx.abuse.check_upload(room_id, user_id, &mut conn).await {
+        tracing::warn!("Failed to run abuse checks on upload: {:?}", e);
+    }
+
+    Ok(())
+}
+
+/// Call whenever slots may have freed up in the room.
+pub(crate) async fn promote_waitlisted_yamls(
+    room_id: RoomId,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let promoted = db::promote_waitlisted_yamls(room_id, conn).await?;
+    if !promoted.is_empty() {
+        tracing::info!(%room_id, ?promoted, "Promoted YAMLs off the waitlist");
+    }
+
+    Ok(())
+}
+
+/// Fetches a room that `session` organizes, admins organize every room.
+pub(crate) async fn get_my_room(
+    room_id: RoomId,
+    session: &Session,
+    conn: &mut AsyncPgConnection,
+) -> Result<Room> {
+    let room = db::get_room(room_id, conn).await?;
+    let is_my_room = session.is_admin || session.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden("Only the room's organizer can do that"));
+    }
+
+    Ok(room)
+}
+
+/// Who gets to see the redacted options of a YAML: its owner, the room's organizer and admins.
+pub(crate) fn sees_unredacted_yaml(session: &Session, room: &Room, owner_id: i64) -> bool {
+    session.is_admin
+        || session.user_id == Some(owner_id)
+        || session.user_id == Some(room.settings.author_id)
+}
+
+/// Sent by the small confirmation forms in front of every destructive action.
+#[derive(rocket::form::FromForm)]
+pub(crate) struct ConfirmationForm<'a> {
+    confirmation_token: &'a str,
+}
+
+impl ConfirmationForm<'_> {
+    pub(crate) fn check(&self, session: &Session) -> Result<()> {
+        session.check_confirmation_token(self.confirmation_token)
+    }
 }
 
-#[get("/room/<room_id>/delete/<yaml_id>")]
-#[tracing::instrument(skip(redirect_to, session, ctx))]
+/// Destructive actions used to be GET routes. Keep answering the old URLs so stale links and
+/// bookmarks explain what happened instead of 404ing.
+pub(crate) fn method_not_allowed<T>() -> ApiResult<T> {
+    Err(ApiError {
+        error: anyhow::anyhow!(
+            "This action now requires a POST request. Go back to the page and use the delete button instead."
+        ),
+        status: Status::MethodNotAllowed,
+    })
+}
+
+#[post("/room/<room_id>/delete/<yaml_id>", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
 async fn delete_yaml(
     redirect_to: &RedirectTo,
     room_id: RoomId,
     yaml_id: YamlId,
+    confirmation: Form<ConfirmationForm<'_>>,
     session: LoggedInSession,
     ctx: &State<Context>,
 ) -> Result<Redirect> {
     redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn)
         .await
         .context("Unknown room")?;
     if room.is_closed() {
-        return Err(anyhow::anyhow!("This room is closed, you're late").into());
+        return Err(Error::conflict("This room is closed, you're late"));
     }
 
     let yaml = db::get_yaml_by_id(yaml_id, &mut conn).await?;
 
     let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
     if yaml.owner_id != session.user_id() && !is_my_room {
-        Err(anyhow::anyhow!("Can't delete a yaml file that isn't yours"))?
+        Err(Error::forbidden(
+            "Can't delete a yaml file that isn't yours",
+        ))?
     }
 
     db::remove_yaml(yaml_id, &mut conn).await?;
+    promote_waitlisted_yamls(room_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
+    // Organizers cleaning up their room isn't suspicious.
+    if yaml.owner_id == session.user_id() {
+        if let Err(e) = ctx
+            .abuse
+            .check_delete(room_id, session.user_id(), &mut conn)
+            .await
+        {
+            tracing::warn!("Failed to run abuse checks on delete: {:?}", e);
+        }
+    }
 
     Ok(Redirect::to(format!("/room/{}", room_id)))
 }
 
+#[get("/room/<_room_id>/delete/<_yaml_id>")]
+fn delete_yaml_get(_room_id: RoomId, _yaml_id: YamlId) -> ApiResult<()> {
+    method_not_allowed()
+}
+
+/// Also available to personal tokens with the `download-yamls` scope. Redacted options are
+/// masked in the YAMLs the session isn't allowed to see whole, see [`sees_unredacted_yaml`].
 #[get("/room/<room_id>/yamls")]
-#[tracing::instrument(skip(redirect_to, ctx, _session))]
+#[tracing::instrument(skip(redirect_to, redacted_options, ctx, session))]
 async fn download_yamls<'a>(
     redirect_to: &RedirectTo,
     room_id: RoomId,
+    redacted_options: &State<RedactedOptions>,
     ctx: &State<Context>,
-    _session: LoggedInSession,
+    session: ApiSession,
 ) -> Result<ZipFile<'a>> {
     redirect_to.set(&format!("/room/{}", room_id));
+    let session = session.require(UserTokenScope::DownloadYamls)?;
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn).await?;
     let yamls = db::get_yamls_for_room(room_id, &mut conn).await?;
     let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
@@ -262,7 +758,12 @@ async fn download_yamls<'a>(
         zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
     let mut emitted_names = HashSet::new();
 
-    for yaml in yamls {
+    // Excluded YAMLs belong to players who didn't show up for the check-in, waitlisted ones never
+    // got a slot.
+    for yaml in yamls
+        .into_iter()
+        .filter(|yaml| !yaml.excluded && !yaml.waitlisted)
+    {
         let player_name = yaml.sanitized_name();
         let mut original_file_name = format!("{}.yaml", player_name);
 
@@ -279,7 +780,11 @@ async fn download_yamls<'a>(
         }
         writer.start_file(original_file_name.clone(), options)?;
         emitted_names.insert(original_file_name);
-        writer.write_all(yaml.content.as_bytes())?;
+        if sees_unredacted_yaml(&session.0, &room, yaml.owner_id) {
+            writer.write_all(yaml.content.as_bytes())?;
+        } else {
+            writer.write_all(redacted_options.redact(&yaml.content).as_bytes())?;
+        }
     }
 
     let res = writer.finish()?;
@@ -295,10 +800,11 @@ async fn download_yamls<'a>(
 }
 
 #[get("/room/<room_id>/worlds")]
-#[tracing::instrument(skip(redirect_to, ctx, index_manager, session, cookies))]
+#[tracing::instrument(skip(redirect_to, ctx, index_manager, session, cookies, _not_saturated))]
 async fn room_worlds<'a>(
     room_id: RoomId,
     session: LoggedInSession,
+    _not_saturated: NotSaturated,
     index_manager: &State<IndexManager>,
     redirect_to: &RedirectTo,
     cookies: &CookieJar<'_>,
@@ -306,7 +812,7 @@ async fn room_worlds<'a>(
 ) -> Result<RoomApworldsTpl<'a>> {
     redirect_to.set(&format!("/room/{}", room_id));
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn).await?;
     let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
 
@@ -326,28 +832,57 @@ async fn room_worlds<'a>(
 
     supported_apworlds.sort_by_cached_key(|(_, (world, _))| world.display_name.clone());
     unsupported_apworlds.sort_by_cached_key(|(_, (world, _))| world.display_name.clone());
+    let dev_overrides = supported_apworlds
+        .iter()
+        .chain(&unsupported_apworlds)
+        .filter(|(world_name, _)| index_manager.dev_override(world_name).is_some())
+        .map(|(_, (world, _))| world.display_name.clone())
+        .collect();
+
+    let yamls = db::get_yamls_for_room(room_id, &mut conn).await?;
+    let mut required_by = BTreeMap::<String, Vec<String>>::new();
+    for yaml in &yamls {
+        let Ok(documents) = ap_lobby::yaml::parse_raw_yamls(&[&yaml.content]) else {
+            continue;
+        };
+        for (_, parsed) in documents {
+            for game in parsed.game.possible_games() {
+                let Ok((world, _)) = room.settings.manifest.resolve_from_game_name(game, &index)
+                else {
+                    continue;
+                };
+                let players = required_by.entry(world.display_name.clone()).or_default();
+                if !players.contains(&yaml.player_name) {
+                    players.push(yaml.player_name.clone());
+                }
+            }
+        }
+    }
 
     Ok(RoomApworldsTpl {
         base: TplContext::from_session("room", session.0, cookies),
         is_my_room,
         supported_apworlds,
         unsupported_apworlds,
+        required_by: required_by.into_iter().collect(),
+        dev_overrides,
         room,
     })
 }
 
 #[get("/room/<room_id>/worlds/download_all")]
-#[tracing::instrument(skip(ctx, _session, index_manager, redirect_to))]
+#[tracing::instrument(skip(ctx, _session, index_manager, redirect_to, _not_saturated))]
 async fn room_download_all_worlds<'a>(
     room_id: RoomId,
     _session: LoggedInSession,
+    _not_saturated: NotSaturated,
     index_manager: &'a State<IndexManager>,
     redirect_to: &'a RedirectTo,
     ctx: &'a State<Context>,
 ) -> Result<ZipFile<'a>> {
     redirect_to.set(&format!("/room/{}", room_id));
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn).await?;
 
     Ok(index_manager
@@ -362,19 +897,24 @@ pub(crate) struct YamlContent<'a> {
     headers: Header<'a>,
 }
 
-#[get("/room/<room_id>/download/<yaml_id>")]
-#[tracing::instrument(skip(redirect_to, ctx))]
+#[get("/room/<room_id>/download/<yaml_id>?<original>")]
+#[tracing::instrument(skip(redirect_to, session, redacted_options, ctx))]
 async fn download_yaml<'a>(
     redirect_to: &RedirectTo,
     room_id: RoomId,
     yaml_id: YamlId,
+    original: Option<bool>,
+    session: Session,
+    redacted_options: &State<RedactedOptions>,
     ctx: &State<Context>,
 ) -> Result<YamlContent<'a>> {
     redirect_to.set("/");
 
-    Ok(api::download_yaml(room_id, yaml_id, ctx)
-        .await
-        .map_err(|api_err| api_err.error)?)
+    Ok(
+        api::download_yaml(room_id, yaml_id, original, session, redacted_options, ctx)
+            .await
+            .map_err(|api_err| api_err.error)?,
+    )
 }
 
 #[get("/static/<file..>")]
@@ -411,7 +951,10 @@ pub fn routes() -> Vec<rocket::Route> {
         room_worlds,
         room_download_all_worlds,
         upload_yaml,
+        retry_upload,
+        discard_pending_upload,
         delete_yaml,
+        delete_yaml_get,
         download_yamls,
         download_yaml,
         dist,
@@ -0,0 +1,281 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use ap_lobby::db::{self, RoomId, YamlWithoutContent};
+use ap_lobby::error::{ApiResult, Result, WithContext, WithStatus};
+use itertools::Itertools;
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{get, routes, Request, State};
+
+use crate::views::Asset;
+use crate::Context;
+
+const WIDTH: usize = 1200;
+const HEIGHT: usize = 630;
+const MARGIN: f32 = 80.;
+
+const BACKGROUND: [u8; 3] = [0x12, 0x20, 0x2c];
+const ACCENT: [u8; 3] = [0x2d, 0x53, 0x73];
+const TEXT: [u8; 3] = [0xff, 0xff, 0xff];
+const MUTED: [u8; 3] = [0xa3, 0xb8, 0xc2];
+
+/// Absolute origin used to build the URLs in the Open Graph tags, link previews ignore relative
+/// ones. Read from `PUBLIC_URL` once at startup, the request's host headers are client
+/// controlled and would let anyone poison cached previews.
+#[derive(Clone)]
+pub struct PublicOrigin(pub String);
+
+impl PublicOrigin {
+    pub fn new(public_url: &str) -> Self {
+        Self(public_url.trim_end_matches('/').to_string())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PublicOrigin {
+    type Error = ap_lobby::error::Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(public_origin) = request.rocket().state::<PublicOrigin>() else {
+            return Outcome::Error((
+                Status::InternalServerError,
+                ap_lobby::error::Error::Internal(anyhow::anyhow!("Public origin isn't managed")),
+            ));
+        };
+
+        Outcome::Success(public_origin.clone())
+    }
+}
+
+/// What ends up on a room's preview card.
+pub struct RoomCard<'a> {
+    pub name: &'a str,
+    pub status: String,
+    pub player_count: usize,
+    pub game_count: usize,
+}
+
+#[derive(rocket::Responder)]
+#[response(status = 200, content_type = "image/png")]
+pub(crate) struct PngImage<'a> {
+    pub(crate) content: Vec<u8>,
+    pub(crate) headers: Header<'a>,
+}
+
+#[get("/room/<room_id>/og.png")]
+#[tracing::instrument(skip(ctx))]
+async fn room_og_image<'a>(room_id: RoomId, ctx: &State<Context>) -> ApiResult<PngImage<'a>> {
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Couldn't find the room")
+        .status(Status::NotFound)?;
+    let yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn).await?;
+
+    let status = if room.is_closed() {
+        "Closed".to_string()
+    } else {
+        format!(
+            "Closes {} UTC",
+            room.settings.close_date.format("%Y-%m-%d %H:%M")
+        )
+    };
+    let card = RoomCard {
+        name: &room.settings.name,
+        status,
+        player_count: yamls.len(),
+        game_count: count_games(&yamls),
+    };
+
+    // DejaVu Sans Bold cut down to the Latin, Greek and Cyrillic blocks plus punctuation, other
+    // scripts render as the missing glyph box.
+    let font =
+        Asset::get("fonts/DejaVuSans-Bold-subset.ttf").expect("The OG font should be embedded");
+    let content = render_room_card(&font.data, &card)?;
+
+    Ok(PngImage {
+        content,
+        // Previews get cached by Discord anyway, no need to rerender for every unfurl.
+        headers: Header::new("Cache-Control", "public, max-age=300"),
+    })
+}
+
+/// Number of distinct games in a room, YAMLs rolling between several games aren't counted.
+pub(crate) fn count_games(yamls: &[(YamlWithoutContent, String)]) -> usize {
+    yamls
+        .iter()
+        .filter(|yaml| !yaml.0.game.starts_with("Random ("))
+        .unique_by(|yaml| &yaml.0.game)
+        .count()
+}
+
+pub fn render_room_card(font_data: &[u8], card: &RoomCard) -> Result<Vec<u8>> {
+    let font = FontRef::try_from_slice(font_data)?;
+    let mut canvas = Canvas::new();
+
+    canvas.fill_rect(0, 0, 24, HEIGHT, ACCENT);
+    canvas.fill_rect(0, HEIGHT - 120, WIDTH, 120, ACCENT);
+
+    let max_width = WIDTH as f32 - 2. * MARGIN;
+    canvas.draw_text(
+        &font,
+        36.,
+        (MARGIN, 120.),
+        "Archipelago lobby",
+        MUTED,
+        max_width,
+    );
+    canvas.draw_text(&font, 80., (MARGIN, 260.), card.name, TEXT, max_width);
+    canvas.draw_text(&font, 44., (MARGIN, 360.), &card.status, MUTED, max_width);
+    canvas.draw_text(
+        &font,
+        44.,
+        (MARGIN, HEIGHT as f32 - 45.),
+        &format!(
+            "{} {}  ·  {} {}",
+            card.player_count,
+            if card.player_count == 1 {
+                "player"
+            } else {
+                "players"
+            },
+            card.game_count,
+            if card.game_count == 1 {
+                "game"
+            } else {
+                "games"
+            },
+        ),
+        TEXT,
+        max_width,
+    );
+
+    canvas.encode()
+}
+
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        Self {
+            pixels: BACKGROUND.repeat(WIDTH * HEIGHT),
+        }
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        for row in y..(y + height).min(HEIGHT) {
+            for col in x..(x + width).min(WIDTH) {
+                let idx = (row * WIDTH + col) * 3;
+                self.pixels[idx..idx + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: [u8; 3], coverage: f32) {
+        if x < 0 || y < 0 || x as usize >= WIDTH || y as usize >= HEIGHT {
+            return;
+        }
+
+        let idx = (y as usize * WIDTH + x as usize) * 3;
+        let coverage = coverage.clamp(0., 1.);
+        for (channel, value) in self.pixels[idx..idx + 3].iter_mut().zip(color) {
+            *channel = (*channel as f32 * (1. - coverage) + value as f32 * coverage) as u8;
+        }
+    }
+
+    /// Draws `text` on a single line starting at `origin` (left end of the baseline). Text that
+    /// doesn't fit in `max_width` gets cut with an ellipsis.
+    fn draw_text(
+        &mut self,
+        font: &FontRef,
+        size: f32,
+        origin: (f32, f32),
+        text: &str,
+        color: [u8; 3],
+        max_width: f32,
+    ) {
+        let scaled = font.as_scaled(PxScale::from(size));
+        let text = fit_text(&scaled, text, max_width);
+
+        let mut caret = origin.0;
+        let mut previous = None;
+        for c in text.chars() {
+            let mut glyph = scaled.scaled_glyph(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, glyph.id);
+            }
+            glyph.position = point(caret, origin.1);
+            caret += scaled.h_advance(glyph.id);
+            previous = Some(glyph.id);
+
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|x, y, coverage| {
+                self.blend(
+                    bounds.min.x as i32 + x as i32,
+                    bounds.min.y as i32 + y as i32,
+                    color,
+                    coverage,
+                )
+            });
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut content = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut content, WIDTH as u32, HEIGHT as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.pixels)?;
+            writer.finish()?;
+        }
+
+        Ok(content)
+    }
+}
+
+fn text_width<F: Font>(font: &impl ScaleFont<F>, text: &str) -> f32 {
+    text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+}
+
+fn fit_text<F: Font>(font: &impl ScaleFont<F>, text: &str, max_width: f32) -> String {
+    if text_width(font, text) <= max_width {
+        return text.to_string();
+    }
+
+    let mut fitted = text.to_string();
+    while !fitted.is_empty() && text_width(font, &format!("{}…", fitted)) > max_width {
+        fitted.pop();
+    }
+
+    format!("{}…", fitted.trim_end())
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![room_og_image]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_room_card, RoomCard};
+    use crate::views::Asset;
+
+    #[test]
+    fn test_render_room_card() {
+        let font = Asset::get("fonts/DejaVuSans-Bold-subset.ttf").unwrap();
+        let card = RoomCard {
+            name: "A very long room name that definitely doesn't fit on a single line of the card",
+            status: "Closes 2026-10-16 20:00 UTC".to_string(),
+            player_count: 12,
+            game_count: 9,
+        };
+
+        let png = render_room_card(&font.data, &card).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+}
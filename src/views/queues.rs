@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
 use ap_lobby::jobs::{YamlValidationParams, YamlValidationResponse};
+use rocket::mtls::Certificate;
+use rocket::request::Outcome;
+use rocket::Request;
 use wq::{JobId, JobStatus};
 
 #[derive(serde::Deserialize)]
@@ -26,12 +29,14 @@ macro_rules! declare_queues {
     ($($mod_name:ident<$param_ty:ty, $resp_ty:ty>),*) => {
         $(pub mod $mod_name {
             use anyhow::anyhow;
+            use ap_lobby::db;
             use ap_lobby::error::{ApiResult, ApiError};
             use rocket::State;
             use rocket::serde::json::Json;
             use rocket::{http::Status, request::{FromRequest, Outcome}, Request};
             use wq::{Job,WorkQueue};
             use super::*;
+            use crate::Context;
 
             struct QueueAuth;
             #[rocket::async_trait]
@@ -39,38 +44,63 @@ macro_rules! declare_queues {
                 type Error = ap_lobby::error::ApiError;
 
                 async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-                    let Some(queue_auth) = request.rocket().state::<QueueTokens>() else {
-                        return Outcome::Error((
-                            Status::InternalServerError,
-                            ApiError {
-                                error: anyhow!("Internal error during authentication"),
-                                status: Status::InternalServerError,
-                            }
-                        ))
+                    let internal_error = |error: anyhow::Error| Outcome::Error((
+                        Status::InternalServerError,
+                        ApiError {
+                            error: error.context("Internal error during authentication"),
+                            status: Status::InternalServerError,
+                        }
+                    ));
+                    let unauthorized = || Outcome::Error((
+                        Status::Unauthorized,
+                        ApiError {
+                            status: Status::Unauthorized,
+                            error: anyhow!("Invalid token passed as `X-Worker-Auth` or header missing")
+                        }
+                    ));
+
+                    let Some(mtls) = request.rocket().state::<WorkerMtls>() else {
+                        return internal_error(anyhow!("Worker mTLS settings aren't managed"));
                     };
-                    let Some(expected_token) = queue_auth.0.get(stringify!($mod_name)) else {
+                    if !mtls.verify(request).await {
                         return Outcome::Error((
-                            Status::InternalServerError,
+                            Status::Unauthorized,
                             ApiError {
-                                error: anyhow!("Internal error during authentication. That queue wasn't declared properly."),
-                                status: Status::InternalServerError,
+                                status: Status::Unauthorized,
+                                error: anyhow!("A valid client certificate is required")
                             }
-                        ))
-                    };
+                        ));
+                    }
 
-                    let current_token = request.headers().get("X-Worker-Auth").next();
+                    let Some(current_token) = request.headers().get_one("X-Worker-Auth") else {
+                        return unauthorized();
+                    };
 
-                    if current_token == Some(expected_token) {
+                    // Tokens from the environment predate worker credentials, they can't be
+                    // rotated without a redeploy.
+                    let Some(queue_auth) = request.rocket().state::<QueueTokens>() else {
+                        return internal_error(anyhow!("Queue tokens aren't managed"));
+                    };
+                    if queue_auth.0.get(stringify!($mod_name)).is_some_and(|expected_token| expected_token == current_token) {
                         return Outcome::Success(QueueAuth);
                     }
 
-                    return Outcome::Error((
-                        Status::Unauthorized,
-                        ApiError {
-                            status: Status::Unauthorized,
-                            error: anyhow!("Invalid token passed as `X-Worker-Auth` or header missing")
+                    let Some(ctx) = request.rocket().state::<Context>() else {
+                        return internal_error(anyhow!("Context isn't managed"));
+                    };
+                    let mut conn = match ctx.db_conn().await {
+                        Ok(conn) => conn,
+                        Err(e) => return internal_error(e.into_inner()),
+                    };
+
+                    match db::authenticate_worker(current_token, stringify!($mod_name), &mut conn).await {
+                        Ok(Some(credential)) => {
+                            tracing::info!(worker_credential = %credential.name, "Worker authenticated");
+                            Outcome::Success(QueueAuth)
                         }
-                    ));
+                        Ok(None) => unauthorized(),
+                        Err(e) => internal_error(e.into_inner()),
+                    }
                 }
             }
 
@@ -84,6 +114,7 @@ macro_rules! declare_queues {
             #[rocket::post("/reclaim_job", data="<data>")]
             async fn reclaim_job(auth: ApiResult<QueueAuth>, queue: &State<WorkQueue<$param_ty, $resp_ty>>, data: Json<ReclaimJobForm>) -> ApiResult<()> {
                 auth?;
+                ap_lobby::jobs::tag_sentry_job_id(&data.job_id);
 
                 queue.reclaim_job(&data.job_id, &data.worker_id).await?;
                 Ok(())
@@ -91,12 +122,20 @@ macro_rules! declare_queues {
 
             #[rocket::post("/resolve_job", data="<data>")]
             #[tracing::instrument(skip_all)]
-            async fn resolve_job(auth: ApiResult<QueueAuth>, queue: &State<WorkQueue<$param_ty, $resp_ty>>, data: Json<ResolveJobForm<$resp_ty>>) -> ApiResult<()> {
+            async fn resolve_job(auth: ApiResult<QueueAuth>, queue: &State<WorkQueue<$param_ty, $resp_ty>>, data: Json<ResolveJobForm<$resp_ty>>, ctx: &State<Context>) -> ApiResult<()> {
                 // TODO: Attach this to the sent otlp context
                 auth?;
+                ap_lobby::jobs::tag_sentry_job_id(&data.job_id);
 
                 queue.resolve_job(&data.worker_id, data.job_id, data.status, data.result.clone()).await.unwrap();
 
+                let job_id = ap_lobby::jobs::job_id_to_string(&data.job_id);
+                let resolution = format!("{:?}", data.status);
+                let mut conn = ctx.db_conn().await?;
+                if let Err(e) = db::record_job_resolution(&job_id, &resolution, Some(&data.worker_id), &mut conn).await {
+                    tracing::warn!("Failed to record the resolution of job {}: {:?}", job_id, e);
+                }
+
                 Ok(())
             }
 
@@ -105,6 +144,8 @@ macro_rules! declare_queues {
             }
         })*
 
+        pub const QUEUE_NAMES: &[&str] = &[$(stringify!($mod_name)),*];
+
         pub fn routes() -> Vec<rocket::Route> {
             let mut routes = vec![];
             $(
@@ -125,9 +166,108 @@ macro_rules! declare_queues {
     };
 }
 
+/// Tokens set through the environment, keyed by queue name. Worker credentials stored in the
+/// database are accepted too.
 pub struct QueueTokens<'a>(pub HashMap<&'a str, String>);
 
+/// Client certificate requirements for the worker endpoints, checked before the token. Rocket only
+/// asks clients for a certificate when `tls.mutual` is configured.
+pub struct WorkerMtls {
+    pub required: bool,
+    /// Certificate common names allowed on the queues. When empty, any certificate signed by the
+    /// configured CA is accepted.
+    pub allowed_subjects: Vec<String>,
+}
+
+impl WorkerMtls {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let allowed_subjects = std::env::var("QUEUE_MTLS_ALLOWED_SUBJECTS")
+            .map(|subjects| {
+                subjects
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|subject| !subject.is_empty())
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let required = std::env::var("QUEUE_MTLS_REQUIRED")
+            .map(|required| parse_flag(&required))
+            .unwrap_or(Ok(false))?;
+
+        Ok(Self {
+            required,
+            allowed_subjects,
+        })
+    }
+
+    fn allows(&self, common_name: Option<&str>) -> bool {
+        self.allowed_subjects.is_empty()
+            || common_name.is_some_and(|common_name| {
+                self.allowed_subjects
+                    .iter()
+                    .any(|subject| subject == common_name)
+            })
+    }
+
+    async fn verify(&self, request: &Request<'_>) -> bool {
+        if !self.required {
+            return true;
+        }
+
+        let Outcome::Success(certificate) = request.guard::<Certificate<'_>>().await else {
+            return false;
+        };
+
+        self.allows(certificate.subject().common_name())
+    }
+}
+
 declare_queues!(
     yaml_validation<YamlValidationParams, YamlValidationResponse>,
     test<YamlValidationParams, YamlValidationResponse>
 );
+
+/// Reads a yes/no setting. Anything unexpected is an error rather than silently turning it on or
+/// off.
+fn parse_flag(value: &str) -> anyhow::Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "" | "0" | "false" | "no" | "off" => Ok(false),
+        _ => anyhow::bail!("`{}` isn't a boolean, use true or false", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_flag, WorkerMtls};
+
+    #[test]
+    fn test_parse_flag() {
+        assert!(parse_flag("1").unwrap());
+        assert!(parse_flag("True").unwrap());
+        assert!(!parse_flag("0").unwrap());
+        assert!(!parse_flag("false").unwrap());
+        assert!(!parse_flag("").unwrap());
+        assert!(parse_flag("nope").is_err());
+    }
+
+    #[test]
+    fn test_mtls_allowed_subjects() {
+        let any = WorkerMtls {
+            required: true,
+            allowed_subjects: vec![],
+        };
+        assert!(any.allows(Some("validator-1")));
+        assert!(any.allows(None));
+
+        let restricted = WorkerMtls {
+            required: true,
+            allowed_subjects: vec!["validator-1".to_string()],
+        };
+        assert!(restricted.allows(Some("validator-1")));
+        assert!(!restricted.allows(Some("validator-2")));
+        assert!(!restricted.allows(None));
+    }
+}
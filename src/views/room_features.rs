@@ -0,0 +1,250 @@
+use std::collections::BTreeSet;
+
+use ap_lobby::db::{self, Room, RoomId, YamlWithoutContent};
+use ap_lobby::error::{ApiResult, RedirectTo, Result, WithContext};
+use ap_lobby::extractor::YamlFeature;
+use ap_lobby::session::Session;
+use askama::Template;
+use http::header::CONTENT_DISPOSITION;
+use rocket::http::{CookieJar, Header};
+use rocket::serde::json::Json;
+use rocket::{get, routes, State};
+use serde::Serialize;
+
+use super::filters;
+use super::yaml_annotations::{csv_field, CsvFile};
+use crate::{Context, TplContext};
+
+/// How the rows of the matrix are ordered. Features sort the players most likely to have them
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FeatureSort {
+    Player,
+    Game,
+    Feature(YamlFeature),
+}
+
+impl FeatureSort {
+    /// Unknown keys fall back to sorting by player, old links shouldn't break when a feature goes
+    /// away.
+    fn parse(sort: Option<&str>, features: &[YamlFeature]) -> Self {
+        match sort {
+            Some("game") => FeatureSort::Game,
+            Some(key) => features
+                .iter()
+                .find(|feature| feature_key(feature) == key)
+                .map(|feature| FeatureSort::Feature(*feature))
+                .unwrap_or(FeatureSort::Player),
+            None => FeatureSort::Player,
+        }
+    }
+
+    fn key(&self) -> String {
+        match self {
+            FeatureSort::Player => "player".to_string(),
+            FeatureSort::Game => "game".to_string(),
+            FeatureSort::Feature(feature) => feature_key(feature),
+        }
+    }
+}
+
+fn feature_key(feature: &YamlFeature) -> String {
+    feature.as_str().to_string()
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FeatureRow {
+    pub(crate) id: String,
+    pub(crate) player_name: String,
+    pub(crate) game: String,
+    /// Probabilities out of 10000, in the same order as the matrix's features.
+    pub(crate) probabilities: Vec<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FeatureMatrix {
+    pub(crate) features: Vec<YamlFeature>,
+    pub(crate) rows: Vec<FeatureRow>,
+}
+
+/// One row per YAML taking part in the room and one column per feature at least one of them might
+/// have, using the features stored at upload.
+pub(crate) fn feature_matrix(yamls: &[YamlWithoutContent], sort: Option<&str>) -> FeatureMatrix {
+    let yamls = yamls
+        .iter()
+        .filter(|yaml| !yaml.excluded && !yaml.waitlisted)
+        .collect::<Vec<_>>();
+    let features = yamls
+        .iter()
+        .flat_map(|yaml| yaml.features.iter())
+        .filter(|(_, probability)| **probability > 0)
+        .map(|(feature, _)| *feature)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let mut rows = yamls
+        .into_iter()
+        .map(|yaml| FeatureRow {
+            id: yaml.id.to_string(),
+            player_name: yaml.player_name.clone(),
+            game: yaml.game.clone(),
+            probabilities: features
+                .iter()
+                .map(|feature| yaml.features.get(feature).copied().unwrap_or(0))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    match FeatureSort::parse(sort, &features) {
+        FeatureSort::Player => rows.sort_by(|a, b| a.player_name.cmp(&b.player_name)),
+        FeatureSort::Game => {
+            rows.sort_by(|a, b| (&a.game, &a.player_name).cmp(&(&b.game, &b.player_name)))
+        }
+        FeatureSort::Feature(feature) => {
+            let column = features.iter().position(|f| *f == feature).unwrap();
+            rows.sort_by(|a, b| {
+                b.probabilities[column]
+                    .cmp(&a.probabilities[column])
+                    .then_with(|| a.player_name.cmp(&b.player_name))
+            })
+        }
+    }
+
+    FeatureMatrix { features, rows }
+}
+
+fn percentage(probability: u32) -> String {
+    format!("{:.2}", probability as f64 / 100.)
+}
+
+#[derive(Template)]
+#[template(path = "room_manager/room_features.html")]
+pub(crate) struct RoomFeaturesTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) room: Room,
+    pub(crate) matrix: FeatureMatrix,
+    pub(crate) sort: FeatureSort,
+}
+
+impl RoomFeaturesTpl<'_> {
+    fn feature_name(&self, feature: &YamlFeature) -> String {
+        filters::feature_to_name(feature).to_string()
+    }
+
+    fn feature_key(&self, feature: &YamlFeature) -> String {
+        feature_key(feature)
+    }
+
+    fn sort_key(&self) -> String {
+        self.sort.key()
+    }
+
+    fn is_sorted_by(&self, key: &str) -> bool {
+        self.sort.key() == key
+    }
+
+    fn percentage(&self, probability: &u32) -> String {
+        percentage(*probability)
+    }
+}
+
+#[get("/room/<room_id>/features?<sort>")]
+#[tracing::instrument(skip(redirect_to, session, cookies, ctx))]
+async fn room_features<'a>(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    sort: Option<&str>,
+    session: Session,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<RoomFeaturesTpl<'a>> {
+    redirect_to.set(&format!("/room/{}", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Unknown room")?;
+    let yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn)
+        .await?
+        .into_iter()
+        .map(|(yaml, _)| yaml)
+        .collect::<Vec<_>>();
+    let matrix = feature_matrix(&yamls, sort);
+    let sort = FeatureSort::parse(sort, &matrix.features);
+
+    Ok(RoomFeaturesTpl {
+        base: TplContext::from_session("room", session, cookies),
+        room,
+        matrix,
+        sort,
+    })
+}
+
+#[get("/room/<room_id>/features.csv?<sort>")]
+#[tracing::instrument(skip(redirect_to, ctx))]
+async fn room_features_csv(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    sort: Option<&str>,
+    ctx: &State<Context>,
+) -> Result<CsvFile<'static>> {
+    redirect_to.set(&format!("/room/{}/features", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Unknown room")?;
+    let yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn)
+        .await?
+        .into_iter()
+        .map(|(yaml, _)| yaml)
+        .collect::<Vec<_>>();
+    let matrix = feature_matrix(&yamls, sort);
+
+    let mut headers = vec!["Player", "Game"];
+    headers.extend(matrix.features.iter().map(filters::feature_to_name));
+    let mut content = headers
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",");
+    content.push_str("\r\n");
+    for row in &matrix.rows {
+        let mut fields = vec![csv_field(&row.player_name), csv_field(&row.game)];
+        fields.extend(row.probabilities.iter().map(|p| percentage(*p)));
+        content.push_str(&fields.join(","));
+        content.push_str("\r\n");
+    }
+
+    let value = format!(
+        "attachment; filename=\"room-{}-features.csv\"",
+        room.settings.close_date.format("%Y-%m-%d_%H_%M_%S")
+    );
+
+    Ok(CsvFile {
+        content,
+        headers: Header::new(CONTENT_DISPOSITION.as_str(), value),
+    })
+}
+
+#[get("/room/<room_id>/features.json?<sort>")]
+#[tracing::instrument(skip(ctx))]
+async fn room_features_json(
+    room_id: RoomId,
+    sort: Option<&str>,
+    ctx: &State<Context>,
+) -> ApiResult<Json<FeatureMatrix>> {
+    let mut conn = ctx.db_conn().await?;
+    let yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn)
+        .await?
+        .into_iter()
+        .map(|(yaml, _)| yaml)
+        .collect::<Vec<_>>();
+
+    Ok(Json(feature_matrix(&yamls, sort)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![room_features, room_features_csv, room_features_json]
+}
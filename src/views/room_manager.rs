@@ -1,7 +1,8 @@
 #![allow(clippy::blocks_in_conditions)]
 
+use ap_lobby::body_limit::{ContentLength, ROOM_SETTINGS};
 use ap_lobby::db::{self, Author, NewRoom, Room, RoomFilter, RoomId, RoomTemplateId};
-use ap_lobby::error::{RedirectTo, Result, WithContext};
+use ap_lobby::error::{ApiResult, Error, RedirectTo, Result, WithContext};
 use ap_lobby::index_manager::IndexManager;
 use ap_lobby::session::LoggedInSession;
 use askama::Template;
@@ -19,6 +20,7 @@ use rocket::State;
 use super::manifest_editor::{manifest_from_form, ManifestForm};
 use super::room_settings::RoomSettingsBuilder;
 use super::room_settings::RoomSettingsType;
+use super::{method_not_allowed, ConfirmationForm};
 
 #[derive(Template)]
 #[template(path = "room_manager/edit_room.html")]
@@ -31,6 +33,8 @@ struct EditRoom<'a> {
 #[derive(FromForm, Debug)]
 pub struct CreateRoomForm<'a> {
     room: RoomSettingsForm<'a>,
+    /// Skips the check for a room that was already created with the same settings.
+    allow_duplicate: bool,
 }
 
 #[derive(FromForm, Debug)]
@@ -72,7 +76,7 @@ async fn my_rooms<'a>(
         Author::User(session.user_id())
     };
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let current_page = page.unwrap_or(1);
 
     let (rooms, max_pages) = db::list_rooms(
@@ -104,7 +108,7 @@ async fn create_room<'a>(
     let index = index_manager.index.read().await;
 
     let form_builder = if let Some(template_id) = from_template {
-        let mut conn = ctx.db_pool.get().await?;
+        let mut conn = ctx.db_conn().await?;
         let template = db::get_room_template_by_id(template_id, &mut conn)
             .await
             .context("Couldn't get the specified template")?;
@@ -126,12 +130,12 @@ async fn create_room<'a>(
 
 pub fn parse_date(date: &str, tz_offset: i32) -> Result<DateTime<Utc>> {
     let offset = chrono::FixedOffset::west_opt(tz_offset * 60)
-        .ok_or_else(|| ap_lobby::error::Error(anyhow::anyhow!("Wrong timezone offset")))?;
+        .ok_or_else(|| Error::validation("Wrong timezone offset"))?;
     let datetime = chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M")?;
     let date = offset
         .from_local_datetime(&datetime)
         .single()
-        .ok_or_else(|| ap_lobby::error::Error(anyhow::anyhow!("Cannot parse passed datetime")))?;
+        .ok_or_else(|| Error::validation("Cannot parse passed datetime"))?;
 
     Ok(date.into())
 }
@@ -143,10 +147,13 @@ async fn create_room_submit<'a>(
     redirect_to: &RedirectTo,
     ctx: &State<Context>,
     index_manager: &State<IndexManager>,
+    content_length: ContentLength,
     mut room_form: Form<CreateRoomForm<'a>>,
-    session: LoggedInSession,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
 ) -> Result<Redirect> {
     redirect_to.set("/create-room");
+    content_length.check(&ROOM_SETTINGS)?;
 
     validate_room_form(&mut room_form.room)?;
     let room_manifest = {
@@ -178,19 +185,39 @@ async fn create_room_submit<'a>(
         manifest: db::Json(room_manifest),
         show_apworlds: room_form.room.show_apworlds,
         from_template_id: Some(from_template),
+        exact_yaml_storage: None,
+        upload_hints: None,
+        max_slots: None,
+        feature_policies: None,
+        room_url_visibility: None,
     };
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     if let Some(template_id) = from_template {
         let tpl = db::get_room_template_by_id(template_id, &mut conn)
             .await
             .context("The given template couldn't be found")?;
         if !tpl.global && tpl.settings.author_id != session.user_id() {
-            Err(anyhow::anyhow!("The given template couldn't be found"))?
+            Err(Error::not_found("The given template couldn't be found"))?
+        }
+    }
+
+    if !room_form.allow_duplicate {
+        let duplicate =
+            db::find_duplicate_room(author_id, new_room.name, new_room.close_date, &mut conn)
+                .await?;
+        if let Some(duplicate) = duplicate {
+            session.0.warning_msg.push(
+                "You already have an open room with this name closing around the same time, here it is. To create another one anyway, tick the duplicate box on the creation form."
+                    .to_string(),
+            );
+            session.0.save(cookies)?;
+            return Ok(Redirect::to(format!("/room/{}", duplicate.id)));
         }
     }
 
     let new_room = db::create_room(&new_room, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
 
     Ok(Redirect::to(format!("/room/{}", new_room.id)))
 }
@@ -204,12 +231,12 @@ async fn edit_room<'a>(
     index_manager: &State<IndexManager>,
     cookies: &CookieJar<'a>,
 ) -> Result<EditRoom<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn).await?;
     let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
 
     if !is_my_room {
-        return Err(anyhow::anyhow!("You're not allowed to edit this room").into());
+        return Err(Error::forbidden("You're not allowed to edit this room"));
     }
 
     let index = index_manager.index.read().await;
@@ -226,43 +253,56 @@ async fn edit_room<'a>(
     })
 }
 
-#[get("/edit-room/<room_id>/delete")]
-#[tracing::instrument(skip(ctx, session))]
+#[post("/edit-room/<room_id>/delete", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, ctx, confirmation, session))]
 async fn delete_room<'a>(
+    redirect_to: &RedirectTo,
     ctx: &State<Context>,
     room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
     session: LoggedInSession,
 ) -> Result<Redirect> {
-    let mut conn = ctx.db_pool.get().await?;
+    redirect_to.set(&format!("/edit-room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn).await?;
     let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
 
     if !is_my_room {
-        return Err(anyhow::anyhow!("You're not allowed to delete this room").into());
+        return Err(Error::forbidden("You're not allowed to delete this room"));
     }
 
     db::delete_room(room_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
 
     Ok(Redirect::to("/"))
 }
 
+#[get("/edit-room/<_room_id>/delete")]
+fn delete_room_get(_room_id: RoomId) -> ApiResult<()> {
+    method_not_allowed()
+}
+
 #[post("/edit-room/<room_id>", data = "<room_form>")]
-#[tracing::instrument(skip(redirect_to, room_form, index_manager, ctx, session))]
+#[tracing::instrument(skip(redirect_to, content_length, room_form, index_manager, ctx, session))]
 async fn edit_room_submit<'a>(
     redirect_to: &RedirectTo,
     room_id: RoomId,
+    content_length: ContentLength,
     mut room_form: Form<CreateRoomForm<'a>>,
     ctx: &State<Context>,
     index_manager: &State<IndexManager>,
     session: LoggedInSession,
 ) -> Result<Redirect> {
     redirect_to.set(&format!("/edit-room/{}", room_id));
+    content_length.check(&ROOM_SETTINGS)?;
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room = db::get_room(room_id, &mut conn).await?;
     let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
     if !is_my_room {
-        return Err(anyhow::anyhow!("You're not allowed to edit this room").into());
+        return Err(Error::forbidden("You're not allowed to edit this room"));
     }
 
     validate_room_form(&mut room_form.room)?;
@@ -294,44 +334,52 @@ async fn edit_room_submit<'a>(
         manifest: db::Json(room_manifest),
         show_apworlds: room_form.room.show_apworlds,
         from_template_id: None,
+        exact_yaml_storage: None,
+        upload_hints: None,
+        max_slots: None,
+        feature_policies: None,
+        room_url_visibility: None,
     };
 
-    db::update_room(&new_room, &mut conn).await?;
+    db::update_room_with_revision(&room, &new_room, session.0.user_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
 
     Ok(Redirect::to(format!("/room/{}", room_id)))
 }
 
 pub fn validate_room_form(room_form: &mut RoomSettingsForm<'_>) -> Result<()> {
     if room_form.room_name.trim().is_empty() {
-        return Err(anyhow::anyhow!("The room name shouldn't be empty").into());
+        return Err(Error::validation("The room name shouldn't be empty"));
     }
 
     if room_form.room_name.len() > 200 {
-        return Err(anyhow::anyhow!("The room name shouldn't exceed 200 characters. Seriously it doesn't need to be that long.").into());
+        return Err(Error::validation("The room name shouldn't exceed 200 characters. Seriously it doesn't need to be that long."));
     }
 
     let room_url = room_form.room_url.trim();
     if !room_url.is_empty() {
         if let Err(e) = http::uri::Uri::parse::<Absolute>(room_url) {
-            return Err(anyhow::anyhow!("Error while parsing room URL: {}", e).into());
+            return Err(Error::Validation(anyhow::anyhow!(
+                "Error while parsing room URL: {}",
+                e
+            )));
         }
     }
     room_form.room_url = room_url;
 
     if room_form.yaml_limit_per_user && room_form.yaml_limit_per_user_nb <= 0 {
-        return Err(
-            anyhow::anyhow!("The per player YAML limit should be greater or equal to 1").into(),
-        );
+        return Err(Error::validation(
+            "The per player YAML limit should be greater or equal to 1",
+        ));
     }
 
     if !room_form.yaml_limit_bypass_list.is_empty() {
         let possible_ids = room_form.yaml_limit_bypass_list.split(',');
         for possible_id in possible_ids {
             if i64::from_str(possible_id).is_err() {
-                return Err(anyhow::anyhow!(
-                    "The YAML limit bypass list should be a comma delimited list of discord IDs."
-                )
-                .into());
+                return Err(Error::validation(
+                    "The YAML limit bypass list should be a comma delimited list of discord IDs.",
+                ));
             }
         }
     }
@@ -345,6 +393,7 @@ pub fn routes() -> Vec<rocket::Route> {
         create_room,
         edit_room,
         delete_room,
+        delete_room_get,
         create_room_submit,
         edit_room_submit
     ]
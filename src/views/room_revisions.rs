@@ -0,0 +1,177 @@
+use ap_lobby::db::{self, Room, RoomId, RoomRevision, RoomRevisionId, RoomSettingsSnapshot};
+use ap_lobby::deadline::Deadline;
+use ap_lobby::error::{RedirectTo, Result};
+use ap_lobby::index_manager::IndexManager;
+use ap_lobby::jobs::YamlValidationQueue;
+use ap_lobby::session::LoggedInSession;
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use super::{get_my_room, ConfirmationForm};
+use crate::{Context, TplContext};
+
+pub(crate) struct RevisionRow {
+    pub(crate) revision: RoomRevision,
+    /// Who replaced these settings, `None` when it was the admin API key.
+    pub(crate) replaced_by: Option<String>,
+    pub(crate) changed_settings: Vec<&'static str>,
+}
+
+/// Pairs every revision with the settings that replaced it, which are the ones of the next
+/// revision or the room's current settings for the latest one.
+pub(crate) fn revision_rows(
+    room: &Room,
+    revisions: Vec<(RoomRevision, Option<String>)>,
+) -> Vec<RevisionRow> {
+    let mut replaced_with = RoomSettingsSnapshot::from(&room.settings);
+    revisions
+        .into_iter()
+        .map(|(revision, replaced_by)| {
+            let changed_settings = revision.settings.changed_settings(&replaced_with);
+            replaced_with = revision.settings.0.clone();
+            RevisionRow {
+                revision,
+                replaced_by,
+                changed_settings,
+            }
+        })
+        .collect()
+}
+
+#[derive(Template)]
+#[template(path = "room_manager/room_settings_history.html")]
+pub(crate) struct RoomSettingsHistoryTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) room: Room,
+    pub(crate) revisions: Vec<RevisionRow>,
+}
+
+#[get("/room/<room_id>/settings-history")]
+#[tracing::instrument(skip(redirect_to, session, cookies, ctx))]
+async fn room_settings_history<'a>(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<RoomSettingsHistoryTpl<'a>> {
+    redirect_to.set(&format!("/room/{}", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+    let revisions = db::list_room_revisions(room_id, &mut conn).await?;
+    let revisions = revision_rows(&room, revisions);
+
+    Ok(RoomSettingsHistoryTpl {
+        base: TplContext::from_session("room", session.0, cookies),
+        room,
+        revisions,
+    })
+}
+
+/// Puts the settings of a revision back. The settings being replaced are kept as a revision too,
+/// so a rollback can itself be rolled back. When the apworlds or validation change, the YAMLs
+/// already in the room get validated again against the restored settings.
+#[post(
+    "/room/<room_id>/settings-history/<revision_id>/rollback",
+    data = "<confirmation>"
+)]
+#[tracing::instrument(skip(
+    redirect_to,
+    confirmation,
+    session,
+    cookies,
+    index_manager,
+    yaml_validation_queue,
+    deadline,
+    ctx
+))]
+async fn rollback_room_settings(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    revision_id: RoomRevisionId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}/settings-history", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = deadline.run(ctx.db_conn()).await??;
+    let previous = get_my_room(room_id, &session.0, &mut conn).await?;
+    let revision = db::get_room_revision(room_id, revision_id, &mut conn).await?;
+
+    let settings = revision.settings.into_settings(previous.settings.author_id);
+    db::update_room_with_revision(
+        &previous,
+        &settings.as_new_room(room_id, None),
+        session.0.user_id,
+        &mut conn,
+    )
+    .await?;
+    // The revision may have a higher cap, or none at all.
+    super::promote_waitlisted_yamls(room_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
+
+    let room = db::get_room(room_id, &mut conn).await?;
+    let previous_settings = RoomSettingsSnapshot::from(&previous.settings);
+    let needs_revalidation = room.settings.yaml_validation
+        && !room.is_closed()
+        && (!previous.settings.yaml_validation
+            || revision.settings.manifest_differs(&previous_settings));
+    if !needs_revalidation {
+        return Ok(Redirect::to(format!("/room/{}/settings-history", room_id)));
+    }
+
+    let yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn).await?;
+    let mut failed = vec![];
+    for (yaml, _) in yamls.iter().filter(|(yaml, _)| !yaml.excluded) {
+        let content = db::get_yaml_by_id(yaml.id, &mut conn).await?.content;
+        let result = ap_lobby::yaml::revalidate_yaml(
+            &room,
+            yaml.id,
+            &content,
+            yaml.owner_id,
+            yaml_validation_queue,
+            index_manager,
+            &deadline,
+            &mut conn,
+        )
+        .await;
+
+        match result {
+            Ok(_) => {}
+            Err(e) if e.status().code < 500 => {
+                failed.push(yaml.player_name.as_str());
+            }
+            Err(e) => {
+                tracing::warn!("Stopping revalidation after a rollback: {}", e);
+                session.0.warning_msg.push(
+                    "Not every YAML could be validated again in time, re-run the remaining ones from the validation page"
+                        .to_string(),
+                );
+                break;
+            }
+        }
+    }
+    if !failed.is_empty() {
+        session.0.warning_msg.push(format!(
+            "These YAMLs don't pass validation with the restored settings anymore: {}",
+            failed.join(", ")
+        ));
+    }
+    session.0.save(cookies)?;
+
+    Ok(Redirect::to(format!("/room/{}/settings-history", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![room_settings_history, rollback_room_settings]
+}
@@ -0,0 +1,157 @@
+use ap_lobby::body_limit::{ContentLength, ROOM_SETTINGS};
+use ap_lobby::db::{
+    self, NewRoomSchedule, RoomSchedule, RoomScheduleId, RoomTemplate, RoomTemplateId, WEEKDAYS,
+};
+use ap_lobby::error::{Error, RedirectTo, Result, WithContext};
+use ap_lobby::session::LoggedInSession;
+use askama::Template;
+use chrono::{NaiveTime, Utc};
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use super::ConfirmationForm;
+use crate::{Context, TplContext};
+
+const MAX_OPEN_FOR_HOURS: i32 = 24 * 7;
+// Only discord webhooks, the lobby shouldn't be made to send requests anywhere else.
+const WEBHOOK_PREFIXES: [&str; 2] = [
+    "https://discord.com/api/webhooks/",
+    "https://discordapp.com/api/webhooks/",
+];
+
+#[derive(Template)]
+#[template(path = "room_manager/room_schedules.html")]
+pub(crate) struct RoomSchedulesTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) schedules: Vec<(RoomSchedule, String)>,
+    pub(crate) room_templates: Vec<RoomTemplate>,
+}
+
+impl RoomSchedulesTpl<'_> {
+    fn weekdays(&self) -> impl Iterator<Item = (usize, &'static str)> {
+        WEEKDAYS.into_iter().enumerate()
+    }
+}
+
+#[get("/room-schedules")]
+#[tracing::instrument(skip_all)]
+async fn list_schedules<'a>(
+    ctx: &State<Context>,
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+) -> Result<RoomSchedulesTpl<'a>> {
+    let mut conn = ctx.db_conn().await?;
+    let schedules = db::list_room_schedules_for_author(session.user_id(), &mut conn).await?;
+    let room_templates = db::get_room_templates_for_author(session.user_id(), &mut conn).await?;
+
+    Ok(RoomSchedulesTpl {
+        base: TplContext::from_session("room-schedules", session.0, cookies),
+        schedules,
+        room_templates,
+    })
+}
+
+#[derive(rocket::form::FromForm)]
+struct CreateScheduleForm<'a> {
+    confirmation_token: &'a str,
+    template_id: RoomTemplateId,
+    weekday: i16,
+    start_time: &'a str,
+    open_for_hours: i32,
+    webhook_url: &'a str,
+}
+
+#[post("/room-schedules", data = "<form>")]
+#[tracing::instrument(skip_all)]
+async fn create_schedule(
+    redirect_to: &RedirectTo,
+    content_length: ContentLength,
+    form: Form<CreateScheduleForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/room-schedules");
+    content_length.check(&ROOM_SETTINGS)?;
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    if !(0..WEEKDAYS.len() as i16).contains(&form.weekday) {
+        return Err(Error::validation("Pick a day of the week"));
+    }
+    let Ok(start_time) = NaiveTime::parse_from_str(form.start_time, "%H:%M") else {
+        return Err(Error::validation("The start time should look like 20:00"));
+    };
+    if !(1..=MAX_OPEN_FOR_HOURS).contains(&form.open_for_hours) {
+        return Err(Error::validation(format!(
+            "Rooms can stay open between 1 and {} hours",
+            MAX_OPEN_FOR_HOURS
+        )));
+    }
+    let webhook_url = form.webhook_url.trim();
+    if !webhook_url.is_empty()
+        && !WEBHOOK_PREFIXES
+            .iter()
+            .any(|prefix| webhook_url.starts_with(prefix))
+    {
+        return Err(Error::validation("Only discord webhooks are supported"));
+    }
+
+    let mut conn = ctx.db_conn().await?;
+    let tpl = db::get_room_template_by_id(form.template_id, &mut conn)
+        .await
+        .context("Unknown room template")?;
+    let can_use =
+        session.0.is_admin || tpl.global || session.0.user_id == Some(tpl.settings.author_id);
+    if !can_use {
+        return Err(Error::forbidden("You're not allowed to use this template"));
+    }
+
+    let new_schedule = NewRoomSchedule {
+        id: RoomScheduleId::new_v4(),
+        template_id: tpl.id,
+        author_id: session.user_id(),
+        weekday: form.weekday,
+        start_time,
+        open_for_hours: form.open_for_hours,
+        webhook_url,
+        next_run_at: db::next_schedule_run(form.weekday, start_time, Utc::now().naive_utc()),
+    };
+    db::create_room_schedule(&new_schedule, &mut conn).await?;
+
+    Ok(Redirect::to("/room-schedules"))
+}
+
+/// Stops the schedule. Rooms it already opened are left alone.
+#[post("/room-schedules/<schedule_id>/delete", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn delete_schedule(
+    redirect_to: &RedirectTo,
+    schedule_id: RoomScheduleId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/room-schedules");
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let schedule = db::get_room_schedule(schedule_id, &mut conn)
+        .await
+        .context("Unknown schedule")?;
+    let is_my_schedule = session.0.is_admin || session.0.user_id == Some(schedule.author_id);
+    if !is_my_schedule {
+        return Err(Error::forbidden(
+            "You're not allowed to delete this schedule",
+        ));
+    }
+    db::delete_room_schedule(schedule_id, &mut conn).await?;
+
+    Ok(Redirect::to("/room-schedules"))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![list_schedules, create_schedule, delete_schedule]
+}
@@ -0,0 +1,216 @@
+use ap_lobby::db::{self, Author, Room, RoomFilter, RoomId, RoomStatus, WithYaml};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use askama::Template;
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use rocket::form::Form;
+use rocket::http::{CookieJar, RawStr};
+use rocket::response::Redirect;
+use rocket::{get, post, routes, FromForm, State};
+
+use crate::{Context, TplContext};
+
+#[derive(FromForm, Default)]
+struct RoomSearch {
+    #[field(default = String::new())]
+    q: String,
+    #[field(default = RoomStatus::Any)]
+    status: RoomStatus,
+    #[field(default = String::new())]
+    game: String,
+    #[field(default = String::new())]
+    author: String,
+    /// `YYYY-MM-DD`, inclusive.
+    #[field(default = String::new())]
+    from: String,
+    /// `YYYY-MM-DD`, inclusive.
+    #[field(default = String::new())]
+    to: String,
+    #[field(default = false)]
+    archived: bool,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn parse_day(day: &str) -> Result<Option<NaiveDateTime>> {
+    let Some(day) = non_empty(day) else {
+        return Ok(None);
+    };
+    let day = NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+        .map_err(|_| Error::validation("Dates should look like 2024-12-31"))?;
+
+    Ok(Some(day.and_time(Default::default())))
+}
+
+impl RoomSearch {
+    fn filter(&self) -> Result<RoomFilter> {
+        let closes_after = parse_day(&self.from)?;
+        let closes_before = parse_day(&self.to)?.map(|to| to + TimeDelta::days(1));
+
+        Ok(RoomFilter::default()
+            .with_status(self.status)
+            .with_name(non_empty(&self.q))
+            .with_game(non_empty(&self.game))
+            .with_author_name(non_empty(&self.author))
+            .closing_between(closes_after, closes_before)
+            .with_archived(self.archived))
+    }
+
+    /// The search's query string, pointing at `page`.
+    fn query_string(&self, page: u64) -> String {
+        let mut params = vec![];
+        for (name, value) in [
+            ("q", self.q.as_str()),
+            ("game", self.game.as_str()),
+            ("author", self.author.as_str()),
+            ("from", self.from.as_str()),
+            ("to", self.to.as_str()),
+        ] {
+            if !value.trim().is_empty() {
+                params.push(format!("{}={}", name, RawStr::new(value).percent_encode()));
+            }
+        }
+        if self.status != RoomStatus::Any {
+            params.push(format!("status={}", self.status.as_str()));
+        }
+        if self.archived {
+            params.push("archived=true".to_string());
+        }
+        params.push(format!("page={}", page));
+
+        format!("?{}", params.join("&"))
+    }
+}
+
+#[derive(Template)]
+#[template(path = "room_manager/room_search.html")]
+struct RoomSearchTpl<'a> {
+    base: TplContext<'a>,
+    search: RoomSearch,
+    rooms: Vec<Room>,
+    current_page: u64,
+    max_pages: u64,
+}
+
+impl RoomSearchTpl<'_> {
+    fn page_url(&self, page: u64) -> String {
+        self.search.query_string(page)
+    }
+}
+
+/// Searches every room for admins. Everyone else searches the rooms they organize or have YAMLs
+/// in, rooms aren't listed publicly.
+#[get("/rooms/search?<page>&<search..>")]
+#[tracing::instrument(skip_all)]
+async fn search_rooms<'a>(
+    search: RoomSearch,
+    page: Option<u64>,
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<RoomSearchTpl<'a>> {
+    let mut filter = search.filter()?;
+    if !session.0.is_admin {
+        let user_id = session.user_id();
+        filter = filter
+            .with_author(Author::User(user_id))
+            .with_yamls_from(WithYaml::AndFor(user_id));
+    }
+
+    let mut conn = ctx.db_conn().await?;
+    let current_page = page.unwrap_or(1);
+    let (rooms, max_pages) = db::list_rooms(filter, current_page, &mut conn).await?;
+
+    Ok(RoomSearchTpl {
+        base: TplContext::from_session("rooms", session.0, cookies),
+        search,
+        rooms,
+        current_page,
+        max_pages,
+    })
+}
+
+#[derive(FromForm)]
+struct ArchiveForm<'a> {
+    confirmation_token: &'a str,
+    archived: bool,
+}
+
+#[post("/room/<room_id>/archive", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, form, session, ctx))]
+async fn archive_room(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    form: Form<ArchiveForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden("You're not allowed to archive this room"));
+    }
+    db::set_room_archived(room_id, form.archived, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![search_rooms, archive_room]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoomSearch;
+    use ap_lobby::db::RoomStatus;
+
+    #[test]
+    fn test_room_search_query_string() {
+        let search = RoomSearch {
+            q: "weekly async".to_string(),
+            status: RoomStatus::Closed,
+            game: "A Link to the Past".to_string(),
+            archived: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            search.query_string(2),
+            "?q=weekly%20async&game=A%20Link%20to%20the%20Past&status=closed&archived=true&page=2"
+        );
+        assert_eq!(RoomSearch::default().query_string(1), "?page=1");
+    }
+
+    #[test]
+    fn test_room_search_filter() {
+        let search = RoomSearch {
+            from: "2024-12-01".to_string(),
+            to: "2024-12-31".to_string(),
+            ..Default::default()
+        };
+        let filter = search.filter().unwrap();
+        assert_eq!(
+            filter.closes_after.unwrap().to_string(),
+            "2024-12-01 00:00:00"
+        );
+        assert_eq!(
+            filter.closes_before.unwrap().to_string(),
+            "2025-01-01 00:00:00"
+        );
+
+        let search = RoomSearch {
+            to: "31/12/2024".to_string(),
+            ..Default::default()
+        };
+        assert!(search.filter().is_err());
+    }
+}
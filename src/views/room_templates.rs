@@ -1,6 +1,6 @@
-use anyhow::anyhow;
+use ap_lobby::body_limit::{ContentLength, ROOM_SETTINGS};
 use ap_lobby::db::Room;
-use ap_lobby::error::Result;
+use ap_lobby::error::{ApiResult, Error, Result};
 use ap_lobby::{
     db::{self, NewRoomTemplate, RoomTemplate, RoomTemplateId},
     error::RedirectTo,
@@ -17,8 +17,10 @@ use crate::{Context, TplContext};
 use super::room_manager::RoomSettingsForm;
 use super::{
     manifest_editor::manifest_from_form,
+    method_not_allowed,
     room_manager::{parse_date, validate_room_form},
     room_settings::{RoomSettingsBuilder, RoomSettingsType},
+    ConfirmationForm,
 };
 
 #[derive(Debug, FromForm)]
@@ -61,7 +63,7 @@ async fn list_templates<'a>(
     session: LoggedInSession,
     cookies: &CookieJar<'a>,
 ) -> Result<ListRoomTemplatesTpl<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let room_templates = db::get_room_templates_for_author(session.user_id(), &mut conn).await?;
 
     Ok(ListRoomTemplatesTpl {
@@ -97,10 +99,12 @@ async fn create_tpl_submit<'a>(
     redirect_to: &RedirectTo,
     ctx: &State<Context>,
     index_manager: &State<IndexManager>,
+    content_length: ContentLength,
     mut tpl_form: Form<CreateTplForm<'a>>,
     session: LoggedInSession,
 ) -> Result<Redirect> {
     redirect_to.set("/room-templates/create");
+    content_length.check(&ROOM_SETTINGS)?;
 
     validate_tpl_form(&mut tpl_form)?;
     let room_manifest = {
@@ -135,7 +139,7 @@ async fn create_tpl_submit<'a>(
         global: tpl_form.tpl_global && session.0.is_admin,
     };
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     db::create_room_template(&new_tpl, &mut conn).await?;
 
     Ok(Redirect::to("/room-templates"))
@@ -150,11 +154,13 @@ async fn edit_template<'a>(
     session: LoggedInSession,
     cookies: &CookieJar<'a>,
 ) -> Result<EditRoomTemplateTpl<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let template = db::get_room_template_by_id(tpl_id, &mut conn).await?;
     let is_my_template = template.settings.author_id == session.user_id();
     if !is_my_template && !template.global {
-        Err(anyhow!("You are not allowed to edit this template"))?;
+        Err(Error::forbidden(
+            "You are not allowed to edit this template",
+        ))?;
     }
 
     let index = index_manager.index.read().await;
@@ -173,22 +179,26 @@ async fn edit_template<'a>(
 }
 
 #[post("/room-templates/<tpl_id>", data = "<tpl_form>")]
-#[tracing::instrument(skip(redirect_to, tpl_form, index_manager, ctx, session))]
+#[tracing::instrument(skip(redirect_to, content_length, tpl_form, index_manager, ctx, session))]
 async fn edit_tpl_submit<'a>(
     redirect_to: &RedirectTo,
     tpl_id: RoomTemplateId,
+    content_length: ContentLength,
     mut tpl_form: Form<CreateTplForm<'a>>,
     ctx: &State<Context>,
     index_manager: &State<IndexManager>,
     session: LoggedInSession,
 ) -> Result<Redirect> {
     redirect_to.set(&format!("/room-templates/{}", tpl_id));
+    content_length.check(&ROOM_SETTINGS)?;
 
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let tpl = db::get_room_template_by_id(tpl_id, &mut conn).await?;
     let is_my_tpl = session.0.is_admin || session.0.user_id == Some(tpl.settings.author_id);
     if !is_my_tpl {
-        return Err(anyhow::anyhow!("You're not allowed to edit this room template").into());
+        return Err(Error::forbidden(
+            "You're not allowed to edit this room template",
+        ));
     }
 
     validate_tpl_form(&mut tpl_form)?;
@@ -228,19 +238,26 @@ async fn edit_tpl_submit<'a>(
     Ok(Redirect::to("/room-templates"))
 }
 
-#[get("/room-templates/<tpl_id>/delete")]
-#[tracing::instrument(skip(ctx, session))]
+#[post("/room-templates/<tpl_id>/delete", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, ctx, confirmation, session))]
 async fn delete_template<'a>(
+    redirect_to: &RedirectTo,
     ctx: &State<Context>,
     tpl_id: RoomTemplateId,
+    confirmation: Form<ConfirmationForm<'_>>,
     session: LoggedInSession,
 ) -> Result<Redirect> {
-    let mut conn = ctx.db_pool.get().await?;
+    redirect_to.set(&format!("/room-templates/{}", tpl_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
     let tpl = db::get_room_template_by_id(tpl_id, &mut conn).await?;
     let is_my_tpl = session.0.is_admin || session.0.user_id == Some(tpl.settings.author_id);
 
     if !is_my_tpl {
-        return Err(anyhow::anyhow!("You're not allowed to delete this room template").into());
+        return Err(Error::forbidden(
+            "You're not allowed to delete this room template",
+        ));
     }
 
     db::delete_room_template(tpl_id, &mut conn).await?;
@@ -248,6 +265,11 @@ async fn delete_template<'a>(
     Ok(Redirect::to("/room-templates"))
 }
 
+#[get("/room-templates/<_tpl_id>/delete")]
+fn delete_template_get(_tpl_id: RoomTemplateId) -> ApiResult<()> {
+    method_not_allowed()
+}
+
 #[get("/room-templates/<tpl_id>/rooms?<page>")]
 #[tracing::instrument(skip(ctx, session))]
 pub async fn list_associated_rooms<'a>(
@@ -257,13 +279,13 @@ pub async fn list_associated_rooms<'a>(
     session: LoggedInSession,
     cookies: &CookieJar<'a>,
 ) -> Result<AssociatedRoomsTpl<'a>> {
-    let mut conn = ctx.db_pool.get().await?;
+    let mut conn = ctx.db_conn().await?;
     let tpl = db::get_room_template_by_id(tpl_id, &mut conn).await?;
     let is_my_tpl =
         tpl.global || session.0.is_admin || session.0.user_id == Some(tpl.settings.author_id);
 
     if !is_my_tpl {
-        return Err(anyhow::anyhow!("Couldn't find the given template").into());
+        return Err(Error::not_found("Couldn't find the given template"));
     }
 
     let current_page = page.unwrap_or(1);
@@ -282,7 +304,7 @@ pub fn validate_tpl_form(tpl_form: &mut CreateTplForm<'_>) -> Result<()> {
     validate_room_form(&mut tpl_form.room)?;
 
     if tpl_form.tpl_name.trim().is_empty() {
-        return Err(anyhow::anyhow!("The template name shouldn't be empty").into());
+        return Err(Error::validation("The template name shouldn't be empty"));
     }
 
     Ok(())
@@ -294,6 +316,7 @@ pub fn routes() -> Vec<rocket::Route> {
         create_template,
         edit_template,
         delete_template,
+        delete_template_get,
         create_tpl_submit,
         edit_tpl_submit,
         list_associated_rooms,
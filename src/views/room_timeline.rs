@@ -0,0 +1,80 @@
+use ap_lobby::db::{self, Room, RoomId, TimelineDay};
+use ap_lobby::error::{ApiResult, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use askama::Template;
+use chrono::NaiveDateTime;
+use rocket::http::CookieJar;
+use rocket::serde::json::Json;
+use rocket::{get, routes, State};
+use serde::Serialize;
+
+use super::get_my_room;
+use crate::{Context, TplContext};
+
+#[derive(Template)]
+#[template(path = "room_manager/room_timeline.html")]
+pub(crate) struct RoomTimelineTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) room: Room,
+    pub(crate) days: Vec<TimelineDay>,
+}
+
+impl RoomTimelineTpl<'_> {
+    /// Width of a day's bar, relative to the busiest day.
+    fn bar_width(&self, uploads: usize) -> usize {
+        let busiest = self.days.iter().map(|day| day.uploads).max().unwrap_or(0);
+        if busiest == 0 {
+            return 0;
+        }
+        uploads * 100 / busiest
+    }
+}
+
+#[derive(Serialize)]
+struct RoomTimeline {
+    close_date: NaiveDateTime,
+    days: Vec<TimelineDay>,
+}
+
+#[get("/room/<room_id>/timeline")]
+#[tracing::instrument(skip(redirect_to, session, cookies, ctx))]
+async fn room_timeline<'a>(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<RoomTimelineTpl<'a>> {
+    redirect_to.set(&format!("/room/{}", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+    let days = db::get_room_timeline(room_id, &mut conn).await?;
+
+    Ok(RoomTimelineTpl {
+        base: TplContext::from_session("room", session.0, cookies),
+        room,
+        days,
+    })
+}
+
+#[get("/room/<room_id>/timeline.json")]
+#[tracing::instrument(skip(session, ctx))]
+async fn room_timeline_json(
+    room_id: RoomId,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> ApiResult<Json<RoomTimeline>> {
+    let mut conn = ctx.db_conn().await?;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+    let days = db::get_room_timeline(room_id, &mut conn).await?;
+
+    Ok(Json(RoomTimeline {
+        close_date: room.settings.close_date,
+        days,
+    }))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![room_timeline, room_timeline_json]
+}
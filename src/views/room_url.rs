@@ -0,0 +1,63 @@
+use ap_lobby::db::{self, RoomId, RoomUrlVisibility};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use super::{get_my_room, ConfirmationForm};
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct VisibilityForm<'a> {
+    confirmation_token: &'a str,
+    visibility: RoomUrlVisibility,
+}
+
+#[post("/room/<room_id>/room-url/visibility", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, form, session, ctx))]
+async fn set_visibility(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    form: Form<VisibilityForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    db::set_room_url_visibility(room_id, form.visibility, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post("/room/<room_id>/room-url/reveal", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn reveal(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    get_my_room(room_id, &session.0, &mut conn).await?;
+    if !db::reveal_room_url(room_id, &mut conn).await? {
+        return Err(Error::conflict(
+            "This room's URL isn't waiting to be revealed",
+        ));
+    }
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![set_visibility, reveal]
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use ap_lobby::db::{self, JobRecord, Room, RoomId, YamlId, YamlWithoutContent};
+use ap_lobby::deadline::Deadline;
+use ap_lobby::error::{Error, RedirectTo, Result, WithContext};
+use ap_lobby::index_manager::IndexManager;
+use ap_lobby::jobs::YamlValidationQueue;
+use ap_lobby::session::LoggedInSession;
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use super::{get_my_room, ConfirmationForm};
+use crate::{Context, TplContext};
+
+#[derive(Template)]
+#[template(path = "room_manager/room_validation.html")]
+pub(crate) struct RoomValidationTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) room: Room,
+    pub(crate) yamls: Vec<(YamlWithoutContent, String)>,
+    pub(crate) jobs: HashMap<YamlId, JobRecord>,
+}
+
+impl RoomValidationTpl<'_> {
+    fn job(&self, yaml_id: YamlId) -> Option<&JobRecord> {
+        self.jobs.get(&yaml_id)
+    }
+
+    fn status(&self, yaml_id: YamlId) -> &str {
+        match self.job(yaml_id) {
+            None => "Never validated",
+            Some(job) => job.resolution.as_deref().unwrap_or("Pending"),
+        }
+    }
+
+    fn can_rerun(&self, yaml_id: YamlId) -> bool {
+        self.room.settings.yaml_validation
+            && !self.room.is_closed()
+            && !matches!(self.status(yaml_id), "Success" | "Pending")
+    }
+}
+
+#[get("/room/<room_id>/validation")]
+#[tracing::instrument(skip(redirect_to, session, cookies, ctx))]
+async fn room_validation<'a>(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<RoomValidationTpl<'a>> {
+    redirect_to.set(&format!("/room/{}", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+    let mut yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn).await?;
+    yamls.sort_by(|(a, _), (b, _)| a.player_name.cmp(&b.player_name));
+    let jobs = db::get_latest_validation_jobs_for_room(room_id, &mut conn).await?;
+
+    Ok(RoomValidationTpl {
+        base: TplContext::from_session("room", session.0, cookies),
+        room,
+        yamls,
+        jobs,
+    })
+}
+
+/// Sends a YAML through validation again, for when the last run failed or timed out.
+#[post("/room/<room_id>/validation/<yaml_id>/rerun", data = "<confirmation>")]
+#[tracing::instrument(skip(
+    redirect_to,
+    confirmation,
+    session,
+    ctx,
+    index_manager,
+    yaml_validation_queue,
+    deadline
+))]
+async fn rerun_validation(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    yaml_id: YamlId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
+    index_manager: &State<IndexManager>,
+    yaml_validation_queue: &State<YamlValidationQueue>,
+    deadline: Deadline,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}/validation", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = deadline.run(ctx.db_conn()).await??;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+    if !room.settings.yaml_validation {
+        return Err(Error::conflict("YAML validation is disabled for this room"));
+    }
+    if room.is_closed() {
+        return Err(Error::conflict("This room is closed"));
+    }
+    let yaml = db::get_yaml_by_id(yaml_id, &mut conn)
+        .await
+        .context("Unknown YAML")?;
+    if yaml.room_id != room_id {
+        return Err(Error::not_found("This YAML isn't in this room"));
+    }
+
+    let warnings = ap_lobby::yaml::revalidate_yaml(
+        &room,
+        yaml_id,
+        &yaml.content,
+        yaml.owner_id,
+        yaml_validation_queue,
+        index_manager,
+        &deadline,
+        &mut conn,
+    )
+    .await?;
+    if !warnings.is_empty() {
+        session.0.warning_msg.push(format!(
+            "{} passed validation with {} warning(s)",
+            yaml.player_name,
+            warnings.len()
+        ));
+        session.0.save(cookies)?;
+    }
+    ctx.landing_cache.invalidate().await;
+
+    Ok(Redirect::to(format!("/room/{}/validation", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![room_validation, rerun_validation]
+}
@@ -0,0 +1,86 @@
+use ap_lobby::db::{self, Theme, UserSettings, UserToken, UserTokenScope};
+use ap_lobby::error::{RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use askama::Template;
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use crate::{Context, TplContext};
+
+#[derive(Template)]
+#[template(path = "settings.html")]
+pub(crate) struct SettingsTpl<'a> {
+    pub(crate) base: TplContext<'a>,
+    pub(crate) settings: UserSettings,
+    pub(crate) tokens: Vec<UserToken>,
+    /// Only set right after a token was created, it can't be shown again afterwards.
+    pub(crate) new_token: Option<String>,
+}
+
+#[derive(rocket::form::FromForm)]
+struct SettingsForm<'a> {
+    confirmation_token: &'a str,
+    theme: Theme,
+    compact_layout: bool,
+}
+
+#[get("/settings")]
+#[tracing::instrument(skip_all)]
+async fn settings<'a>(
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<SettingsTpl<'a>> {
+    let settings = session.0.settings.clone();
+    let tokens = match session.0.user_id {
+        Some(user_id) => {
+            let mut conn = ctx.db_conn().await?;
+            db::list_user_tokens(user_id, &mut conn).await?
+        }
+        None => vec![],
+    };
+
+    Ok(SettingsTpl {
+        base: TplContext::from_session("settings", session.0, cookies),
+        settings,
+        tokens,
+        new_token: None,
+    })
+}
+
+#[post("/settings", data = "<form>")]
+#[tracing::instrument(skip_all)]
+async fn save_settings(
+    redirect_to: &RedirectTo,
+    form: Form<SettingsForm<'_>>,
+    mut session: LoggedInSession,
+    cookies: &CookieJar<'_>,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/settings");
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let settings = UserSettings {
+        theme: form.theme,
+        compact_layout: form.compact_layout,
+    };
+
+    // The admin API key doesn't come with a user to attach settings to.
+    if let Some(user_id) = session.0.user_id {
+        let mut conn = ctx.db_conn().await?;
+        db::set_user_settings(user_id, &settings, &mut conn).await?;
+    }
+
+    session.0.settings = settings;
+    session.0.save(cookies)?;
+
+    Ok(Redirect::to("/settings"))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![settings, save_settings]
+}
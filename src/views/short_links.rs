@@ -0,0 +1,123 @@
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{ApiResult, Error, RedirectTo, Result, WithContext, WithStatus};
+use ap_lobby::session::LoggedInSession;
+use qrcode::{Color, QrCode};
+use rocket::form::Form;
+use rocket::http::{Header, Status};
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use super::og::{PngImage, PublicOrigin};
+use super::ConfirmationForm;
+use crate::Context;
+
+const QR_MODULE_SIZE: usize = 10;
+// The QR spec asks for at least 4 modules of blank space around the code.
+const QR_QUIET_ZONE: usize = 4;
+
+pub fn short_link_url(public_origin: &PublicOrigin, slug: &str) -> String {
+    format!("{}/r/{}", public_origin.0, slug)
+}
+
+#[get("/r/<slug>")]
+#[tracing::instrument(skip(ctx))]
+async fn resolve_short_link(slug: &str, ctx: &State<Context>) -> Result<Redirect> {
+    let mut conn = ctx.db_conn().await?;
+    let room_id = db::resolve_room_short_link(slug, &mut conn)
+        .await
+        .context("This link doesn't lead anywhere anymore")?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[post("/room/<room_id>/short-link/regenerate", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, ctx, confirmation, session))]
+async fn regenerate_short_link(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden(
+            "You're not allowed to change this room's short link",
+        ));
+    }
+
+    db::regenerate_room_short_link(room_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[get("/room/<room_id>/qr.png")]
+#[tracing::instrument(skip(ctx, public_origin))]
+async fn room_qr_code<'a>(
+    room_id: RoomId,
+    public_origin: PublicOrigin,
+    ctx: &State<Context>,
+) -> ApiResult<PngImage<'a>> {
+    let mut conn = ctx.db_conn().await?;
+    let slug = db::get_room_short_link(room_id, &mut conn)
+        .await?
+        .ok_or_else(|| Error::not_found("This room doesn't have a short link yet"))
+        .status(Status::NotFound)?;
+
+    Ok(PngImage {
+        content: render_qr_code(&short_link_url(&public_origin, &slug))?,
+        headers: Header::new("Cache-Control", "public, max-age=300"),
+    })
+}
+
+pub fn render_qr_code(data: &str) -> Result<Vec<u8>> {
+    let code = QrCode::new(data)?;
+    let modules = code.to_colors();
+    let width = code.width();
+    let size = (width + 2 * QR_QUIET_ZONE) * QR_MODULE_SIZE;
+
+    let mut pixels = vec![0xffu8; size * size];
+    for (idx, color) in modules.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+
+        let x = (idx % width + QR_QUIET_ZONE) * QR_MODULE_SIZE;
+        let y = (idx / width + QR_QUIET_ZONE) * QR_MODULE_SIZE;
+        for row in y..y + QR_MODULE_SIZE {
+            pixels[row * size + x..row * size + x + QR_MODULE_SIZE].fill(0);
+        }
+    }
+
+    let mut content = vec![];
+    {
+        let mut encoder = png::Encoder::new(&mut content, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
+    }
+
+    Ok(content)
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![resolve_short_link, regenerate_short_link, room_qr_code]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_qr_code;
+
+    #[test]
+    fn test_render_qr_code() {
+        let png = render_qr_code("https://lobby.example/r/abc234").unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+}
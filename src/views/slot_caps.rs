@@ -0,0 +1,60 @@
+use ap_lobby::body_limit::{ContentLength, ROOM_SETTINGS};
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct SlotCapForm<'a> {
+    confirmation_token: &'a str,
+    max_slots: &'a str,
+}
+
+/// An empty cap removes it. Raising or removing the cap promotes waitlisted YAMLs right away.
+#[post("/room/<room_id>/slot-cap", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, content_length, form, session, ctx))]
+async fn set_slot_cap(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    content_length: ContentLength,
+    form: Form<SlotCapForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    content_length.check(&ROOM_SETTINGS)?;
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let max_slots = match form.max_slots.trim() {
+        "" => None,
+        max_slots => Some(
+            max_slots
+                .parse()
+                .map_err(|_| Error::validation("The slot cap should be a number"))?,
+        ),
+    };
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden(
+            "You're not allowed to change this room's slot cap",
+        ));
+    }
+    db::set_room_slot_cap(&room, max_slots, session.0.user_id, &mut conn).await?;
+    super::promote_waitlisted_yamls(room_id, &mut conn).await?;
+    ctx.landing_cache.invalidate().await;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![set_slot_cap]
+}
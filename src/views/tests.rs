@@ -0,0 +1,814 @@
+use std::collections::HashMap;
+
+use ap_lobby::db::{
+    CheckIn, FeaturePolicies, FeaturePolicy, GuestLink, JobRecord, Json, RoomId, RoomRevision,
+    RoomRevisionId, RoomSchedule, RoomScheduleId, RoomSettingsSnapshot, RoomTemplate,
+    RoomTemplateId, RoomUrlPolicy, RoomUrlVisibility, Theme, TimelineDay, UserSettings, UserToken,
+    UserTokenId, YamlAnnotation, YamlFlag, YamlId, YamlSearchResult, YamlSnippet,
+};
+use ap_lobby::extractor::YamlFeature;
+use ap_lobby::jobs::UnknownReference;
+use ap_lobby::landing_cache::LandingStats;
+use ap_lobby::pending_uploads::PendingUpload;
+use ap_lobby::yaml::NameCollision;
+use askama::Template;
+use chrono::{NaiveDate, NaiveTime};
+use rocket::request::FromParam;
+
+use super::admin::{AffectedRoom, WorldImpactTpl, YamlSearchTpl};
+use super::embed::{RoomEmbedTpl, RoomSummary};
+use super::fixtures::{date, room_fixture, room_tpl, yaml_fixture, AUTHOR_ID, PLAYER_ID, ROOM_ID};
+use super::guest_links::GuestUploadTpl;
+use super::room_features::{feature_matrix, FeatureSort, RoomFeaturesTpl};
+use super::room_revisions::{revision_rows, RoomSettingsHistoryTpl};
+use super::room_schedules::RoomSchedulesTpl;
+use super::room_timeline::RoomTimelineTpl;
+use super::room_validation::RoomValidationTpl;
+use super::settings::SettingsTpl;
+use super::{IndexFilters, IndexTpl, RoomApworldsTpl, RoomTpl};
+use crate::TplContext;
+
+fn base(user_id: Option<i64>) -> TplContext<'static> {
+    TplContext {
+        is_admin: false,
+        is_logged_in: user_id.is_some(),
+        cur_module: "room",
+        user_id,
+        err_msg: vec![],
+        warning_msg: vec![],
+        css_version: "test",
+        js_version: "test",
+        confirmation_token: "token".to_string(),
+        settings: UserSettings::default(),
+    }
+}
+
+#[test]
+fn test_room_without_yamls() {
+    let tpl = room_tpl(base(None), room_fixture());
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("Weekly async"));
+    assert!(!rendered.contains("Download all YAMLs"));
+    assert!(rendered.contains(&format!(
+        r#"<meta property="og:image" content="https://lobby.example/room/{}/og.png">"#,
+        ROOM_ID
+    )));
+    assert!(rendered.contains("Some <strong>markdown</strong> description"));
+    assert!(!rendered.contains("editButton"));
+}
+
+#[test]
+fn test_room_with_yamls() {
+    let tpl = RoomTpl {
+        yamls: vec![
+            (
+                yaml_fixture(
+                    "0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e01",
+                    "Player1",
+                    "A Link to the Past",
+                    PLAYER_ID,
+                ),
+                "player".to_string(),
+            ),
+            (
+                yaml_fixture(
+                    "0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e02",
+                    "Other",
+                    "Pokemon Emerald",
+                    AUTHOR_ID,
+                ),
+                "organizer".to_string(),
+            ),
+        ],
+        player_count: 2,
+        unique_player_count: 2,
+        unique_game_count: 2,
+        has_room_url: true,
+        upload_hints: "Use your **discord** name <script>alert(1)</script>".to_string(),
+        ..room_tpl(base(Some(PLAYER_ID)), room_fixture())
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("Use your <strong>discord</strong> name"));
+    assert!(!rendered.contains("<script>alert(1)</script>"));
+    assert!(rendered.contains("Player1"));
+    assert!(rendered.contains("Download all YAMLs"));
+    assert!(rendered.contains("https://archipelago.gg/room/abc"));
+    assert!(rendered.contains(r#"method="POST" action="/room/"#));
+    assert!(rendered.contains(r#"name="confirmation_token" value="token""#));
+    assert!(rendered.contains("/yaml/0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e01/rename"));
+    assert!(!rendered.contains("/yaml/0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e02/rename"));
+    assert!(rendered.contains("2 player(s) have Deathlink on"));
+    assert!(rendered.contains("Pokemon Emerald"));
+    assert!(!rendered.contains("editButton"));
+}
+
+#[test]
+fn test_room_with_check_in() {
+    let mut confirmed = yaml_fixture(
+        "0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e01",
+        "Player1",
+        "A Link to the Past",
+        PLAYER_ID,
+    );
+    confirmed.checked_in_at = Some(date("2024-12-01 18:00:00"));
+    confirmed.validation_warnings = Json(vec![UnknownReference {
+        game: "A Link to the Past".to_string(),
+        option: "exclude_locations".to_string(),
+        name: "Dark World Chests".to_string(),
+    }]);
+    let mut excluded = yaml_fixture(
+        "0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e02",
+        "Ghost",
+        "Pokemon Emerald",
+        3,
+    );
+    excluded.excluded = true;
+
+    let tpl = RoomTpl {
+        yamls: vec![
+            (confirmed, "player".to_string()),
+            (excluded, "ghost".to_string()),
+        ],
+        player_count: 2,
+        unique_player_count: 2,
+        unique_game_count: 2,
+        has_room_url: true,
+        room_url_policy: RoomUrlPolicy {
+            visibility: RoomUrlVisibility::Manual,
+            revealed_at: None,
+        },
+        is_my_room: true,
+        check_in: Some(CheckIn {
+            opened_at: date("2024-12-01 12:00:00"),
+            closed_at: None,
+        }),
+        checked_in_count: 1,
+        annotations: HashMap::from([(
+            YamlId::from_param("0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e01").unwrap(),
+            YamlAnnotation {
+                flag: Some(YamlFlag::Problematic),
+                notes: "Asked for a reupload".to_string(),
+            },
+        )]),
+        join_code: Some("abcd2345".to_string()),
+        guest_links: vec![
+            (
+                GuestLink {
+                    code: "abcdefgh23456789".to_string(),
+                    room_id: RoomId::from_param(ROOM_ID).unwrap(),
+                    guest_id: None,
+                    created_at: date("2024-12-01 12:00:00"),
+                    used_at: None,
+                },
+                None,
+            ),
+            (
+                GuestLink {
+                    code: "zyxwvuts98765432".to_string(),
+                    room_id: RoomId::from_param(ROOM_ID).unwrap(),
+                    guest_id: Some(-3),
+                    created_at: date("2024-12-01 12:00:00"),
+                    used_at: Some(date("2024-12-01 13:00:00")),
+                },
+                Some("Ghost (guest)".to_string()),
+            ),
+        ],
+        ..room_tpl(base(Some(AUTHOR_ID)), room_fixture())
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("1/2 confirmed"));
+    assert!(rendered.contains("/check-in/exclude-unconfirmed"));
+    assert!(rendered.contains(r#"class="excluded""#));
+    assert!(!rendered.contains("I'm in"));
+    assert!(rendered.contains(r#"data-flag="problematic""#));
+    assert!(rendered.contains(r#"data-flag="none""#));
+    assert!(rendered.contains("Asked for a reupload"));
+    assert!(rendered.contains("/export.csv"));
+    assert!(rendered.contains("Dark World Chests"));
+    assert!(rendered.contains("<code>abcd2345</code>"));
+    assert!(rendered.contains(&format!("/room/{}/embed.json", ROOM_ID)));
+    assert!(rendered.contains(r#"<option value="manual" selected>"#));
+    assert!(rendered.contains(&format!(r#"action="/room/{}/room-url/reveal""#, ROOM_ID)));
+    assert!(rendered.contains(&format!(
+        "<code>https://lobby.example/room/{}/guest/abcdefgh23456789</code>",
+        ROOM_ID
+    )));
+    assert!(rendered.contains("Used by Ghost (guest)"));
+    assert!(rendered.contains("Create a short link and QR code"));
+}
+
+#[test]
+fn test_guest_upload() {
+    let tpl = GuestUploadTpl {
+        base: base(None),
+        room: room_fixture(),
+        code: "abcdefgh23456789".to_string(),
+        upload_hints: "No *async* games".to_string(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(
+        r#"action="/room/{}/guest/abcdefgh23456789""#,
+        ROOM_ID
+    )));
+    assert!(rendered.contains(r#"name="confirmation_token" value="token""#));
+    assert!(rendered.contains("No <em>async</em> games"));
+}
+
+#[test]
+fn test_room_needing_join_code() {
+    let tpl = RoomTpl {
+        needs_join_code: true,
+        ..room_tpl(base(Some(PLAYER_ID)), room_fixture())
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(r#"action="/room/{}/join""#, ROOM_ID)));
+    assert!(!rendered.contains("uploadButton"));
+    assert!(!rendered.contains("Invite code</td>"));
+}
+
+#[test]
+fn test_room_with_previous_rooms() {
+    let tpl = RoomTpl {
+        previous_rooms: vec![(
+            RoomId::from_param("0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e10").unwrap(),
+            "Last month's async".to_string(),
+        )],
+        ..room_tpl(base(Some(PLAYER_ID)), room_fixture())
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(r#"action="/room/{}/copy-yamls""#, ROOM_ID)));
+    assert!(rendered.contains(r#"<option value="0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e10">"#));
+    assert!(rendered.contains("Last month&#x27;s async"));
+}
+
+#[test]
+fn test_room_with_pending_upload() {
+    let tpl = RoomTpl {
+        pending_upload: Some(PendingUpload {
+            documents: vec!["name: Player1\ngame: Clique\n".to_string()],
+            collision: NameCollision {
+                document_index: 0,
+                player_name: "Player1".to_string(),
+                suggestions: vec!["Player1{number}".to_string(), "Player2".to_string()],
+            },
+        }),
+        ..room_tpl(base(Some(PLAYER_ID)), room_fixture())
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(r#"action="/room/{}/upload/retry""#, ROOM_ID)));
+    assert!(rendered.contains(r#"name="name" value="Player1{number}""#));
+    assert!(rendered.contains(r#"name="name" value="Player2""#));
+    assert!(rendered.contains(&format!(r#"action="/room/{}/upload/discard""#, ROOM_ID)));
+}
+
+#[test]
+fn test_room_with_waitlist() {
+    let mut waitlisted = yaml_fixture(
+        "7b1c8a0e-2d3f-4a5b-9c6d-7e8f9a0b1c2d",
+        "Player2",
+        "Clique",
+        PLAYER_ID,
+    );
+    waitlisted.waitlisted = true;
+    let yamls = vec![
+        (
+            yaml_fixture(
+                "5d7a3f4c-8b1e-4c2a-9f6d-1e2b3c4d5e6f",
+                "Player1",
+                "Clique",
+                AUTHOR_ID,
+            ),
+            "organizer".to_string(),
+        ),
+        (waitlisted, "player".to_string()),
+    ];
+    let tpl = RoomTpl {
+        player_count: yamls.len(),
+        yamls,
+        unique_player_count: 2,
+        unique_game_count: 1,
+        is_my_room: true,
+        slot_cap: Some(1),
+        feature_policies: FeaturePolicies::from([(YamlFeature::DeathLink, FeaturePolicy::Block)]),
+        ..room_tpl(base(Some(AUTHOR_ID)), room_fixture())
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("1/1 taken"));
+    assert_eq!(
+        rendered
+            .matches(r#"class="yaml-flag flag-waitlisted""#)
+            .count(),
+        1
+    );
+    assert!(rendered.contains(&format!(r#"action="/room/{}/slot-cap""#, ROOM_ID)));
+    assert!(rendered.contains(r#"placeholder="No cap" value="1""#));
+    assert!(rendered.contains(r#"<select name="policies[DeathLink]">"#));
+    assert!(rendered.contains(r#"<option value="block" selected>Not allowed</option>"#));
+    assert!(!rendered.contains(r#"id="feature-rules""#));
+    assert!(rendered.contains(&format!(r#"action="/room/{}/yaml-storage""#, ROOM_ID)));
+    assert!(rendered.contains("Store uploads exactly"));
+}
+
+#[test]
+fn test_room_timeline() {
+    let day = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    let tpl = RoomTimelineTpl {
+        base: base(Some(AUTHOR_ID)),
+        room: room_fixture(),
+        days: vec![
+            TimelineDay {
+                day: day(1),
+                uploads: 2,
+                uploads_with_warnings: 1,
+                check_ins: 0,
+            },
+            TimelineDay {
+                day: day(2),
+                uploads: 8,
+                uploads_with_warnings: 0,
+                check_ins: 3,
+            },
+        ],
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("<td>2024-03-02</td>"));
+    assert!(rendered.contains("width: 25%"));
+    assert!(rendered.contains("width: 100%"));
+    assert!(!rendered.contains("Nobody uploaded anything yet."));
+}
+
+#[test]
+fn test_room_features() {
+    let link = yaml_fixture(
+        "a0000000-0000-0000-0000-000000000001",
+        "Link",
+        "A Link to the Past",
+        PLAYER_ID,
+    );
+    let mut samus = yaml_fixture(
+        "a0000000-0000-0000-0000-000000000002",
+        "Samus",
+        "Super Metroid",
+        PLAYER_ID,
+    );
+    samus.features = Json(HashMap::from([
+        (YamlFeature::DeathLink, 2500),
+        (YamlFeature::RingLink, 10000),
+    ]));
+    let mut waitlisted = yaml_fixture(
+        "a0000000-0000-0000-0000-000000000003",
+        "Ash",
+        "Pokemon Red and Blue",
+        PLAYER_ID,
+    );
+    waitlisted.waitlisted = true;
+    waitlisted.features = Json(HashMap::from([(YamlFeature::TrainerSanity, 10000)]));
+    let yamls = vec![samus, link, waitlisted];
+
+    let matrix = feature_matrix(&yamls, None);
+    assert_eq!(
+        matrix.features,
+        vec![YamlFeature::DeathLink, YamlFeature::RingLink]
+    );
+    assert_eq!(matrix.rows[0].player_name, "Link");
+    assert_eq!(matrix.rows[0].probabilities, vec![10000, 0]);
+    assert_eq!(matrix.rows[1].probabilities, vec![2500, 10000]);
+
+    let matrix = feature_matrix(&yamls, Some("RingLink"));
+    assert_eq!(matrix.rows[0].player_name, "Samus");
+
+    let tpl = RoomFeaturesTpl {
+        base: base(None),
+        room: room_fixture(),
+        matrix,
+        sort: FeatureSort::Feature(YamlFeature::RingLink),
+    };
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(r#"<a href="?sort=RingLink">Ringlink</a> ▼"#));
+    assert!(rendered.contains("<td>25.00%</td>"));
+    assert!(rendered.contains(&format!(
+        r#"href="/room/{}/features.csv?sort=RingLink""#,
+        ROOM_ID
+    )));
+    assert!(!rendered.contains("Ash"));
+}
+
+#[test]
+fn test_room_validation() {
+    let passed = yaml_fixture(
+        "a0000000-0000-0000-0000-000000000001",
+        "Link",
+        "A Link to the Past",
+        PLAYER_ID,
+    );
+    let failed = yaml_fixture(
+        "a0000000-0000-0000-0000-000000000002",
+        "Samus",
+        "Super Metroid",
+        PLAYER_ID,
+    );
+    let never_validated = yaml_fixture(
+        "a0000000-0000-0000-0000-000000000003",
+        "Zelda",
+        "Ocarina of Time",
+        PLAYER_ID,
+    );
+    let job = |id: &str, yaml_id: YamlId, resolution: &str| JobRecord {
+        id: id.to_string(),
+        kind: "yaml_validation".to_string(),
+        room_id: Some(room_fixture().id),
+        yaml_id: Some(yaml_id),
+        user_id: Some(PLAYER_ID),
+        enqueued_at: date("2024-03-01 10:00:00"),
+        resolved_at: Some(date("2024-03-01 10:00:05")),
+        resolution: Some(resolution.to_string()),
+        worker_id: None,
+        priority: "normal".to_string(),
+    };
+    let jobs = HashMap::from([
+        (passed.id, job("job-1", passed.id, "Success")),
+        (failed.id, job("job-2", failed.id, "TimedOut")),
+    ]);
+    // Closed rooms can't re-run validation.
+    let mut room = room_fixture();
+    room.settings.close_date = date("2999-01-01 00:00:00");
+    let tpl = RoomValidationTpl {
+        base: base(Some(AUTHOR_ID)),
+        room,
+        yamls: vec![
+            (passed, "player".to_string()),
+            (failed, "player".to_string()),
+            (never_validated, "player".to_string()),
+        ],
+        jobs,
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("<td>TimedOut</td>"));
+    assert!(rendered.contains("<td>Never validated</td>"));
+    assert_eq!(rendered.matches("<button>Re-run</button>").count(), 2);
+    assert!(rendered.contains("/validation/a0000000-0000-0000-0000-000000000002/rerun"));
+}
+
+#[test]
+fn test_room_settings_history() {
+    let room = room_fixture();
+    let revision = |id: &str, settings: RoomSettingsSnapshot, created_at: &str| RoomRevision {
+        id: RoomRevisionId::from_param(id).unwrap(),
+        room_id: room.id,
+        author_id: Some(AUTHOR_ID),
+        settings: Json(settings),
+        created_at: date(created_at),
+    };
+    let mut before_description = RoomSettingsSnapshot::from(&room.settings);
+    before_description.description = "Old description".to_string();
+    let mut before_rename = before_description.clone();
+    before_rename.name = "Weekly sync".to_string();
+    before_rename.yaml_validation = !before_rename.yaml_validation;
+    let revisions = revision_rows(
+        &room,
+        vec![
+            (
+                revision(
+                    "e0000000-0000-0000-0000-000000000002",
+                    before_description,
+                    "2024-03-02 10:00:00",
+                ),
+                Some("organizer".to_string()),
+            ),
+            (
+                revision(
+                    "e0000000-0000-0000-0000-000000000001",
+                    before_rename,
+                    "2024-03-01 10:00:00",
+                ),
+                None,
+            ),
+        ],
+    );
+    assert_eq!(revisions[0].changed_settings, vec!["description"]);
+    assert_eq!(
+        revisions[1].changed_settings,
+        vec!["name", "YAML validation"]
+    );
+
+    let tpl = RoomSettingsHistoryTpl {
+        base: base(Some(AUTHOR_ID)),
+        room,
+        revisions,
+    };
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("<td>name, YAML validation</td>"));
+    assert!(rendered.contains("<td>organizer</td>"));
+    assert!(rendered.contains("<td>Admin API key</td>"));
+    assert!(rendered.contains(&format!(
+        r#"action="/room/{}/settings-history/e0000000-0000-0000-0000-000000000001/rollback""#,
+        ROOM_ID
+    )));
+    assert_eq!(rendered.matches("<button>Roll back</button>").count(), 2);
+}
+
+#[test]
+fn test_room_schedules() {
+    let tpl_id = RoomTemplateId::from_param("c0000000-0000-0000-0000-000000000001").unwrap();
+    let tpl = RoomSchedulesTpl {
+        base: base(Some(AUTHOR_ID)),
+        schedules: vec![(
+            RoomSchedule {
+                id: RoomScheduleId::from_param("d0000000-0000-0000-0000-000000000001").unwrap(),
+                template_id: tpl_id,
+                author_id: AUTHOR_ID,
+                weekday: 4,
+                start_time: NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+                open_for_hours: 48,
+                webhook_url: String::new(),
+                next_run_at: date("2024-03-08 20:00:00"),
+                last_room_id: Some(room_fixture().id),
+                created_at: date("2024-03-01 10:00:00"),
+            },
+            "Weekly async".to_string(),
+        )],
+        room_templates: vec![RoomTemplate {
+            id: tpl_id,
+            settings: room_fixture().settings,
+            global: false,
+            tpl_name: "Weekly async".to_string(),
+        }],
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("<td>Friday at 20:00</td>"));
+    assert!(rendered.contains(&format!("<a href=\"/room/{}\">Room</a>", ROOM_ID)));
+    assert!(rendered.contains("<option value=\"6\">Sunday</option>"));
+    assert!(!rendered.contains("You don't have any room schedule at the moment."));
+}
+
+#[test]
+fn test_room_embed() {
+    let tpl = RoomEmbedTpl {
+        room: RoomSummary {
+            name: "Test room".to_string(),
+            url: format!("https://lobby.example/room/{}", ROOM_ID),
+            close_date: date("2024-12-01 12:00:00"),
+            is_closed: false,
+            player_count: 1,
+            game_count: 3,
+        },
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(r#"href="https://lobby.example/room/{}""#, ROOM_ID)));
+    assert!(rendered.contains(r#"datetime="2024-12-01T12:00:00Z""#));
+    assert!(rendered.contains("1 player · 3 games"));
+    assert!(!rendered.contains("confirmation_token"));
+}
+
+#[test]
+fn test_world_impact() {
+    let tpl = WorldImpactTpl {
+        base: base(Some(AUTHOR_ID)),
+        world_name: "zelda".to_string(),
+        display_name: "A Link to the Past".to_string(),
+        supported: true,
+        rooms: vec![AffectedRoom {
+            room_id: RoomId::from_param(ROOM_ID).unwrap(),
+            name: "Test room".to_string(),
+            version: semver::Version::new(1, 2, 3),
+            allow_unsupported: false,
+            yamls: 4,
+        }],
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("Official"));
+    assert!(rendered.contains(r#"action="/admin/worlds/zelda/refresh""#));
+    assert!(rendered.contains(&format!(r#"<a href="/room/{}">Test room</a>"#, ROOM_ID)));
+    assert!(rendered.contains("<td>1.2.3</td>"));
+    assert!(!rendered.contains("No open room uses this apworld."));
+}
+
+#[test]
+fn test_yaml_search() {
+    let yaml_id = "0f9d1c2a-5b7e-4e0a-8c1d-3a2b1c0d9e01";
+    let tpl = YamlSearchTpl {
+        base: base(Some(AUTHOR_ID)),
+        query: "death link".to_string(),
+        results: vec![YamlSearchResult {
+            yaml_id: YamlId::from_param(yaml_id).unwrap(),
+            room_id: RoomId::from_param(ROOM_ID).unwrap(),
+            room_name: "Test room".to_string(),
+            player_name: "Link".to_string(),
+            game: "A Link to the Past".to_string(),
+            snippets: vec![YamlSnippet {
+                line: 12,
+                before: "  <b>".to_string(),
+                matched: "death link".to_string(),
+                after: ": true".to_string(),
+            }],
+        }],
+        current_page: 1,
+        max_pages: 2,
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(r#"<a href="/room/{}">Test room</a>"#, ROOM_ID)));
+    assert!(rendered.contains(&format!(r#"href="/room/{}/download/{}""#, ROOM_ID, yaml_id)));
+    assert!(rendered.contains("<mark>death link</mark>"));
+    assert!(!rendered.contains("<b>"));
+    assert!(rendered.contains(r#"href="?q=death%20link&amp;page=2""#));
+}
+
+#[test]
+fn test_index() {
+    let tpl = IndexTpl {
+        base: base(Some(AUTHOR_ID)),
+        rooms: vec![room_fixture()],
+        current_page: 1,
+        max_pages: 1,
+        landing_stats: None,
+        index_filters: IndexFilters::default(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!("/room/{}", ROOM_ID)));
+    assert!(rendered.contains("Weekly async"));
+    assert!(!rendered.contains("<td>Open rooms</td>"));
+}
+
+#[test]
+fn test_index_with_filters() {
+    let tpl = IndexTpl {
+        base: base(Some(PLAYER_ID)),
+        rooms: vec![room_fixture()],
+        current_page: 1,
+        max_pages: 2,
+        landing_stats: None,
+        index_filters: IndexFilters {
+            status: RoomStatus::Open,
+            feature: "DeathLink".to_string(),
+        },
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(r#"<option value="DeathLink" selected>Deathlink</option>"#));
+    assert!(rendered.contains(r#"<option value="open" selected>Open</option>"#));
+    assert!(rendered.contains(r#"href="?status=open&amp;feature=DeathLink&amp;page=2""#));
+}
+
+#[test]
+fn test_index_with_user_settings() {
+    let mut base = base(Some(AUTHOR_ID));
+    base.settings = UserSettings {
+        theme: Theme::Light,
+        compact_layout: true,
+    };
+    let tpl = IndexTpl {
+        base,
+        rooms: vec![room_fixture()],
+        current_page: 1,
+        max_pages: 1,
+        landing_stats: None,
+        index_filters: IndexFilters::default(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(r#"<body class="theme-light compact">"#));
+    assert!(rendered.contains("/static/css/light.css?test"));
+}
+
+#[test]
+fn test_archived_room() {
+    let mut room = room_fixture();
+    room.archived = true;
+    let tpl = RoomTpl {
+        is_closed: true,
+        is_my_room: true,
+        ..room_tpl(base(Some(AUTHOR_ID)), room)
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!(r#"action="/room/{}/archive""#, ROOM_ID)));
+    assert!(rendered.contains(r#"<input type="hidden" name="archived" value="false">"#));
+    assert!(rendered.contains("Unarchive"));
+}
+
+#[test]
+fn test_settings_with_new_token() {
+    let tpl = SettingsTpl {
+        base: base(Some(PLAYER_ID)),
+        settings: UserSettings::default(),
+        tokens: vec![UserToken {
+            id: UserTokenId::from_param("3c9e2b7a-1d4f-4e8a-b6c5-0a1b2c3d4e5f").unwrap(),
+            user_id: PLAYER_ID,
+            name: "Upload script".to_string(),
+            scopes: vec!["upload-yaml".to_string(), "download-yamls".to_string()],
+            created_at: date("2024-11-01 20:00:00"),
+            last_used_at: None,
+            revoked_at: None,
+        }],
+        new_token: Some("apu_secret".to_string()),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(r#"<code id="new-token">apu_secret</code>"#));
+    assert!(rendered.contains("upload-yaml, download-yamls"));
+    assert!(rendered
+        .contains(r#"action="/settings/tokens/3c9e2b7a-1d4f-4e8a-b6c5-0a1b2c3d4e5f/revoke""#));
+    assert_eq!(rendered.matches(r#"name="scopes""#).count(), 3);
+}
+
+#[test]
+fn test_empty_index() {
+    let tpl = IndexTpl {
+        base: base(None),
+        rooms: vec![],
+        current_page: 1,
+        max_pages: 1,
+        landing_stats: None,
+        index_filters: IndexFilters::default(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("There's nothing here (yet)."));
+    assert!(!rendered.contains("<td>Open rooms</td>"));
+}
+
+#[test]
+fn test_landing_index() {
+    let tpl = IndexTpl {
+        base: base(None),
+        rooms: vec![],
+        current_page: 1,
+        max_pages: 1,
+        landing_stats: Some(LandingStats {
+            open_rooms: 3,
+            open_rooms_yamls: 42,
+            closed_rooms: 7,
+        }),
+        index_filters: IndexFilters::default(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("<td>42</td>"));
+    assert!(rendered.contains("<td>Open rooms</td>"));
+    assert!(!rendered.contains("There's nothing here (yet)."));
+}
+
+#[test]
+fn test_room_apworlds() {
+    let tpl = RoomApworldsTpl {
+        base: base(Some(AUTHOR_ID)),
+        is_my_room: true,
+        supported_apworlds: vec![],
+        unsupported_apworlds: vec![],
+        required_by: vec![],
+        dev_overrides: vec![],
+        room: room_fixture(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains(&format!("/edit-room/{}", ROOM_ID)));
+    assert!(rendered.contains("No YAML in this room needs an apworld from this list yet."));
+}
+
+#[test]
+fn test_room_apworlds_required_by() {
+    let tpl = RoomApworldsTpl {
+        base: base(Some(PLAYER_ID)),
+        is_my_room: false,
+        supported_apworlds: vec![],
+        unsupported_apworlds: vec![],
+        required_by: vec![(
+            "A Link to the Past".to_string(),
+            vec!["Link".to_string(), "Zelda".to_string()],
+        )],
+        dev_overrides: vec![],
+        room: room_fixture(),
+    };
+
+    let rendered = tpl.render().unwrap();
+    assert!(rendered.contains("<td>A Link to the Past</td>"));
+    assert!(rendered.contains("<td>Link, Zelda</td>"));
+    assert!(!rendered.contains("No YAML in this room needs an apworld"));
+}
+
+#[test]
+fn test_templates_accessibility() {
+    for (template, violations) in super::a11y::audit_templates().unwrap() {
+        assert!(
+            violations.is_empty(),
+            "{} has accessibility violations: {:#?}",
+            template,
+            violations
+        );
+    }
+}
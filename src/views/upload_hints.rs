@@ -0,0 +1,48 @@
+use ap_lobby::body_limit::{ContentLength, ROOM_SETTINGS};
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct UploadHintsForm<'a> {
+    confirmation_token: &'a str,
+    hints: &'a str,
+}
+
+#[post("/room/<room_id>/upload-hints", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, content_length, form, session, ctx))]
+async fn set_upload_hints(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    content_length: ContentLength,
+    form: Form<UploadHintsForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    content_length.check(&ROOM_SETTINGS)?;
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden(
+            "You're not allowed to change this room's upload hints",
+        ));
+    }
+    db::set_room_upload_hints(room_id, form.hints, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![set_upload_hints]
+}
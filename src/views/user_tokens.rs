@@ -0,0 +1,149 @@
+use ap_lobby::db::{self, UserToken, UserTokenId, UserTokenScope};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::{LoggedInSession, Session};
+use rocket::form::Form;
+use rocket::http::{CookieJar, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::Redirect;
+use rocket::{post, routes, Request, State};
+
+use super::settings::SettingsTpl;
+use super::ConfirmationForm;
+use crate::{Context, TplContext};
+
+/// A logged in session, either from the session cookie or from a personal token sent as
+/// `Authorization: Bearer <token>`. Routes accepting it say what they do with [`Self::require`],
+/// token sessions are only let through if their token has that scope.
+pub(crate) struct ApiSession {
+    session: LoggedInSession,
+    token: Option<UserToken>,
+}
+
+impl ApiSession {
+    pub(crate) fn require(self, scope: UserTokenScope) -> Result<LoggedInSession> {
+        if self.token.is_some_and(|token| !token.allows(scope)) {
+            return Err(Error::forbidden(format!(
+                "This token doesn't have the `{}` scope",
+                scope.as_str()
+            )));
+        }
+
+        Ok(self.session)
+    }
+
+    pub(crate) fn is_token(&self) -> bool {
+        self.token.is_some()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiSession {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let bearer = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|authorization| authorization.strip_prefix("Bearer "));
+        let Some(bearer) = bearer else {
+            return LoggedInSession::from_request(request)
+                .await
+                .map(|session| ApiSession {
+                    session,
+                    token: None,
+                });
+        };
+
+        let Some(ctx) = request.rocket().state::<Context>() else {
+            return Outcome::Error((
+                Status::InternalServerError,
+                Error::Internal(anyhow::anyhow!("Context isn't managed")),
+            ));
+        };
+        let token = match ctx.db_conn().await {
+            Ok(mut conn) => db::authenticate_user_token(bearer.trim(), &mut conn).await,
+            Err(e) => Err(e),
+        };
+
+        match token {
+            Ok(Some(token)) => {
+                tracing::info!(user_id = token.user_id, token_id = %token.id, "User authenticated with a token");
+                Outcome::Success(ApiSession {
+                    session: LoggedInSession(Session {
+                        is_logged_in: true,
+                        user_id: Some(token.user_id),
+                        ..Default::default()
+                    }),
+                    token: Some(token),
+                })
+            }
+            Ok(None) => Outcome::Error((
+                Status::Unauthorized,
+                Error::forbidden("Invalid or revoked token"),
+            )),
+            Err(e) => Outcome::Error((Status::InternalServerError, e)),
+        }
+    }
+}
+
+#[derive(rocket::form::FromForm)]
+struct NewTokenForm<'a> {
+    confirmation_token: &'a str,
+    name: &'a str,
+    scopes: Vec<UserTokenScope>,
+}
+
+/// Shows the settings page right away instead of redirecting, it's the only time the token can
+/// be seen.
+#[post("/settings/tokens", data = "<form>")]
+#[tracing::instrument(skip_all)]
+async fn create_token<'a>(
+    redirect_to: &RedirectTo,
+    form: Form<NewTokenForm<'_>>,
+    session: LoggedInSession,
+    cookies: &CookieJar<'a>,
+    ctx: &State<Context>,
+) -> Result<SettingsTpl<'a>> {
+    redirect_to.set("/settings");
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+    // The admin API key doesn't come with a user to act as.
+    let Some(user_id) = session.0.user_id else {
+        return Err(Error::forbidden("Log in with discord to create tokens"));
+    };
+
+    let mut conn = ctx.db_conn().await?;
+    let (_, token) = db::create_user_token(user_id, form.name, &form.scopes, &mut conn).await?;
+    let tokens = db::list_user_tokens(user_id, &mut conn).await?;
+    let settings = session.0.settings.clone();
+
+    Ok(SettingsTpl {
+        base: TplContext::from_session("settings", session.0, cookies),
+        settings,
+        tokens,
+        new_token: Some(token),
+    })
+}
+
+#[post("/settings/tokens/<token_id>/revoke", data = "<confirmation>")]
+#[tracing::instrument(skip(redirect_to, confirmation, session, ctx))]
+async fn revoke_token(
+    redirect_to: &RedirectTo,
+    token_id: UserTokenId,
+    confirmation: Form<ConfirmationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set("/settings");
+    confirmation.check(&session.0)?;
+
+    let mut conn = ctx.db_conn().await?;
+    db::revoke_user_token(session.user_id(), token_id, &mut conn).await?;
+
+    Ok(Redirect::to("/settings"))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![create_token, revoke_token]
+}
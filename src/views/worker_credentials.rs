@@ -0,0 +1,129 @@
+use ap_lobby::db::{self, WorkerCredential, WorkerCredentialId};
+use ap_lobby::error::{ApiResult, Error};
+use ap_lobby::session::AdminSession;
+use chrono::NaiveDateTime;
+use rocket::serde::json::Json;
+use rocket::{get, post, routes, State};
+use serde::{Deserialize, Serialize};
+
+use crate::views::queues::QUEUE_NAMES;
+use crate::Context;
+
+#[derive(Serialize)]
+struct WorkerCredentialResponse {
+    id: String,
+    name: String,
+    queues: Vec<String>,
+    created_at: NaiveDateTime,
+    rotated_at: Option<NaiveDateTime>,
+    last_used_at: Option<NaiveDateTime>,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+impl From<WorkerCredential> for WorkerCredentialResponse {
+    fn from(credential: WorkerCredential) -> Self {
+        Self {
+            id: credential.id.to_string(),
+            name: credential.name,
+            queues: credential.queues,
+            created_at: credential.created_at,
+            rotated_at: credential.rotated_at,
+            last_used_at: credential.last_used_at,
+            revoked_at: credential.revoked_at,
+        }
+    }
+}
+
+/// Only ever returned when a token gets created or rotated, it can't be retrieved afterwards.
+#[derive(Serialize)]
+struct WorkerTokenResponse {
+    credential: WorkerCredentialResponse,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct CreateWorkerCredentialForm {
+    name: String,
+    queues: Vec<String>,
+}
+
+#[get("/admin/worker-credentials")]
+#[tracing::instrument(skip_all)]
+async fn list_credentials(
+    _admin_session: AdminSession,
+    ctx: &State<Context>,
+) -> ApiResult<Json<Vec<WorkerCredentialResponse>>> {
+    let mut conn = ctx.db_conn().await?;
+    let credentials = db::list_worker_credentials(&mut conn).await?;
+
+    Ok(Json(credentials.into_iter().map(Into::into).collect()))
+}
+
+#[post("/admin/worker-credentials", data = "<form>")]
+#[tracing::instrument(skip(_admin_session, form, ctx))]
+async fn create_credential(
+    _admin_session: AdminSession,
+    form: Json<CreateWorkerCredentialForm>,
+    ctx: &State<Context>,
+) -> ApiResult<Json<WorkerTokenResponse>> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(Error::validation("A worker credential needs a name").into());
+    }
+    if form.queues.is_empty() {
+        return Err(Error::validation("A worker credential needs at least one queue").into());
+    }
+    if let Some(unknown) = form
+        .queues
+        .iter()
+        .find(|queue| !QUEUE_NAMES.contains(&queue.as_str()))
+    {
+        return Err(Error::validation(format!("Unknown queue: {}", unknown)).into());
+    }
+
+    let mut conn = ctx.db_conn().await?;
+    let (credential, token) = db::create_worker_credential(name, &form.queues, &mut conn).await?;
+
+    Ok(Json(WorkerTokenResponse {
+        credential: credential.into(),
+        token,
+    }))
+}
+
+#[post("/admin/worker-credentials/<credential_id>/rotate")]
+#[tracing::instrument(skip(_admin_session, ctx))]
+async fn rotate_credential(
+    _admin_session: AdminSession,
+    credential_id: WorkerCredentialId,
+    ctx: &State<Context>,
+) -> ApiResult<Json<WorkerTokenResponse>> {
+    let mut conn = ctx.db_conn().await?;
+    let (credential, token) = db::rotate_worker_credential(credential_id, &mut conn).await?;
+
+    Ok(Json(WorkerTokenResponse {
+        credential: credential.into(),
+        token,
+    }))
+}
+
+#[post("/admin/worker-credentials/<credential_id>/revoke")]
+#[tracing::instrument(skip(_admin_session, ctx))]
+async fn revoke_credential(
+    _admin_session: AdminSession,
+    credential_id: WorkerCredentialId,
+    ctx: &State<Context>,
+) -> ApiResult<()> {
+    let mut conn = ctx.db_conn().await?;
+    db::revoke_worker_credential(credential_id, &mut conn).await?;
+
+    Ok(())
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        list_credentials,
+        create_credential,
+        rotate_credential,
+        revoke_credential
+    ]
+}
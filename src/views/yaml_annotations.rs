@@ -0,0 +1,160 @@
+use ap_lobby::db::{self, RoomId, YamlFlag, YamlId};
+use ap_lobby::error::{Error, RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use http::header::CONTENT_DISPOSITION;
+use rocket::form::Form;
+use rocket::http::Header;
+use rocket::response::Redirect;
+use rocket::{get, post, routes, State};
+
+use crate::Context;
+
+const MAX_NOTES_LEN: usize = 2000;
+
+#[derive(rocket::form::FromForm)]
+struct AnnotationForm<'a> {
+    confirmation_token: &'a str,
+    flag: Option<YamlFlag>,
+    notes: &'a str,
+}
+
+#[derive(rocket::Responder)]
+#[response(status = 200, content_type = "text/csv")]
+pub(crate) struct CsvFile<'a> {
+    pub(crate) content: String,
+    pub(crate) headers: Header<'a>,
+}
+
+#[post("/room/<room_id>/yaml/<yaml_id>/annotation", data = "<annotation>")]
+#[tracing::instrument(skip(redirect_to, annotation, session, ctx))]
+async fn set_annotation(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    yaml_id: YamlId,
+    annotation: Form<AnnotationForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(annotation.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden(
+            "Only the organizer can annotate YAML files",
+        ));
+    }
+
+    let yaml = db::get_yaml_by_id(yaml_id, &mut conn).await?;
+    if yaml.room_id != room_id {
+        return Err(Error::not_found("This YAML file isn't part of this room"));
+    }
+
+    let notes = annotation.notes.trim();
+    if notes.chars().count() > MAX_NOTES_LEN {
+        return Err(Error::validation(format!(
+            "Notes can't be longer than {} characters",
+            MAX_NOTES_LEN
+        )));
+    }
+
+    db::set_yaml_annotation(yaml_id, annotation.flag, notes, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+#[get("/room/<room_id>/export.csv")]
+#[tracing::instrument(skip(redirect_to, session, ctx))]
+async fn export_room<'a>(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<CsvFile<'a>> {
+    redirect_to.set(&format!("/room/{}", room_id));
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if !is_my_room {
+        return Err(Error::forbidden("Only the organizer can export this room"));
+    }
+
+    let mut yamls = db::get_yamls_for_room_with_author_names(room_id, &mut conn).await?;
+    yamls.sort_by(|a, b| a.0.game.cmp(&b.0.game));
+    let annotations = db::get_yaml_annotations_for_room(room_id, &mut conn).await?;
+    let has_check_in = db::get_room_check_in(room_id, &mut conn).await?.is_some();
+
+    let mut content = String::from("player,game,discord_user,check_in,flag,notes\r\n");
+    for (yaml, username) in yamls {
+        let annotation = annotations.get(&yaml.id).cloned().unwrap_or_default();
+        let check_in = match (has_check_in, yaml.excluded, yaml.checked_in_at) {
+            (false, _, _) => "",
+            (true, true, _) => "excluded",
+            (true, false, Some(_)) => "confirmed",
+            (true, false, None) => "waiting",
+        };
+        let row = [
+            yaml.player_name.as_str(),
+            yaml.game.as_str(),
+            username.as_str(),
+            check_in,
+            annotation.flag.map(|flag| flag.as_str()).unwrap_or(""),
+            annotation.notes.as_str(),
+        ];
+        content.push_str(
+            &row.iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        content.push_str("\r\n");
+    }
+
+    let value = format!(
+        "attachment; filename=\"room-{}.csv\"",
+        room.settings.close_date.format("%Y-%m-%d_%H_%M_%S")
+    );
+
+    Ok(CsvFile {
+        content,
+        headers: Header::new(CONTENT_DISPOSITION.as_str(), value),
+    })
+}
+
+/// Quotes a field for the export. Player names come from uploaded YAMLs, so anything a
+/// spreadsheet would evaluate as a formula gets defused too.
+pub(crate) fn csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![set_annotation, export_room]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_field;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Player1"), "Player1");
+        assert_eq!(csv_field("Hello, world"), "\"Hello, world\"");
+        assert_eq!(csv_field("Say \"hi\""), "\"Say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+    }
+}
@@ -0,0 +1,54 @@
+use ap_lobby::db::{self, RoomId, YamlId};
+use ap_lobby::error::{Error, RedirectTo, Result, WithContext};
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct RenameForm<'a> {
+    confirmation_token: &'a str,
+    name: &'a str,
+}
+
+#[post("/room/<room_id>/yaml/<yaml_id>/rename", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, form, session, ctx))]
+async fn rename_yaml(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    yaml_id: YamlId,
+    form: Form<RenameForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = db::get_room(room_id, &mut conn)
+        .await
+        .context("Unknown room")?;
+    if room.is_closed() {
+        return Err(Error::conflict("This room is closed, you're late"));
+    }
+
+    let yaml = db::get_yaml_by_id(yaml_id, &mut conn).await?;
+    let is_my_room = session.0.is_admin || session.0.user_id == Some(room.settings.author_id);
+    if yaml.owner_id != session.user_id() && !is_my_room {
+        return Err(Error::forbidden(
+            "Can't rename a yaml file that isn't yours",
+        ));
+    }
+
+    ap_lobby::yaml::rename_player(room_id, yaml_id, form.name, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![rename_yaml]
+}
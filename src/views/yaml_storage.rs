@@ -0,0 +1,41 @@
+use ap_lobby::db::{self, RoomId};
+use ap_lobby::error::{RedirectTo, Result};
+use ap_lobby::session::LoggedInSession;
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::{post, routes, State};
+
+use super::get_my_room;
+use crate::Context;
+
+#[derive(rocket::form::FromForm)]
+struct YamlStorageForm<'a> {
+    confirmation_token: &'a str,
+    exact: bool,
+}
+
+/// YAMLs already in the room stay as they were stored.
+#[post("/room/<room_id>/yaml-storage", data = "<form>")]
+#[tracing::instrument(skip(redirect_to, form, session, ctx))]
+async fn set_yaml_storage(
+    redirect_to: &RedirectTo,
+    room_id: RoomId,
+    form: Form<YamlStorageForm<'_>>,
+    session: LoggedInSession,
+    ctx: &State<Context>,
+) -> Result<Redirect> {
+    redirect_to.set(&format!("/room/{}", room_id));
+    session
+        .0
+        .check_confirmation_token(form.confirmation_token)?;
+
+    let mut conn = ctx.db_conn().await?;
+    let room = get_my_room(room_id, &session.0, &mut conn).await?;
+    db::set_room_exact_yaml_storage(&room, form.exact, session.0.user_id, &mut conn).await?;
+
+    Ok(Redirect::to(format!("/room/{}", room_id)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![set_yaml_storage]
+}
@@ -1,7 +1,8 @@
-use crate::db::{self, Room, YamlFile, YamlGame};
+use crate::db::{self, Room, RoomId, YamlFile, YamlGame, YamlId};
+use crate::deadline::Deadline;
 use crate::error::{Error, Result, WithContext};
 use crate::extractor::YamlFeatures;
-use crate::jobs::{YamlValidationParams, YamlValidationQueue};
+use crate::jobs::{UnknownReference, YamlValidationParams, YamlValidationQueue};
 use crate::session::LoggedInSession;
 
 use crate::index_manager::IndexManager;
@@ -12,34 +13,56 @@ use diesel_async::AsyncPgConnection;
 use itertools::Itertools;
 use rocket::http::CookieJar;
 use semver::Version;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::io::BufReader;
+use std::fmt::Display;
 use std::time::Duration;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 use wq::JobStatus;
 
-pub fn parse_raw_yamls(yamls: &[&str]) -> Result<Vec<(String, YamlFile)>> {
-    let yaml = yamls
-        .iter()
-        .map(|yaml| {
-            yaml.trim()
-                .trim_start_matches("---")
-                .trim_end_matches("---")
-        })
-        .join("\n---\n");
+// Archipelago truncates longer names.
+const MAX_PLAYER_NAME_LEN: usize = 16;
+const MAX_NAME_SUGGESTIONS: usize = 3;
 
-    let reader = BufReader::new(yaml.as_bytes());
-    let documents = yaml_split::DocumentIterator::new(reader);
+/// Returned as a conflict when a document's player name is already taken in the room.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameCollision {
+    /// Position of the offending document in the upload.
+    pub document_index: usize,
+    pub player_name: String,
+    /// Names that would be accepted instead, as they should be written in the YAML.
+    pub suggestions: Vec<String>,
+}
 
-    let documents = documents
-        .into_iter()
-        .map(|doc| {
-            let Ok(doc) = doc else {
-                anyhow::bail!("Invalid yaml file. Syntax error.")
-            };
+impl Display for NameCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Adding this yaml would duplicate a player name: {}",
+            self.player_name
+        )?;
+        if !self.suggestions.is_empty() {
+            write!(
+                f,
+                ". These names are still available: {}",
+                self.suggestions.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NameCollision {}
 
-            let doc = doc.trim_start_matches('\u{feff}').to_string();
-            let Ok(parsed) = serde_yaml::from_str(&doc) else {
+/// Splits the uploaded files into documents and parses them. Every document is returned as it was
+/// uploaded, comments and separator included, so that it can be stored and downloaded as is. Use
+/// [`normalize_document`] to get the text that should actually be parsed.
+pub fn parse_raw_yamls(yamls: &[&str]) -> Result<Vec<(String, YamlFile)>> {
+    let documents = yamls
+        .iter()
+        .flat_map(|yaml| split_documents(yaml))
+        .map(|doc| {
+            let Ok(parsed) = serde_yaml::from_str(&normalize_document(&doc)) else {
                 anyhow::bail!(
                     "This does not look like an archipelago YAML. Check that your YAML syntax is valid."
                 )
@@ -51,6 +74,205 @@ pub fn parse_raw_yamls(yamls: &[&str]) -> Result<Vec<(String, YamlFile)>> {
     Ok(documents)
 }
 
+fn is_document_separator(line: &str) -> bool {
+    let line = line.trim_start_matches('\u{feff}').trim_end();
+    line == "---" || line.starts_with("--- ") || line.starts_with("---\t")
+}
+
+/// Whether a line is more than a comment, a blank line, a directive or a document marker.
+fn is_content_line(line: &str) -> bool {
+    let line = line.trim_start_matches('\u{feff}').trim();
+    let line = if is_document_separator(line) {
+        line[3..].trim_start()
+    } else {
+        line
+    };
+
+    !(line.is_empty() || line == "..." || line.starts_with('#') || line.starts_with('%'))
+}
+
+/// Splits a file on its document separators without touching the text. Comments that aren't part
+/// of a document go with the document that follows them, or the last one at the end of the file.
+fn split_documents(yaml: &str) -> Vec<String> {
+    let mut documents: Vec<String> = vec![];
+    let mut current = String::new();
+    let mut has_content = false;
+    for line in yaml.split_inclusive('\n') {
+        if is_document_separator(line) && has_content {
+            documents.push(std::mem::take(&mut current));
+            has_content = false;
+        }
+        has_content |= is_content_line(line);
+        current.push_str(line);
+    }
+
+    match documents.last_mut() {
+        Some(last) if !has_content => last.push_str(&current),
+        _ => documents.push(current),
+    }
+
+    documents
+}
+
+/// The text of a document as validation and the extractor expect it, without the byte order mark
+/// and document markers.
+pub fn normalize_document(document: &str) -> String {
+    document
+        .trim_start_matches('\u{feff}')
+        .split_inclusive('\n')
+        .filter(|line| !is_document_separator(line) || is_content_line(line))
+        .filter(|line| line.trim_end() != "...")
+        .collect()
+}
+
+/// Cleans up a document before it's stored: no byte order mark nor document markers, no
+/// trailing whitespace, `\n` line endings and two spaces per indentation level. Comments stay
+/// where they are. Every step is checked against what the document means, a cleanup that would
+/// change it is skipped so the document is never stored as something else than what was uploaded.
+pub fn tidy_document(document: &str) -> String {
+    let Ok(value) = serde_yaml::from_str::<serde_yaml::Value>(&normalize_document(document)) else {
+        return document.to_string();
+    };
+    let means_the_same = |tidied: &str| {
+        serde_yaml::from_str::<serde_yaml::Value>(tidied).is_ok_and(|tidied| tidied == value)
+    };
+
+    let trimmed = normalize_document(document)
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.is_empty())
+        .fold(String::new(), |mut trimmed, line| {
+            trimmed.push_str(line);
+            trimmed.push('\n');
+            trimmed
+        });
+    let trimmed = format!("{}\n", trimmed.trim_end());
+    let reindented = reindent(&trimmed);
+
+    if means_the_same(&reindented) {
+        reindented
+    } else if means_the_same(&trimmed) {
+        trimmed
+    } else {
+        document.to_string()
+    }
+}
+
+/// Whether the value of a `key: value` or `- value` line starts a block scalar.
+fn starts_block_scalar(line: &str) -> bool {
+    let value = line.split_once(": ").map_or(line, |(_, value)| value);
+    let value = value.strip_prefix("- ").unwrap_or(value);
+    let value = value.split(" #").next().unwrap_or_default().trim();
+    value.starts_with(['|', '>'])
+        && value[1..]
+            .chars()
+            .all(|c| c == '-' || c == '+' || c.is_ascii_digit())
+}
+
+/// Indents every nesting level with two spaces. Block scalars keep their content as it is,
+/// relative to the line that opens them.
+fn reindent(document: &str) -> String {
+    // (original indentation, new indentation) of the parents of the current line.
+    let mut levels: Vec<(usize, usize)> = vec![];
+    // (original indentation of the opening line, new indentation of its content, original
+    // indentation of its content) while in a block scalar.
+    let mut block_scalar: Option<(usize, usize, Option<usize>)> = None;
+    let mut reindented = String::with_capacity(document.len());
+
+    for line in document.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if let Some((opened_at, new_indent, content_indent)) = &mut block_scalar {
+            if trimmed.is_empty() {
+                reindented.push('\n');
+                continue;
+            }
+            if indent > *opened_at {
+                let content_indent = *content_indent.get_or_insert(indent);
+                let extra = indent.saturating_sub(content_indent);
+                reindented.push_str(&" ".repeat(*new_indent + extra));
+                reindented.push_str(trimmed);
+                reindented.push('\n');
+                continue;
+            }
+            block_scalar = None;
+        }
+
+        if trimmed.is_empty() {
+            reindented.push('\n');
+            continue;
+        }
+
+        // Comments go with the closest level they're under and don't open one.
+        let is_comment = trimmed.starts_with('#');
+        let new_indent = if is_comment {
+            match levels
+                .iter()
+                .rev()
+                .find(|(original, _)| *original <= indent)
+            {
+                Some((original, new)) if *original == indent => *new,
+                Some((_, new)) => new + 2,
+                None => 0,
+            }
+        } else {
+            while levels
+                .last()
+                .is_some_and(|(original, _)| *original > indent)
+            {
+                levels.pop();
+            }
+            match levels.last().copied() {
+                Some((original, new)) if original == indent => new,
+                Some((_, new)) => {
+                    levels.push((indent, new + 2));
+                    new + 2
+                }
+                None => {
+                    levels.push((indent, 0));
+                    0
+                }
+            }
+        };
+
+        // `-   key: value` would leave the next keys of the item misaligned.
+        let (trimmed, item_offset) = match trimmed.strip_prefix('-') {
+            Some(item) if item.starts_with(' ') => {
+                let item = item.trim_start();
+                (format!("- {}", item), trimmed.len() - item.len())
+            }
+            _ => (trimmed.to_string(), 0),
+        };
+        reindented.push_str(&" ".repeat(new_indent));
+        reindented.push_str(&trimmed);
+        reindented.push('\n');
+
+        if !is_comment && starts_block_scalar(&trimmed) {
+            // The content of `- key: |` goes under `key`, not under the dash.
+            block_scalar = if item_offset > 0 && trimmed.contains(": ") {
+                Some((indent + item_offset, new_indent + 4, None))
+            } else {
+                Some((indent, new_indent + 2, None))
+            };
+        }
+    }
+
+    reindented
+}
+
+/// A document that passed validation, ready to be added to the room.
+pub struct ValidatedYaml<'a> {
+    pub game_name: String,
+    pub document: &'a String,
+    pub parsed: &'a YamlFile,
+    pub features: YamlFeatures,
+    pub unknown_references: Vec<UnknownReference>,
+    /// The queue job that validated the document, if it went through one.
+    pub validation_job_id: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip_all)]
 pub async fn parse_and_validate_yamls_for_room<'a>(
     room: &Room,
@@ -59,8 +281,9 @@ pub async fn parse_and_validate_yamls_for_room<'a>(
     cookies: &CookieJar<'_>,
     yaml_validation_queue: &YamlValidationQueue,
     index_manager: &IndexManager,
+    deadline: &Deadline,
     conn: &mut AsyncPgConnection,
-) -> Result<Vec<(String, &'a String, &'a YamlFile, YamlFeatures)>> {
+) -> Result<Vec<ValidatedYaml<'a>>> {
     let yamls_in_room = db::get_yamls_for_room(room.id, conn)
         .await
         .context("Couldn't get room yamls")?;
@@ -77,9 +300,11 @@ pub async fn parse_and_validate_yamls_for_room<'a>(
         .map(|yaml| get_ap_player_name(&yaml.player_name, &mut player_counter))
         .collect::<HashSet<String>>();
 
+    let feature_policies = &room.settings.feature_policies;
+    let feature_definitions = index_manager.feature_definitions().await;
     let mut games = Vec::with_capacity(documents.len());
 
-    for (document, parsed) in documents.iter() {
+    for (document_index, (document, parsed)) in documents.iter().enumerate() {
         if let Some(yaml_limit_per_user) = room.settings.yaml_limit_per_user {
             let allow_bypass = session.0.is_admin
                 || room
@@ -87,28 +312,43 @@ pub async fn parse_and_validate_yamls_for_room<'a>(
                     .yaml_limit_bypass_list
                     .contains(&session.user_id());
             if own_games_nb >= yaml_limit_per_user && !allow_bypass {
-                return Err(anyhow::anyhow!(format!(
+                return Err(Error::validation(format!(
                     "The room only allows {} game(s) per person. Cannot upload.",
                     yaml_limit_per_user
-                ))
-                .into());
+                )));
             }
         }
-        let player_name =
-            validate_player_name(&parsed.name, &players_in_room, &mut player_counter)?;
+        let player_name = validate_player_name(
+            &parsed.name,
+            &players_in_room,
+            &mut player_counter,
+            document_index,
+        )?;
         players_in_room.insert(player_name);
 
         let game_name = validate_game(&parsed.game)?;
 
+        let mut unknown_references = vec![];
+        let mut validation_job_id = None;
         if room.settings.yaml_validation {
-            let unsupported_games = validate_yaml(
+            let YamlValidation {
+                unsupported_games,
+                unknown_references: yaml_unknown_references,
+                job_id,
+            } = validate_yaml(
                 document,
                 parsed,
                 &room.settings.manifest,
                 index_manager,
                 yaml_validation_queue,
+                deadline,
+                room.id,
+                session.user_id(),
+                None,
+                conn,
             )
             .await?;
+            validation_job_id = job_id;
             if !unsupported_games.is_empty() {
                 if room.settings.allow_unsupported {
                     session.0.warning_msg.push(format!(
@@ -132,49 +372,319 @@ pub async fn parse_and_validate_yamls_for_room<'a>(
                     Err(anyhow::anyhow!(err))?
                 }
             }
+
+            if !yaml_unknown_references.is_empty() {
+                session.0.warning_msg.push(format!(
+                    "{}'s YAML refers to things that don't exist, those settings won't do anything: {}",
+                    parsed.name,
+                    yaml_unknown_references.iter().join("; ")
+                ));
+                session.0.save(cookies)?;
+            }
+            unknown_references = yaml_unknown_references;
         }
 
-        let features = crate::extractor::extract_features(parsed, document)?;
+        let features = crate::extractor::extract_features(
+            parsed,
+            &normalize_document(document),
+            &feature_definitions,
+        )?;
+        let feature_warnings =
+            db::check_feature_policies(feature_policies, &parsed.name, &features)?;
+        if !feature_warnings.is_empty() {
+            session.0.warning_msg.extend(feature_warnings);
+            session.0.save(cookies)?;
+        }
 
-        games.push((game_name, document, parsed, features));
+        games.push(ValidatedYaml {
+            game_name,
+            document,
+            parsed,
+            features,
+            unknown_references,
+            validation_job_id,
+        });
         own_games_nb += 1;
     }
 
     Ok(games)
 }
 
+/// Validates an uploaded YAML again against the room's current manifest and updates its
+/// warnings. The job is tied to the YAML whether validation passes or not, so that failures show
+/// up in the room's validation status.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(room, content, yaml_validation_queue, index_manager, deadline, conn))]
+pub async fn revalidate_yaml(
+    room: &Room,
+    yaml_id: YamlId,
+    content: &str,
+    owner_id: i64,
+    yaml_validation_queue: &YamlValidationQueue,
+    index_manager: &IndexManager,
+    deadline: &Deadline,
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<UnknownReference>> {
+    let documents = parse_raw_yamls(&[content])?;
+    let Some((document, parsed)) = documents.first() else {
+        return Err(Error::validation("This YAML doesn't contain anything"));
+    };
+
+    let validation = validate_yaml(
+        document,
+        parsed,
+        &room.settings.manifest,
+        index_manager,
+        yaml_validation_queue,
+        deadline,
+        room.id,
+        owner_id,
+        Some(yaml_id),
+        conn,
+    )
+    .await?;
+    if !validation.unsupported_games.is_empty() && !room.settings.allow_unsupported {
+        return Err(Error::validation(format!(
+            "This room doesn't support {} anymore",
+            validation.unsupported_games.iter().join(", ")
+        )));
+    }
+
+    db::set_yaml_validation_warnings(yaml_id, &validation.unknown_references, conn).await?;
+
+    Ok(validation.unknown_references)
+}
+
+/// Renames the player of an uploaded YAML. Only the `name` field of the document changes and the
+/// new name goes through the same checks as on upload.
+#[tracing::instrument(skip(conn))]
+pub async fn rename_player(
+    room_id: RoomId,
+    yaml_id: YamlId,
+    new_name: &str,
+    conn: &mut AsyncPgConnection,
+) -> Result<()> {
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err(Error::validation("The new name can't be empty"));
+    }
+    if new_name.len() > MAX_PLAYER_NAME_LEN {
+        return Err(Error::validation(format!(
+            "Names can't be longer than {} characters",
+            MAX_PLAYER_NAME_LEN
+        )));
+    }
+
+    let yaml = db::get_yaml_by_id(yaml_id, conn).await?;
+    if yaml.room_id != room_id {
+        return Err(Error::not_found("This YAML file isn't part of this room"));
+    }
+
+    let other_yamls = db::get_yamls_for_room_with_author_names(room_id, conn)
+        .await?
+        .into_iter()
+        .filter(|(yaml, _)| yaml.id != yaml_id)
+        .collect::<Vec<_>>();
+    let mut player_counter = Counter::new();
+    let players_in_room = other_yamls
+        .iter()
+        .map(|(yaml, _)| get_ap_player_name(&yaml.player_name, &mut player_counter))
+        .collect::<HashSet<String>>();
+    validate_player_name(&new_name, &players_in_room, &mut player_counter, 0)?;
+
+    let content = set_document_name(&yaml.content, &new_name)?;
+    // The upload as it was sent gets renamed too. It's dropped when its name can't be found
+    // rather than handing out a document with the old name.
+    let original_content = yaml
+        .original_content
+        .and_then(|original| set_document_name(&original, &new_name).ok());
+    db::rename_yaml(
+        yaml_id,
+        &content,
+        original_content.as_deref(),
+        &new_name,
+        conn,
+    )
+    .await
+}
+
+/// Rewrites the top level `name` field of a YAML document, leaving the rest of it untouched.
+pub(crate) fn set_document_name(document: &str, new_name: &str) -> Result<String> {
+    let cant_rename = || {
+        Error::validation("Couldn't find where to change the name in this YAML, edit it by hand")
+    };
+
+    let value = serde_yaml::to_string(new_name)?;
+    let mut found = false;
+    let renamed = document
+        .split_inclusive('\n')
+        .map(|line| {
+            if found || !line.starts_with("name:") {
+                return line.to_string();
+            }
+            found = true;
+            let line_ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+            format!("name: {}{}", value.trim_end(), line_ending)
+        })
+        .collect::<String>();
+    if !found {
+        return Err(cant_rename());
+    }
+
+    match serde_yaml::from_str::<YamlFile>(&renamed) {
+        Ok(parsed) if parsed.name == new_name => Ok(renamed),
+        _ => Err(cant_rename()),
+    }
+}
+
+/// One value an option can roll. The weight is only there when the option is a weighted choice.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct OptionValue {
+    pub value: String,
+    pub weight: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct YamlOption {
+    pub name: String,
+    pub values: Vec<OptionValue>,
+}
+
+/// The options set in the section of a game the YAML can roll.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GameOptions {
+    pub game: String,
+    pub options: Vec<YamlOption>,
+}
+
+fn option_value_to_string(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::Null => "null".to_string(),
+        serde_yaml::Value::Bool(b) => b.to_string(),
+        serde_yaml::Value::Number(n) => n.to_string(),
+        serde_yaml::Value::String(s) => s.clone(),
+        // Lists and nested maps (plando, start inventory...) are shown the way they're written.
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+fn is_zero_weight(weight: &serde_yaml::Value) -> bool {
+    weight.as_f64() == Some(0.)
+}
+
+/// Lists what each option of a YAML is set to, per game section. A map under an option is read as
+/// its weights, values with a weight of 0 can't roll and are left out.
+pub fn option_summary(document: &str) -> Result<Vec<GameOptions>> {
+    let document = normalize_document(document);
+    let parsed: YamlFile = serde_yaml::from_str(&document)?;
+    let value: serde_yaml::Value = serde_yaml::from_str(&document)?;
+
+    let mut summary = vec![];
+    for game in parsed.game.possible_games() {
+        let Some(section) = value.get(game).and_then(|section| section.as_mapping()) else {
+            continue;
+        };
+        let options = section
+            .iter()
+            .map(|(name, value)| {
+                let values = match value.as_mapping() {
+                    Some(weights) => weights
+                        .iter()
+                        .filter(|(_, weight)| !is_zero_weight(weight))
+                        .map(|(value, weight)| OptionValue {
+                            value: option_value_to_string(value),
+                            weight: Some(option_value_to_string(weight)),
+                        })
+                        .collect(),
+                    None => vec![OptionValue {
+                        value: option_value_to_string(value),
+                        weight: None,
+                    }],
+                };
+                YamlOption {
+                    name: option_value_to_string(name),
+                    values,
+                }
+            })
+            .collect();
+
+        summary.push(GameOptions {
+            game: game.to_string(),
+            options,
+        });
+    }
+    summary.sort_by(|a, b| a.game.cmp(&b.game));
+
+    Ok(summary)
+}
+
 fn validate_player_name<'a>(
     original_player_name: &'a String,
     players_in_room: &HashSet<String>,
     player_counter: &'a mut Counter<String>,
+    document_index: usize,
 ) -> Result<String> {
     // AP 0.5.0 doesn't like non ASCII names while hosting.
     if !original_player_name.is_ascii() {
-        return Err(Error(anyhow::anyhow!(format!(
+        return Err(Error::validation(format!(
             "Your YAML contains an invalid name: {}.",
             original_player_name
-        ))));
+        )));
     }
 
     let player_name = get_ap_player_name(original_player_name, player_counter);
 
     if is_reserved_name(&player_name) {
-        return Err(Error(anyhow::anyhow!(format!(
+        return Err(Error::validation(format!(
             "{} is a reserved name",
             player_name
-        ))));
+        )));
     }
 
     if players_in_room.contains(&player_name) {
-        return Err(Error(anyhow::anyhow!(format!(
-            "Adding this yaml would duplicate a player name: {}",
-            player_name
-        ))));
+        // Suggestions get evaluated as if this document had never been seen.
+        let mut player_counter = player_counter.clone();
+        player_counter[original_player_name] -= 1;
+        let suggestions = suggest_player_names(&player_name, players_in_room, &player_counter);
+        return Err(Error::Conflict(anyhow::Error::new(NameCollision {
+            document_index,
+            player_name,
+            suggestions,
+        })));
     }
 
     Ok(player_name)
 }
 
+/// Variants of `player_name` that aren't taken in the room, either relying on AP's `{NUMBER}`
+/// and `{number}` placeholders or with a numeral appended.
+fn suggest_player_names(
+    player_name: &str,
+    players_in_room: &HashSet<String>,
+    player_counter: &Counter<String>,
+) -> Vec<String> {
+    let base = &player_name[..player_name.len().min(MAX_PLAYER_NAME_LEN - 1)];
+    let candidates = ["{NUMBER}", "{number}"]
+        .into_iter()
+        .map(|placeholder| format!("{}{}", base, placeholder))
+        .chain((2..10).map(|n| format!("{}{}", base, n)));
+
+    let mut taken = players_in_room.clone();
+    let mut suggestions = vec![];
+    for candidate in candidates {
+        let resolved = get_ap_player_name(&candidate, &mut player_counter.clone());
+        if is_reserved_name(&resolved) || !taken.insert(resolved) {
+            continue;
+        }
+        suggestions.push(candidate);
+        if suggestions.len() == MAX_NAME_SUGGESTIONS {
+            break;
+        }
+    }
+
+    suggestions
+}
+
 fn validate_game(game: &YamlGame) -> Result<String> {
     match game {
         YamlGame::Name(name) => Ok(name.clone()),
@@ -185,14 +695,35 @@ fn validate_game(game: &YamlGame) -> Result<String> {
             match weighted_map.len() {
                 1 => Ok(weighted_map.keys().next().unwrap().to_string()),
                 n if n > 1 => Ok(format!("Random ({})", n)),
-                _ => Err(anyhow::anyhow!(
-                    "Your YAML contains games but none of them has any chance of getting rolled"
+                _ => Err(Error::validation(
+                    "Your YAML contains games but none of them has any chance of getting rolled",
                 ))?,
             }
         }
     }
 }
 
+#[derive(Default)]
+struct YamlValidation {
+    /// Games that couldn't be validated at all because the room doesn't have their apworld.
+    unsupported_games: Vec<String>,
+    unknown_references: Vec<UnknownReference>,
+    job_id: Option<String>,
+}
+
+/// Keeps the jobs table up to date. It's only there to help debugging, so failing to update it
+/// doesn't fail validation.
+async fn record_validation_job_resolution(
+    job_id: &str,
+    resolution: &str,
+    conn: &mut AsyncPgConnection,
+) {
+    if let Err(e) = db::record_job_resolution(job_id, resolution, None, conn).await {
+        tracing::warn!("Failed to record the resolution of job {}: {:?}", job_id, e);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip_all)]
 async fn validate_yaml(
     yaml: &str,
@@ -200,15 +731,25 @@ async fn validate_yaml(
     manifest: &Manifest,
     index_manager: &IndexManager,
     yaml_validation_queue: &YamlValidationQueue,
-) -> Result<Vec<String>> {
+    deadline: &Deadline,
+    room_id: RoomId,
+    user_id: i64,
+    yaml_id: Option<YamlId>,
+    conn: &mut AsyncPgConnection,
+) -> Result<YamlValidation> {
     let apworlds = match get_apworlds_for_games(index_manager, manifest, &parsed.game).await {
         Ok(apworlds) => apworlds,
-        Err(unsupported) => return Ok(unsupported),
+        Err(unsupported_games) => {
+            return Ok(YamlValidation {
+                unsupported_games,
+                ..Default::default()
+            })
+        }
     };
 
     let mut params = YamlValidationParams {
         apworlds,
-        yaml: yaml.to_string(),
+        yaml: normalize_document(yaml),
         otlp_context: HashMap::new(),
     };
 
@@ -220,14 +761,44 @@ async fn validate_yaml(
     let job_id = yaml_validation_queue
         .enqueue_job(&params, wq::Priority::Normal, Duration::from_secs(30))
         .await?;
+    crate::jobs::tag_sentry_job_id(&job_id);
+    let job_id_str = crate::jobs::job_id_to_string(&job_id);
+    if let Err(e) = db::record_enqueued_job(
+        &job_id_str,
+        "yaml_validation",
+        "normal",
+        Some(room_id),
+        Some(user_id),
+        conn,
+    )
+    .await
+    {
+        tracing::warn!("Failed to record job {}: {:?}", job_id_str, e);
+    } else if let Some(yaml_id) = yaml_id {
+        if let Err(e) = db::link_job_to_yaml(&job_id_str, yaml_id, conn).await {
+            tracing::warn!("Failed to link job {} to its YAML: {:?}", job_id_str, e);
+        }
+    }
 
-    let Some(status) = yaml_validation_queue
-        .wait_for_job(&job_id, Some(Duration::from_secs(30)))
-        .await?
-    else {
+    let wait = yaml_validation_queue.wait_for_job(
+        &job_id,
+        Some(deadline.remaining().min(Duration::from_secs(30))),
+    );
+    let status = match deadline.run(wait).await {
+        Ok(status) => status?,
+        Err(e) => {
+            // Nobody is going to look at the result anymore, don't keep a worker busy for it.
+            yaml_validation_queue.cancel_job(job_id).await?;
+            record_validation_job_resolution(&job_id_str, "Cancelled", conn).await;
+            return Err(e);
+        }
+    };
+
+    let Some(status) = status else {
         // TODO: alert, this is not normal
         yaml_validation_queue.cancel_job(job_id).await?;
-        Err(anyhow!("Timed out while validating this YAML. Either generation is very slow or the service is overloaded. Try again a bit later."))?
+        record_validation_job_resolution(&job_id_str, "TimedOut", conn).await;
+        Err(Error::Upstream(anyhow!("Timed out while validating this YAML. Either generation is very slow or the service is overloaded. Try again a bit later.")))?
     };
 
     if matches!(status, JobStatus::InternalError) {
@@ -238,17 +809,20 @@ async fn validate_yaml(
 
     if matches!(status, JobStatus::Failure) {
         let result = yaml_validation_queue.get_job_result(job_id).await?;
-        Err(anyhow!(
+        Err(Error::Validation(anyhow!(
             "Error: {}",
             result.error.unwrap_or_else(|| "Internal error".to_string())
-        ))?
+        )))?
     }
 
     assert_eq!(status, JobStatus::Success);
+    let result = yaml_validation_queue.get_job_result(job_id).await?;
 
-    // TODO: Maybe show warnings from validation?
-
-    return Ok(vec![]);
+    Ok(YamlValidation {
+        unsupported_games: vec![],
+        unknown_references: result.unknown_references,
+        job_id: Some(job_id_str),
+    })
 }
 
 fn get_ap_player_name<'a>(
@@ -281,7 +855,7 @@ fn get_ap_player_name<'a>(
             }),
         );
 
-    new_name.trim_start()[..std::cmp::min(new_name.len(), 16)]
+    new_name.trim_start()[..std::cmp::min(new_name.len(), MAX_PLAYER_NAME_LEN)]
         .trim_end()
         .to_string()
 }
@@ -326,3 +900,138 @@ async fn get_apworlds_for_games(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use counter::Counter;
+
+    use super::{
+        normalize_document, option_summary, parse_raw_yamls, set_document_name,
+        suggest_player_names, tidy_document, OptionValue,
+    };
+
+    #[test]
+    fn test_suggest_player_names() {
+        let players_in_room = HashSet::from(["Link".to_string(), "Link1".to_string()]);
+        let mut player_counter = Counter::new();
+        player_counter[&"Link".to_string()] += 1;
+        player_counter[&"Link{number}".to_string()] += 1;
+
+        // `Link{NUMBER}` resolves to `Link` and `Link{number}` to `Link2`.
+        assert_eq!(
+            suggest_player_names("Link", &players_in_room, &player_counter),
+            vec!["Link{number}", "Link3", "Link4"]
+        );
+
+        let suggestions =
+            suggest_player_names("SixteenCharsName", &HashSet::new(), &Counter::new());
+        assert_eq!(suggestions[2], "SixteenCharsNam2");
+    }
+
+    #[test]
+    fn test_set_document_name() {
+        let document = "# Made by hand\r\nname: Old{number}  # me\r\ngame: Clique\r\nClique:\r\n  name: nested\r\n";
+        assert_eq!(
+            set_document_name(document, "New").unwrap(),
+            "# Made by hand\r\nname: New\r\ngame: Clique\r\nClique:\r\n  name: nested\r\n"
+        );
+        assert_eq!(
+            set_document_name("name: Old\ngame: Clique", "123").unwrap(),
+            "name: '123'\ngame: Clique"
+        );
+
+        assert!(set_document_name("game: Clique\n", "New").is_err());
+        assert!(set_document_name("name:\n  Old\ngame: Clique\n", "New").is_err());
+    }
+
+    #[test]
+    fn test_parse_raw_yamls() {
+        let file = "\u{feff}# Header\n---\nname: A\ngame: Clique\n# About A\n--- # B\nname: B\ngame: Clique\n---\n# The end\n";
+        let documents = parse_raw_yamls(&[file, "name: C\r\ngame: Clique"]).unwrap();
+        assert_eq!(documents.len(), 3);
+        assert_eq!(
+            documents[..2]
+                .iter()
+                .map(|(doc, _)| doc.as_str())
+                .collect::<String>(),
+            file
+        );
+        assert_eq!(
+            documents[0].0,
+            "\u{feff}# Header\n---\nname: A\ngame: Clique\n# About A\n"
+        );
+        assert_eq!(documents[1].1.name, "B");
+        assert_eq!(documents[2].0, "name: C\r\ngame: Clique");
+
+        assert_eq!(
+            normalize_document(&documents[0].0),
+            "# Header\nname: A\ngame: Clique\n# About A\n"
+        );
+        assert_eq!(
+            normalize_document(&documents[1].0),
+            "name: B\ngame: Clique\n# The end\n"
+        );
+
+        assert!(parse_raw_yamls(&["# Nothing here\n---\n"]).is_err());
+    }
+
+    #[test]
+    fn test_option_summary() {
+        let document = "name: Link\ngame:\n  A Link to the Past: 1\n  Clique: 0\nA Link to the Past:\n  goal:\n    ganon: 50\n    pedestal: 0\n    triforce_hunt: 10\n  death_link: true\n  start_inventory: [Bow]\nClique:\n  hard_mode: true\n";
+        let summary = option_summary(document).unwrap();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].game, "A Link to the Past");
+
+        let options = &summary[0].options;
+        assert_eq!(options[0].name, "goal");
+        assert_eq!(
+            options[0].values,
+            vec![
+                OptionValue {
+                    value: "ganon".into(),
+                    weight: Some("50".into())
+                },
+                OptionValue {
+                    value: "triforce_hunt".into(),
+                    weight: Some("10".into())
+                },
+            ]
+        );
+        assert_eq!(
+            options[1].values,
+            vec![OptionValue {
+                value: "true".into(),
+                weight: None
+            }]
+        );
+        assert_eq!(options[2].values[0].value, r#"["Bow"]"#);
+
+        assert!(option_summary("not: [an, archipelago, yaml]").is_err());
+    }
+
+    #[test]
+    fn test_tidy_document() {
+        let document = "\u{feff}---\r\nname: Link   \r\ngame: A Link to the Past\r\n\r\nA Link to the Past:\r\n    # The goal\r\n    goal:\r\n        ganon: 50\r\n    start_inventory:\r\n        -   Bow\r\n";
+        assert_eq!(
+            tidy_document(document),
+            "name: Link\ngame: A Link to the Past\n\nA Link to the Past:\n  # The goal\n  goal:\n    ganon: 50\n  start_inventory:\n    - Bow\n"
+        );
+
+        // Block scalars keep their own indentation.
+        let document = "description: |\n    first\n      second\ngame: Clique\n";
+        assert_eq!(
+            tidy_document(document),
+            "description: |\n  first\n    second\ngame: Clique\n"
+        );
+
+        // Trailing whitespace that's part of the value isn't touched.
+        let document = "name: Link\ndescription: |\n    kept   \n";
+        assert_eq!(tidy_document(document), document);
+
+        // Documents that don't parse are stored as they are.
+        let document = "name: [Link\n";
+        assert_eq!(tidy_document(document), document);
+    }
+}
@@ -45,7 +45,7 @@ for(const item of deletableItems) {
         if (event.cancelable) {
             event.preventDefault();
             openConfirmationPopup(resourceName, resourceType, () => {
-                location.href = item.href;
+                item.form.submit();
             });
         }
 
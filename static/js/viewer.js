@@ -26,6 +26,40 @@ function showError(msg) {
     setTimeout(() => { error.remove() }, 5000);
 }
 
+function loadYamlOptions(roomId, yamlId, container) {
+    const url = new URL("/api/room/" + roomId + "/yaml/" + yamlId + "/options", document.location)
+    fetch(url)
+        .then((response) => response.ok ? response.json() : [])
+        .then((sections) => {
+            if (sections.length == 0) {
+                container.remove();
+                return;
+            }
+
+            const summary = document.createElement("summary");
+            summary.innerText = "Options";
+            container.appendChild(summary);
+
+            for (const section of sections) {
+                const table = document.createElement("table");
+                table.classList = "styled";
+                const caption = document.createElement("caption");
+                caption.innerText = section.game;
+                table.appendChild(caption);
+
+                for (const option of section.options) {
+                    const row = table.insertRow();
+                    row.insertCell().innerText = option.name;
+                    row.insertCell().innerText = option.values
+                        .map((value) => value.weight === null ? value.value : value.value + ": " + value.weight)
+                        .join(", ");
+                }
+                container.appendChild(table);
+            }
+        })
+        .catch(() => container.remove());
+}
+
 function openYamlPopup(title, yaml, roomId, yamlId) {
     const popup = document.createElement("dialog");
     popup.classList = "popup";
@@ -36,6 +70,11 @@ function openYamlPopup(title, yaml, roomId, yamlId) {
     popupTitle.innerText = title;
     popup.appendChild(popupTitle)
 
+    const optionsContainer = document.createElement("details");
+    optionsContainer.classList = "yaml-options";
+    popup.appendChild(optionsContainer);
+    loadYamlOptions(roomId, yamlId, optionsContainer);
+
     const popupContent = document.createElement("pre");
     popupContent.textContent = yaml;
     popupContent.classList = "language-yaml"